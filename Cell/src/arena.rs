@@ -0,0 +1,281 @@
+//! A typed arena: values that live exactly as long as the arena that allocated them.
+
+use alloc::vec::Vec;
+use core::{cmp, fmt, iter, mem, slice};
+
+use crate::refcell::RefCell;
+
+// Capacity of the first chunk when none was asked for.
+const MIN_CAPACITY: usize = 8;
+
+/// An allocator for values of a single type `T` that are all freed together.
+///
+/// [`Arena::alloc`] takes `&self`, moves the value into the arena and hands back a
+/// `&mut T` that lives as long as the arena. Values are never freed one at a time; they
+/// are all dropped when the arena is. Because many references into the arena can be alive
+/// at once, values can point at each other with plain references, which is enough to build
+/// graphs and cyclic structures without [`Rc`](crate::rc::Rc) or reference counts:
+///
+/// ```
+/// use Cell::arena::Arena;
+///
+/// struct Node<'a> {
+///     value: u32,
+///     next: std::cell::Cell<Option<&'a Node<'a>>>,
+/// }
+///
+/// let arena = Arena::new();
+/// let a = arena.alloc(Node { value: 1, next: std::cell::Cell::new(None) });
+/// let b = arena.alloc(Node { value: 2, next: std::cell::Cell::new(Some(a)) });
+/// a.next.set(Some(b));
+///
+/// assert_eq!(a.next.get().unwrap().next.get().unwrap().value, 1);
+/// ```
+///
+/// The values are stored in chunks. A chunk is a `Vec` that is never pushed past its
+/// capacity, so it never reallocates and the values in it never move. When the current
+/// chunk is full it is set aside and a new one, twice as large, takes its place.
+pub struct Arena<T> {
+    chunks: RefCell<Chunks<T>>,
+}
+
+struct Chunks<T> {
+    current: Vec<T>,
+    rest: Vec<Vec<T>>,
+}
+
+impl<T> Chunks<T> {
+    // Sets the current chunk aside and starts one with room for at least `additional`
+    // more values.
+    #[cold]
+    fn grow(&mut self, additional: usize) {
+        let capacity = cmp::max(self.current.capacity().saturating_mul(2), additional);
+        let chunk = mem::replace(&mut self.current, Vec::with_capacity(capacity));
+        if !chunk.is_empty() {
+            self.rest.push(chunk);
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    /// Creates an empty arena.
+    pub fn new() -> Self {
+        Self::with_capacity(MIN_CAPACITY)
+    }
+
+    /// Creates an empty arena whose first chunk has room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Arena {
+            chunks: RefCell::new(Chunks {
+                current: Vec::with_capacity(cmp::max(capacity, 1)),
+                rest: Vec::new(),
+            }),
+        }
+    }
+
+    /// Moves `value` into the arena and returns a mutable reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc(&self, value: T) -> &mut T {
+        let mut chunks = self.chunks.borrow_mut();
+        if chunks.current.len() == chunks.current.capacity() {
+            chunks.grow(1);
+        }
+        let index = chunks.current.len();
+        chunks.current.push(value);
+        // SAFETY: the push above stayed within the chunk's capacity, so the chunk did not
+        // reallocate and no value handed out earlier has moved. The chunk's buffer is only
+        // freed when the arena is dropped, which the returned lifetime rules out, and this
+        // slot is never handed out again, so the reference is unique.
+        unsafe { &mut *chunks.current.as_mut_ptr().add(index) }
+    }
+
+    /// Moves every value of `iter` into the arena and returns them as one slice.
+    ///
+    /// The values are stored next to each other, so if they do not fit in the current
+    /// chunk a new one is started for them.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_extend<I>(&self, iter: I) -> &mut [T]
+    where
+        I: IntoIterator<Item = T>,
+    {
+        // Collecting first keeps `iter` from running while the chunks are borrowed: it
+        // might call back into the arena.
+        let values: Vec<T> = iter.into_iter().collect();
+        let mut chunks = self.chunks.borrow_mut();
+        if chunks.current.capacity() - chunks.current.len() < values.len() {
+            chunks.grow(values.len());
+        }
+        let start = chunks.current.len();
+        chunks.current.extend(values);
+        let len = chunks.current.len() - start;
+        // SAFETY: as in `alloc`, the values were added within the chunk's capacity, so
+        // nothing moved, and the range `start..start + len` is handed out only once.
+        unsafe { slice::from_raw_parts_mut(chunks.current.as_mut_ptr().add(start), len) }
+    }
+
+    /// Returns the number of values allocated in the arena.
+    pub fn len(&self) -> usize {
+        let chunks = self.chunks.borrow();
+        chunks.current.len() + chunks.rest.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Returns `true` if nothing has been allocated in the arena.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over mutable references to the values, in allocation order.
+    ///
+    /// Taking `&mut self` guarantees that no reference returned by `alloc` is still alive.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let chunks = self.chunks.get_mut();
+        IterMut {
+            inner: chunks
+                .rest
+                .iter_mut()
+                .chain(iter::once(&mut chunks.current))
+                .flatten(),
+        }
+    }
+
+    /// Consumes the arena and returns its values, in allocation order.
+    pub fn into_vec(self) -> Vec<T> {
+        let Chunks { current, rest } = self.chunks.into_inner();
+        let mut values =
+            Vec::with_capacity(current.len() + rest.iter().map(Vec::len).sum::<usize>());
+        for chunk in rest {
+            values.extend(chunk);
+        }
+        values.extend(current);
+        values
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Arena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The values may be mutably borrowed through references from `alloc`, so they
+        // cannot be printed.
+        f.debug_struct("Arena")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over mutable references to the values of an [`Arena`].
+///
+/// Created by [`Arena::iter_mut`].
+pub struct IterMut<'a, T> {
+    #[allow(clippy::type_complexity)]
+    inner: iter::Flatten<iter::Chain<slice::IterMut<'a, Vec<T>>, iter::Once<&'a mut Vec<T>>>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> iter::FusedIterator for IterMut<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc, string::String, vec};
+
+    #[test]
+    fn test_alloc_returns_stable_references() {
+        let arena = Arena::with_capacity(1);
+        let first = arena.alloc(0);
+        let refs: Vec<&mut i32> = (1..100).map(|i| arena.alloc(i)).collect();
+        *first += 1000;
+        assert_eq!(*first, 1000);
+        assert!(refs.iter().enumerate().all(|(i, r)| **r == i as i32 + 1));
+        assert_eq!(arena.len(), 100);
+    }
+
+    #[test]
+    fn test_cyclic_graph() {
+        struct Node<'a> {
+            name: &'static str,
+            edges: crate::refcell::RefCell<Vec<&'a Node<'a>>>,
+        }
+
+        let arena = Arena::new();
+        let node = |name| {
+            &*arena.alloc(Node {
+                name,
+                edges: crate::refcell::RefCell::new(Vec::new()),
+            })
+        };
+        let a = node("a");
+        let b = node("b");
+        let c = node("c");
+        a.edges.borrow_mut().push(b);
+        b.edges.borrow_mut().push(c);
+        c.edges.borrow_mut().push(a);
+
+        let mut walk = vec![];
+        let mut current = a;
+        for _ in 0..4 {
+            walk.push(current.name);
+            current = current.edges.borrow()[0];
+        }
+        assert_eq!(walk, ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_alloc_extend() {
+        let arena = Arena::with_capacity(4);
+        let one = arena.alloc(String::from("one"));
+        let many = arena.alloc_extend((0..10).map(|i| i.to_string()));
+        assert_eq!(many.len(), 10);
+        many[3].push('!');
+        assert_eq!(many[3], "3!");
+        assert_eq!(one, "one");
+        assert!(arena.alloc_extend(iter::empty()).is_empty());
+        assert_eq!(arena.len(), 11);
+    }
+
+    #[test]
+    fn test_iter_mut_and_into_vec_keep_allocation_order() {
+        let mut arena = Arena::with_capacity(2);
+        for i in 0..7 {
+            arena.alloc(i);
+        }
+        arena.iter_mut().for_each(|value| *value *= 10);
+        assert_eq!(arena.iter_mut().count(), 7);
+        assert_eq!(arena.into_vec(), [0, 10, 20, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn test_drop_drops_every_value() {
+        struct Counted(Rc<Cell<usize>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let arena = Arena::with_capacity(3);
+        for _ in 0..20 {
+            arena.alloc(Counted(drops.clone()));
+        }
+        arena.alloc_extend((0..5).map(|_| Counted(drops.clone())));
+        assert_eq!(drops.get(), 0);
+        drop(arena);
+        assert_eq!(drops.get(), 25);
+    }
+}
@@ -0,0 +1,1617 @@
+//! A max-heap backed by a `Vec`, along with its variants: comparator- and key-ordered
+//! heaps, d-ary heaps, an indexed heap with decrease-key, a heap with element handles, a
+//! FIFO-stable heap, and a `Reverse` wrapper for min-heaps.
+
+pub mod slice;
+
+use alloc::{
+    collections::BTreeMap,
+    vec,
+    vec::{Drain, Vec},
+};
+use core::{
+    cmp::Ordering,
+    fmt,
+    mem::swap,
+    ops::{Deref, DerefMut},
+};
+
+/// A max-heap: `pop` and `peek` return the greatest element.
+///
+/// Elements live in a `Vec` laid out as an implicit binary tree, where the children of index
+/// `i` are at `2 * i + 1` and `2 * i + 2` and no child is greater than its parent. `push` and
+/// `pop` are O(log n), `peek` is O(1) and building a heap `From` a `Vec` is O(n).
+#[derive(Clone)]
+pub struct BinaryHeap<T> {
+    data: Vec<T>,
+}
+
+impl<T: Ord> BinaryHeap<T> {
+    /// Creates an empty heap without allocating.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { data: vec![] }
+    }
+
+    /// Creates an empty heap with room for at least `capacity` elements.
+    #[must_use]
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Adds an element in O(log n).
+    pub fn push(&mut self, item: T) {
+        let old_len = self.len();
+        self.data.push(item);
+        sift_up::<2, _, _>(&mut self.data, 0, old_len, &mut T::lt);
+    }
+
+    /// Removes and returns the greatest element in O(log n).
+    pub fn pop(&mut self) -> Option<T> {
+        self.data.pop().map(|mut last_item| {
+            if !self.is_empty() {
+                swap(&mut last_item, &mut self.data[0]);
+                sift_down_to_bottom::<2, _, _>(&mut self.data, 0, &mut T::lt);
+            }
+            last_item
+        })
+    }
+
+    /// Returns the greatest element without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    /// Gives mutable access to the greatest element. The heap is fixed up when the returned
+    /// guard is dropped, which is cheaper than a `pop` followed by a `push`.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(PeekMut {
+                heap: self,
+                original_len: None,
+            })
+        }
+    }
+
+    /// Removes the elements in priority order as the returned iterator is advanced. Whatever
+    /// is left when it's dropped is removed too.
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, T> {
+        DrainSorted { heap: self }
+    }
+
+    /// Restores the heap property over the whole backing vector in O(n) by sifting down
+    /// every parent, starting from the last one. Call it after changing elements through
+    /// [`as_mut_slice`](Self::as_mut_slice).
+    pub fn rebuild(&mut self) {
+        rebuild::<2, _, _>(&mut self.data, &mut T::lt);
+    }
+
+    /// Moves all elements of `other` into `self`, leaving `other` empty. The larger heap is
+    /// kept as the base, then the smaller one is either pushed element by element, costing
+    /// about `added * log2(len)` comparisons, or concatenated and the whole heap rebuilt,
+    /// costing about `2 * total` comparisons, whichever is less.
+    pub fn append(&mut self, other: &mut Self) {
+        if self.len() < other.len() {
+            swap(self, other);
+        }
+        let len = self.len();
+        let added = other.len();
+        let log2_len = (usize::BITS - len.leading_zeros()) as usize;
+        if 2 * (len + added) < added * log2_len {
+            self.data.append(&mut other.data);
+            self.rebuild();
+        } else {
+            for item in other.data.drain(..) {
+                self.push(item);
+            }
+        }
+    }
+
+    /// Consuming variant of `append`.
+    pub fn merge(mut self, mut other: Self) -> Self {
+        self.append(&mut other);
+        self
+    }
+
+    /// Returns the elements sorted in ascending order, heapsorting the backing vector in place.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        sort_heap::<2, _, _>(&mut self.data, &mut T::lt);
+        self.into_vec()
+    }
+
+    /// Checks the heap property over the whole backing vector in O(n) and panics, naming
+    /// the offending index, if some element is greater than its parent. A broken `Ord`
+    /// implementation or a bad edit through `as_mut_slice` otherwise goes unnoticed until
+    /// elements come out in the wrong order.
+    pub fn assert_valid(&self) {
+        if let Some(i) = first_violation::<2, _, _>(&self.data, &mut T::lt) {
+            panic!(
+                "heap property violated: element {i} is greater than its parent {}",
+                (i - 1) / 2
+            );
+        }
+    }
+
+    /// Runs [`assert_valid`](Self::assert_valid) in debug builds and does nothing in release
+    /// builds.
+    pub fn debug_validate(&self) {
+        if cfg!(debug_assertions) {
+            self.assert_valid();
+        }
+    }
+}
+
+impl<T> BinaryHeap<T> {
+    /// The backing vector in heap order, not sorted.
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// The backing vector in heap order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Mutable access to the backing vector. Changing the elements can break the heap
+    /// order, which leaves the heap safe to use but returning elements in the wrong order
+    /// until [`rebuild`](Self::rebuild) is called.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// Removes all elements in heap (arbitrary) order. The heap is empty afterwards even if
+    /// the iterator is leaked before it's exhausted.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        self.data.drain(..)
+    }
+}
+
+impl<T: Ord> Default for BinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Shows the elements in heap order, like the backing vector.
+impl<T: fmt::Debug> fmt::Debug for BinaryHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.data).finish()
+    }
+}
+
+impl<T> From<BinaryHeap<T>> for Vec<T> {
+    fn from(heap: BinaryHeap<T>) -> Self {
+        heap.into_vec()
+    }
+}
+
+/// Iterator returned by `BinaryHeap::drain_sorted`, yielding the greatest element first.
+pub struct DrainSorted<'a, T: Ord> {
+    heap: &'a mut BinaryHeap<T>,
+}
+
+impl<T: Ord> Iterator for DrainSorted<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.heap.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.heap.len();
+        (len, Some(len))
+    }
+}
+
+impl<T: Ord> ExactSizeIterator for DrainSorted<'_, T> {}
+
+impl<T: Ord> Drop for DrainSorted<'_, T> {
+    fn drop(&mut self) {
+        // The order no longer matters, so skip the sifting `pop` would do.
+        self.heap.data.clear();
+    }
+}
+/// Guard returned by `BinaryHeap::peek_mut`. Dropping it sifts the root down to its place.
+pub struct PeekMut<'a, T: Ord> {
+    heap: &'a mut BinaryHeap<T>,
+    // Set once the root has been handed out mutably. The backing vector is shortened to the
+    // root while the guard lives, so leaking the guard (with `mem::forget`) leaks the rest of
+    // the elements instead of leaving a broken heap behind.
+    original_len: Option<usize>,
+}
+
+impl<T: Ord> PeekMut<'_, T> {
+    /// Removes the peeked element from the heap and returns it.
+    pub fn pop(mut this: Self) -> T {
+        if let Some(len) = this.original_len.take() {
+            // SAFETY: `len` is the length the vector had before we shortened it, and the
+            // elements past the root have not been touched since.
+            unsafe { this.heap.data.set_len(len) };
+        }
+        this.heap.pop().unwrap()
+    }
+}
+
+impl<T: Ord> Deref for PeekMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.heap.data[0]
+    }
+}
+
+impl<T: Ord> DerefMut for PeekMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        if self.original_len.is_none() {
+            let len = self.heap.len();
+            self.original_len = Some(len);
+            // SAFETY: 1 <= len, and the elements past the root stay initialized.
+            unsafe { self.heap.data.set_len(1) };
+        }
+        &mut self.heap.data[0]
+    }
+}
+
+impl<T: Ord> Drop for PeekMut<'_, T> {
+    fn drop(&mut self) {
+        if let Some(len) = self.original_len {
+            // SAFETY: see `PeekMut::pop`.
+            unsafe { self.heap.data.set_len(len) };
+            sift_down_range::<2, _, _>(&mut self.heap.data, 0, len, &mut T::lt);
+        }
+    }
+}
+
+// The sift primitives below work on any slice laid out as a `D`-ary heap and take an
+// `is_less` comparator, so every heap flavour in this module shares them. The element that
+// compares greatest sits at the root, and the children of `pos` are `D * pos + 1..=D * pos + D`.
+
+fn sift_up<const D: usize, T, F: FnMut(&T, &T) -> bool>(
+    data: &mut [T],
+    start: usize,
+    mut pos: usize,
+    is_less: &mut F,
+) -> usize {
+    while pos > start {
+        let parent = (pos - 1) / D;
+        if !is_less(&data[parent], &data[pos]) {
+            break;
+        }
+        data.swap(pos, parent);
+        pos = parent;
+    }
+    pos
+}
+
+// Index of the greatest of the children starting at `first`, looking only at `data[..end]`.
+// `first` must be less than `end`.
+fn greatest_child<const D: usize, T, F: FnMut(&T, &T) -> bool>(
+    data: &[T],
+    first: usize,
+    end: usize,
+    is_less: &mut F,
+) -> usize {
+    let mut greatest = first;
+    for child in first + 1..end.min(first + D) {
+        if !is_less(&data[child], &data[greatest]) {
+            greatest = child;
+        }
+    }
+    greatest
+}
+
+// Moves the element at `pos` all the way down to a leaf, always following the greatest
+// child, and then sifts it back up. Popped-in elements come from the bottom of the heap
+// and usually belong there, so this does fewer comparisons than a regular sift down.
+fn sift_down_to_bottom<const D: usize, T, F: FnMut(&T, &T) -> bool>(
+    data: &mut [T],
+    mut pos: usize,
+    is_less: &mut F,
+) {
+    let end = data.len();
+    let start = pos;
+
+    let mut child = D * pos + 1;
+
+    while child < end {
+        child = greatest_child::<D, _, _>(data, child, end, is_less);
+
+        data.swap(pos, child);
+        pos = child;
+        child = D * pos + 1;
+    }
+    sift_up::<D, _, _>(data, start, pos, is_less);
+}
+
+// Regular sift down that only looks at `data[..end]`.
+fn sift_down_range<const D: usize, T, F: FnMut(&T, &T) -> bool>(
+    data: &mut [T],
+    mut pos: usize,
+    end: usize,
+    is_less: &mut F,
+) {
+    let mut child = D * pos + 1;
+
+    while child < end {
+        child = greatest_child::<D, _, _>(data, child, end, is_less);
+        if !is_less(&data[pos], &data[child]) {
+            break;
+        }
+        data.swap(pos, child);
+        pos = child;
+        child = D * pos + 1;
+    }
+}
+
+fn rebuild<const D: usize, T, F: FnMut(&T, &T) -> bool>(data: &mut [T], is_less: &mut F) {
+    let len = data.len();
+    if len < 2 {
+        return;
+    }
+    // Start from the parent of the last element.
+    let mut n = (len - 2) / D + 1;
+    while n > 0 {
+        n -= 1;
+        sift_down_range::<D, _, _>(data, n, len, is_less);
+    }
+}
+
+// Turns a heap into a slice sorted in ascending order.
+fn sort_heap<const D: usize, T, F: FnMut(&T, &T) -> bool>(data: &mut [T], is_less: &mut F) {
+    let mut end = data.len();
+    while end > 1 {
+        end -= 1;
+        data.swap(0, end);
+        sift_down_range::<D, _, _>(data, 0, end, is_less);
+    }
+}
+
+// Returns the index of the first element that is greater than its parent, if any.
+fn first_violation<const D: usize, T, F: FnMut(&T, &T) -> bool>(
+    data: &[T],
+    is_less: &mut F,
+) -> Option<usize> {
+    (1..data.len()).find(|&i| is_less(&data[(i - 1) / D], &data[i]))
+}
+
+/// A max-heap ordered by a comparator closure instead of `Ord`, e.g. for `f64` scores
+/// compared with `total_cmp`.
+pub struct BinaryHeapBy<T, F> {
+    data: Vec<T>,
+    cmp: F,
+}
+
+impl<T, F: FnMut(&T, &T) -> Ordering> BinaryHeapBy<T, F> {
+    pub fn new_by(cmp: F) -> Self {
+        Self { data: vec![], cmp }
+    }
+
+    pub fn from_vec_by(data: Vec<T>, cmp: F) -> Self {
+        let mut heap = Self { data, cmp };
+        rebuild::<2, _, _>(&mut heap.data, &mut less_by(&mut heap.cmp));
+        heap
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push(&mut self, item: T) {
+        let old_len = self.len();
+        self.data.push(item);
+        sift_up::<2, _, _>(&mut self.data, 0, old_len, &mut less_by(&mut self.cmp));
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.data.pop().map(|mut last_item| {
+            if !self.data.is_empty() {
+                swap(&mut last_item, &mut self.data[0]);
+                sift_down_to_bottom::<2, _, _>(&mut self.data, 0, &mut less_by(&mut self.cmp));
+            }
+            last_item
+        })
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        sort_heap::<2, _, _>(&mut self.data, &mut less_by(&mut self.cmp));
+        self.data
+    }
+}
+
+fn less_by<T>(cmp: &mut impl FnMut(&T, &T) -> Ordering) -> impl FnMut(&T, &T) -> bool + '_ {
+    move |a, b| cmp(a, b) == Ordering::Less
+}
+
+/// A max-heap ordered by a key extracted from each element, e.g. a struct ordered by one of
+/// its fields. The key is recomputed on every comparison, so it should be cheap.
+pub struct BinaryHeapByKey<T, F> {
+    data: Vec<T>,
+    key: F,
+}
+
+impl<T, K: Ord, F: FnMut(&T) -> K> BinaryHeapByKey<T, F> {
+    pub fn new_by_key(key: F) -> Self {
+        Self { data: vec![], key }
+    }
+
+    pub fn from_vec_by_key(data: Vec<T>, key: F) -> Self {
+        let mut heap = Self { data, key };
+        rebuild::<2, _, _>(&mut heap.data, &mut less_by_key(&mut heap.key));
+        heap
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push(&mut self, item: T) {
+        let old_len = self.len();
+        self.data.push(item);
+        sift_up::<2, _, _>(&mut self.data, 0, old_len, &mut less_by_key(&mut self.key));
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.data.pop().map(|mut last_item| {
+            if !self.data.is_empty() {
+                swap(&mut last_item, &mut self.data[0]);
+                sift_down_to_bottom::<2, _, _>(&mut self.data, 0, &mut less_by_key(&mut self.key));
+            }
+            last_item
+        })
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        sort_heap::<2, _, _>(&mut self.data, &mut less_by_key(&mut self.key));
+        self.data
+    }
+}
+
+fn less_by_key<T, K: Ord>(key: &mut impl FnMut(&T) -> K) -> impl FnMut(&T, &T) -> bool + '_ {
+    move |a, b| key(a) < key(b)
+}
+
+/// A max-heap where every node has `D` children instead of two. It runs on the same sift
+/// code as `BinaryHeap` (which is the `D = 2` case). A wider node makes the tree shallower,
+/// so a `push` does fewer swaps, and the children of a node sit next to each other in
+/// memory. For push-heavy workloads a 4-ary heap is usually faster than a binary one, at the
+/// cost of more comparisons per level on `pop`.
+pub struct DaryHeap<T, const D: usize> {
+    data: Vec<T>,
+}
+
+impl<T: Ord, const D: usize> DaryHeap<T, D> {
+    pub fn new() -> Self {
+        const { assert!(D >= 2, "a d-ary heap needs at least two children per node") };
+        Self { data: vec![] }
+    }
+
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        let mut heap = Self::new();
+        heap.data.reserve_exact(capacity);
+        heap
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push(&mut self, item: T) {
+        let old_len = self.len();
+        self.data.push(item);
+        sift_up::<D, _, _>(&mut self.data, 0, old_len, &mut T::lt);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.data.pop().map(|mut last_item| {
+            if !self.is_empty() {
+                swap(&mut last_item, &mut self.data[0]);
+                sift_down_to_bottom::<D, _, _>(&mut self.data, 0, &mut T::lt);
+            }
+            last_item
+        })
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        sort_heap::<D, _, _>(&mut self.data, &mut T::lt);
+        self.data
+    }
+
+    /// Same as [`BinaryHeap::assert_valid`].
+    pub fn assert_valid(&self) {
+        if let Some(i) = first_violation::<D, _, _>(&self.data, &mut T::lt) {
+            panic!(
+                "heap property violated: element {i} is greater than its parent {}",
+                (i - 1) / D
+            );
+        }
+    }
+
+    /// Same as [`BinaryHeap::debug_validate`].
+    pub fn debug_validate(&self) {
+        if cfg!(debug_assertions) {
+            self.assert_valid();
+        }
+    }
+}
+
+impl<T: Ord, const D: usize> Default for DaryHeap<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const D: usize> From<Vec<T>> for DaryHeap<T, D> {
+    fn from(data: Vec<T>) -> Self {
+        let mut heap = Self::new();
+        heap.data = data;
+        rebuild::<D, _, _>(&mut heap.data, &mut T::lt);
+        heap
+    }
+}
+
+/// A max-heap of `(key, priority)` pairs that remembers where every key sits in the heap,
+/// so the priority of any key can be looked up, changed or removed in O(log n). This is the
+/// decrease-key priority queue Dijkstra and A* need (with `Reverse` priorities for a
+/// min-queue). Each key appears at most once.
+pub struct IndexedBinaryHeap<K, P> {
+    data: Vec<(K, P)>,
+    positions: BTreeMap<K, usize>,
+}
+
+impl<K: Ord + Clone, P: Ord> IndexedBinaryHeap<K, P> {
+    pub fn new() -> Self {
+        Self {
+            data: vec![],
+            positions: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn priority(&self, key: &K) -> Option<&P> {
+        self.positions.get(key).map(|&pos| &self.data[pos].1)
+    }
+
+    pub fn peek(&self) -> Option<(&K, &P)> {
+        self.data.first().map(|(key, priority)| (key, priority))
+    }
+
+    /// Inserts `key`, or changes its priority if it's already in the heap, returning the old
+    /// priority in that case.
+    pub fn push(&mut self, key: K, priority: P) -> Option<P> {
+        if self.contains_key(&key) {
+            return self.change_priority(&key, priority);
+        }
+        let pos = self.len();
+        self.positions.insert(key.clone(), pos);
+        self.data.push((key, priority));
+        self.sift_up(pos);
+        None
+    }
+
+    pub fn pop(&mut self) -> Option<(K, P)> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Returns the old priority, or `None` (leaving the heap untouched) if `key` isn't in it.
+    pub fn change_priority(&mut self, key: &K, priority: P) -> Option<P> {
+        let pos = *self.positions.get(key)?;
+        let old = core::mem::replace(&mut self.data[pos].1, priority);
+        let pos = self.sift_up(pos);
+        self.sift_down(pos);
+        Some(old)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<P> {
+        let pos = *self.positions.get(key)?;
+        Some(self.remove_at(pos).1)
+    }
+
+    fn remove_at(&mut self, pos: usize) -> (K, P) {
+        let last = self.len() - 1;
+        self.swap_entries(pos, last);
+        let (key, priority) = self.data.pop().unwrap();
+        self.positions.remove(&key);
+        if pos < last {
+            // The element moved in from the end can belong above or below `pos`.
+            let pos = self.sift_up(pos);
+            self.sift_down(pos);
+        }
+        (key, priority)
+    }
+
+    // Swaps two entries and records their new positions.
+    fn swap_entries(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        self.data.swap(a, b);
+        *self.positions.get_mut(&self.data[a].0).unwrap() = a;
+        *self.positions.get_mut(&self.data[b].0).unwrap() = b;
+    }
+
+    // The shared sift primitives can't be used here because every swap has to update
+    // `positions` as well.
+    fn sift_up(&mut self, mut pos: usize) -> usize {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.data[pos].1 <= self.data[parent].1 {
+                break;
+            }
+            self.swap_entries(pos, parent);
+            pos = parent;
+        }
+        pos
+    }
+
+    fn sift_down(&mut self, mut pos: usize) {
+        let end = self.len();
+        let mut child = 2 * pos + 1;
+        while child < end {
+            if child + 1 < end && self.data[child].1 <= self.data[child + 1].1 {
+                child += 1;
+            }
+            if self.data[pos].1 >= self.data[child].1 {
+                break;
+            }
+            self.swap_entries(pos, child);
+            pos = child;
+            child = 2 * pos + 1;
+        }
+    }
+}
+
+impl<K: Ord + Clone, P: Ord> Default for IndexedBinaryHeap<K, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A max-heap whose `push_with_handle` returns a [`Handle`] to the new element, so that a
+/// scheduler can later `update` or `remove` that one element in O(log n) instead of
+/// rebuilding the heap.
+///
+/// Every element remembers its slot in a side table, and the slot remembers where the
+/// element currently sits in the heap. Slots of popped elements are reused, and a generation
+/// counter makes handles of popped elements stale instead of letting them refer to whatever
+/// element got the slot next.
+pub struct HandleHeap<T> {
+    data: Vec<(T, usize)>,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+struct Slot {
+    // Position in `data`, `None` while the slot is free.
+    pos: Option<usize>,
+    generation: u64,
+}
+
+/// Refers to an element of a [`HandleHeap`]. Goes stale once the element leaves the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+    slot: usize,
+    generation: u64,
+}
+
+impl<T: Ord> HandleHeap<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            data: vec![],
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first().map(|(value, _)| value)
+    }
+
+    pub fn push_with_handle(&mut self, value: T) -> Handle {
+        let pos = self.len();
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].pos = Some(pos);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    pos: Some(pos),
+                    generation: 0,
+                });
+                self.slots.len() - 1
+            }
+        };
+        self.data.push((value, slot));
+        self.sift_up(pos);
+        Handle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Returns the element of `handle`, or `None` if it has left the heap.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.position(handle).map(|pos| &self.data[pos].0)
+    }
+
+    /// Replaces the element of `handle` and moves it up or down to its new place, returning
+    /// the old element. Returns `Err` with `value` if the handle is stale.
+    pub fn update(&mut self, handle: Handle, value: T) -> Result<T, T> {
+        let Some(pos) = self.position(handle) else {
+            return Err(value);
+        };
+        let old = core::mem::replace(&mut self.data[pos].0, value);
+        let pos = self.sift_up(pos);
+        self.sift_down(pos);
+        Ok(old)
+    }
+
+    /// Removes the element of `handle`, or returns `None` if it has left the heap.
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let pos = self.position(handle)?;
+        Some(self.remove_at(pos))
+    }
+
+    fn position(&self, handle: Handle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.pos
+    }
+
+    fn remove_at(&mut self, pos: usize) -> T {
+        let last = self.len() - 1;
+        self.swap_entries(pos, last);
+        let (value, slot) = self.data.pop().unwrap();
+        let freed = &mut self.slots[slot];
+        freed.pos = None;
+        freed.generation += 1;
+        self.free.push(slot);
+        if pos < last {
+            let pos = self.sift_up(pos);
+            self.sift_down(pos);
+        }
+        value
+    }
+
+    fn swap_entries(&mut self, a: usize, b: usize) {
+        self.data.swap(a, b);
+        self.slots[self.data[a].1].pos = Some(a);
+        self.slots[self.data[b].1].pos = Some(b);
+    }
+
+    // Same as `IndexedBinaryHeap`, every swap has to update the slots as well.
+    fn sift_up(&mut self, mut pos: usize) -> usize {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.data[pos].0 <= self.data[parent].0 {
+                break;
+            }
+            self.swap_entries(pos, parent);
+            pos = parent;
+        }
+        pos
+    }
+
+    fn sift_down(&mut self, mut pos: usize) {
+        let end = self.len();
+        let mut child = 2 * pos + 1;
+        while child < end {
+            if child + 1 < end && self.data[child].0 <= self.data[child + 1].0 {
+                child += 1;
+            }
+            if self.data[pos].0 >= self.data[child].0 {
+                break;
+            }
+            self.swap_entries(pos, child);
+            pos = child;
+            child = 2 * pos + 1;
+        }
+    }
+}
+
+impl<T: Ord> Default for HandleHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A max-heap where equal elements come out in the order they were pushed, e.g. for a
+/// scheduler that must not starve tasks of the same priority.
+///
+/// A plain binary heap gives no such guarantee. Here every element is paired with an
+/// increasing sequence number, and among equal elements the smaller number wins.
+pub struct StableBinaryHeap<T> {
+    heap: BinaryHeap<Sequenced<T>>,
+    next_seq: u64,
+}
+
+struct Sequenced<T> {
+    value: T,
+    seq: u64,
+}
+
+impl<T: Ord> PartialEq for Sequenced<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Sequenced<T> {}
+
+impl<T: Ord> PartialOrd for Sequenced<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Sequenced<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value
+            .cmp(&other.value)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<T: Ord> StableBinaryHeap<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn push(&mut self, value: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.heap.push(Sequenced { value, seq });
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.heap.pop().map(|entry| entry.value)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.peek().map(|entry| &entry.value)
+    }
+}
+
+impl<T: Ord> Default for StableBinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wrapper that flips the ordering of `T`, turning the max-heap into a min-heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Reverse<T>(pub T);
+
+impl<T: PartialOrd> PartialOrd for Reverse<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        other.0.partial_cmp(&self.0)
+    }
+}
+
+impl<T: Ord> Ord for Reverse<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+/// A heap that pops its smallest element first.
+pub type MinHeap<T> = BinaryHeap<Reverse<T>>;
+
+impl<T: Ord> From<Vec<T>> for BinaryHeap<T> {
+    fn from(data: Vec<T>) -> Self {
+        let mut heap = BinaryHeap { data };
+        heap.rebuild();
+        heap
+    }
+}
+
+// Heaps are serialized as their backing sequence. Deserializing heapifies the sequence, so
+// any sequence (not only one already in heap order) yields a valid heap.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for BinaryHeap<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.data)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Ord + serde::Deserialize<'de>> serde::Deserialize<'de> for BinaryHeap<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(BinaryHeap::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let heap: BinaryHeap<i32> = BinaryHeap::new();
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_new_with_capacity() {
+        let heap: BinaryHeap<i32> = BinaryHeap::new_with_capacity(10);
+        assert!(heap.is_empty());
+        assert_eq!(heap.data.capacity(), 10);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let heap: BinaryHeap<i32> = BinaryHeap::new();
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_pop_empty() {
+        let mut heap: BinaryHeap<i32> = BinaryHeap::new();
+        assert!(heap.pop().is_none());
+    }
+
+    #[test]
+    fn test_pop_non_empty() {
+        let mut heap: BinaryHeap<i32> = BinaryHeap::new();
+        heap.push(1);
+        heap.push(2);
+        heap.push(3);
+        heap.push(4);
+        assert_eq!(heap.pop(), Some(4));
+        assert_eq!(heap.peek(), Some(&3));
+    }
+
+    #[test]
+    fn test_pop_keeps_heap_order() {
+        let mut heap = BinaryHeap::new();
+        for x in [3, 2, 1, 7, 5, 9, 4, 4, 0] {
+            heap.push(x);
+        }
+        let mut popped = vec![];
+        while let Some(x) = heap.pop() {
+            popped.push(x);
+        }
+        assert_eq!(popped, [9, 7, 5, 4, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_into_sorted_vec() {
+        let mut heap = BinaryHeap::new();
+        for x in [5, 1, 8, 3, 3, 9, 2] {
+            heap.push(x);
+        }
+        assert_eq!(heap.into_sorted_vec(), [1, 2, 3, 3, 5, 8, 9]);
+
+        let empty: BinaryHeap<i32> = BinaryHeap::new();
+        assert!(empty.into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn test_into_vec_and_as_slice() {
+        let mut heap = BinaryHeap::new();
+        heap.push(1);
+        heap.push(3);
+        heap.push(2);
+        assert_eq!(heap.as_slice()[0], 3);
+        assert_eq!(heap.as_slice().len(), 3);
+
+        let mut data = heap.into_vec();
+        assert_eq!(data[0], 3);
+        data.sort();
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    fn is_heap<T: Ord>(data: &[T]) -> bool {
+        slice::is_heap(data)
+    }
+
+    // A small xorshift generator, so the property tests below are reproducible without a
+    // dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    // Applies a random sequence of operations to a heap and to a sorted `Vec` model, checking
+    // the heap property and comparing the results after every step.
+    fn check_against_model(seed: u64, steps: usize) {
+        let mut rng = Rng(seed);
+        let mut heap = BinaryHeap::new();
+        let mut model: Vec<u64> = vec![];
+        for _ in 0..steps {
+            match rng.below(6) {
+                0..=2 => {
+                    let x = rng.below(50);
+                    heap.push(x);
+                    let at = model.partition_point(|&y| y <= x);
+                    model.insert(at, x);
+                }
+                3 => assert_eq!(heap.pop(), model.pop()),
+                4 => {
+                    if let Some(mut top) = heap.peek_mut() {
+                        let x = rng.below(50);
+                        *top = x;
+                        drop(top);
+                        model.pop();
+                        let at = model.partition_point(|&y| y <= x);
+                        model.insert(at, x);
+                    }
+                }
+                _ => {
+                    let other: Vec<_> = (0..rng.below(10)).map(|_| rng.below(50)).collect();
+                    model.extend(&other);
+                    model.sort();
+                    heap.append(&mut BinaryHeap::from(other));
+                }
+            }
+            heap.assert_valid();
+            assert_eq!(heap.len(), model.len());
+            assert_eq!(heap.peek(), model.last());
+        }
+        assert_eq!(heap.into_sorted_vec(), model);
+    }
+
+    #[test]
+    fn test_random_operations() {
+        for seed in 1..=20 {
+            check_against_model(seed, 300);
+        }
+    }
+
+    #[test]
+    fn test_assert_valid() {
+        let mut heap = BinaryHeap::from(vec![4, 8, 1, 3]);
+        heap.assert_valid();
+        heap.debug_validate();
+        DaryHeap::<_, 3>::from(vec![4, 8, 1, 3, 7]).assert_valid();
+
+        heap.as_mut_slice()[3] = 100;
+        let err = std::panic::catch_unwind(|| heap.assert_valid()).unwrap_err();
+        let msg = err.downcast_ref::<std::string::String>().unwrap();
+        assert_eq!(
+            msg,
+            "heap property violated: element 3 is greater than its parent 1"
+        );
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let heap = BinaryHeap::from(vec![1, 6, 2, 9, 4, 4, 8, 0, 3]);
+        assert!(is_heap(heap.as_slice()));
+        assert_eq!(heap.into_sorted_vec(), [0, 1, 2, 3, 4, 4, 6, 8, 9]);
+
+        let heap = BinaryHeap::from(Vec::<i32>::new());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let mut heap = BinaryHeap::from(vec![5, 3, 4, 1]);
+        heap.as_mut_slice()[2] = 10;
+        heap.as_mut_slice()[3] = 7;
+        assert!(!is_heap(heap.as_slice()));
+        heap.rebuild();
+        assert!(is_heap(heap.as_slice()));
+        assert_eq!(heap.pop(), Some(10));
+        assert_eq!(heap.pop(), Some(7));
+    }
+
+    #[test]
+    fn test_reverse_ordering() {
+        assert!(Reverse(1) > Reverse(2));
+        assert!(Reverse(2.0) < Reverse(1.0));
+        assert_eq!(Reverse(3).cmp(&Reverse(3)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_min_heap() {
+        let mut heap: MinHeap<i32> = BinaryHeap::new();
+        for x in [5, 1, 8, 3] {
+            heap.push(Reverse(x));
+        }
+        assert_eq!(heap.peek(), Some(&Reverse(1)));
+        assert_eq!(heap.pop(), Some(Reverse(1)));
+        assert_eq!(heap.pop(), Some(Reverse(3)));
+        assert_eq!(heap.pop().map(|Reverse(x)| x), Some(5));
+    }
+
+    #[test]
+    fn test_heap_by_comparator() {
+        let mut heap = BinaryHeapBy::new_by(|a: &f64, b: &f64| a.total_cmp(b));
+        for x in [0.5, -1.0, 2.25, 1.5] {
+            heap.push(x);
+        }
+        assert_eq!(heap.len(), 4);
+        assert_eq!(heap.peek(), Some(&2.25));
+        assert_eq!(heap.pop(), Some(2.25));
+        assert_eq!(heap.pop(), Some(1.5));
+
+        let heap = BinaryHeapBy::from_vec_by(vec![3, 1, 2], |a: &i32, b: &i32| b.cmp(a));
+        assert_eq!(heap.into_sorted_vec(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_heap_by_key() {
+        struct Task {
+            name: &'static str,
+            priority: u8,
+        }
+        let mut heap = BinaryHeapByKey::new_by_key(|t: &Task| t.priority);
+        heap.push(Task {
+            name: "low",
+            priority: 1,
+        });
+        heap.push(Task {
+            name: "high",
+            priority: 9,
+        });
+        heap.push(Task {
+            name: "mid",
+            priority: 5,
+        });
+        assert_eq!(heap.peek().map(|t| t.name), Some("high"));
+        let names: Vec<_> = core::iter::from_fn(|| heap.pop()).map(|t| t.name).collect();
+        assert_eq!(names, ["high", "mid", "low"]);
+        assert!(heap.is_empty());
+
+        let heap = BinaryHeapByKey::from_vec_by_key(vec![-3, 1, -2], |x: &i32| x.abs());
+        assert_eq!(heap.into_sorted_vec(), [1, -2, -3]);
+    }
+
+    #[test]
+    fn test_peek_mut() {
+        let mut heap = BinaryHeap::from(vec![5, 9, 2, 7]);
+        assert!(BinaryHeap::<i32>::new().peek_mut().is_none());
+
+        // Only reading through the guard leaves the heap alone.
+        assert_eq!(*heap.peek_mut().unwrap(), 9);
+
+        *heap.peek_mut().unwrap() = 1;
+        assert!(is_heap(heap.as_slice()));
+        assert_eq!(heap.peek(), Some(&7));
+
+        {
+            let mut top = heap.peek_mut().unwrap();
+            *top += 10;
+        }
+        assert_eq!(heap.into_sorted_vec(), [1, 2, 5, 17]);
+    }
+
+    #[test]
+    fn test_peek_mut_pop() {
+        let mut heap = BinaryHeap::from(vec![3, 8, 4]);
+        let mut top = heap.peek_mut().unwrap();
+        *top = 0;
+        assert_eq!(PeekMut::pop(top), 0);
+        assert_eq!(heap.len(), 2);
+        assert_eq!(heap.pop(), Some(4));
+        assert_eq!(heap.pop(), Some(3));
+    }
+
+    #[test]
+    fn test_peek_mut_leak() {
+        let mut heap = BinaryHeap::from(vec![3, 8, 4]);
+        let mut top = heap.peek_mut().unwrap();
+        *top = 0;
+        core::mem::forget(top);
+        assert_eq!(heap.len(), 1);
+        assert!(is_heap(heap.as_slice()));
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut heap = BinaryHeap::from(vec![4, 1, 3, 2]);
+        let mut drained: Vec<_> = heap.drain().collect();
+        drained.sort();
+        assert_eq!(drained, [1, 2, 3, 4]);
+        assert!(heap.is_empty());
+
+        let mut heap = BinaryHeap::from(vec![4, 1, 3]);
+        core::mem::forget(heap.drain());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_drain_sorted() {
+        let mut heap = BinaryHeap::from(vec![4, 1, 3, 2, 5]);
+        let mut drain = heap.drain_sorted();
+        assert_eq!(drain.len(), 5);
+        assert_eq!(drain.next(), Some(5));
+        assert_eq!(drain.next(), Some(4));
+        assert_eq!(drain.len(), 3);
+        drop(drain);
+        assert!(heap.is_empty());
+
+        heap.push(2);
+        heap.push(7);
+        assert_eq!(heap.drain_sorted().collect::<Vec<_>>(), [7, 2]);
+    }
+
+    #[test]
+    fn test_append() {
+        // Small into large: pushes.
+        let mut a = BinaryHeap::from((0..100).collect::<Vec<_>>());
+        let mut b = BinaryHeap::from(vec![1000, -1]);
+        a.append(&mut b);
+        assert!(b.is_empty());
+        assert_eq!(a.len(), 102);
+        assert!(is_heap(a.as_slice()));
+        assert_eq!(a.peek(), Some(&1000));
+
+        // Large into small: swaps the two first.
+        let mut a = BinaryHeap::from(vec![7]);
+        let mut b = BinaryHeap::from((0..50).collect::<Vec<_>>());
+        a.append(&mut b);
+        assert!(b.is_empty());
+        assert!(is_heap(a.as_slice()));
+        let mut expected: Vec<_> = (0..50).collect();
+        expected.push(7);
+        expected.sort();
+        assert_eq!(a.into_sorted_vec(), expected);
+
+        // Equal sizes: concatenates and rebuilds.
+        let mut a = BinaryHeap::from((0..64).collect::<Vec<_>>());
+        let mut b = BinaryHeap::from((64..128).collect::<Vec<_>>());
+        a.append(&mut b);
+        assert!(is_heap(a.as_slice()));
+        assert_eq!(a.into_sorted_vec(), (0..128).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = BinaryHeap::from(vec![1, 5, 3]);
+        let b = BinaryHeap::from(vec![4, 2]);
+        assert_eq!(a.merge(b).into_sorted_vec(), [1, 2, 3, 4, 5]);
+    }
+
+    fn is_dary_heap<T: Ord, const D: usize>(data: &[T]) -> bool {
+        (1..data.len()).all(|i| data[(i - 1) / D] >= data[i])
+    }
+
+    // Deterministic pseudo-random values with plenty of duplicates.
+    fn scrambled(n: usize) -> Vec<usize> {
+        (0..n).map(|i| (i * 7919 + 13) % 97).collect()
+    }
+
+    #[test]
+    fn test_dary_heap_push_pop() {
+        let mut heap: DaryHeap<usize, 4> = DaryHeap::new_with_capacity(200);
+        assert!(heap.is_empty());
+        for x in scrambled(200) {
+            heap.push(x);
+            assert!(is_dary_heap::<_, 4>(&heap.data));
+        }
+        assert_eq!(heap.len(), 200);
+
+        let mut expected = scrambled(200);
+        expected.sort_by(|a, b| b.cmp(a));
+        let mut popped = vec![];
+        while let Some(x) = heap.pop() {
+            popped.push(x);
+            assert!(is_dary_heap::<_, 4>(&heap.data));
+        }
+        assert_eq!(popped, expected);
+        assert!(heap.peek().is_none());
+    }
+
+    #[test]
+    fn test_dary_heap_from_vec_and_sort() {
+        let mut expected = scrambled(100);
+        expected.sort();
+
+        let heap: DaryHeap<_, 3> = DaryHeap::from(scrambled(100));
+        assert!(is_dary_heap::<_, 3>(&heap.data));
+        assert_eq!(heap.peek(), expected.last());
+        assert_eq!(heap.into_sorted_vec(), expected);
+
+        let heap: DaryHeap<_, 8> = DaryHeap::from(scrambled(100));
+        assert_eq!(heap.into_sorted_vec(), expected);
+    }
+
+    #[test]
+    fn test_binary_heap_is_two_ary() {
+        let heap = BinaryHeap::from(scrambled(50));
+        let dary: DaryHeap<_, 2> = DaryHeap::from(scrambled(50));
+        assert_eq!(heap.as_slice(), &dary.data[..]);
+    }
+
+    fn check_indexed<K: Ord + Clone, P: Ord>(heap: &IndexedBinaryHeap<K, P>) {
+        let priorities: Vec<_> = heap.data.iter().map(|(_, p)| p).collect();
+        assert!(is_heap(&priorities));
+        assert_eq!(heap.positions.len(), heap.data.len());
+        for (pos, (key, _)) in heap.data.iter().enumerate() {
+            assert_eq!(heap.positions[key], pos);
+        }
+    }
+
+    #[test]
+    fn test_indexed_heap() {
+        let mut heap = IndexedBinaryHeap::new();
+        assert!(heap.is_empty());
+        for (key, priority) in [("a", 3), ("b", 8), ("c", 1), ("d", 5), ("e", 7)] {
+            assert_eq!(heap.push(key, priority), None);
+            check_indexed(&heap);
+        }
+        assert_eq!(heap.len(), 5);
+        assert_eq!(heap.peek(), Some((&"b", &8)));
+        assert!(heap.contains_key(&"c"));
+        assert_eq!(heap.priority(&"d"), Some(&5));
+
+        // Raise and lower priorities.
+        assert_eq!(heap.change_priority(&"c", 10), Some(1));
+        check_indexed(&heap);
+        assert_eq!(heap.peek(), Some((&"c", &10)));
+        assert_eq!(heap.push("c", 0), Some(10));
+        check_indexed(&heap);
+        assert_eq!(heap.change_priority(&"z", 1), None);
+
+        assert_eq!(heap.remove(&"e"), Some(7));
+        assert_eq!(heap.remove(&"e"), None);
+        assert!(!heap.contains_key(&"e"));
+        check_indexed(&heap);
+
+        let mut popped = vec![];
+        while let Some((key, _)) = heap.pop() {
+            check_indexed(&heap);
+            popped.push(key);
+        }
+        assert_eq!(popped, ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn test_indexed_heap_dijkstra() {
+        // Shortest distances from node 0, decreasing keys as shorter paths show up.
+        let edges: [&[(usize, u32)]; 5] = [
+            &[(1, 4), (2, 1)],
+            &[(3, 1)],
+            &[(1, 2), (3, 5)],
+            &[(4, 3)],
+            &[],
+        ];
+        let mut dist = [u32::MAX; 5];
+        let mut queue = IndexedBinaryHeap::new();
+        dist[0] = 0;
+        queue.push(0, Reverse(0));
+        while let Some((node, Reverse(d))) = queue.pop() {
+            for &(next, weight) in edges[node] {
+                if d + weight < dist[next] {
+                    dist[next] = d + weight;
+                    queue.push(next, Reverse(d + weight));
+                }
+            }
+        }
+        assert_eq!(dist, [0, 3, 1, 4, 7]);
+    }
+
+    #[test]
+    fn test_clone_and_default() {
+        let heap = BinaryHeap::from(vec![2, 7, 4]);
+        let mut copy = heap.clone();
+        copy.push(9);
+        assert_eq!(heap.len(), 3);
+        assert_eq!(copy.pop(), Some(9));
+        assert_eq!(copy.into_sorted_vec(), heap.into_sorted_vec());
+
+        let empty: BinaryHeap<i32> = BinaryHeap::default();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut heap = BinaryHeap::new();
+        heap.push(1);
+        heap.push(3);
+        assert_eq!(std::format!("{heap:?}"), "[3, 1]");
+    }
+
+    #[test]
+    fn test_into_vec_via_from() {
+        let heap = BinaryHeap::from(vec![1, 5, 3]);
+        let mut data = Vec::from(heap);
+        data.sort();
+        assert_eq!(data, [1, 3, 5]);
+    }
+
+    fn check_handles<T: Ord>(heap: &HandleHeap<T>) {
+        let values: Vec<_> = heap.data.iter().map(|(v, _)| v).collect();
+        assert!(is_heap(&values));
+        for (pos, &(_, slot)) in heap.data.iter().enumerate() {
+            assert_eq!(heap.slots[slot].pos, Some(pos));
+        }
+    }
+
+    #[test]
+    fn test_handle_heap_update() {
+        let mut heap = HandleHeap::new();
+        let handles: Vec<_> = [5, 1, 8, 3, 9, 2]
+            .into_iter()
+            .map(|x| heap.push_with_handle(x))
+            .collect();
+        check_handles(&heap);
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&9));
+        assert_eq!(heap.get(handles[2]), Some(&8));
+
+        // Raise one element to the top and lower the top one to the bottom.
+        assert_eq!(heap.update(handles[1], 20), Ok(1));
+        check_handles(&heap);
+        assert_eq!(heap.peek(), Some(&20));
+        assert_eq!(heap.update(handles[4], 0), Ok(9));
+        check_handles(&heap);
+
+        assert_eq!(heap.remove(handles[0]), Some(5));
+        check_handles(&heap);
+        let mut popped = vec![];
+        while let Some(x) = heap.pop() {
+            check_handles(&heap);
+            popped.push(x);
+        }
+        assert_eq!(popped, [20, 8, 3, 2, 0]);
+    }
+
+    #[test]
+    fn test_handle_heap_stale_handles() {
+        let mut heap = HandleHeap::new();
+        let a = heap.push_with_handle(1);
+        assert_eq!(heap.pop(), Some(1));
+        assert_eq!(heap.get(a), None);
+        assert_eq!(heap.update(a, 5), Err(5));
+        assert_eq!(heap.remove(a), None);
+
+        // The slot is reused, but the old handle still doesn't see the new element.
+        let b = heap.push_with_handle(2);
+        assert_eq!(heap.slots.len(), 1);
+        assert_ne!(a, b);
+        assert_eq!(heap.get(a), None);
+        assert_eq!(heap.get(b), Some(&2));
+    }
+
+    #[test]
+    fn test_stable_heap_is_fifo_among_equals() {
+        // Ordered by priority only; the name tells the pushes apart.
+        #[derive(Debug)]
+        struct Task(u8, &'static str);
+        impl PartialEq for Task {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Task {}
+        impl PartialOrd for Task {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Task {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut heap = StableBinaryHeap::new();
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        for (i, name) in names.into_iter().enumerate() {
+            heap.push(Task(i as u8 % 2, name));
+        }
+        assert_eq!(heap.len(), 8);
+        assert_eq!(heap.peek().map(|t| t.1), Some("b"));
+        let order: Vec<_> = core::iter::from_fn(|| heap.pop()).map(|t| t.1).collect();
+        assert_eq!(order, ["b", "d", "f", "h", "a", "c", "e", "g"]);
+        assert!(heap.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut heap = BinaryHeap::new();
+        heap.push(1);
+        heap.push(5);
+        heap.push(3);
+        let json = serde_json::to_string(&heap).unwrap();
+
+        let mut back: BinaryHeap<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 3);
+        assert_eq!(back.pop(), Some(5));
+        assert_eq!(back.pop(), Some(3));
+        assert_eq!(back.pop(), Some(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_unordered_sequence() {
+        let mut heap: BinaryHeap<i32> = serde_json::from_str("[1, 9, 4, 7]").unwrap();
+        assert_eq!(heap.peek(), Some(&9));
+        assert_eq!(heap.pop(), Some(9));
+        assert_eq!(heap.pop(), Some(7));
+    }
+}
@@ -0,0 +1,69 @@
+//! Heap algorithms on plain slices, for callers who own a `Vec` (or an array) and don't want
+//! to move it into a [`BinaryHeap`](super::BinaryHeap). They run on the same sift code as
+//! the heap itself, and a slice heapified here has exactly the layout a `BinaryHeap` uses.
+
+use super::{first_violation, rebuild, sort_heap};
+
+/// Rearranges `data` into a max-heap in O(n).
+pub fn heapify<T: Ord>(data: &mut [T]) {
+    rebuild::<2, _, _>(data, &mut T::lt);
+}
+
+/// Sorts `data` in ascending order in O(n log n), in place and without allocating. The sort
+/// is not stable.
+pub fn heapsort<T: Ord>(data: &mut [T]) {
+    heapify(data);
+    sort_heap::<2, _, _>(data, &mut T::lt);
+}
+
+/// Returns whether no element of `data` is greater than its parent.
+pub fn is_heap<T: Ord>(data: &[T]) -> bool {
+    first_violation::<2, _, _>(data, &mut T::lt).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_heap::BinaryHeap;
+    use std::vec::Vec;
+
+    fn scrambled(n: usize) -> Vec<usize> {
+        (0..n).map(|i| (i * 7919 + 13) % 97).collect()
+    }
+
+    #[test]
+    fn test_heapify() {
+        let mut data = scrambled(100);
+        assert!(!is_heap(&data));
+        heapify(&mut data);
+        assert!(is_heap(&data));
+
+        // Same layout as a heap built from the same vector.
+        let heap = BinaryHeap::from(scrambled(100));
+        assert_eq!(heap.as_slice(), &data[..]);
+    }
+
+    #[test]
+    fn test_heapsort() {
+        let mut data = scrambled(200);
+        let mut expected = data.clone();
+        expected.sort();
+        heapsort(&mut data);
+        assert_eq!(data, expected);
+
+        let mut array = [3, 1, 2];
+        heapsort(&mut array);
+        assert_eq!(array, [1, 2, 3]);
+
+        let mut empty: [i32; 0] = [];
+        heapsort(&mut empty);
+        assert!(is_heap(&empty));
+    }
+
+    #[test]
+    fn test_is_heap() {
+        assert!(is_heap(&[9, 5, 8, 1, 5, 8]));
+        assert!(!is_heap(&[9, 5, 8, 6]));
+        assert!(is_heap(&[1]));
+    }
+}
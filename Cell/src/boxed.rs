@@ -0,0 +1,365 @@
+use alloc::alloc::{handle_alloc_error, Allocator, Global, Layout};
+use core::{
+    any::Any,
+    borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::{PhantomData, Unsize},
+    mem::ManuallyDrop,
+    ops::{CoerceUnsized, Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+/// A pointer that uniquely owns a heap allocation holding a `T`.
+///
+/// `Box::new` moves the value into memory from the box's [`Allocator`], and dropping the box
+/// drops the value and frees the memory. Ownership can also leave the box as a raw pointer
+/// with [`Box::into_raw`] and come back with [`Box::from_raw`], which is how the nodes of
+/// [`LinkedList`](crate::linkedlist::LinkedList) and the allocation of
+/// [`Rc`](crate::rc::Rc) are managed.
+///
+/// `T` can be unsized. A `Box<T>` coerces to a `Box<dyn Trait>` for any trait `T`
+/// implements, or from `Box<[T; N]>` to `Box<[T]>`, just like the standard library's box:
+///
+/// ```
+/// use Cell::boxed::Box;
+///
+/// let shapes: [Box<dyn Fn(f64) -> f64>; 2] = [Box::new(|r| r * r), Box::new(|r| 4.0 * r)];
+/// assert_eq!(shapes.iter().map(|area| area(2.0)).sum::<f64>(), 12.0);
+/// ```
+pub struct Box<T: ?Sized, A: Allocator = Global> {
+    ptr: NonNull<T>,
+    alloc: A,
+    // We own a `T`, so dropping a box may drop a `T`.
+    marker: PhantomData<T>,
+}
+
+// SAFETY: a box owns its value like a plain `T` does.
+unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for Box<T, A> {}
+unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for Box<T, A> {}
+
+// Moving the box never moves the value behind it.
+impl<T: ?Sized, A: Allocator> Unpin for Box<T, A> {}
+
+// This is what lets `Box<T>` turn into `Box<dyn Trait>` or `Box<[T]>`: the pointer gets the
+// vtable or length attached, and the allocation stays the same.
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: Allocator> CoerceUnsized<Box<U, A>> for Box<T, A> {}
+
+impl<T> Box<T> {
+    pub fn new(x: T) -> Self {
+        Self::new_in(x, Global)
+    }
+}
+
+impl<T: ?Sized> Box<T> {
+    /// Takes ownership of an allocation made by a `Box<T>` back from [`Box::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must come from `Box::into_raw`, and nothing else may own it any more.
+    pub unsafe fn from_raw(raw: *mut T) -> Self {
+        unsafe { Self::from_raw_in(raw, Global) }
+    }
+
+    /// Gives up ownership of the value, which is not dropped. The caller becomes
+    /// responsible for it, usually by turning the pointer back into a box with
+    /// [`Box::from_raw`] eventually.
+    pub fn into_raw(b: Self) -> *mut T {
+        Self::into_raw_with_allocator(b).0
+    }
+}
+
+impl<T, A: Allocator> Box<T, A> {
+    /// Moves `x` into memory from `alloc`.
+    pub fn new_in(x: T, alloc: A) -> Self {
+        let layout = Layout::new::<T>();
+        // Zero-sized values need no memory at all.
+        let ptr: NonNull<T> = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            match alloc.allocate(layout) {
+                Ok(ptr) => ptr.cast(),
+                Err(_) => handle_alloc_error(layout),
+            }
+        };
+        // SAFETY: the memory is fresh and fits a `T`.
+        unsafe { ptr.as_ptr().write(x) };
+        Self {
+            ptr,
+            alloc,
+            marker: PhantomData,
+        }
+    }
+
+    /// Moves the value out of the box and frees the allocation.
+    pub fn into_inner(b: Self) -> T {
+        let (raw, alloc) = Self::into_raw_with_allocator(b);
+        // SAFETY: we own the value; reading it leaves the memory to be freed without
+        // dropping it again.
+        let value = unsafe { raw.read() };
+        let layout = Layout::new::<T>();
+        if layout.size() != 0 {
+            // SAFETY: `raw` was allocated by `alloc` with this layout.
+            unsafe { alloc.deallocate(NonNull::new_unchecked(raw).cast(), layout) };
+        }
+        value
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Box<T, A> {
+    /// Like [`Box::from_raw`], for a box that allocated from `alloc`.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must come from [`Box::into_raw_with_allocator`] (or `into_raw`) of a box that
+    /// used `alloc`, or an equivalent allocator, and nothing else may own it any more.
+    pub unsafe fn from_raw_in(raw: *mut T, alloc: A) -> Self {
+        Self {
+            // SAFETY: a box's pointer is never null.
+            ptr: unsafe { NonNull::new_unchecked(raw) },
+            alloc,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn into_raw_with_allocator(b: Self) -> (*mut T, A) {
+        let b = ManuallyDrop::new(b);
+        // SAFETY: `b` is never dropped, so the allocator is moved out exactly once.
+        (b.ptr.as_ptr(), unsafe { ptr::read(&b.alloc) })
+    }
+
+    /// Leaks the box, returning a reference to its value that lives as long as the
+    /// allocator does. Neither the value nor the memory is freed.
+    pub fn leak<'a>(b: Self) -> &'a mut T
+    where
+        A: 'a,
+    {
+        // SAFETY: the box is never dropped, so the value stays valid for good.
+        unsafe { &mut *ManuallyDrop::new(b).ptr.as_ptr() }
+    }
+
+    pub fn allocator(b: &Self) -> &A {
+        &b.alloc
+    }
+}
+
+impl<A: Allocator> Box<dyn Any, A> {
+    /// Turns a `Box<dyn Any>` back into the box of the concrete type it was made from, or
+    /// hands it back if it holds some other type.
+    pub fn downcast<T: Any>(self) -> Result<Box<T, A>, Self> {
+        if (*self).is::<T>() {
+            let (raw, alloc) = Self::into_raw_with_allocator(self);
+            // SAFETY: the value is a `T`, so this is the allocation of a `Box<T>`.
+            Ok(unsafe { Box::from_raw_in(raw.cast::<T>(), alloc) })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for Box<T, A> {
+    fn drop(&mut self) {
+        // SAFETY: the box owns a live value that was allocated with its layout, and the
+        // layout has to be read before the value is dropped.
+        unsafe {
+            let layout = Layout::for_value(self.ptr.as_ref());
+            ptr::drop_in_place(self.ptr.as_ptr());
+            if layout.size() != 0 {
+                self.alloc.deallocate(self.ptr.cast(), layout);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Deref for Box<T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the box owns a live value, and `&self` keeps it from being mutated.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> DerefMut for Box<T, A> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the box owns the value, and `&mut self` makes the access exclusive.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> From<T> for Box<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> From<alloc::vec::Vec<T>> for Box<[T]> {
+    fn from(vec: alloc::vec::Vec<T>) -> Self {
+        // The standard box uses the same global allocator and layout, so its allocation can
+        // simply change hands.
+        let raw = alloc::boxed::Box::into_raw(vec.into_boxed_slice());
+        // SAFETY: the slice was allocated by `Global` with the layout of `[T]`.
+        unsafe { Self::from_raw(raw) }
+    }
+}
+
+impl From<&str> for Box<str> {
+    fn from(s: &str) -> Self {
+        let raw = alloc::boxed::Box::into_raw(alloc::boxed::Box::<str>::from(s));
+        // SAFETY: as above.
+        unsafe { Self::from_raw(raw) }
+    }
+}
+
+impl<T: Default> Default for Box<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for Box<T, A> {
+    fn clone(&self) -> Self {
+        Self::new_in((**self).clone(), self.alloc.clone())
+    }
+}
+
+impl<T: ?Sized + PartialEq, A: Allocator> PartialEq for Box<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq, A: Allocator> Eq for Box<T, A> {}
+
+impl<T: ?Sized + PartialOrd, A: Allocator> PartialOrd for Box<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Ord, A: Allocator> Ord for Box<T, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Hash, A: Allocator> Hash for Box<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: ?Sized, A: Allocator> AsRef<T> for Box<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> AsMut<T> for Box<T, A> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Borrow<T> for Box<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized, A: Allocator> BorrowMut<T> for Box<T, A> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+// So that a `Box<dyn Iterator>` is an iterator itself.
+impl<I: Iterator + ?Sized, A: Allocator> Iterator for Box<I, A> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        (**self).next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (**self).size_hint()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, A: Allocator> fmt::Debug for Box<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display, A: Allocator> fmt::Display for Box<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized, A: Allocator> fmt::Pointer for Box<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&self.ptr, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, rc::Rc as StdRc, string::ToString, vec};
+
+    #[test]
+    fn test_deref_and_into_inner() {
+        let mut b = Box::new(5);
+        *b += 1;
+        assert_eq!(*b, 6);
+        assert_eq!(format!("{b:?} {b}"), "6 6");
+        assert_eq!(Box::into_inner(b), 6);
+        let () = Box::into_inner(Box::new(()));
+    }
+
+    #[test]
+    fn test_raw_round_trip_keeps_value_alive() {
+        let value = StdRc::new(());
+        let raw = Box::into_raw(Box::new(StdRc::clone(&value)));
+        assert_eq!(StdRc::strong_count(&value), 2);
+        let b = unsafe { Box::from_raw(raw) };
+        assert_eq!(StdRc::strong_count(&value), 2);
+        drop(b);
+        assert_eq!(StdRc::strong_count(&value), 1);
+
+        let leaked: &'static mut i32 = Box::leak(Box::new(1));
+        *leaked += 1;
+        drop(unsafe { Box::from_raw(leaked) });
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        let values: vec::Vec<Box<dyn fmt::Display>> = vec![Box::new(1), Box::new("two")];
+        let text: vec::Vec<_> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(text, ["1", "two"]);
+
+        let iter: Box<dyn Iterator<Item = u32>> = Box::new((1..4).map(|x| x * 2));
+        assert_eq!(iter.sum::<u32>(), 12);
+
+        let any: Box<dyn Any> = Box::new(StdRc::new(7));
+        let any = any.downcast::<i32>().unwrap_err();
+        assert_eq!(**any.downcast::<StdRc<i32>>().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_slices_and_strings() {
+        let slice: Box<[i32]> = Box::new([1, 2, 3]);
+        assert_eq!(slice.len(), 3);
+        let from_vec = Box::<[i32]>::from(vec![1, 2, 3]);
+        assert_eq!(slice, from_vec);
+        let empty: Box<[StdRc<()>]> = Box::new([]);
+        assert!(empty.is_empty());
+        let s = Box::<str>::from("boxed");
+        assert_eq!(&*s, "boxed");
+        assert!(Box::new("a") < Box::new("b"));
+        assert_eq!(Box::new(vec![1]).clone(), Box::new(vec![1]));
+    }
+}
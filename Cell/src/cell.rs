@@ -1,8 +1,11 @@
-use std::fmt::Debug;
+use core::fmt::Debug;
 
 use crate::unsafecell::UnsafeCell;
 
+// Transparent over `UnsafeCell<T>`, which is transparent over core's `UnsafeCell<T>`, so a
+// `Cell<T>` has the layout of a `T`. `from_mut` and `as_slice_of_cells` rely on it.
 #[derive(Debug)]
+#[repr(transparent)]
 pub struct Cell<T: ?Sized> {
     value: UnsafeCell<T>,
 }
@@ -75,6 +78,15 @@ impl<T: Copy> Cell<T> {
         // but `Cell` is not `Sync` so this is safe
         unsafe { *self.value.get() }
     }
+
+    // Replaces the contained value with `f` applied to it, and returns the new value.
+    // `f` gets a copy, so it can't observe the cell mid-update even if it reads it.
+
+    pub fn update(&self, f: impl FnOnce(T) -> T) -> T {
+        let new = f(self.get());
+        self.set(new);
+        new
+    }
 }
 
 impl<T: ?Sized> Cell<T> {
@@ -92,12 +104,29 @@ impl<T: ?Sized> Cell<T> {
     }
 
     // Returns a `&Cell<T>` from a `&mut T`
+    // For a slice, `Cell::from_mut(&mut buf[..])` gives a `&Cell<[T]>`, see `as_slice_of_cells`.
 
     pub fn from_mut(t: &mut T) -> &Cell<T> {
+        // SAFETY: `Cell<T>` has the layout of `T`, and the `&mut` guarantees no other
+        // reference to `t` exists while the returned one lives.
         unsafe { &*(t as *mut T as *const Cell<T>) }
     }
 }
 
+impl<T> Cell<[T]> {
+    // Returns a `&[Cell<T>]` from a `&Cell<[T]>`, so every element can be set on its own.
+
+    pub fn as_slice_of_cells(&self) -> &[Cell<T>] {
+        // SAFETY: `Cell<[T]>` has the layout of `[T]`, and `[Cell<T>]` is laid out like
+        // `[T]` too, since each `Cell<T>` is laid out like a `T`. The cast keeps the length,
+        // and the whole slice sits in core's `UnsafeCell`, so the element cells may still be
+        // written through the shared reference.
+        // Sharing the elements' cells is fine for the same reason sharing the slice's cell
+        // is: `Cell` is `!Sync`, so they are all used from this thread.
+        unsafe { &*(self as *const Cell<[T]> as *const [Cell<T>]) }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,6 +153,14 @@ mod tests {
         assert_eq!(c.get(), 40);
     }
 
+    #[test]
+    fn test_update() {
+        let c = Cell::new(5);
+        assert_eq!(c.update(|x| x + 1), 6);
+        assert_eq!(c.update(|x| x * 2), 12);
+        assert_eq!(c.get(), 12);
+    }
+
     #[test]
     fn test_into_inner() {
         let c = Cell::new(50);
@@ -179,4 +216,16 @@ mod tests {
         c.set(130);
         assert_eq!(value, 130);
     }
+
+    #[test]
+    fn test_as_slice_of_cells() {
+        let mut buf = [1, 2, 3, 4];
+        let cells = Cell::from_mut(&mut buf[..]).as_slice_of_cells();
+        assert_eq!(cells.len(), 4);
+        // Prefix sums in place, reading one element while writing the next.
+        for (prev, next) in cells.iter().zip(&cells[1..]) {
+            next.set(prev.get() + next.get());
+        }
+        assert_eq!(buf, [1, 3, 6, 10]);
+    }
 }
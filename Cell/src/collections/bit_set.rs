@@ -0,0 +1,326 @@
+//! A set of small integers, stored as a bit vector.
+
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{BitAnd, BitOr, BitXor, Sub},
+};
+
+use super::bit_vec::{BitVec, IterOnes};
+
+/// A set of `usize`s where element `i` is bit `i` of a [`BitVec`].
+///
+/// Membership tests and updates are a single bit operation, and the set operations work
+/// 64 elements at a time. The memory used grows with the largest element rather than with
+/// the number of elements, so this suits dense sets of small numbers, such as the ids of
+/// nodes in a graph.
+///
+/// ```
+/// use Cell::collections::BitSet;
+///
+/// let evens: BitSet = (0..10).step_by(2).collect();
+/// let small: BitSet = (0..5).collect();
+/// assert_eq!((&evens & &small).iter().collect::<Vec<_>>(), [0, 2, 4]);
+/// assert_eq!((&small - &evens).iter().collect::<Vec<_>>(), [1, 3]);
+/// ```
+#[derive(Clone, Default)]
+pub struct BitSet {
+    bits: BitVec,
+    len: usize,
+}
+
+impl BitSet {
+    /// Creates an empty set without allocating.
+    pub const fn new() -> Self {
+        Self {
+            bits: BitVec::new(),
+            len: 0,
+        }
+    }
+
+    /// Creates an empty set with room for the elements below `capacity`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bits: BitVec::with_capacity(capacity),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        self.bits.get(value).unwrap_or(false)
+    }
+
+    /// Adds `value` to the set, returning `true` if it was not there yet.
+    pub fn insert(&mut self, value: usize) -> bool {
+        if value >= self.bits.len() {
+            self.bits.resize(value + 1, false);
+        }
+        let new = !self.contains(value);
+        if new {
+            self.bits.set(value, true);
+            self.len += 1;
+        }
+        new
+    }
+
+    /// Removes `value` from the set, returning `true` if it was there.
+    pub fn remove(&mut self, value: usize) -> bool {
+        let present = self.contains(value);
+        if present {
+            self.bits.set(value, false);
+            self.len -= 1;
+        }
+        present
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+        self.len = 0;
+    }
+
+    /// Iterates over the elements in increasing order.
+    pub fn iter(&self) -> IterOnes<'_> {
+        self.bits.iter_ones()
+    }
+
+    /// Returns the bits the set is stored in.
+    pub fn as_bit_vec(&self) -> &BitVec {
+        &self.bits
+    }
+
+    /// Adds every element of `other` to this set.
+    pub fn union_with(&mut self, other: &BitSet) {
+        self.grow_to(other);
+        self.zip_words(other, |a, b| a | b);
+    }
+
+    /// Keeps only the elements that are also in `other`.
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        self.zip_words(other, |a, b| a & b);
+    }
+
+    /// Removes every element of `other` from this set.
+    pub fn difference_with(&mut self, other: &BitSet) {
+        self.zip_words(other, |a, b| a & !b);
+    }
+
+    /// Keeps the elements that are in exactly one of the two sets.
+    pub fn symmetric_difference_with(&mut self, other: &BitSet) {
+        self.grow_to(other);
+        self.zip_words(other, |a, b| a ^ b);
+    }
+
+    pub fn is_disjoint(&self, other: &BitSet) -> bool {
+        self.words_zipped(other).all(|(a, b)| a & b == 0)
+    }
+
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.len <= other.len && self.words_zipped(other).all(|(a, b)| a & !b == 0)
+    }
+
+    pub fn is_superset(&self, other: &BitSet) -> bool {
+        other.is_subset(self)
+    }
+
+    fn grow_to(&mut self, other: &BitSet) {
+        if self.bits.len() < other.bits.len() {
+            self.bits.resize(other.bits.len(), false);
+        }
+    }
+
+    // Replaces every word of this set with `f(word, other's word)`, treating words past
+    // the end of `other` as zero, and recounts the elements. Each `f` maps two zero bits to
+    // a zero bit, so the bits past the length stay clear.
+    fn zip_words(&mut self, other: &BitSet, f: impl Fn(u64, u64) -> u64) {
+        let theirs = other.bits.as_words();
+        for (i, word) in self.bits.as_mut_words().iter_mut().enumerate() {
+            *word = f(*word, theirs.get(i).copied().unwrap_or(0));
+        }
+        self.len = self.bits.count_ones();
+    }
+
+    // Pairs up the words of both sets, padding the shorter one with zeros.
+    fn words_zipped<'a>(&'a self, other: &'a BitSet) -> impl Iterator<Item = (u64, u64)> + 'a {
+        let (ours, theirs) = (self.bits.as_words(), other.bits.as_words());
+        (0..ours.len().max(theirs.len())).map(move |i| {
+            (
+                ours.get(i).copied().unwrap_or(0),
+                theirs.get(i).copied().unwrap_or(0),
+            )
+        })
+    }
+
+    // The words up to the last non-zero one, which is all that two equal sets share: one
+    // of them may have grown further before its largest elements were removed.
+    fn significant_words(&self) -> &[u64] {
+        let words = self.bits.as_words();
+        let len = words
+            .iter()
+            .rposition(|&word| word != 0)
+            .map_or(0, |i| i + 1);
+        &words[..len]
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &BitSet) -> bool {
+        self.significant_words() == other.significant_words()
+    }
+}
+
+impl Eq for BitSet {}
+
+impl Hash for BitSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant_words().hash(state)
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<usize> for BitSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|value| {
+            self.insert(value);
+        });
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = usize;
+    type IntoIter = IterOnes<'a>;
+
+    fn into_iter(self) -> IterOnes<'a> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl BitOr<&BitSet> for &BitSet {
+    type Output = BitSet;
+
+    /// Returns the union of `self` and `rhs` as a new set.
+    fn bitor(self, rhs: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.union_with(rhs);
+        set
+    }
+}
+
+impl BitAnd<&BitSet> for &BitSet {
+    type Output = BitSet;
+
+    /// Returns the intersection of `self` and `rhs` as a new set.
+    fn bitand(self, rhs: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.intersect_with(rhs);
+        set
+    }
+}
+
+impl Sub<&BitSet> for &BitSet {
+    type Output = BitSet;
+
+    /// Returns the difference of `self` and `rhs` as a new set.
+    fn sub(self, rhs: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.difference_with(rhs);
+        set
+    }
+}
+
+impl BitXor<&BitSet> for &BitSet {
+    type Output = BitSet;
+
+    /// Returns the symmetric difference of `self` and `rhs` as a new set.
+    fn bitxor(self, rhs: &BitSet) -> BitSet {
+        let mut set = self.clone();
+        set.symmetric_difference_with(rhs);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, vec::Vec};
+
+    fn set(values: &[usize]) -> BitSet {
+        values.iter().copied().collect()
+    }
+
+    fn elements(set: &BitSet) -> Vec<usize> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn test_insert_remove_contains() {
+        let mut set = BitSet::new();
+        assert!(set.insert(100));
+        assert!(!set.insert(100));
+        assert!(set.insert(3));
+        assert!(set.contains(3) && set.contains(100));
+        assert!(!set.contains(4) && !set.contains(1000));
+        assert_eq!(set.len(), 2);
+        assert!(set.remove(100));
+        assert!(!set.remove(100));
+        assert!(!set.remove(1000));
+        assert_eq!(elements(&set), [3]);
+        assert_eq!(format!("{set:?}"), "{3}");
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = set(&[1, 2, 64, 200]);
+        let b = set(&[2, 3, 64]);
+        assert_eq!(elements(&(&a | &b)), [1, 2, 3, 64, 200]);
+        assert_eq!(elements(&(&a & &b)), [2, 64]);
+        assert_eq!(elements(&(&a - &b)), [1, 200]);
+        assert_eq!(elements(&(&b - &a)), [3]);
+        assert_eq!(elements(&(&a ^ &b)), [1, 3, 200]);
+        assert_eq!((&a ^ &b).len(), 3);
+        assert_eq!((&b & &a).len(), 2);
+    }
+
+    #[test]
+    fn test_subset_and_disjoint() {
+        let a = set(&[1, 70]);
+        let b = set(&[1, 2, 70, 300]);
+        assert!(a.is_subset(&b) && b.is_superset(&a));
+        assert!(!b.is_subset(&a));
+        assert!(BitSet::new().is_subset(&a));
+        assert!(set(&[0, 128]).is_disjoint(&set(&[1, 64])));
+        assert!(!a.is_disjoint(&b));
+    }
+
+    #[test]
+    fn test_equality_ignores_capacity() {
+        let mut grown = set(&[5, 500]);
+        grown.remove(500);
+        assert_eq!(grown, set(&[5]));
+        assert_ne!(grown, set(&[6]));
+
+        use std::hash::BuildHasher;
+        let hasher = std::hash::RandomState::new();
+        assert_eq!(hasher.hash_one(&grown), hasher.hash_one(set(&[5])));
+    }
+}
@@ -0,0 +1,381 @@
+//! A vector of bits, packed 64 to a word.
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, iter::FusedIterator};
+
+const BITS: usize = u64::BITS as usize;
+
+/// A growable array of `bool`s that takes one bit per element.
+///
+/// Bit `i` lives in bit `i % 64` of word `i / 64`. Bits of the last word past `len` are
+/// always zero, so whole-word operations such as [`count_ones`](BitVec::count_ones) and
+/// comparisons can work a word at a time without masking.
+///
+/// ```
+/// use Cell::collections::BitVec;
+///
+/// let mut bits: BitVec = [true, false, true].into_iter().collect();
+/// bits.push(true);
+/// bits.set(1, true);
+/// assert_eq!(bits.count_ones(), 4);
+/// assert_eq!(bits.rank(3), 3);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitVec {
+    words: Vec<u64>,
+    len: usize,
+}
+
+// Splits a bit index into its word index and the mask of the bit within that word.
+fn locate(index: usize) -> (usize, u64) {
+    (index / BITS, 1 << (index % BITS))
+}
+
+impl BitVec {
+    /// Creates an empty bit vector without allocating.
+    pub const fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Creates an empty bit vector with room for `bits` bits.
+    pub fn with_capacity(bits: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(bits.div_ceil(BITS)),
+            len: 0,
+        }
+    }
+
+    /// Creates a bit vector of `len` bits, all set to `bit`.
+    pub fn from_elem(len: usize, bit: bool) -> Self {
+        let mut bits = Self {
+            words: vec![if bit { u64::MAX } else { 0 }; len.div_ceil(BITS)],
+            len,
+        };
+        bits.clear_unused();
+        bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bits the vector can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.words.capacity() * BITS
+    }
+
+    /// Returns the bit at `index`, or `None` if it is out of bounds.
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len {
+            return None;
+        }
+        let (word, mask) = locate(index);
+        Some(self.words[word] & mask != 0)
+    }
+
+    /// Sets the bit at `index` to `bit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, bit: bool) {
+        assert!(
+            index < self.len,
+            "bit index {index} out of bounds for BitVec of length {}",
+            self.len
+        );
+        let (word, mask) = locate(index);
+        if bit {
+            self.words[word] |= mask;
+        } else {
+            self.words[word] &= !mask;
+        }
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(BITS) {
+            self.words.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, bit);
+    }
+
+    pub fn pop(&mut self) -> Option<bool> {
+        let bit = self.get(self.len.checked_sub(1)?)?;
+        self.truncate(self.len - 1);
+        Some(bit)
+    }
+
+    /// Shortens the vector to `len` bits. Does nothing if it is not longer than that.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+            self.words.truncate(len.div_ceil(BITS));
+            self.clear_unused();
+        }
+    }
+
+    /// Changes the length to `len`, filling any new bits with `bit`.
+    pub fn resize(&mut self, len: usize, bit: bool) {
+        if len <= self.len {
+            self.truncate(len);
+            return;
+        }
+        let fill = if bit { u64::MAX } else { 0 };
+        if bit && !self.len.is_multiple_of(BITS) {
+            // Set the unused tail of the last word, which is about to be used.
+            *self.words.last_mut().unwrap() |= u64::MAX << (self.len % BITS);
+        }
+        self.words.resize(len.div_ceil(BITS), fill);
+        self.len = len;
+        self.clear_unused();
+    }
+
+    /// Removes every bit.
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+
+    /// Returns the number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    /// Returns the number of set bits before `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length.
+    pub fn rank(&self, index: usize) -> usize {
+        assert!(
+            index <= self.len,
+            "bit index {index} out of bounds for BitVec of length {}",
+            self.len
+        );
+        let (word, mask) = locate(index);
+        let full: usize = self.words[..word]
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum();
+        // `index` may be the length itself, right at the start of a word that does not exist.
+        let partial = self.words.get(word).map_or(0, |bits| bits & (mask - 1));
+        full + partial.count_ones() as usize
+    }
+
+    /// Returns the words the bits are packed into; bits past the length are zero.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    // Whole-word access for `BitSet`, which keeps the unused bits zero itself.
+    pub(super) fn as_mut_words(&mut self) -> &mut [u64] {
+        &mut self.words
+    }
+
+    /// Iterates over the bits, front to back.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            bits: self,
+            front: 0,
+            back: self.len,
+        }
+    }
+
+    /// Iterates over the indices of the set bits, in increasing order.
+    pub fn iter_ones(&self) -> IterOnes<'_> {
+        IterOnes {
+            words: &self.words,
+            index: 0,
+            current: self.words.first().copied().unwrap_or(0),
+        }
+    }
+
+    fn clear_unused(&mut self) {
+        if !self.len.is_multiple_of(BITS) {
+            *self.words.last_mut().unwrap() &= (1 << (self.len % BITS)) - 1;
+        }
+    }
+}
+
+impl FromIterator<bool> for BitVec {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bits = BitVec::new();
+        bits.extend(iter);
+        bits
+    }
+}
+
+impl Extend<bool> for BitVec {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.words
+            .reserve((self.len + iter.size_hint().0).div_ceil(BITS) - self.words.len());
+        iter.for_each(|bit| self.push(bit));
+    }
+}
+
+impl<'a> IntoIterator for &'a BitVec {
+    type Item = bool;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for BitVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the bits of a [`BitVec`].
+#[derive(Clone)]
+pub struct Iter<'a> {
+    bits: &'a BitVec,
+    front: usize,
+    back: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.bits.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<bool> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.bits.get(self.back)
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+/// Iterator over the indices of the set bits of a [`BitVec`].
+///
+/// Skips a whole word at a time while it has no bits left.
+#[derive(Clone)]
+pub struct IterOnes<'a> {
+    words: &'a [u64],
+    // The word being read, and its bits that have not been yielded yet.
+    index: usize,
+    current: u64,
+}
+
+impl Iterator for IterOnes<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.index += 1;
+            self.current = *self.words.get(self.index)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // Clear the lowest set bit.
+        self.current &= self.current - 1;
+        Some(self.index * BITS + bit)
+    }
+}
+
+impl FusedIterator for IterOnes<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, vec::Vec};
+
+    #[test]
+    fn test_push_get_set_pop() {
+        let mut bits = BitVec::new();
+        for i in 0..200 {
+            bits.push(i % 3 == 0);
+        }
+        assert_eq!(bits.len(), 200);
+        assert_eq!(bits.get(63), Some(true));
+        assert_eq!(bits.get(64), Some(false));
+        assert_eq!(bits.get(200), None);
+        bits.set(64, true);
+        assert_eq!(bits.get(64), Some(true));
+        assert_eq!(bits.pop(), Some(false));
+        assert_eq!(bits.pop(), Some(true));
+        assert_eq!(bits.len(), 198);
+        assert_eq!(bits.count_ones(), 67);
+        assert_eq!(bits.count_zeros(), 131);
+    }
+
+    #[test]
+    fn test_unused_bits_stay_zero() {
+        let mut bits = BitVec::from_elem(70, true);
+        assert_eq!(bits.as_words(), [u64::MAX, 0b11_1111]);
+        bits.truncate(65);
+        assert_eq!(bits.as_words(), [u64::MAX, 1]);
+        bits.resize(130, true);
+        assert_eq!(bits.count_ones(), 130);
+        bits.resize(140, false);
+        assert_eq!(bits.count_ones(), 130);
+        assert_eq!(bits, {
+            let mut other = BitVec::from_elem(130, true);
+            other.resize(140, false);
+            other
+        });
+    }
+
+    #[test]
+    fn test_rank() {
+        let bits: BitVec = (0..130).map(|i| i % 2 == 1).collect();
+        assert_eq!(bits.rank(0), 0);
+        assert_eq!(bits.rank(2), 1);
+        assert_eq!(bits.rank(64), 32);
+        assert_eq!(bits.rank(128), 64);
+        assert_eq!(bits.rank(130), 65);
+        assert_eq!(BitVec::from_elem(128, true).rank(128), 128);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let bits: BitVec = (0..150).map(|i| i % 50 == 7 || i == 149).collect();
+        assert_eq!(bits.iter_ones().collect::<Vec<_>>(), [7, 57, 107, 149]);
+        assert_eq!(bits.iter().filter(|&bit| bit).count(), 4);
+        assert_eq!(bits.iter().rev().position(|bit| bit), Some(0));
+        assert_eq!(bits.iter().len(), 150);
+        assert_eq!(BitVec::new().iter_ones().next(), None);
+        assert_eq!(format!("{:?}", BitVec::from_elem(2, true)), "[true, true]");
+    }
+
+    #[test]
+    #[should_panic(expected = "bit index 3 out of bounds for BitVec of length 3")]
+    fn test_set_out_of_bounds_panics() {
+        BitVec::from_elem(3, false).set(3, true);
+    }
+}
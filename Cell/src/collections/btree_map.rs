@@ -0,0 +1,1477 @@
+//! An ordered map stored as a B-tree.
+
+use alloc::vec::{self, Vec};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem,
+    ops::{Bound, Index, RangeBounds},
+    ptr::NonNull,
+};
+
+// The minimum degree: every node but the root has between `B - 1` and `2 * B - 1` keys.
+const B: usize = 6;
+const CAPACITY: usize = 2 * B - 1;
+const MIN_LEN: usize = B - 1;
+
+/// A map that keeps its keys sorted, stored as a B-tree.
+///
+/// Each node holds up to eleven sorted keys with their values and, unless it is a leaf, one
+/// more child than keys; the keys of child `i` lie between keys `i - 1` and `i`. Every leaf
+/// is at the same depth and every node but the root is at least half full, so lookups,
+/// insertions and removals are O(log n) while touching far fewer nodes than a binary tree
+/// would.
+///
+/// Because the keys are ordered, the map can also iterate over a [`range`](Self::range) of
+/// them, from either end, and find its smallest and largest entries.
+#[derive(Clone)]
+pub struct BTreeMap<K, V> {
+    root: Node<K, V>,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Node<K, V> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    // Empty for a leaf, otherwise one longer than `keys`.
+    children: Vec<Node<K, V>>,
+}
+
+impl<K, V> Node<K, V> {
+    const fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            vals: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.keys.binary_search_by(|k| k.borrow().cmp(key))
+    }
+
+    fn descendant_mut(&mut self, path: &[usize]) -> &mut Self {
+        path.iter()
+            .fold(self, |node, &child| &mut node.children[child])
+    }
+
+    fn descendant(&self, path: &[usize]) -> &Self {
+        path.iter().fold(self, |node, &child| &node.children[child])
+    }
+
+    // Splits a full node around its middle key: the upper half moves to the returned node,
+    // and the middle key is returned for the parent.
+    fn split(&mut self) -> (K, V, Self) {
+        debug_assert_eq!(self.keys.len(), CAPACITY);
+        let keys = self.keys.split_off(B);
+        let vals = self.vals.split_off(B);
+        let children = if self.is_leaf() {
+            Vec::new()
+        } else {
+            self.children.split_off(B)
+        };
+        let key = self.keys.pop().unwrap();
+        let val = self.vals.pop().unwrap();
+        (
+            key,
+            val,
+            Self {
+                keys,
+                vals,
+                children,
+            },
+        )
+    }
+
+    fn split_child(&mut self, index: usize) {
+        let (key, val, right) = self.children[index].split();
+        self.keys.insert(index, key);
+        self.vals.insert(index, val);
+        self.children.insert(index + 1, right);
+    }
+
+    // Removes the entry at `index` in the node at `path` below this one, and rebalances
+    // every node on the way back up.
+    fn remove_at(&mut self, path: &[usize], index: usize) -> (K, V) {
+        match path.split_first() {
+            Some((&child, rest)) => {
+                let entry = self.children[child].remove_at(rest, index);
+                self.fix_child(child);
+                entry
+            }
+            None if self.is_leaf() => (self.keys.remove(index), self.vals.remove(index)),
+            None => {
+                // Replace the entry with its predecessor, which always sits in a leaf.
+                let (key, val) = self.children[index].pop_last();
+                let entry = (
+                    mem::replace(&mut self.keys[index], key),
+                    mem::replace(&mut self.vals[index], val),
+                );
+                self.fix_child(index);
+                entry
+            }
+        }
+    }
+
+    fn pop_last(&mut self) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.vals.pop().unwrap());
+        }
+        let last = self.children.len() - 1;
+        let entry = self.children[last].pop_last();
+        self.fix_child(last);
+        entry
+    }
+
+    // Tops a child up to the minimum length after a removal, by taking a key from a sibling
+    // that can spare one or else by merging it with a sibling.
+    fn fix_child(&mut self, index: usize) {
+        if self.children[index].keys.len() >= MIN_LEN {
+            return;
+        }
+        if index > 0 && self.children[index - 1].keys.len() > MIN_LEN {
+            // Rotate right: the left sibling's last key goes up, the separator comes down.
+            let left = &mut self.children[index - 1];
+            let (key, val) = (left.keys.pop().unwrap(), left.vals.pop().unwrap());
+            let grandchild = left.children.pop();
+            let key = mem::replace(&mut self.keys[index - 1], key);
+            let val = mem::replace(&mut self.vals[index - 1], val);
+            let child = &mut self.children[index];
+            child.keys.insert(0, key);
+            child.vals.insert(0, val);
+            if let Some(grandchild) = grandchild {
+                child.children.insert(0, grandchild);
+            }
+        } else if index + 1 < self.children.len() && self.children[index + 1].keys.len() > MIN_LEN {
+            // Rotate left, the mirror image.
+            let right = &mut self.children[index + 1];
+            let (key, val) = (right.keys.remove(0), right.vals.remove(0));
+            let grandchild = (!right.is_leaf()).then(|| right.children.remove(0));
+            let key = mem::replace(&mut self.keys[index], key);
+            let val = mem::replace(&mut self.vals[index], val);
+            let child = &mut self.children[index];
+            child.keys.push(key);
+            child.vals.push(val);
+            child.children.extend(grandchild);
+        } else {
+            // Both neighbours are minimal, so one of them and the child fit in one node
+            // together with the separator between them.
+            let left = index.saturating_sub(1);
+            let right = self.children.remove(left + 1);
+            let node = &mut self.children[left];
+            node.keys.push(self.keys.remove(left));
+            node.vals.push(self.vals.remove(left));
+            node.keys.extend(right.keys);
+            node.vals.extend(right.vals);
+            node.children.extend(right.children);
+        }
+    }
+
+    fn drain_into(self, entries: &mut Vec<(K, V)>) {
+        let mut children = self.children.into_iter();
+        for entry in self.keys.into_iter().zip(self.vals) {
+            if let Some(child) = children.next() {
+                child.drain_into(entries);
+            }
+            entries.push(entry);
+        }
+        if let Some(child) = children.next() {
+            child.drain_into(entries);
+        }
+    }
+}
+
+impl<K, V> BTreeMap<K, V> {
+    /// Creates an empty map without allocating.
+    pub const fn new() -> Self {
+        Self {
+            root: Node::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// The entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while !node.is_leaf() {
+            node = &node.children[0];
+        }
+        Some((node.keys.first()?, node.vals.first()?))
+    }
+
+    /// The entry with the largest key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = &self.root;
+        while let Some(child) = node.children.last() {
+            node = child;
+        }
+        Some((node.keys.last()?, node.vals.last()?))
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = &self.root;
+        while !node.is_leaf() {
+            path.push(0);
+            node = &node.children[0];
+        }
+        Some(self.remove_at(&path, 0))
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = &self.root;
+        while !node.is_leaf() {
+            path.push(node.keys.len());
+            node = &node.children[node.keys.len()];
+        }
+        let index = node.keys.len() - 1;
+        Some(self.remove_at(&path, index))
+    }
+
+    /// Iterates over the entries in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            range: Range::all(&self.root),
+            remaining: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            remaining: self.len,
+            range: RangeMut::all(&mut self.root),
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    pub fn into_keys(self) -> impl DoubleEndedIterator<Item = K> {
+        self.into_iter().map(|(key, _)| key)
+    }
+
+    pub fn into_values(self) -> impl DoubleEndedIterator<Item = V> {
+        self.into_iter().map(|(_, value)| value)
+    }
+
+    fn remove_at(&mut self, path: &[usize], index: usize) -> (K, V) {
+        let entry = self.root.remove_at(path, index);
+        self.len -= 1;
+        // The root is the one node allowed to run low; once it is out of keys, its only
+        // child takes over and the tree gets one level shorter.
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            self.root = self.root.children.pop().unwrap();
+        }
+        entry
+    }
+}
+
+impl<K: Ord, V> BTreeMap<K, V> {
+    /// Inserts a key-value pair. If the key was already present, its value is replaced and
+    /// the old one returned; the key itself is kept.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &self.root;
+        loop {
+            match node.search(key) {
+                Ok(index) => return Some((&node.keys[index], &node.vals[index])),
+                Err(_) if node.is_leaf() => return None,
+                Err(index) => node = &node.children[index],
+            }
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &mut self.root;
+        loop {
+            match node.search(key) {
+                Ok(index) => return Some(&mut node.vals[index]),
+                Err(_) if node.is_leaf() => return None,
+                Err(index) => node = &mut node.children[index],
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut path = Vec::new();
+        let mut node = &self.root;
+        loop {
+            match node.search(key) {
+                Ok(index) => return Some(self.remove_at(&path, index)),
+                Err(_) if node.is_leaf() => return None,
+                Err(index) => {
+                    path.push(index);
+                    node = &node.children[index];
+                }
+            }
+        }
+    }
+
+    /// Keeps only the entries for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        // Rebuilding is much simpler than removing entries from under a walk of the tree,
+        // and since the survivors come out sorted, every insert lands in the last leaf.
+        for (key, mut value) in mem::take(self) {
+            if f(&key, &mut value) {
+                self.insert(key, value);
+            }
+        }
+    }
+
+    /// Iterates over the entries whose keys lie in `range`, in key order.
+    ///
+    /// Panics if the range starts after it ends, or if it is empty with both ends excluded,
+    /// like `std`'s.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_range(&range);
+        Range::new(&self.root, range.start_bound(), range.end_bound())
+    }
+
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_range(&range);
+        RangeMut::new(&mut self.root, range.start_bound(), range.end_bound())
+    }
+
+    /// Gets the entry for `key`, to look at, insert or update it with a single lookup.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        // Split full nodes on the way down, so that a vacant entry always ends up in a leaf
+        // with room to spare and inserting never has to walk back up. Splitting a node that
+        // didn't need it yet keeps the tree valid, so we don't mind doing it for an occupied
+        // entry too.
+        if self.root.keys.len() == CAPACITY {
+            let mut left = mem::replace(&mut self.root, Node::new());
+            let (key, val, right) = left.split();
+            self.root = Node {
+                keys: alloc::vec![key],
+                vals: alloc::vec![val],
+                children: alloc::vec![left, right],
+            };
+        }
+        let mut path = Vec::new();
+        let mut node = &mut self.root;
+        let found = loop {
+            match node.search(&key) {
+                Ok(index) => break Ok(index),
+                Err(index) if node.is_leaf() => break Err(index),
+                Err(mut index) => {
+                    if node.children[index].keys.len() == CAPACITY {
+                        node.split_child(index);
+                        // The middle key of the child moved up to `index`.
+                        match key.cmp(&node.keys[index]) {
+                            Ordering::Less => {}
+                            Ordering::Equal => break Ok(index),
+                            Ordering::Greater => index += 1,
+                        }
+                    }
+                    path.push(index);
+                    node = &mut node.children[index];
+                }
+            }
+        };
+        match found {
+            Ok(index) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                path,
+                index,
+            }),
+            Err(index) => Entry::Vacant(VacantEntry {
+                map: self,
+                path,
+                index,
+                key,
+            }),
+        }
+    }
+}
+
+fn check_range<Q: Ord + ?Sized>(range: &impl RangeBounds<Q>) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end => {
+            panic!("range start and end are equal and excluded in BTreeMap")
+        }
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) if start > end => panic!("range start is greater than range end in BTreeMap"),
+        _ => {}
+    }
+}
+
+/// A view into one key of a [`BTreeMap`], from [`BTreeMap::entry`].
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// An [`Entry`] whose key is in the map.
+pub struct OccupiedEntry<'a, K, V> {
+    map: &'a mut BTreeMap<K, V>,
+    // The children to follow from the root to the entry's node, and its index in there.
+    path: Vec<usize>,
+    index: usize,
+}
+
+/// An [`Entry`] whose key isn't in the map yet.
+pub struct VacantEntry<'a, K, V> {
+    map: &'a mut BTreeMap<K, V>,
+    // The leaf the key goes into, which has room for it, and its index in there.
+    path: Vec<usize>,
+    index: usize,
+    key: K,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value, inserting `default` first if the key is vacant.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Runs `f` on the value if the key is occupied.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.map.root.descendant(&self.path).keys[self.index]
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.root.descendant(&self.path).vals[self.index]
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.root.descendant_mut(&self.path).vals[self.index]
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.root.descendant_mut(&self.path).vals[self.index]
+    }
+
+    /// Replaces the value and returns the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_at(&self.path, self.index)
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.len += 1;
+        let leaf = self.map.root.descendant_mut(&self.path);
+        leaf.keys.insert(self.index, self.key);
+        leaf.vals.insert(self.index, value);
+        &mut leaf.vals[self.index]
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for Entry<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Occupied(entry) => f
+                .debug_struct("OccupiedEntry")
+                .field("key", entry.key())
+                .field("value", entry.get())
+                .finish(),
+            Entry::Vacant(entry) => f.debug_tuple("VacantEntry").field(entry.key()).finish(),
+        }
+    }
+}
+
+type Position<K, V> = (NonNull<Node<K, V>>, usize);
+
+// A pair of paths into the tree that walk towards each other, from the start and the end of
+// a range. Both iterators over references use it; the nodes are reached through raw
+// pointers so that the mutable one can hand out `&mut V`s into nodes that are still on its
+// paths.
+struct Cursors<K, V> {
+    // Every frame is a node and the index of the next key to yield in it; the node at the
+    // top of the stack comes first.
+    front: Vec<Position<K, V>>,
+    // The same from the end, except that the index is one past the next key to yield.
+    back: Vec<Position<K, V>>,
+    // Whether the nodes may be written to through these pointers.
+    mutable: bool,
+}
+
+impl<K, V> Clone for Cursors<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            front: self.front.clone(),
+            back: self.back.clone(),
+            mutable: self.mutable,
+        }
+    }
+}
+
+impl<K, V> Cursors<K, V> {
+    // Descends from `root` to both ends, picking the child at `start(keys)` on the way to the
+    // front and the one at `end(keys)` on the way to the back.
+    //
+    // SAFETY: `root` must stay valid and unchanged for as long as the cursors are used, and
+    // if `mutable`, it must come from a `&mut`.
+    unsafe fn new(
+        root: NonNull<Node<K, V>>,
+        mutable: bool,
+        start: impl Fn(&[K]) -> usize,
+        end: impl Fn(&[K]) -> usize,
+    ) -> Self {
+        // SAFETY: the caller guarantees that the tree is valid.
+        let mut cursors = unsafe {
+            Self {
+                front: descend(root, mutable, start),
+                back: descend(root, mutable, end),
+                mutable,
+            }
+        };
+        if cursors.peek_front().is_none() || cursors.peek_back().is_none() {
+            cursors.front.clear();
+            cursors.back.clear();
+        }
+        cursors
+    }
+
+    // SAFETY: as for `new`.
+    unsafe fn all(root: NonNull<Node<K, V>>, mutable: bool) -> Self {
+        unsafe { Self::new(root, mutable, |_| 0, |keys| keys.len()) }
+    }
+
+    // SAFETY: as for `new`.
+    unsafe fn range<Q>(
+        root: NonNull<Node<K, V>>,
+        mutable: bool,
+        start: Bound<&Q>,
+        end: Bound<&Q>,
+    ) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Descend to the first key at or after `start`, and the last one before `end`. In
+        // every node, the child we go into holds the keys on both sides of the bound, if any.
+        let start = |keys: &[K]| match start {
+            Bound::Included(key) => keys.partition_point(|k| k.borrow() < key),
+            Bound::Excluded(key) => keys.partition_point(|k| k.borrow() <= key),
+            Bound::Unbounded => 0,
+        };
+        let end = |keys: &[K]| match end {
+            Bound::Included(key) => keys.partition_point(|k| k.borrow() <= key),
+            Bound::Excluded(key) => keys.partition_point(|k| k.borrow() < key),
+            Bound::Unbounded => keys.len(),
+        };
+        let mut cursors = unsafe { Self::new(root, mutable, start, end) };
+        // If the first key in the range comes after the last one, the range is empty.
+        if let (Some(first), Some(last)) = (cursors.peek_front(), cursors.peek_back()) {
+            if unsafe { Borrow::<Q>::borrow(key(first)) > key(last).borrow() } {
+                cursors.front.clear();
+                cursors.back.clear();
+            }
+        }
+        cursors
+    }
+
+    fn peek_front(&mut self) -> Option<Position<K, V>> {
+        while let Some(&(node, index)) = self.front.last() {
+            // SAFETY: the nodes on the paths are live.
+            if index < unsafe { node.as_ref() }.keys.len() {
+                return Some((node, index));
+            }
+            self.front.pop();
+        }
+        None
+    }
+
+    fn peek_back(&mut self) -> Option<Position<K, V>> {
+        while let Some(&(node, index)) = self.back.last() {
+            if index > 0 {
+                return Some((node, index - 1));
+            }
+            self.back.pop();
+        }
+        None
+    }
+
+    fn next_front(&mut self) -> Option<Position<K, V>> {
+        let position @ (node, index) = self.peek_front()?;
+        if self.peek_back() == Some(position) {
+            // The ends have met: this is the last entry.
+            self.front.clear();
+            self.back.clear();
+            return Some(position);
+        }
+        self.front.last_mut().unwrap().1 = index + 1;
+        // The next key is the leftmost one in the subtree right after this key.
+        if !unsafe { node.as_ref() }.is_leaf() {
+            let mut descendant = unsafe { child(node, index + 1, self.mutable) };
+            loop {
+                self.front.push((descendant, 0));
+                if unsafe { descendant.as_ref() }.is_leaf() {
+                    break;
+                }
+                descendant = unsafe { child(descendant, 0, self.mutable) };
+            }
+        }
+        Some(position)
+    }
+
+    fn next_back(&mut self) -> Option<Position<K, V>> {
+        let position @ (node, index) = self.peek_back()?;
+        if self.peek_front() == Some(position) {
+            self.front.clear();
+            self.back.clear();
+            return Some(position);
+        }
+        self.back.last_mut().unwrap().1 = index;
+        // The next key is the rightmost one in the subtree right before this key.
+        if !unsafe { node.as_ref() }.is_leaf() {
+            let mut descendant = unsafe { child(node, index, self.mutable) };
+            loop {
+                let len = unsafe { descendant.as_ref() }.keys.len();
+                self.back.push((descendant, len));
+                if unsafe { descendant.as_ref() }.is_leaf() {
+                    break;
+                }
+                descendant = unsafe { child(descendant, len, self.mutable) };
+            }
+        }
+        Some(position)
+    }
+}
+
+// The path from `node` down to a leaf that takes the child at `index_in(keys)` at every step.
+//
+// SAFETY: as for `Cursors::new`.
+unsafe fn descend<K, V>(
+    mut node: NonNull<Node<K, V>>,
+    mutable: bool,
+    index_in: impl Fn(&[K]) -> usize,
+) -> Vec<Position<K, V>> {
+    let mut path = Vec::new();
+    loop {
+        let n = unsafe { node.as_ref() };
+        let index = index_in(&n.keys);
+        path.push((node, index));
+        if n.is_leaf() {
+            return path;
+        }
+        node = unsafe { child(node, index, mutable) };
+    }
+}
+
+// SAFETY: `node` must be a live internal node, reached mutably if `mutable`.
+unsafe fn child<K, V>(
+    node: NonNull<Node<K, V>>,
+    index: usize,
+    mutable: bool,
+) -> NonNull<Node<K, V>> {
+    // Go through the raw pointers all the way, never through a `&mut Node`, which would
+    // claim the whole node for itself and invalidate the other cursor's pointers into it.
+    unsafe {
+        let children = if mutable {
+            (*node.as_ptr()).children.as_mut_ptr()
+        } else {
+            (*node.as_ptr()).children.as_ptr().cast_mut()
+        };
+        NonNull::new_unchecked(children.add(index))
+    }
+}
+
+// SAFETY: the node must be live for `'a`, and `index` in bounds.
+unsafe fn key<'a, K: 'a, V: 'a>((node, index): Position<K, V>) -> &'a K {
+    unsafe { &node.as_ref().keys[index] }
+}
+
+unsafe fn entry<'a, K: 'a, V: 'a>(position @ (node, index): Position<K, V>) -> (&'a K, &'a V) {
+    unsafe { (key(position), &node.as_ref().vals[index]) }
+}
+
+// SAFETY: as for `entry`, and the node must have been reached mutably. Every position is
+// yielded once, so the `&mut V`s never alias.
+unsafe fn entry_mut<'a, K: 'a, V: 'a>(
+    position @ (node, index): Position<K, V>,
+) -> (&'a K, &'a mut V) {
+    unsafe {
+        (
+            key(position),
+            &mut *(*node.as_ptr()).vals.as_mut_ptr().add(index),
+        )
+    }
+}
+
+/// Iterator over a range of entries of a [`BTreeMap`], from [`BTreeMap::range`].
+pub struct Range<'a, K, V> {
+    cursors: Cursors<K, V>,
+    marker: PhantomData<&'a Node<K, V>>,
+}
+
+// SAFETY: a `Range` only hands out shared references, like `&BTreeMap`.
+unsafe impl<K: Sync, V: Sync> Send for Range<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Range<'_, K, V> {}
+
+impl<'a, K, V> Range<'a, K, V> {
+    fn all(root: &'a Node<K, V>) -> Self {
+        Self {
+            // SAFETY: we borrow the tree for `'a`.
+            cursors: unsafe { Cursors::all(NonNull::from(root), false) },
+            marker: PhantomData,
+        }
+    }
+
+    fn new<Q>(root: &'a Node<K, V>, start: Bound<&Q>, end: Bound<&Q>) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self {
+            cursors: unsafe { Cursors::range(NonNull::from(root), false, start, end) },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the tree is borrowed for `'a`.
+        self.cursors
+            .next_front()
+            .map(|position| unsafe { entry(position) })
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Range<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursors
+            .next_back()
+            .map(|position| unsafe { entry(position) })
+    }
+}
+
+impl<K, V> FusedIterator for Range<'_, K, V> {}
+
+impl<K, V> Clone for Range<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            cursors: self.cursors.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Mutable iterator over a range of entries of a [`BTreeMap`], from
+/// [`BTreeMap::range_mut`].
+pub struct RangeMut<'a, K, V> {
+    cursors: Cursors<K, V>,
+    marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// SAFETY: a `RangeMut` hands out `&K` and `&mut V`, like `&mut BTreeMap`.
+unsafe impl<K: Sync, V: Send> Send for RangeMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for RangeMut<'_, K, V> {}
+
+impl<'a, K, V> RangeMut<'a, K, V> {
+    fn all(root: &'a mut Node<K, V>) -> Self {
+        Self {
+            // SAFETY: we borrow the tree mutably for `'a`.
+            cursors: unsafe { Cursors::all(NonNull::from(root), true) },
+            marker: PhantomData,
+        }
+    }
+
+    fn new<Q>(root: &'a mut Node<K, V>, start: Bound<&Q>, end: Bound<&Q>) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self {
+            cursors: unsafe { Cursors::range(NonNull::from(root), true, start, end) },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the tree is borrowed mutably for `'a`, through the cursors.
+        self.cursors
+            .next_front()
+            .map(|position| unsafe { entry_mut(position) })
+    }
+}
+
+impl<K, V> DoubleEndedIterator for RangeMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursors
+            .next_back()
+            .map(|position| unsafe { entry_mut(position) })
+    }
+}
+
+impl<K, V> FusedIterator for RangeMut<'_, K, V> {}
+
+/// Iterator over the entries of a [`BTreeMap`], in key order.
+pub struct Iter<'a, K, V> {
+    range: Range<'a, K, V>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            range: self.range.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// Mutable iterator over the entries of a [`BTreeMap`], in key order.
+pub struct IterMut<'a, K, V> {
+    range: RangeMut<'a, K, V>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Owning iterator over the entries of a [`BTreeMap`], in key order.
+///
+/// Takes the tree apart into a `Vec` up front, which costs an allocation but no unsafe code.
+pub struct IntoIter<K, V> {
+    entries: vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.entries.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// Iterator over the keys of a [`BTreeMap`], in order.
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// Iterator over the values of a [`BTreeMap`], in key order.
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// Mutable iterator over the values of a [`BTreeMap`], in key order.
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> IntoIterator for BTreeMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        let mut entries = Vec::with_capacity(self.len);
+        self.root.drain_into(&mut entries);
+        IntoIter {
+            entries: entries.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a BTreeMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut BTreeMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for BTreeMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy> Extend<(&'a K, &'a V)> for BTreeMap<K, V> {
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for BTreeMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BTreeMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl<K, Q, V> Index<&Q> for BTreeMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+
+    /// Panics if the key isn't in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in BTreeMap")
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for BTreeMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for BTreeMap<K, V> {}
+
+impl<K: PartialOrd, V: PartialOrd> PartialOrd for BTreeMap<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for BTreeMap<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<K, V> Default for BTreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for BTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeMap as StdBTreeMap, format, panic, string::String, vec::Vec};
+
+    // Checks the B-tree invariants: sorted keys, node lengths, and leaves all at one depth.
+    fn assert_valid<K: Ord, V>(map: &BTreeMap<K, V>) {
+        fn check<K: Ord, V>(node: &Node<K, V>, is_root: bool) -> (usize, usize) {
+            assert!(node.keys.len() <= CAPACITY);
+            assert!(is_root || node.keys.len() >= MIN_LEN);
+            assert_eq!(node.keys.len(), node.vals.len());
+            assert!(node.keys.windows(2).all(|w| w[0] < w[1]));
+            if node.is_leaf() {
+                return (node.keys.len(), 0);
+            }
+            assert_eq!(node.children.len(), node.keys.len() + 1);
+            let mut len = node.keys.len();
+            let mut height = None;
+            for (index, child) in node.children.iter().enumerate() {
+                if index > 0 {
+                    assert!(child.keys[0] > node.keys[index - 1]);
+                }
+                if let Some(key) = node.keys.get(index) {
+                    assert!(child.keys.last().unwrap() < key);
+                }
+                let (child_len, child_height) = check(child, false);
+                assert_eq!(*height.get_or_insert(child_height), child_height);
+                len += child_len;
+            }
+            (len, height.unwrap() + 1)
+        }
+        assert_eq!(check(&map.root, true).0, map.len);
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map = BTreeMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.first_key_value(), None);
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(2, "B"), Some("b"));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&2), Some(&"B"));
+        assert_eq!(map[&1], "a");
+        *map.get_mut(&1).unwrap() = "A";
+        assert_eq!(map.first_key_value(), Some((&1, &"A")));
+        assert_eq!(map.last_key_value(), Some((&2, &"B")));
+        assert_eq!(map.remove(&1), Some("A"));
+        assert_eq!(map.remove(&1), None);
+        assert!(!map.contains_key(&1));
+        assert_valid(&map);
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut map = BTreeMap::new();
+        map.insert(String::from("pear"), 1);
+        map.insert(String::from("apple"), 2);
+        assert_eq!(map.get("pear"), Some(&1));
+        let fruits: Vec<_> = map
+            .range::<str, _>((Bound::Included("b"), Bound::Unbounded))
+            .map(|(k, _)| k.as_str())
+            .collect();
+        assert_eq!(fruits, ["pear"]);
+        assert_eq!(map.remove_entry("apple"), Some((String::from("apple"), 2)));
+    }
+
+    #[test]
+    fn test_sorted_iteration_from_both_ends() {
+        let map: BTreeMap<u32, u32> = (0..1000).rev().map(|i| (i, i * i)).collect();
+        assert_valid(&map);
+        assert!(map.keys().copied().eq(0..1000));
+        assert!(map.keys().rev().copied().eq((0..1000).rev()));
+        let mut iter = map.iter();
+        assert_eq!(iter.len(), 1000);
+        // Alternate between the ends until they meet in the middle.
+        for i in 0..500 {
+            assert_eq!(iter.next(), Some((&i, &(i * i))));
+            assert_eq!(iter.next_back().map(|(k, _)| *k), Some(999 - i));
+        }
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_ranges_match_std() {
+        let keys = (0..300).map(|i| i * 3);
+        let map: BTreeMap<i32, ()> = keys.clone().map(|k| (k, ())).collect();
+        let model: StdBTreeMap<i32, ()> = keys.map(|k| (k, ())).collect();
+        let bounds = [-5, 0, 1, 3, 299, 300, 450, 896, 897, 900];
+        for &a in &bounds {
+            for &b in &bounds {
+                if a <= b {
+                    assert!(map
+                        .range(a..=b)
+                        .map(|(k, _)| k)
+                        .eq(model.range(a..=b).map(|(k, _)| k)));
+                    assert!(map
+                        .range(a..b)
+                        .rev()
+                        .map(|(k, _)| k)
+                        .eq(model.range(a..b).rev().map(|(k, _)| k)));
+                }
+                let excluded = (Bound::Excluded(a), Bound::Included(b));
+                if a <= b {
+                    assert!(map
+                        .range(excluded)
+                        .map(|(k, _)| k)
+                        .eq(model.range(excluded).map(|(k, _)| k)));
+                }
+            }
+            assert!(map
+                .range(..a)
+                .map(|(k, _)| k)
+                .eq(model.range(..a).map(|(k, _)| k)));
+            assert!(map
+                .range(a..)
+                .rev()
+                .map(|(k, _)| k)
+                .eq(model.range(a..).rev().map(|(k, _)| k)));
+        }
+        // Floor and ceiling lookups, the usual reason to want a range.
+        assert_eq!(map.range(..=100).next_back(), Some((&99, &())));
+        assert_eq!(map.range(100..).next(), Some((&102, &())));
+    }
+
+    #[test]
+    fn test_invalid_ranges_panic() {
+        let map: BTreeMap<i32, ()> = BTreeMap::new();
+        let (start, end) = (2, 1);
+        assert!(panic::catch_unwind(|| map.range(start..end).count()).is_err());
+        let excluded = (Bound::Excluded(1), Bound::Excluded(1));
+        assert!(panic::catch_unwind(|| map.range(excluded).count()).is_err());
+        assert_eq!(map.range(1..1).count(), 0);
+    }
+
+    #[test]
+    fn test_matches_std_under_churn() {
+        let mut map = BTreeMap::new();
+        let mut model = StdBTreeMap::new();
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for step in 0..20_000u32 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let key = (seed % 1_000) as u32;
+            match seed % 5 {
+                0 | 1 => assert_eq!(map.insert(key, step), model.insert(key, step)),
+                2 => assert_eq!(map.remove(&key), model.remove(&key)),
+                3 => assert_eq!(map.pop_first(), model.pop_first()),
+                _ => assert_eq!(map.pop_last(), model.pop_last()),
+            }
+            if step % 1000 == 0 {
+                assert_valid(&map);
+            }
+        }
+        assert_valid(&map);
+        assert!(map.iter().eq(model.iter()));
+        assert!(map.into_iter().rev().eq(model.into_iter().rev()));
+    }
+
+    #[test]
+    fn test_entry_api() {
+        let text = "the quick brown fox jumps over the lazy dog the end";
+        let mut counts = BTreeMap::new();
+        for word in text.split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(counts.first_key_value(), Some((&"brown", &1)));
+        assert_eq!(counts["the"], 3);
+
+        // Enough entries to split nodes while entries are being looked up.
+        let mut map: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+        for i in 0..200 {
+            map.entry(i % 50).or_default().push(i);
+        }
+        assert_valid(&map);
+        assert_eq!(map.len(), 50);
+        assert_eq!(map[&7], [7, 57, 107, 157]);
+        map.entry(7).and_modify(|v| v.clear()).or_default();
+        assert!(map[&7].is_empty());
+        match map.entry(8) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &8);
+                assert_eq!(entry.insert(Vec::new()).len(), 4);
+                assert!(entry.remove().is_empty());
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry(8) {
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(alloc::vec![1]), [1]),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert_valid(&map);
+    }
+
+    #[test]
+    fn test_mutable_iteration() {
+        let mut map: BTreeMap<u32, u32> = (0..100).map(|i| (i, 0)).collect();
+        for (k, v) in map.range_mut(10..20) {
+            *v = *k;
+        }
+        let mut iter = map.iter_mut();
+        while let (Some((_, front)), Some((_, back))) = (iter.next(), iter.next_back()) {
+            *front += 1;
+            *back += 1;
+        }
+        for value in map.values_mut().rev().take(10) {
+            *value += 100;
+        }
+        assert_eq!(map[&0], 1);
+        assert_eq!(map[&15], 16);
+        assert_eq!(map[&99], 101);
+        assert_eq!(map.values().filter(|&&v| v == 1).count(), 80);
+    }
+
+    #[test]
+    fn test_traits() {
+        let mut map = BTreeMap::from([(3, 'c'), (1, 'a'), (2, 'b')]);
+        assert_eq!(format!("{map:?}"), "{1: 'a', 2: 'b', 3: 'c'}");
+        let clone = map.clone();
+        assert_eq!(clone, map);
+        map.retain(|&k, _| k != 2);
+        assert_eq!(format!("{map:?}"), "{1: 'a', 3: 'c'}");
+        assert!(clone < map);
+        assert!(map.clone().into_keys().eq([1, 3]));
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+    }
+}
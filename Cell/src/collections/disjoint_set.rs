@@ -0,0 +1,217 @@
+//! Union-find: a partition of `0..n` into disjoint sets.
+
+use alloc::{vec, vec::Vec};
+use core::{fmt, mem};
+
+/// Keeps track of which of the elements `0..len` belong together.
+///
+/// Every set is a tree of elements pointing at their parent, with the root standing for
+/// the whole set. [`find`](DisjointSet::find) follows the parents up to the root and then
+/// points every element it passed straight at it (path compression), and
+/// [`union`](DisjointSet::union) hangs the root of the shallower tree under the root of the
+/// deeper one (union by rank). Together they make both operations take effectively
+/// constant amortized time.
+///
+/// ```
+/// use Cell::collections::DisjointSet;
+///
+/// let mut sets = DisjointSet::new(5);
+/// sets.union(0, 1);
+/// sets.union(3, 4);
+/// sets.union(1, 4);
+/// assert!(sets.same_set(0, 3));
+/// assert!(!sets.same_set(0, 2));
+/// assert_eq!(sets.set_size(4), 4);
+/// assert_eq!(sets.count(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    // Upper bounds on the height of each root's tree. Only meaningful for roots.
+    rank: Vec<u8>,
+    // The number of elements in each root's set. Only meaningful for roots.
+    size: Vec<usize>,
+    count: usize,
+}
+
+impl DisjointSet {
+    /// Creates `len` elements, each in a set of its own.
+    pub fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            rank: vec![0; len],
+            size: vec![1; len],
+            count: len,
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Returns the number of disjoint sets.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Adds a new element in a set of its own and returns it.
+    pub fn make_set(&mut self) -> usize {
+        let element = self.parent.len();
+        self.parent.push(element);
+        self.rank.push(0);
+        self.size.push(1);
+        self.count += 1;
+        element
+    }
+
+    /// Returns the representative of the set containing `element`. Two elements are in the
+    /// same set exactly when they have the same representative, until the next `union`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element` is out of bounds.
+    pub fn find(&mut self, element: usize) -> usize {
+        let mut root = element;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Second pass: point everything on the path directly at the root.
+        let mut current = element;
+        while current != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Merges the sets containing `a` and `b`. Returns `false` if they already were the
+    /// same set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.rank[a] < self.rank[b] {
+            mem::swap(&mut a, &mut b);
+        }
+        // `a` is now the root of the deeper tree, so hanging `b` under it only makes the
+        // tree deeper if both were equally deep.
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        if self.rank[a] == self.rank[b] {
+            self.rank[a] += 1;
+        }
+        self.count -= 1;
+        true
+    }
+
+    /// Returns `true` if `a` and `b` are in the same set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn same_set(&mut self, a: usize, b: usize) -> bool {
+        self.find(a) == self.find(b)
+    }
+
+    /// Returns the number of elements in the set containing `element`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element` is out of bounds.
+    pub fn set_size(&mut self, element: usize) -> usize {
+        let root = self.find(element);
+        self.size[root]
+    }
+
+    /// Returns the sets, each as a list of its elements in increasing order. The sets are
+    /// ordered by their smallest element.
+    pub fn sets(&mut self) -> Vec<Vec<usize>> {
+        // Index of each root's set in `sets`, assigned when its smallest element is seen.
+        let mut slot = vec![usize::MAX; self.len()];
+        let mut sets: Vec<Vec<usize>> = Vec::with_capacity(self.count);
+        for element in 0..self.len() {
+            let root = self.find(element);
+            if slot[root] == usize::MAX {
+                slot[root] = sets.len();
+                sets.push(Vec::with_capacity(self.size[root]));
+            }
+            sets[slot[root]].push(element);
+        }
+        sets
+    }
+}
+
+impl fmt::Debug for DisjointSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `sets` compresses paths, so it needs a copy to work on.
+        f.debug_set().entries(self.clone().sets()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::format;
+
+    #[test]
+    fn test_union_and_find() {
+        let mut sets = DisjointSet::new(10);
+        assert_eq!(sets.count(), 10);
+        assert!(sets.union(1, 2));
+        assert!(sets.union(2, 3));
+        assert!(!sets.union(1, 3));
+        assert!(sets.union(7, 8));
+        assert!(sets.same_set(3, 1));
+        assert!(!sets.same_set(3, 7));
+        assert_eq!(sets.find(1), sets.find(3));
+        assert_eq!(sets.count(), 7);
+        assert_eq!(sets.set_size(2), 3);
+        assert_eq!(sets.set_size(8), 2);
+        assert_eq!(sets.set_size(0), 1);
+    }
+
+    #[test]
+    fn test_path_compression_flattens_chains() {
+        let mut sets = DisjointSet::new(1000);
+        for i in 1..1000 {
+            sets.union(i - 1, i);
+        }
+        // Union by rank keeps the tree shallow even when built as a chain.
+        assert!(sets.rank.iter().all(|&rank| rank <= 10));
+        let root = sets.find(999);
+        sets.find(0);
+        assert_eq!(sets.parent[0], root);
+        assert_eq!(sets.set_size(500), 1000);
+        assert_eq!(sets.count(), 1);
+    }
+
+    #[test]
+    fn test_make_set_and_sets() {
+        let mut sets = DisjointSet::default();
+        assert!(sets.is_empty());
+        let a = sets.make_set();
+        let b = sets.make_set();
+        let c = sets.make_set();
+        sets.union(c, a);
+        assert_eq!(sets.len(), 3);
+        assert_eq!(sets.sets(), [vec![a, c], vec![b]]);
+        assert_eq!(format!("{sets:?}"), "{[0, 2], [1]}");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_find_out_of_bounds_panics() {
+        DisjointSet::new(2).find(2);
+    }
+}
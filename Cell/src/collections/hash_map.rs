@@ -0,0 +1,965 @@
+//! A hash map using open addressing with Robin Hood probing.
+
+use alloc::vec::{self, Vec};
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter::FusedIterator,
+    mem,
+    ops::Index,
+    slice,
+};
+
+pub use super::sip::{RandomState, SipHasher13};
+
+/// A hash map that stores its entries directly in one array of buckets.
+///
+/// A key's hash picks its ideal bucket; if that one is taken, the entry goes into the next
+/// free bucket after it (linear probing). Robin Hood insertion keeps the probe sequences
+/// short: an entry that is further from its ideal bucket than the one sitting in a bucket
+/// takes the bucket over, and the displaced entry moves on instead. So every lookup can stop
+/// as soon as it meets an entry closer to home than the key it searches would be, and
+/// removal shifts the following entries back rather than leaving tombstones.
+///
+/// The table size is a power of two and grows by doubling once it is 7/8 full. Hashes are
+/// stored with the entries, so growing never rehashes a key.
+///
+/// Keys are hashed with [`RandomState`] (SipHash-1-3) by default; any other [`BuildHasher`]
+/// can be plugged in with [`HashMap::with_hasher`].
+pub struct HashMap<K, V, S = RandomState> {
+    table: Table<K, V>,
+    hash_builder: S,
+}
+
+// The part of the map that doesn't depend on the hasher, so entries can borrow it.
+#[derive(Clone)]
+struct Table<K, V> {
+    // Empty, or a power of two long.
+    buckets: Vec<Option<Bucket<K, V>>>,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+// How far the entry with `hash` sits from its ideal bucket when it is in bucket `index`.
+fn distance(hash: u64, index: usize, mask: usize) -> usize {
+    index.wrapping_sub(hash as usize) & mask
+}
+
+impl<K, V> Table<K, V> {
+    const fn new() -> Self {
+        Self {
+            buckets: Vec::new(),
+            len: 0,
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len().wrapping_sub(1)
+    }
+
+    // The number of entries that fit before the table has to grow.
+    fn capacity(&self) -> usize {
+        self.buckets.len() / 8 * 7
+    }
+
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let mask = self.mask();
+        let mut index = hash as usize & mask;
+        // The table is never full, so we always reach an empty bucket eventually.
+        for dist in 0.. {
+            let bucket = self.buckets[index].as_ref()?;
+            // Had our key been here, Robin Hood insertion would have put it before this
+            // entry, which is closer to its own ideal bucket than we are to ours.
+            if distance(bucket.hash, index, mask) < dist {
+                return None;
+            }
+            if bucket.hash == hash && bucket.key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & mask;
+        }
+        unreachable!()
+    }
+
+    // Inserts an entry whose key isn't in the table yet, and returns the index it ended up
+    // at. There must be room for it.
+    fn insert_new(&mut self, hash: u64, key: K, value: V) -> usize {
+        debug_assert!(self.len < self.capacity());
+        let mask = self.mask();
+        let mut carried = Bucket { hash, key, value };
+        let mut index = hash as usize & mask;
+        let mut dist = 0;
+        // Where the new entry landed, once it has displaced some other entry.
+        let mut placed = None;
+        loop {
+            match &mut self.buckets[index] {
+                slot @ None => {
+                    *slot = Some(carried);
+                    self.len += 1;
+                    return placed.unwrap_or(index);
+                }
+                Some(bucket) => {
+                    let existing = distance(bucket.hash, index, mask);
+                    if existing < dist {
+                        // Take from the rich: the resident is closer to home than we are.
+                        mem::swap(bucket, &mut carried);
+                        placed.get_or_insert(index);
+                        dist = existing;
+                    }
+                }
+            }
+            index = (index + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> Bucket<K, V> {
+        let mask = self.mask();
+        let removed = self.buckets[index]
+            .take()
+            .expect("removing an empty bucket");
+        self.len -= 1;
+        // Backward shift: pull the following entries one step closer to home until we meet
+        // an empty bucket or an entry that already is at home.
+        let mut hole = index;
+        loop {
+            let next = (hole + 1) & mask;
+            match &self.buckets[next] {
+                Some(bucket) if distance(bucket.hash, next, mask) > 0 => {
+                    self.buckets[hole] = self.buckets[next].take();
+                    hole = next;
+                }
+                _ => return removed,
+            }
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let needed = self
+            .len
+            .checked_add(additional)
+            .expect("HashMap capacity overflow");
+        if needed <= self.capacity() {
+            return;
+        }
+        let mut buckets = 8;
+        while buckets / 8 * 7 < needed {
+            buckets = buckets.checked_mul(2).expect("HashMap capacity overflow");
+        }
+        self.resize(buckets);
+    }
+
+    fn resize(&mut self, buckets: usize) {
+        let mut new = Vec::with_capacity(buckets);
+        new.resize_with(buckets, || None);
+        let old = mem::replace(&mut self.buckets, new);
+        self.len = 0;
+        for bucket in old.into_iter().flatten() {
+            self.insert_new(bucket.hash, bucket.key, bucket.value);
+        }
+    }
+
+    fn bucket(&self, index: usize) -> &Bucket<K, V> {
+        self.buckets[index]
+            .as_ref()
+            .expect("entry points at an empty bucket")
+    }
+
+    fn bucket_mut(&mut self, index: usize) -> &mut Bucket<K, V> {
+        self.buckets[index]
+            .as_mut()
+            .expect("entry points at an empty bucket")
+    }
+}
+
+impl<K, V> HashMap<K, V, RandomState> {
+    /// Creates an empty map without allocating.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    /// Creates an empty map with room for at least `capacity` entries.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Creates an empty map that hashes keys with `hash_builder`.
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            table: Table::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.table.reserve(capacity);
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.len == 0
+    }
+
+    /// The number of entries the map can hold before it has to grow.
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Removes every entry, keeping the allocated buckets.
+    pub fn clear(&mut self) {
+        self.table
+            .buckets
+            .iter_mut()
+            .for_each(|bucket| *bucket = None);
+        self.table.len = 0;
+    }
+
+    /// Keeps only the entries for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        let mut removed = false;
+        for slot in &mut self.table.buckets {
+            if let Some(bucket) = slot {
+                if !f(&bucket.key, &mut bucket.value) {
+                    *slot = None;
+                    self.table.len -= 1;
+                    removed = true;
+                }
+            }
+        }
+        // Shifting entries back one removal at a time could wrap an entry we already saw
+        // around the end of the table and show it to `f` again. Instead, punch all the holes
+        // first and then put the survivors back in place.
+        if removed {
+            self.table.resize(self.table.buckets.len());
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            buckets: self.table.buckets.iter(),
+            remaining: self.table.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            buckets: self.table.buckets.iter_mut(),
+            remaining: self.table.len,
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    pub fn into_keys(self) -> impl Iterator<Item = K> {
+        self.into_iter().map(|(key, _)| key)
+    }
+
+    pub fn into_values(self) -> impl Iterator<Item = V> {
+        self.into_iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> HashMap<K, V, S> {
+    /// Makes room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        self.table.reserve(additional);
+    }
+
+    /// Shrinks the table as far as it goes while keeping every entry.
+    pub fn shrink_to_fit(&mut self) {
+        if self.table.len == 0 {
+            self.table.buckets = Vec::new();
+            return;
+        }
+        let mut buckets = 8;
+        while buckets / 8 * 7 < self.table.len {
+            buckets *= 2;
+        }
+        if buckets < self.table.buckets.len() {
+            self.table.resize(buckets);
+        }
+    }
+
+    /// Inserts a key-value pair. If the key was already present, its value is replaced and
+    /// the old one returned; the key itself is kept.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.table.find(self.hash_builder.hash_one(key), key)?;
+        let bucket = self.table.bucket(index);
+        Some((&bucket.key, &bucket.value))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.table.find(self.hash_builder.hash_one(key), key)?;
+        Some(&mut self.table.bucket_mut(index).value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.table.find(self.hash_builder.hash_one(key), key)?;
+        let bucket = self.table.remove_at(index);
+        Some((bucket.key, bucket.value))
+    }
+
+    /// Gets the entry for `key`, to look at, insert or update it with a single lookup.
+    ///
+    /// Makes room for one more entry up front, so inserting into a vacant entry never has
+    /// to grow the table.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.table.reserve(1);
+        let hash = self.hash_builder.hash_one(&key);
+        match self.table.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                table: &mut self.table,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                table: &mut self.table,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+/// A view into one key of a [`HashMap`], from [`HashMap::entry`].
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// An [`Entry`] whose key is in the map.
+pub struct OccupiedEntry<'a, K, V> {
+    table: &'a mut Table<K, V>,
+    index: usize,
+}
+
+/// An [`Entry`] whose key isn't in the map yet.
+pub struct VacantEntry<'a, K, V> {
+    table: &'a mut Table<K, V>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value, inserting `default` first if the key is vacant.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Runs `f` on the value if the key is occupied.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.table.bucket(self.index).key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.table.bucket(self.index).value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.table.bucket_mut(self.index).value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.table.bucket_mut(self.index).value
+    }
+
+    /// Replaces the value and returns the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let bucket = self.table.remove_at(self.index);
+        (bucket.key, bucket.value)
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let index = self.table.insert_new(self.hash, self.key, value);
+        &mut self.table.bucket_mut(index).value
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Entry<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Occupied(entry) => f
+                .debug_struct("OccupiedEntry")
+                .field("key", entry.key())
+                .field("value", entry.get())
+                .finish(),
+            Entry::Vacant(entry) => f.debug_tuple("VacantEntry").field(entry.key()).finish(),
+        }
+    }
+}
+
+/// Iterator over the entries of a [`HashMap`], in no particular order.
+pub struct Iter<'a, K, V> {
+    buckets: slice::Iter<'a, Option<Bucket<K, V>>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bucket = self.buckets.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&bucket.key, &bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            buckets: self.buckets.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// Mutable iterator over the entries of a [`HashMap`].
+pub struct IterMut<'a, K, V> {
+    buckets: slice::IterMut<'a, Option<Bucket<K, V>>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bucket = self.buckets.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&bucket.key, &mut bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// Owning iterator over the entries of a [`HashMap`].
+pub struct IntoIter<K, V> {
+    buckets: vec::IntoIter<Option<Bucket<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bucket = self.buckets.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((bucket.key, bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// Iterator over the keys of a [`HashMap`].
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// Iterator over the values of a [`HashMap`].
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// Mutable iterator over the values of a [`HashMap`].
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            remaining: self.table.len,
+            buckets: self.table.buckets.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> Extend<(K, V)> for HashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Eq + Hash + Copy, V: Copy, S: BuildHasher> Extend<(&'a K, &'a V)> for HashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Default> FromIterator<(K, V)> for HashMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Eq + Hash, V, const N: usize> From<[(K, V); N]> for HashMap<K, V, RandomState> {
+    fn from(entries: [(K, V); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Panics if the key isn't in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in HashMap")
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> PartialEq for HashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|v| value == v))
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for HashMap<K, V, S> {}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for HashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for HashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::hash::BuildHasherDefault;
+    use std::{collections::HashMap as StdHashMap, format, string::String, vec::Vec};
+
+    // Checks the Robin Hood invariants: entries sit no further from home than the probe
+    // sequence allows, and the length matches.
+    fn assert_valid<K, V, S>(map: &HashMap<K, V, S>) {
+        let table = &map.table;
+        let mask = table.mask();
+        let mut len = 0;
+        for (index, bucket) in table.buckets.iter().enumerate() {
+            let Some(bucket) = bucket else { continue };
+            len += 1;
+            let dist = distance(bucket.hash, index, mask);
+            // Every bucket between home and here is taken.
+            for back in 1..=dist {
+                assert!(table.buckets[index.wrapping_sub(back) & mask].is_some());
+            }
+        }
+        assert_eq!(len, table.len);
+        assert!(table.len <= table.capacity() || table.buckets.is_empty());
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map = HashMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("a", 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+        assert_eq!(map["b"], 2);
+        assert!(!map.contains_key("c"));
+        *map.get_mut("b").unwrap() += 10;
+        assert_eq!(map.remove("b"), Some(12));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.len(), 1);
+        assert_valid(&map);
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut map: HashMap<String, usize> = HashMap::new();
+        map.insert(String::from("owned"), 1);
+        assert_eq!(map.get("owned"), Some(&1));
+        assert_eq!(
+            map.get_key_value("owned"),
+            Some((&String::from("owned"), &1))
+        );
+        assert_eq!(map.remove_entry("owned"), Some((String::from("owned"), 1)));
+    }
+
+    #[test]
+    fn test_entry_api() {
+        let text = "the quick brown fox jumps over the lazy dog the end";
+        let mut counts = HashMap::new();
+        for word in text.split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(counts["the"], 3);
+        assert_eq!(counts["fox"], 1);
+
+        let mut map: HashMap<&str, Vec<u32>> = HashMap::new();
+        map.entry("a").or_default().push(1);
+        map.entry("a").and_modify(|v| v.push(2)).or_default();
+        assert_eq!(map["a"], [1, 2]);
+        assert_eq!(
+            *map.entry("len")
+                .or_insert_with_key(|k| vec![k.len() as u32]),
+            [3]
+        );
+
+        match map.entry("a") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.insert(vec![9]), [1, 2]);
+                assert_eq!(entry.remove(), [9]);
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("b") {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), "b"),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert!(!map.contains_key("a") && !map.contains_key("b"));
+        assert_valid(&map);
+    }
+
+    #[test]
+    fn test_matches_std_under_churn() {
+        // A deterministic pseudo-random mix of inserts and removes, checked against std.
+        let mut map = HashMap::new();
+        let mut model = StdHashMap::new();
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for step in 0..20_000u32 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let key = (seed % 2_000) as u32;
+            if seed & 1 == 0 {
+                assert_eq!(map.insert(key, step), model.insert(key, step));
+            } else {
+                assert_eq!(map.remove(&key), model.remove(&key));
+            }
+        }
+        assert_valid(&map);
+        assert_eq!(map.len(), model.len());
+        for (key, value) in &model {
+            assert_eq!(map.get(key), Some(value));
+        }
+        let mut entries: Vec<_> = map.into_iter().collect();
+        let mut expected: Vec<_> = model.into_iter().collect();
+        entries.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn test_colliding_hasher() {
+        // A hasher that sends every key to the same bucket still gives correct answers.
+        #[derive(Default)]
+        struct Constant;
+        impl core::hash::Hasher for Constant {
+            fn write(&mut self, _: &[u8]) {}
+            fn finish(&self) -> u64 {
+                7
+            }
+        }
+        let mut map: HashMap<u32, u32, BuildHasherDefault<Constant>> = HashMap::default();
+        for i in 0..50 {
+            map.insert(i, i * 2);
+        }
+        for i in (0..50).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        assert_valid(&map);
+        for i in 0..50 {
+            assert_eq!(map.get(&i).copied(), (i % 3 != 0).then_some(i * 2));
+        }
+    }
+
+    #[test]
+    fn test_capacity_and_shrink() {
+        let mut map = HashMap::with_capacity(100);
+        let capacity = map.capacity();
+        assert!(capacity >= 100);
+        map.extend((0..100).map(|i| (i, i)));
+        assert_eq!(map.capacity(), capacity);
+        map.retain(|&k, v| {
+            *v += 1;
+            k < 10
+        });
+        assert_eq!(map.len(), 10);
+        assert_eq!(map[&3], 4);
+        assert_valid(&map);
+        map.shrink_to_fit();
+        assert!(map.capacity() < capacity);
+        assert_valid(&map);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&3), None);
+    }
+
+    #[test]
+    fn test_iterators_and_traits() {
+        let mut map = HashMap::from([(1, "one"), (2, "two"), (3, "three")]);
+        assert_eq!(map.iter().len(), 3);
+        let mut keys: Vec<_> = map.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [1, 2, 3]);
+        for value in map.values_mut() {
+            *value = "n";
+        }
+        assert!(map.values().all(|&v| v == "n"));
+
+        let clone = map.clone();
+        assert_eq!(clone, map);
+        map.insert(4, "n");
+        assert_ne!(clone, map);
+
+        let single = HashMap::from([(1, 'x')]);
+        assert_eq!(format!("{single:?}"), "{1: 'x'}");
+        let mut values: Vec<_> = map.into_values().collect();
+        values.dedup();
+        assert_eq!(values, ["n"]);
+    }
+}
@@ -0,0 +1,209 @@
+//! A string interner that stores every distinct string once.
+
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    iter::{Enumerate, FusedIterator},
+    slice,
+};
+
+use super::HashMap;
+use crate::rc::Rc;
+
+/// A small handle for a string in an [`Interner`].
+///
+/// Symbols are numbered from zero in the order their strings were first interned. Two
+/// symbols from the same interner are equal exactly when their strings are, so comparing
+/// or hashing them is as cheap as for a `u32`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+/// Deduplicates strings, handing out one shared copy of each.
+///
+/// Compilers and parsers see the same identifiers and keywords over and over. Interning
+/// stores each distinct string once as an [`Rc<str>`](crate::rc::Rc) and hands out either
+/// that `Rc` or a [`Symbol`] for it; the symbol can be turned back into the string with
+/// [`resolve`](Interner::resolve).
+///
+/// ```
+/// use Cell::collections::Interner;
+///
+/// let mut interner = Interner::new();
+/// let a = interner.intern("let");
+/// let b = interner.intern("x");
+/// assert_eq!(interner.intern("let"), a);
+/// assert_ne!(a, b);
+/// assert_eq!(interner.resolve(b), "x");
+/// ```
+#[derive(Default)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    // Indexed by symbol.
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns the symbol for `text`, interning it first if it is new.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` distinct strings are interned.
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many interned strings"));
+        let text = Rc::from(text);
+        self.strings.push(text.clone());
+        self.symbols.insert(text, symbol);
+        symbol
+    }
+
+    /// Returns the shared copy of `text`, interning it first if it is new.
+    ///
+    /// Interning the same string again returns a clone of the same `Rc`.
+    pub fn intern_rc(&mut self, text: &str) -> Rc<str> {
+        let symbol = self.intern(text);
+        self.strings[symbol.0 as usize].clone()
+    }
+
+    /// Returns the symbol for `text` if it has been interned, without interning it.
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        self.symbols.get(text).copied()
+    }
+
+    /// Returns the string for `symbol`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` did not come from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.resolve_rc(symbol)[..]
+    }
+
+    /// Returns the shared copy of the string for `symbol`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` did not come from this interner.
+    pub fn resolve_rc(&self, symbol: Symbol) -> &Rc<str> {
+        self.strings
+            .get(symbol.0 as usize)
+            .expect("symbol from a different interner")
+    }
+
+    /// Iterates over the symbols and their strings, in the order they were interned.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            strings: self.strings.iter().enumerate(),
+        }
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.strings).finish()
+    }
+}
+
+/// Iterator over the symbols and strings of an [`Interner`].
+pub struct Iter<'a> {
+    strings: Enumerate<slice::Iter<'a, Rc<str>>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Symbol, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, text) = self.strings.next()?;
+        Some((Symbol(index as u32), text))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.strings.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, string::String, vec::Vec};
+
+    #[test]
+    fn test_intern_deduplicates() {
+        let mut interner = Interner::new();
+        let words = "the cat sat on the mat the end";
+        let symbols: Vec<Symbol> = words.split(' ').map(|w| interner.intern(w)).collect();
+        assert_eq!(interner.len(), 6);
+        assert_eq!(symbols[0], symbols[4]);
+        assert_eq!(symbols[0], symbols[6]);
+        assert_eq!(symbols[3].as_u32(), 3);
+        let back: Vec<&str> = symbols.iter().map(|&s| interner.resolve(s)).collect();
+        assert_eq!(back.join(" "), words);
+    }
+
+    #[test]
+    fn test_intern_rc_shares_one_allocation() {
+        let mut interner = Interner::new();
+        let a = interner.intern_rc("shared");
+        let b = interner.intern_rc(&String::from("shared"));
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(Rc::ptr_eq(
+            &a,
+            interner.resolve_rc(interner.get("shared").unwrap())
+        ));
+        // One count in the map, one in the list, and the two handed out.
+        assert_eq!(Rc::strong_count(&a), 4);
+    }
+
+    #[test]
+    fn test_get_does_not_intern() {
+        let mut interner = Interner::new();
+        assert_eq!(interner.get("x"), None);
+        assert!(interner.is_empty());
+        let x = interner.intern("x");
+        assert_eq!(interner.get("x"), Some(x));
+    }
+
+    #[test]
+    fn test_iter_and_debug() {
+        let mut interner = Interner::new();
+        for word in ["b", "a", "b", ""] {
+            interner.intern(word);
+        }
+        let all: Vec<(u32, &str)> = interner.iter().map(|(s, t)| (s.as_u32(), t)).collect();
+        assert_eq!(all, [(0, "b"), (1, "a"), (2, "")]);
+        assert_eq!(format!("{interner:?}"), r#"["b", "a", ""]"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "symbol from a different interner")]
+    fn test_resolve_foreign_symbol_panics() {
+        let mut other = Interner::new();
+        other.intern("a");
+        let symbol = other.intern("b");
+        Interner::new().resolve(symbol);
+    }
+}
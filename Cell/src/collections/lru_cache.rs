@@ -0,0 +1,396 @@
+//! A cache that evicts the least recently used entries once it is full.
+
+use core::{borrow::Borrow, fmt, hash::Hash, iter::FusedIterator};
+
+use super::HashMap;
+use crate::linkedlist::{self, LinkedList, NodeHandle};
+
+/// Decides how much of an [`LruCache`]'s budget an entry takes up.
+///
+/// The cache evicts entries until the total weight of what it holds is at most its
+/// `max_weight`. With [`Count`] every entry weighs one, so the budget is a number of
+/// entries; a closure `Fn(&K, &V) -> usize` can weigh entries by size instead.
+pub trait Weigher<K, V> {
+    fn weight(&self, key: &K, value: &V) -> usize;
+}
+
+/// The default [`Weigher`], which gives every entry a weight of one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Count;
+
+impl<K, V> Weigher<K, V> for Count {
+    fn weight(&self, _: &K, _: &V) -> usize {
+        1
+    }
+}
+
+impl<K, V, F: Fn(&K, &V) -> usize> Weigher<K, V> for F {
+    fn weight(&self, key: &K, value: &V) -> usize {
+        self(key, value)
+    }
+}
+
+/// A map with a size budget that makes room by dropping the entries that were used
+/// longest ago.
+///
+/// The entries are kept in a [`LinkedList`] ordered from most to least recently used, so
+/// `get` can move an entry to the front and eviction can take one off the back. A
+/// [`HashMap`] maps every key to the [`NodeHandle`] of its entry, which makes `get`, `put`,
+/// `remove` and `pop_lru` all O(1). The map and the entry both need the key, so keys are
+/// cloned once when they are inserted.
+///
+/// [`LruCache::new`] limits the number of entries; [`LruCache::with_weigher`] limits their
+/// total [weight](Weigher) instead:
+///
+/// ```
+/// use Cell::collections::LruCache;
+///
+/// let mut pages = LruCache::with_weigher(10, |_: &&str, page: &String| page.len());
+/// pages.put("a", String::from("aaaa"));
+/// pages.put("b", String::from("bbbb"));
+/// pages.get("a");
+/// pages.put("c", String::from("cccc"));
+/// assert!(pages.contains("a") && pages.contains("c"));
+/// assert!(!pages.contains("b"));
+/// ```
+pub struct LruCache<K, V, W = Count> {
+    // Every handle in `map` points to an entry in `list`, and every entry in `list` has its
+    // handle in `map` under its key. The `unsafe` blocks below rely on it.
+    map: HashMap<K, NodeHandle<Entry<K, V>>>,
+    list: LinkedList<Entry<K, V>>,
+    weight: usize,
+    max_weight: usize,
+    weigher: W,
+}
+
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    weight: usize,
+}
+
+impl<K, V> LruCache<K, V> {
+    /// Creates a cache that holds at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_weigher(capacity, Count)
+    }
+}
+
+impl<K, V, W> LruCache<K, V, W> {
+    /// Creates a cache that holds entries weighing at most `max_weight` in total.
+    pub fn with_weigher(max_weight: usize, weigher: W) -> Self {
+        Self {
+            map: HashMap::new(),
+            list: LinkedList::new(),
+            weight: 0,
+            max_weight,
+            weigher,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns the total weight of the entries; for a cache made with `new`, the same as
+    /// `len`.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub fn max_weight(&self) -> usize {
+        self.max_weight
+    }
+
+    /// Returns the least recently used entry without touching it.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let entry = self.list.back()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.list.clear();
+        self.weight = 0;
+    }
+
+    /// Iterates over the entries from the most to the least recently used, without
+    /// touching them.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: self.list.iter(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, W> LruCache<K, V, W> {
+    /// Returns the value for `key` and marks it as the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = *self.map.get(key)?;
+        // SAFETY: the handle came from the map, so its entry is in `list`.
+        unsafe {
+            self.list.move_to_front(handle);
+            Some(&self.list.element(handle).value)
+        }
+    }
+
+    /// Returns the value for `key` mutably and marks it as the most recently used.
+    ///
+    /// The entry keeps the weight it was given by `put`, whatever is changed through the
+    /// reference.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = *self.map.get(key)?;
+        // SAFETY: as in `get`.
+        unsafe {
+            self.list.move_to_front(handle);
+            Some(&mut self.list.element_mut(handle).value)
+        }
+    }
+
+    /// Returns the value for `key` without marking it as used.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = *self.map.get(key)?;
+        // SAFETY: as in `get`.
+        Some(unsafe { &self.list.element(handle).value })
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Inserts a value as the most recently used entry, evicting the least recently used
+    /// ones until the cache is within its budget again. If the key was already present,
+    /// the old value is returned.
+    ///
+    /// An entry that weighs more than the whole budget is not cached at all, and any old
+    /// value for its key is removed.
+    pub fn put(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Clone,
+        W: Weigher<K, V>,
+    {
+        let old = self.remove(&key);
+        let weight = self.weigher.weight(&key, &value);
+        if weight > self.max_weight {
+            return old;
+        }
+        // Make room before adding, so the total never goes past `max_weight` and can't
+        // overflow even for a budget near `usize::MAX`.
+        while self.weight > self.max_weight - weight {
+            self.pop_lru();
+        }
+        let handle = self.list.push_front_handle(Entry {
+            key: key.clone(),
+            value,
+            weight,
+        });
+        self.map.insert(key, handle);
+        self.weight += weight;
+        old
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.map.remove(key)?;
+        // SAFETY: the handle was in the map, so its entry is still in `list`; with the
+        // handle gone from the map too, nothing points to the entry afterwards.
+        let entry = unsafe { self.list.remove_handle(handle) };
+        self.weight -= entry.weight;
+        Some(entry.value)
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let entry = self.list.pop_back()?;
+        self.map.remove(&entry.key);
+        self.weight -= entry.weight;
+        Some((entry.key, entry.value))
+    }
+
+    /// Changes the budget, evicting the least recently used entries if the cache is over
+    /// the new one.
+    pub fn set_max_weight(&mut self, max_weight: usize) {
+        self.max_weight = max_weight;
+        while self.weight > self.max_weight {
+            self.pop_lru();
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, W> fmt::Debug for LruCache<K, V, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the entries of an [`LruCache`], from the most to the least recently used.
+pub struct Iter<'a, K, V> {
+    entries: linkedlist::Iter<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, string::String, vec::Vec};
+
+    fn keys<K: Copy, V, W>(cache: &LruCache<K, V, W>) -> Vec<K> {
+        cache.iter().map(|(key, _)| *key).collect()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = LruCache::new(3);
+        for i in 0..3 {
+            assert_eq!(cache.put(i, i * 10), None);
+        }
+        assert_eq!(cache.get(&0), Some(&0));
+        cache.put(3, 30);
+        assert_eq!(keys(&cache), [3, 0, 2]);
+        assert!(!cache.contains(&1));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.peek_lru(), Some((&2, &20)));
+    }
+
+    #[test]
+    fn test_put_existing_key_replaces_and_promotes() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        assert_eq!(cache.put("a", 3), Some(1));
+        cache.put("c", 4);
+        assert_eq!(keys(&cache), ["c", "a"]);
+        assert_eq!(cache.peek("a"), Some(&3));
+    }
+
+    #[test]
+    fn test_peek_does_not_promote() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, ());
+        cache.put(2, ());
+        assert_eq!(cache.peek(&1), Some(&()));
+        cache.put(3, ());
+        assert_eq!(keys(&cache), [3, 2]);
+    }
+
+    #[test]
+    fn test_remove_and_pop_lru() {
+        let mut cache = LruCache::new(4);
+        for i in 0..4 {
+            cache.put(i, format!("v{i}"));
+        }
+        *cache.get_mut(&0).unwrap() += "!";
+        assert_eq!(cache.remove(&2), Some(String::from("v2")));
+        assert_eq!(cache.remove(&2), None);
+        assert_eq!(cache.pop_lru(), Some((1, String::from("v1"))));
+        assert_eq!(cache.pop_lru(), Some((3, String::from("v3"))));
+        assert_eq!(cache.pop_lru(), Some((0, String::from("v0!"))));
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_weight_based_eviction() {
+        let mut cache = LruCache::with_weigher(10, |_: &u32, value: &Vec<u8>| value.len());
+        cache.put(1, vec![0; 4]);
+        cache.put(2, vec![0; 4]);
+        cache.put(3, vec![0; 2]);
+        assert_eq!(cache.weight(), 10);
+        cache.put(4, vec![0; 7]);
+        assert_eq!(keys(&cache), [4, 3]);
+        assert_eq!(cache.weight(), 9);
+        cache.put(3, vec![0; 1]);
+        assert_eq!(cache.weight(), 8);
+    }
+
+    #[test]
+    fn test_entry_heavier_than_budget_is_not_cached() {
+        let mut cache = LruCache::with_weigher(5, |_: &&str, value: &usize| *value);
+        cache.put("small", 2);
+        cache.put("big", 3);
+        assert_eq!(cache.put("big", 6), Some(3));
+        assert_eq!(keys(&cache), ["small"]);
+        assert_eq!(cache.weight(), 2);
+    }
+
+    #[test]
+    fn test_huge_weights_dont_overflow() {
+        let mut cache = LruCache::with_weigher(usize::MAX, |_: &u8, value: &usize| *value);
+        cache.put(1, usize::MAX / 2 + 1);
+        cache.put(2, usize::MAX / 2 + 1);
+        assert_eq!(keys(&cache), [2]);
+        cache.put(3, usize::MAX / 2);
+        assert_eq!(keys(&cache), [3, 2]);
+        assert_eq!(cache.weight(), usize::MAX);
+    }
+
+    #[test]
+    fn test_set_max_weight_and_clear() {
+        let mut cache = LruCache::new(5);
+        for i in 0..5 {
+            cache.put(i, ());
+        }
+        cache.set_max_weight(2);
+        assert_eq!(keys(&cache), [4, 3]);
+        assert_eq!(cache.max_weight(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.weight(), 0);
+        cache.put(7, ());
+        assert_eq!(format!("{cache:?}"), "{7: ()}");
+    }
+
+    #[test]
+    fn test_is_send_and_sync() {
+        // The handles in the map are raw pointers underneath; they mustn't take this away.
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<LruCache<String, Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_zero_capacity_caches_nothing() {
+        let mut cache = LruCache::new(0);
+        assert_eq!(cache.put(1, 1), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+    }
+}
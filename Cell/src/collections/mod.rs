@@ -0,0 +1,31 @@
+//! Collections written from scratch, to see how the ones in `std::collections` work.
+//!
+//! They only need an allocator, so they work without `std` too; the one thing that changes
+//! is that [`RandomState`](hash_map::RandomState) has no randomness to seed itself with.
+
+pub mod bit_set;
+pub mod bit_vec;
+pub mod btree_map;
+pub mod disjoint_set;
+pub mod hash_map;
+pub mod interner;
+pub mod lru_cache;
+pub mod rope;
+mod sip;
+pub mod slab;
+pub mod slot_map;
+pub mod small_vec;
+pub mod timer_wheel;
+
+pub use bit_set::BitSet;
+pub use bit_vec::BitVec;
+pub use btree_map::BTreeMap;
+pub use disjoint_set::DisjointSet;
+pub use hash_map::HashMap;
+pub use interner::{Interner, Symbol};
+pub use lru_cache::LruCache;
+pub use rope::Rope;
+pub use slab::Slab;
+pub use slot_map::SlotMap;
+pub use small_vec::SmallVec;
+pub use timer_wheel::TimerWheel;
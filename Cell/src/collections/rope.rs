@@ -0,0 +1,613 @@
+//! A string stored as a balanced tree of chunks, for editing large texts.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Write},
+    iter::FusedIterator,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::rc::Rc;
+
+// The most bytes a leaf holds. Edits split leaves and joins merge neighbours that fit in
+// one chunk again, so leaves stay between a few bytes and this size.
+const CHUNK_SIZE: usize = 1024;
+
+/// A UTF-8 string that can be edited anywhere in O(log n).
+///
+/// A `String` has to move everything after the edit point on every insert or removal,
+/// which gets slow once the text is megabytes long, as in a text editor. A rope keeps the
+/// text in chunks of at most a kilobyte at the leaves of a balanced binary tree, and every
+/// branch remembers the length of the text below it. To insert, the tree is split at the
+/// insertion point and joined back together around the new text; to remove, it is split
+/// on both ends of the range and the outer parts are joined. Both only touch the nodes on
+/// the way down from the root, so they take O(log n).
+///
+/// Nodes are never changed after they are built; they are shared through
+/// [`Rc`] and an edit builds new nodes along the path it touched while the
+/// rest of the tree is shared with the old version, much like a [`Cow`](crate::cow::Cow)
+/// that only copies the part it writes to. Cloning a rope or taking a [`slice`](Rope::slice)
+/// of it is therefore cheap and leaves the original untouched:
+///
+/// ```
+/// use Cell::collections::Rope;
+///
+/// let mut rope = Rope::from("Hello world");
+/// let before = rope.clone();
+/// rope.insert(5, ",");
+/// rope.insert(rope.len(), "!");
+/// rope.remove(..1);
+/// assert_eq!(rope, "ello, world!");
+/// assert_eq!(before, "Hello world");
+/// assert_eq!(rope.slice(6..11), "world");
+/// ```
+///
+/// Positions are byte offsets, as for `String`, and must lie on `char` boundaries.
+#[derive(Clone, Default)]
+pub struct Rope {
+    root: Option<Rc<Node>>,
+}
+
+enum Node {
+    Leaf(String),
+    Branch {
+        left: Rc<Node>,
+        right: Rc<Node>,
+        len: usize,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(text) => text.len(),
+            Node::Branch { len, .. } => *len,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    fn children(&self) -> (&Rc<Node>, &Rc<Node>) {
+        match self {
+            Node::Branch { left, right, .. } => (left, right),
+            Node::Leaf(_) => unreachable!("a leaf has no children"),
+        }
+    }
+}
+
+fn leaf(text: &str) -> Rc<Node> {
+    Rc::new(Node::Leaf(String::from(text)))
+}
+
+fn branch(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    Rc::new(Node::Branch {
+        len: left.len() + right.len(),
+        height: left.height().max(right.height()) + 1,
+        left,
+        right,
+    })
+}
+
+// Builds a branch out of two subtrees whose heights differ by at most two, rotating it
+// back into balance if they differ by two.
+fn balance(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.height() > right.height() + 1 {
+        let (a, b) = left.children();
+        if a.height() >= b.height() {
+            branch(a.clone(), branch(b.clone(), right))
+        } else {
+            let (b1, b2) = b.children();
+            branch(branch(a.clone(), b1.clone()), branch(b2.clone(), right))
+        }
+    } else if right.height() > left.height() + 1 {
+        let (a, b) = right.children();
+        if b.height() >= a.height() {
+            branch(branch(left, a.clone()), b.clone())
+        } else {
+            let (a1, a2) = a.children();
+            branch(branch(left, a1.clone()), branch(a2.clone(), b.clone()))
+        }
+    } else {
+        branch(left, right)
+    }
+}
+
+// Concatenates two balanced trees into one. The taller tree is followed down its inner
+// edge until the heights match, so this takes O(|height difference| + 1) and only
+// rebuilds that path. Two leaves that fit in a chunk together are merged into one.
+fn join(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    match (&*left, &*right) {
+        (Node::Leaf(a), Node::Leaf(b)) if a.len() + b.len() <= CHUNK_SIZE => {
+            let mut text = String::with_capacity(a.len() + b.len());
+            text.push_str(a);
+            text.push_str(b);
+            Rc::new(Node::Leaf(text))
+        }
+        _ if left.height() > right.height() => {
+            let (a, b) = left.children();
+            balance(a.clone(), join(b.clone(), right))
+        }
+        _ if right.height() > left.height() => {
+            let (a, b) = right.children();
+            balance(join(left, a.clone()), b.clone())
+        }
+        _ => branch(left, right),
+    }
+}
+
+fn concat(left: Option<Rc<Node>>, right: Option<Rc<Node>>) -> Option<Rc<Node>> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(join(left, right)),
+        (left, None) => left,
+        (None, right) => right,
+    }
+}
+
+// Splits the text below `node` into the bytes before `at` and the bytes from `at` on.
+fn split(node: &Rc<Node>, at: usize) -> (Option<Rc<Node>>, Option<Rc<Node>>) {
+    if at == 0 {
+        return (None, Some(node.clone()));
+    }
+    if at == node.len() {
+        return (Some(node.clone()), None);
+    }
+    match &**node {
+        Node::Leaf(text) => (Some(leaf(&text[..at])), Some(leaf(&text[at..]))),
+        Node::Branch { left, right, .. } => {
+            if at <= left.len() {
+                let (before, after) = split(left, at);
+                (before, concat(after, Some(right.clone())))
+            } else {
+                let (before, after) = split(right, at - left.len());
+                (concat(Some(left.clone()), before), after)
+            }
+        }
+    }
+}
+
+// Builds a balanced tree out of `text`, cut into chunks on `char` boundaries.
+fn build(text: &str) -> Option<Rc<Node>> {
+    let mut leaves = Vec::with_capacity(text.len().div_ceil(CHUNK_SIZE));
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(CHUNK_SIZE);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        leaves.push(leaf(&rest[..end]));
+        rest = &rest[end..];
+    }
+    fn build_from(leaves: &[Rc<Node>]) -> Rc<Node> {
+        match leaves {
+            [leaf] => leaf.clone(),
+            _ => {
+                let (left, right) = leaves.split_at(leaves.len() / 2);
+                branch(build_from(left), build_from(right))
+            }
+        }
+    }
+    (!leaves.is_empty()).then(|| build_from(&leaves))
+}
+
+impl Rope {
+    /// Creates an empty rope.
+    pub const fn new() -> Self {
+        Rope { root: None }
+    }
+
+    /// Returns the length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.len())
+    }
+
+    /// Returns `true` if the rope holds no text.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns `true` if byte offset `at` is the start or end of a `char` in the text.
+    ///
+    /// Offsets past the end of the text are not boundaries.
+    pub fn is_char_boundary(&self, at: usize) -> bool {
+        let Some(mut node) = self.root.as_deref() else {
+            return at == 0;
+        };
+        if at > node.len() {
+            return false;
+        }
+        let mut at = at;
+        loop {
+            match node {
+                Node::Leaf(text) => return text.is_char_boundary(at),
+                Node::Branch { left, right, .. } => {
+                    if at < left.len() {
+                        node = left;
+                    } else {
+                        at -= left.len();
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Inserts `text` at byte offset `at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end of the rope or not on a `char` boundary.
+    pub fn insert(&mut self, at: usize, text: &str) {
+        self.assert_char_boundary(at);
+        let (before, after) = self.split_root(at);
+        self.root = concat(concat(before, build(text)), after);
+    }
+
+    /// Removes the bytes in `range` from the rope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or does not start and end on `char` boundaries.
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = self.check_range(range);
+        let (rest, after) = self.split_root(end);
+        let before = rest.and_then(|rest| split(&rest, start).0);
+        self.root = concat(before, after);
+    }
+
+    /// Returns the bytes in `range` as a new rope, which shares its chunks with this one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or does not start and end on `char` boundaries.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Rope {
+        let (start, end) = self.check_range(range);
+        let (rest, _) = self.split_root(end);
+        Rope {
+            root: rest.and_then(|rest| split(&rest, start).1),
+        }
+    }
+
+    /// Moves the text of `other` to the end of this rope.
+    pub fn append(&mut self, other: Rope) {
+        self.root = concat(self.root.take(), other.root);
+    }
+
+    /// Splits the rope in two at byte offset `at`, keeping the text before it and returning
+    /// the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end of the rope or not on a `char` boundary.
+    pub fn split_off(&mut self, at: usize) -> Rope {
+        self.assert_char_boundary(at);
+        let (before, after) = self.split_root(at);
+        self.root = before;
+        Rope { root: after }
+    }
+
+    /// Returns an iterator over the chunks of text the rope is made of, in order.
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    /// Returns an iterator over the `char`s of the text.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    fn split_root(&self, at: usize) -> (Option<Rc<Node>>, Option<Rc<Node>>) {
+        match &self.root {
+            Some(root) => split(root, at),
+            None => (None, None),
+        }
+    }
+
+    fn assert_char_boundary(&self, at: usize) {
+        assert!(
+            at <= self.len(),
+            "byte index {at} is out of bounds of a rope of length {}",
+            self.len()
+        );
+        assert!(
+            self.is_char_boundary(at),
+            "byte index {at} is not a char boundary"
+        );
+    }
+
+    // Turns `range` into start and end offsets, panicking like slicing a `str` would.
+    fn check_range<R: RangeBounds<usize>>(&self, range: R) -> (usize, usize) {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end,
+            "slice index starts at {start} but ends at {end}"
+        );
+        self.assert_char_boundary(start);
+        self.assert_char_boundary(end);
+        (start, end)
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Rope { root: build(text) }
+    }
+}
+
+impl From<String> for Rope {
+    fn from(text: String) -> Self {
+        Rope::from(text.as_str())
+    }
+}
+
+impl From<&Rope> for String {
+    fn from(rope: &Rope) -> Self {
+        let mut text = String::with_capacity(rope.len());
+        rope.chunks().for_each(|chunk| text.push_str(chunk));
+        text
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        self.len() == other.len()
+            && self
+                .chunks()
+                .flat_map(str::bytes)
+                .eq(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl Eq for Rope {}
+
+impl PartialEq<str> for Rope {
+    fn eq(&self, other: &str) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut rest = other.as_bytes();
+        self.chunks().all(|chunk| {
+            let (head, tail) = rest.split_at(chunk.len());
+            rest = tail;
+            head == chunk.as_bytes()
+        })
+    }
+}
+
+impl PartialEq<&str> for Rope {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for chunk in self.chunks() {
+            for c in chunk.chars() {
+                // `char::escape_debug` would also escape `'`, which `str` leaves alone.
+                match c {
+                    '\'' => f.write_char(c)?,
+                    _ => write!(f, "{}", c.escape_debug())?,
+                }
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+/// An iterator over the chunks of a [`Rope`].
+///
+/// Created by [`Rope::chunks`].
+pub struct Chunks<'a> {
+    // The subtrees still to visit, the next one on top.
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Node::Leaf(text) => return Some(text),
+                Node::Branch { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+    }
+}
+
+impl FusedIterator for Chunks<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, string::ToString};
+
+    // Checks that every branch has the right length and height and is balanced, and
+    // returns the height of the tree.
+    fn check(node: &Node) -> usize {
+        match node {
+            Node::Leaf(text) => {
+                assert!(!text.is_empty() && text.len() <= CHUNK_SIZE);
+                0
+            }
+            Node::Branch {
+                left,
+                right,
+                len,
+                height,
+            } => {
+                let (l, r) = (check(left), check(right));
+                assert_eq!(*len, left.len() + right.len());
+                assert_eq!(*height, l.max(r) + 1);
+                assert!(l.abs_diff(r) <= 1, "unbalanced: {l} and {r}");
+                *height
+            }
+        }
+    }
+
+    fn check_rope(rope: &Rope) {
+        if let Some(root) = &rope.root {
+            check(root);
+        }
+    }
+
+    // A small xorshift generator so the tests are repeatable without a dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_build_splits_on_char_boundaries() {
+        let text = "aé€😀".repeat(1000);
+        let rope = Rope::from(text.as_str());
+        check_rope(&rope);
+        assert!(rope.chunks().count() > 1);
+        assert_eq!(rope.len(), text.len());
+        assert_eq!(rope, text.as_str());
+        assert_eq!(rope.to_string(), text);
+        assert!(rope.chars().eq(text.chars()));
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut rope = Rope::new();
+        assert!(rope.is_empty());
+        assert_eq!(rope.chunks().next(), None);
+        assert!(rope.is_char_boundary(0));
+        rope.insert(0, "");
+        assert!(rope.is_empty());
+        rope.remove(..);
+        assert_eq!(rope, "");
+        assert_eq!(Rope::from(""), Rope::new());
+    }
+
+    #[test]
+    fn test_edits_match_string() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut rope = Rope::new();
+        let mut model = String::new();
+        for round in 0..2000 {
+            let at = rng.below(model.len() + 1);
+            if rng.below(3) > 0 {
+                let text = format!("<{round}:{}>", "x".repeat(rng.below(300)));
+                rope.insert(at, &text);
+                model.insert_str(at, &text);
+            } else {
+                let end = at + rng.below(model.len() - at + 1).min(500);
+                rope.remove(at..end);
+                model.replace_range(at..end, "");
+            }
+            check_rope(&rope);
+            assert_eq!(rope.len(), model.len());
+        }
+        assert_eq!(String::from(&rope), model);
+        let (start, end) = (model.len() / 3, model.len() / 2);
+        assert_eq!(rope.slice(start..end), &model[start..end]);
+    }
+
+    #[test]
+    fn test_many_small_inserts_stay_compact() {
+        let mut rope = Rope::new();
+        for i in 0..10_000 {
+            rope.insert(rope.len() / 2, if i % 2 == 0 { "ab" } else { "c" });
+        }
+        check_rope(&rope);
+        assert_eq!(rope.len(), 15_000);
+        assert!(rope.chunks().count() < 100);
+    }
+
+    #[test]
+    fn test_clone_and_slice_share_and_stay_unchanged() {
+        let text = "0123456789".repeat(500);
+        let mut rope = Rope::from(text.as_str());
+        let copy = rope.clone();
+        let middle = rope.slice(2000..3000);
+        rope.remove(1000..4000);
+        rope.insert(0, "new");
+        assert_eq!(copy, text.as_str());
+        assert_eq!(middle, &text[2000..3000]);
+        assert_eq!(rope.len(), text.len() - 3000 + 3);
+        check_rope(&middle);
+    }
+
+    #[test]
+    fn test_append_and_split_off() {
+        let mut left = Rope::from("a".repeat(5000));
+        let right = Rope::from("b".repeat(10));
+        left.append(right);
+        check_rope(&left);
+        assert_eq!(left.len(), 5010);
+        let tail = left.split_off(4990);
+        assert_eq!(
+            tail,
+            format!("{}{}", "a".repeat(10), "b".repeat(10)).as_str()
+        );
+        assert_eq!(left, "a".repeat(4990).as_str());
+        check_rope(&left);
+        check_rope(&tail);
+    }
+
+    #[test]
+    fn test_is_char_boundary() {
+        let rope = Rope::from("aé");
+        assert!(rope.is_char_boundary(1));
+        assert!(!rope.is_char_boundary(2));
+        assert!(rope.is_char_boundary(3));
+        assert!(!rope.is_char_boundary(4));
+    }
+
+    #[test]
+    fn test_debug_and_eq() {
+        let rope = Rope::from("it's \"quoted\"\n");
+        assert_eq!(format!("{rope:?}"), format!("{:?}", "it's \"quoted\"\n"));
+        assert_ne!(Rope::from("abc"), Rope::from("abd"));
+        assert_ne!(Rope::from("abc"), "ab");
+    }
+
+    #[test]
+    #[should_panic(expected = "byte index 2 is not a char boundary")]
+    fn test_insert_inside_char_panics() {
+        Rope::from("aé").insert(2, "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "slice index starts at 3 but ends at 2")]
+    fn test_reversed_range_panics() {
+        let (start, end) = (3, 2);
+        Rope::from("abcd").slice(start..end);
+    }
+}
@@ -0,0 +1,247 @@
+use core::{fmt, hash::BuildHasher, hash::Hasher};
+
+/// SipHash-1-3, the keyed hash behind the standard library's `HashMap`.
+///
+/// SipHash is a pseudo-random function: without the 128-bit key, an attacker can't pick
+/// keys that collide, so a map with a secret key can't be forced into its worst case by
+/// untrusted input. The 1-3 variant (one compression round per word, three finalization
+/// rounds) is what std uses. With the same keys, this hasher produces exactly the same
+/// values as std's `DefaultHasher`.
+#[derive(Clone, Debug)]
+pub struct SipHasher13 {
+    k0: u64,
+    k1: u64,
+    // Bytes written so far; only the low byte ends up in the hash.
+    length: usize,
+    state: State,
+    // Up to 7 bytes that don't fill a whole word yet, little-endian.
+    tail: u64,
+    ntail: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct State {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+}
+
+impl State {
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    // Mixes in one 8-byte word of the message.
+    fn compress(&mut self, m: u64) {
+        self.v3 ^= m;
+        self.round();
+        self.v0 ^= m;
+    }
+}
+
+impl SipHasher13 {
+    /// A hasher with both keys zero, like std's `DefaultHasher::new()`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::new_with_keys(0, 0)
+    }
+
+    #[must_use]
+    pub const fn new_with_keys(k0: u64, k1: u64) -> Self {
+        Self {
+            k0,
+            k1,
+            length: 0,
+            state: State {
+                v0: k0 ^ 0x736f_6d65_7073_6575,
+                v1: k1 ^ 0x646f_7261_6e64_6f6d,
+                v2: k0 ^ 0x6c79_6765_6e65_7261,
+                v3: k1 ^ 0x7465_6462_7974_6573,
+            },
+            tail: 0,
+            ntail: 0,
+        }
+    }
+
+    /// Starts over with the same keys.
+    pub fn reset(&mut self) {
+        *self = Self::new_with_keys(self.k0, self.k1);
+    }
+}
+
+impl Default for SipHasher13 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Reads up to 8 bytes as a little-endian integer.
+fn read_le(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
+
+impl Hasher for SipHasher13 {
+    fn write(&mut self, mut msg: &[u8]) {
+        self.length = self.length.wrapping_add(msg.len());
+
+        // Top up the bytes left over from the last write first.
+        if self.ntail != 0 {
+            let fill = msg.len().min(8 - self.ntail);
+            self.tail |= read_le(&msg[..fill]) << (8 * self.ntail);
+            self.ntail += fill;
+            msg = &msg[fill..];
+            if self.ntail < 8 {
+                return;
+            }
+            self.state.compress(self.tail);
+            self.tail = 0;
+            self.ntail = 0;
+        }
+
+        let mut words = msg.chunks_exact(8);
+        for word in &mut words {
+            self.state.compress(read_le(word));
+        }
+        let rest = words.remainder();
+        self.tail = read_le(rest);
+        self.ntail = rest.len();
+    }
+
+    fn finish(&self) -> u64 {
+        let mut state = self.state;
+        // The last word holds the leftover bytes and the message length mod 256.
+        state.compress(((self.length as u64 & 0xff) << 56) | self.tail);
+        state.v2 ^= 0xff;
+        for _ in 0..3 {
+            state.round();
+        }
+        state.v0 ^ state.v1 ^ state.v2 ^ state.v3
+    }
+}
+
+/// Builds [`SipHasher13`]s with keys chosen when the `RandomState` is created.
+///
+/// With the `std` feature, every `RandomState` gets fresh random keys, so two maps hash the
+/// same key differently and an attacker can't predict collisions. Without `std` there is no
+/// source of randomness: the keys are fixed, which is fine for trusted keys but leaves a map
+/// fed with untrusted input open to collision attacks.
+#[derive(Clone)]
+pub struct RandomState {
+    k0: u64,
+    k1: u64,
+}
+
+impl RandomState {
+    #[must_use]
+    pub fn new() -> Self {
+        #[cfg(feature = "std")]
+        {
+            // std already seeds its own SipHash keys from the OS; hashing with a fresh one of
+            // its states gives us two random words without another randomness source.
+            let random = std::hash::RandomState::new();
+            Self {
+                k0: random.hash_one(0u8),
+                k1: random.hash_one(1u8),
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            Self {
+                k0: 0x0706_0504_0302_0100,
+                k1: 0x0f0e_0d0c_0b0a_0908,
+            }
+        }
+    }
+}
+
+impl BuildHasher for RandomState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.k0, self.k1)
+    }
+}
+
+impl Default for RandomState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RandomState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The keys are secret.
+        f.debug_struct("RandomState").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::hash::Hash;
+    use std::{hash::DefaultHasher, string::String, vec::Vec};
+
+    fn sip<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut hasher = SipHasher13::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn std_sip<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_matches_std_default_hasher() {
+        let bytes: Vec<u8> = (0..64).collect();
+        for len in 0..bytes.len() {
+            assert_eq!(sip(&bytes[..len]), std_sip(&bytes[..len]), "length {len}");
+        }
+        assert_eq!(sip("hello"), std_sip("hello"));
+        assert_eq!(sip(&(1u8, 2u64, -3i32)), std_sip(&(1u8, 2u64, -3i32)));
+        assert_eq!(
+            sip(&String::from("a longer string")),
+            std_sip("a longer string")
+        );
+    }
+
+    #[test]
+    fn test_split_writes() {
+        let mut whole = SipHasher13::new_with_keys(1, 2);
+        whole.write(b"split across several writes");
+        let mut parts = SipHasher13::new_with_keys(1, 2);
+        for part in [&b"split"[..], b" across", b" sev", b"eral writes"] {
+            parts.write(part);
+        }
+        assert_eq!(whole.finish(), parts.finish());
+
+        parts.reset();
+        assert_eq!(parts.finish(), SipHasher13::new_with_keys(1, 2).finish());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_random_states_differ() {
+        let (a, b) = (RandomState::new(), RandomState::new());
+        assert_ne!(a.hash_one("key"), b.hash_one("key"));
+        assert_eq!(a.hash_one("key"), a.clone().hash_one("key"));
+    }
+}
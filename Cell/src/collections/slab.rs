@@ -0,0 +1,422 @@
+//! Storage for values that are addressed by small integer keys.
+
+use alloc::vec::{self, Vec};
+use core::{
+    fmt,
+    iter::{Enumerate, FusedIterator},
+    mem,
+    ops::{Index, IndexMut},
+    slice,
+};
+
+/// A `Vec` of slots that hands out each value's index as its key.
+///
+/// `insert` returns a key, and the value stays under that key until it is removed; removing
+/// other values never moves it. Freed slots are chained into a free list and reused by the
+/// next inserts, so inserting and removing are O(1) and the slab only grows when all its
+/// slots are taken. That makes it a good home for registrations that need a cheap handle,
+/// such as the waiters of a queue: the key is just a `usize`.
+///
+/// A key is only meaningful until its value is removed; afterwards it may be handed out
+/// again for a different value.
+#[derive(Clone)]
+pub struct Slab<T> {
+    entries: Vec<Entry<T>>,
+    len: usize,
+    // The first vacant slot, or `entries.len()` if there is none.
+    next_free: usize,
+}
+
+#[derive(Clone)]
+enum Entry<T> {
+    Occupied(T),
+    // Holds the next vacant slot, like `next_free`.
+    Vacant(usize),
+}
+
+impl<T> Slab<T> {
+    /// Creates an empty slab without allocating.
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            len: 0,
+            next_free: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            len: 0,
+            next_free: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Makes room for `additional` more values than there are free slots.
+    pub fn reserve(&mut self, additional: usize) {
+        let free = self.entries.len() - self.len;
+        self.entries.reserve(additional.saturating_sub(free));
+    }
+
+    /// The key the next `insert` will return.
+    pub fn vacant_key(&self) -> usize {
+        self.next_free
+    }
+
+    /// Stores `value` and returns its key.
+    pub fn insert(&mut self, value: T) -> usize {
+        let key = self.next_free;
+        match self.entries.get_mut(key) {
+            Some(entry) => match mem::replace(entry, Entry::Occupied(value)) {
+                Entry::Vacant(next) => self.next_free = next,
+                Entry::Occupied(_) => unreachable!("free list points at an occupied slot"),
+            },
+            None => {
+                self.entries.push(Entry::Occupied(value));
+                self.next_free = self.entries.len();
+            }
+        }
+        self.len += 1;
+        key
+    }
+
+    /// Like `insert`, but lets the value know its own key.
+    pub fn insert_with_key(&mut self, f: impl FnOnce(usize) -> T) -> usize {
+        let value = f(self.vacant_key());
+        self.insert(value)
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.entries.get(key)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.entries.get_mut(key)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes and returns the value under `key`, if there is one.
+    pub fn try_remove(&mut self, key: usize) -> Option<T> {
+        let entry = self.entries.get_mut(key)?;
+        if let Entry::Vacant(_) = entry {
+            return None;
+        }
+        let Entry::Occupied(value) = mem::replace(entry, Entry::Vacant(self.next_free)) else {
+            unreachable!()
+        };
+        self.next_free = key;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Removes and returns the value under `key`.
+    ///
+    /// Panics if there is no value under `key`.
+    pub fn remove(&mut self, key: usize) -> T {
+        self.try_remove(key).expect("invalid slab key")
+    }
+
+    /// Keeps only the values for which `f` returns `true`. Their keys don't change.
+    pub fn retain(&mut self, mut f: impl FnMut(usize, &mut T) -> bool) {
+        for key in 0..self.entries.len() {
+            if let Entry::Occupied(value) = &mut self.entries[key] {
+                if !f(key, value) {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Removes every value. Keys start over from zero.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+        self.next_free = 0;
+    }
+
+    /// Iterates over the keys and values, in key order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            entries: self.entries.iter().enumerate(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            entries: self.entries.iter_mut().enumerate(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    /// Panics if there is no value under `key`.
+    fn index(&self, key: usize) -> &T {
+        self.get(key).expect("invalid slab key")
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        self.get_mut(key).expect("invalid slab key")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Slab<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the keys and values of a [`Slab`].
+pub struct Iter<'a, T> {
+    entries: Enumerate<slice::Iter<'a, Entry<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.entries.find_map(|(key, entry)| match entry {
+            Entry::Occupied(value) => Some((key, value)),
+            Entry::Vacant(_) => None,
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self
+            .entries
+            .rfind(|(_, entry)| matches!(entry, Entry::Occupied(_)))?;
+        self.remaining -= 1;
+        match item {
+            (key, Entry::Occupied(value)) => Some((key, value)),
+            (_, Entry::Vacant(_)) => unreachable!(),
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// Mutable iterator over the keys and values of a [`Slab`].
+pub struct IterMut<'a, T> {
+    entries: Enumerate<slice::IterMut<'a, Entry<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (usize, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.entries.find_map(|(key, entry)| match entry {
+            Entry::Occupied(value) => Some((key, value)),
+            Entry::Vacant(_) => None,
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for IterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self
+            .entries
+            .rfind(|(_, entry)| matches!(entry, Entry::Occupied(_)))?;
+        self.remaining -= 1;
+        match item {
+            (key, Entry::Occupied(value)) => Some((key, value)),
+            (_, Entry::Vacant(_)) => unreachable!(),
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+impl<T> FusedIterator for IterMut<'_, T> {}
+
+/// Owning iterator over the keys and values of a [`Slab`].
+pub struct IntoIter<T> {
+    entries: Enumerate<vec::IntoIter<Entry<T>>>,
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        let item = self.entries.find_map(|(key, entry)| match entry {
+            Entry::Occupied(value) => Some((key, value)),
+            Entry::Vacant(_) => None,
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<(usize, T)> {
+        let item = self
+            .entries
+            .rfind(|(_, entry)| matches!(entry, Entry::Occupied(_)))?;
+        self.remaining -= 1;
+        match item {
+            (key, Entry::Occupied(value)) => Some((key, value)),
+            (_, Entry::Vacant(_)) => unreachable!(),
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for Slab<T> {
+    type Item = (usize, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            entries: self.entries.into_iter().enumerate(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Slab<T> {
+    type Item = (usize, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Slab<T> {
+    type Item = (usize, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, vec::Vec};
+
+    #[test]
+    fn test_keys_are_stable_and_reused() {
+        let mut slab = Slab::new();
+        let a = slab.insert("a");
+        let b = slab.insert("b");
+        let c = slab.insert("c");
+        assert_eq!((a, b, c), (0, 1, 2));
+        assert_eq!(slab.remove(b), "b");
+        assert_eq!(slab[a], "a");
+        assert_eq!(slab[c], "c");
+        assert_eq!(slab.get(b), None);
+        assert_eq!(slab.try_remove(b), None);
+
+        // The most recently freed slot is reused first.
+        slab.remove(a);
+        assert_eq!(slab.vacant_key(), a);
+        assert_eq!(slab.insert("d"), a);
+        assert_eq!(slab.insert("e"), b);
+        assert_eq!(slab.insert("f"), 3);
+        assert_eq!(slab.len(), 4);
+    }
+
+    #[test]
+    fn test_insert_with_key() {
+        let mut slab = Slab::new();
+        slab.insert(0);
+        let key = slab.insert_with_key(|key| key * 10);
+        assert_eq!(slab[key], 10);
+    }
+
+    #[test]
+    fn test_iteration_skips_vacant_slots() {
+        let mut slab: Slab<u32> = Slab::with_capacity(8);
+        for i in 0..8 {
+            slab.insert(i);
+        }
+        slab.retain(|key, _| key % 3 != 0);
+        assert_eq!(slab.len(), 5);
+        let keys: Vec<usize> = slab.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, [1, 2, 4, 5, 7]);
+        assert_eq!(slab.iter().len(), 5);
+        for (_, value) in slab.iter_mut().rev().take(2) {
+            *value *= 100;
+        }
+        assert_eq!(format!("{slab:?}"), "{1: 1, 2: 2, 4: 4, 5: 500, 7: 700}");
+        let owned: Vec<(usize, u32)> = slab.clone().into_iter().rev().collect();
+        assert_eq!(owned[0], (7, 700));
+        slab.clear();
+        assert!(slab.is_empty());
+        assert_eq!(slab.insert(9), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid slab key")]
+    fn test_remove_vacant_panics() {
+        let mut slab = Slab::new();
+        let key = slab.insert(());
+        slab.remove(key);
+        slab.remove(key);
+    }
+}
@@ -0,0 +1,454 @@
+//! Storage whose keys notice when the value they pointed at has been removed.
+
+use alloc::vec::{self, Vec};
+use core::{
+    fmt,
+    iter::{Enumerate, FusedIterator},
+    mem,
+    ops::{Index, IndexMut},
+    slice,
+};
+
+/// A key into a [`SlotMap`]: a slot index plus the generation of the slot when the key
+/// was handed out.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Key {
+    index: u32,
+    generation: u32,
+}
+
+/// A [`Slab`](super::Slab) whose keys can't be confused with keys for values that were
+/// removed.
+///
+/// A slab reuses the index of a removed value, so an old key held somewhere quietly starts
+/// referring to whatever was inserted next. A slot map also reuses slots, but every slot
+/// counts how often it was emptied, and a key carries that generation along with the
+/// index. Looking up a key whose value was removed therefore finds a newer generation in
+/// the slot and returns `None`, even after the slot was reused. That makes keys safe to
+/// keep around as long-lived handles, such as cursors into a list or the positions of
+/// entries in a heap.
+///
+/// ```
+/// use Cell::collections::SlotMap;
+///
+/// let mut players = SlotMap::new();
+/// let alice = players.insert("alice");
+/// players.remove(alice);
+/// let bob = players.insert("bob");
+/// assert_eq!(players.get(alice), None);
+/// assert_eq!(players[bob], "bob");
+/// ```
+///
+/// A slot is retired once its generation counter would overflow, after four billion
+/// removals, so a key can never match a later value by wrapping around.
+#[derive(Clone)]
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    len: usize,
+    // The first slot on the free list, or `slots.len()` if there is none.
+    next_free: usize,
+}
+
+impl Key {
+    fn new(index: usize, generation: u32) -> Self {
+        Key {
+            index: index as u32,
+            generation,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    generation: u32,
+    entry: Entry<T>,
+}
+
+#[derive(Clone)]
+enum Entry<T> {
+    Occupied(T),
+    // Holds the next slot on the free list, like `next_free`.
+    Vacant(usize),
+}
+
+impl<T> Slot<T> {
+    fn get(&self, key: Key) -> Option<&T> {
+        match &self.entry {
+            Entry::Occupied(value) if self.generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        match &mut self.entry {
+            Entry::Occupied(value) if self.generation == key.generation => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<T> SlotMap<T> {
+    /// Creates an empty slot map without allocating.
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            len: 0,
+            next_free: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            len: 0,
+            next_free: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Stores `value` and returns its key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map would need more than `u32::MAX` slots.
+    pub fn insert(&mut self, value: T) -> Key {
+        self.insert_with_key(|_| value)
+    }
+
+    /// Like `insert`, but lets the value know its own key.
+    pub fn insert_with_key(&mut self, f: impl FnOnce(Key) -> T) -> Key {
+        let index = self.next_free;
+        let key = Key {
+            index: u32::try_from(index).expect("too many slots in SlotMap"),
+            generation: self.slots.get(index).map_or(0, |slot| slot.generation),
+        };
+        let value = f(key);
+        match self.slots.get_mut(index) {
+            Some(slot) => match mem::replace(&mut slot.entry, Entry::Occupied(value)) {
+                Entry::Vacant(next) => self.next_free = next,
+                Entry::Occupied(_) => unreachable!("free list points at an occupied slot"),
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Entry::Occupied(value),
+                });
+                self.next_free = self.slots.len();
+            }
+        }
+        self.len += 1;
+        key
+    }
+
+    pub fn get(&self, key: Key) -> Option<&T> {
+        self.slots.get(key.index as usize)?.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        self.slots.get_mut(key.index as usize)?.get_mut(key)
+    }
+
+    /// Returns `true` if `key` still refers to a value.
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes and returns the value under `key`. Returns `None` if the key's value was
+    /// already removed, even if its slot holds a newer value now.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let index = key.index as usize;
+        self.slots.get(index)?.get(key)?;
+        let slot = &mut self.slots[index];
+        let Entry::Occupied(value) = mem::replace(&mut slot.entry, Entry::Vacant(self.next_free))
+        else {
+            unreachable!()
+        };
+        // Once out of generations, the slot stays vacant and off the free list for good.
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.next_free = index;
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Keeps only the values for which `f` returns `true`. Their keys stay valid.
+    pub fn retain(&mut self, mut f: impl FnMut(Key, &mut T) -> bool) {
+        for index in 0..self.slots.len() {
+            let slot = &mut self.slots[index];
+            let key = Key::new(index, slot.generation);
+            if let Entry::Occupied(value) = &mut slot.entry {
+                if !f(key, value) {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Removes every value. Keys to them become invalid, and stay invalid when their slots
+    /// are reused.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Iterates over the keys and values, in slot order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            slots: self.slots.iter().enumerate(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            slots: self.slots.iter_mut().enumerate(),
+            remaining: self.len,
+        }
+    }
+
+    /// Iterates over the keys, in slot order.
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Iterates over the values, in slot order.
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<Key> for SlotMap<T> {
+    type Output = T;
+
+    /// Panics if `key` no longer refers to a value.
+    fn index(&self, key: Key) -> &T {
+        self.get(key).expect("invalid SlotMap key")
+    }
+}
+
+impl<T> IndexMut<Key> for SlotMap<T> {
+    fn index_mut(&mut self, key: Key) -> &mut T {
+        self.get_mut(key).expect("invalid SlotMap key")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SlotMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the keys and values of a [`SlotMap`].
+pub struct Iter<'a, T> {
+    slots: Enumerate<slice::Iter<'a, Slot<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Key, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.slots.find_map(|(index, slot)| match &slot.entry {
+            Entry::Occupied(value) => Some((Key::new(index, slot.generation), value)),
+            Entry::Vacant(_) => None,
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// Mutable iterator over the keys and values of a [`SlotMap`].
+pub struct IterMut<'a, T> {
+    slots: Enumerate<slice::IterMut<'a, Slot<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Key, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.slots.find_map(|(index, slot)| {
+            let key = Key::new(index, slot.generation);
+            match &mut slot.entry {
+                Entry::Occupied(value) => Some((key, value)),
+                Entry::Vacant(_) => None,
+            }
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+impl<T> FusedIterator for IterMut<'_, T> {}
+
+/// Owning iterator over the keys and values of a [`SlotMap`].
+pub struct IntoIter<T> {
+    slots: Enumerate<vec::IntoIter<Slot<T>>>,
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (Key, T);
+
+    fn next(&mut self) -> Option<(Key, T)> {
+        let item = self.slots.find_map(|(index, slot)| match slot.entry {
+            Entry::Occupied(value) => Some((Key::new(index, slot.generation), value)),
+            Entry::Vacant(_) => None,
+        })?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for SlotMap<T> {
+    type Item = (Key, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            slots: self.slots.into_iter().enumerate(),
+            remaining: self.len,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SlotMap<T> {
+    type Item = (Key, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut SlotMap<T> {
+    type Item = (Key, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, vec::Vec};
+
+    #[test]
+    fn test_stale_keys_are_detected() {
+        let mut map = SlotMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+        assert_eq!(map.remove(a), Some("a"));
+        let c = map.insert("c");
+        // `c` reuses the slot of `a` with a newer generation.
+        assert_eq!(c.index, a.index);
+        assert_ne!(c, a);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map.remove(a), None);
+        assert!(!map.contains_key(a));
+        assert_eq!(map[c], "c");
+        assert_eq!(map[b], "b");
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_with_key() {
+        let mut map = SlotMap::new();
+        let first = map.insert_with_key(|key| (key, 1));
+        map.remove(first);
+        let second = map.insert_with_key(|key| (key, 2));
+        assert_eq!(map[second], (second, 2));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_retain_and_iteration() {
+        let mut map = SlotMap::with_capacity(6);
+        let keys: Vec<Key> = (0..6).map(|i| map.insert(i)).collect();
+        map.retain(|_, value| *value % 2 == 0);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.keys().collect::<Vec<_>>(), [keys[0], keys[2], keys[4]]);
+        for (_, value) in &mut map {
+            *value *= 10;
+        }
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), [0, 20, 40]);
+        assert_eq!(map.iter().len(), 3);
+        let owned: Vec<(Key, i32)> = map.into_iter().collect();
+        assert_eq!(owned, [(keys[0], 0), (keys[2], 20), (keys[4], 40)]);
+    }
+
+    #[test]
+    fn test_clear_invalidates_keys() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1);
+        map.clear();
+        assert!(map.is_empty());
+        let b = map.insert(2);
+        assert_eq!(map.get(a), None);
+        assert_eq!(map[b], 2);
+        assert_eq!(format!("{map:?}"), format!("{{{b:?}: 2}}"));
+    }
+
+    #[test]
+    fn test_exhausted_slot_is_retired() {
+        let mut map = SlotMap::new();
+        let key = map.insert(());
+        map.slots[0].generation = u32::MAX;
+        let key = Key {
+            generation: u32::MAX,
+            ..key
+        };
+        assert_eq!(map.remove(key), Some(()));
+        let next = map.insert(());
+        assert_eq!(next.index, 1);
+        assert_eq!(map.get(key), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid SlotMap key")]
+    fn test_index_with_stale_key_panics() {
+        let mut map = SlotMap::new();
+        let key = map.insert(0);
+        map.remove(key);
+        map.insert(1);
+        let _ = map[key];
+    }
+}
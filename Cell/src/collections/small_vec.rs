@@ -0,0 +1,588 @@
+//! A vector that keeps its first few elements inline.
+
+use alloc::vec::{self, Vec};
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr, slice,
+};
+
+use crate::maybeuninit::MaybeUninit;
+
+/// A vector that stores up to `N` elements inline and moves them to the heap once it
+/// grows past that.
+///
+/// Most buffers in a program hold a handful of elements and live for a moment; a
+/// `SmallVec` with a fitting `N` serves them without touching the allocator at all. The
+/// price is a bigger value, since the inline array is always there, and a branch on every
+/// access to check where the elements are. Once spilled to the heap, a `SmallVec` stays
+/// there, like a `Vec` keeps its capacity.
+///
+/// It dereferences to a slice, so everything slices can do works on it too.
+pub struct SmallVec<T, const N: usize> {
+    storage: Storage<T, N>,
+}
+
+enum Storage<T, const N: usize> {
+    // The first `len` elements of `buf` are initialized.
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        len: usize,
+    },
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> Storage<T, N> {
+    const fn empty() -> Self {
+        Storage::Inline {
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    /// Creates an empty vector without allocating.
+    pub const fn new() -> Self {
+        Self {
+            storage: Storage::empty(),
+        }
+    }
+
+    /// Creates an empty vector with room for `capacity` elements, which is on the heap only
+    /// if that's more than `N`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut vec = Self::new();
+        vec.reserve(capacity);
+        vec
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { len, .. } => *len,
+            Storage::Heap(vec) => vec.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { .. } => N,
+            Storage::Heap(vec) => vec.capacity(),
+        }
+    }
+
+    /// Whether the elements have moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.storage, Storage::Heap(_))
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match &self.storage {
+            // SAFETY: the first `len` elements are initialized.
+            Storage::Inline { buf, len } => unsafe {
+                slice::from_raw_parts(buf.as_ptr().cast(), *len)
+            },
+            Storage::Heap(vec) => vec,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match &mut self.storage {
+            // SAFETY: as in `as_slice`.
+            Storage::Inline { buf, len } => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), *len)
+            },
+            Storage::Heap(vec) => vec,
+        }
+    }
+
+    /// Makes room for at least `additional` more elements, moving to the heap if they don't
+    /// fit inline.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .expect("SmallVec capacity overflow");
+        match &mut self.storage {
+            Storage::Inline { .. } if needed <= N => {}
+            // Twice the inline size at least, so that pushing one by one past `N` doesn't
+            // reallocate right away.
+            Storage::Inline { .. } => self.spill(needed.max(N * 2)),
+            Storage::Heap(vec) => vec.reserve(additional),
+        }
+    }
+
+    // Moves the inline elements into a `Vec` with the given capacity.
+    fn spill(&mut self, capacity: usize) {
+        if let Storage::Inline { buf, len } = &mut self.storage {
+            let mut vec = Vec::with_capacity(capacity);
+            // SAFETY: the first `len` elements are initialized, and the `Vec` has room for
+            // them. Setting `len` to zero afterwards hands them over to the `Vec`.
+            unsafe {
+                ptr::copy_nonoverlapping(buf.as_ptr().cast(), vec.as_mut_ptr(), *len);
+                vec.set_len(*len);
+            }
+            *len = 0;
+            self.storage = Storage::Heap(vec);
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        match &mut self.storage {
+            Storage::Inline { buf, len } if *len < N => {
+                buf[*len].write(value);
+                *len += 1;
+            }
+            Storage::Inline { .. } => {
+                self.reserve(1);
+                self.push(value);
+            }
+            Storage::Heap(vec) => vec.push(value),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.storage {
+            Storage::Inline { len: 0, .. } => None,
+            Storage::Inline { buf, len } => {
+                *len -= 1;
+                // SAFETY: the element was initialized, and is now outside the live prefix.
+                Some(unsafe { buf[*len].assume_init_read() })
+            }
+            Storage::Heap(vec) => vec.pop(),
+        }
+    }
+
+    /// Inserts `value` at `index`, shifting everything after it to the right.
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(
+            index <= len,
+            "insertion index {index} out of bounds (len {len})"
+        );
+        self.reserve(1);
+        match &mut self.storage {
+            Storage::Inline { buf, len } => {
+                // SAFETY: there is room for one more, and `index <= len`.
+                unsafe {
+                    let at = buf.as_mut_ptr().add(index);
+                    ptr::copy(at, at.add(1), *len - index);
+                    at.write(MaybeUninit::new(value));
+                }
+                *len += 1;
+            }
+            Storage::Heap(vec) => vec.insert(index, value),
+        }
+    }
+
+    /// Removes the element at `index`, shifting everything after it to the left.
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(
+            index < len,
+            "removal index {index} out of bounds (len {len})"
+        );
+        match &mut self.storage {
+            Storage::Inline { buf, len } => {
+                *len -= 1;
+                // SAFETY: `index` was in bounds; the elements after it close the gap.
+                unsafe {
+                    let at = buf.as_mut_ptr().add(index);
+                    let value = at.read().assume_init();
+                    ptr::copy(at.add(1), at, *len - index);
+                    value
+                }
+            }
+            Storage::Heap(vec) => vec.remove(index),
+        }
+    }
+
+    /// Removes the element at `index` and puts the last element in its place.
+    ///
+    /// Panics if `index >= len`.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        let last = self.len().wrapping_sub(1);
+        self.as_mut_slice().swap(index, last);
+        self.pop().unwrap()
+    }
+
+    /// Drops the elements from `len` on.
+    pub fn truncate(&mut self, new_len: usize) {
+        match &mut self.storage {
+            Storage::Inline { buf, len } => {
+                if new_len >= *len {
+                    return;
+                }
+                let tail = *len - new_len;
+                // Shorten first, so a panicking `drop` leaks the rest instead of dropping
+                // something twice.
+                *len = new_len;
+                // SAFETY: the tail was initialized and is now outside the live prefix.
+                unsafe {
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                        buf.as_mut_ptr().add(new_len).cast::<T>(),
+                        tail,
+                    ));
+                }
+            }
+            Storage::Heap(vec) => vec.truncate(new_len),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keeps only the elements for which `f` returns `true`, in their order.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let mut kept = 0;
+        let slice = self.as_mut_slice();
+        for index in 0..slice.len() {
+            if f(&slice[index]) {
+                slice.swap(kept, index);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.extend(other.iter().cloned());
+    }
+
+    /// Converts into a `Vec`, which allocates unless the elements are on the heap already.
+    pub fn into_vec(mut self) -> Vec<T> {
+        let len = self.len();
+        self.spill(len);
+        match mem::replace(&mut self.storage, Storage::empty()) {
+            Storage::Heap(vec) => vec,
+            Storage::Inline { .. } => unreachable!("spilled above"),
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for SmallVec<T, N> {
+    fn drop(&mut self) {
+        if let Storage::Inline { .. } = self.storage {
+            self.clear();
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize> AsRef<[T]> for SmallVec<T, N> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, const N: usize> AsMut<[T]> for SmallVec<T, N> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for SmallVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut vec = Self::with_capacity(self.len());
+        vec.extend_from_slice(self);
+        vec
+    }
+}
+
+impl<T, const N: usize> Extend<T> for SmallVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SmallVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T, const N: usize> From<Vec<T>> for SmallVec<T, N> {
+    /// Moves the elements inline if they fit, otherwise keeps the `Vec`'s allocation.
+    fn from(vec: Vec<T>) -> Self {
+        if vec.len() <= N {
+            vec.into_iter().collect()
+        } else {
+            Self {
+                storage: Storage::Heap(vec),
+            }
+        }
+    }
+}
+
+impl<T: Clone, const N: usize> From<&[T]> for SmallVec<T, N> {
+    fn from(slice: &[T]) -> Self {
+        let mut vec = Self::with_capacity(slice.len());
+        vec.extend_from_slice(slice);
+        vec
+    }
+}
+
+impl<T, const N: usize, const M: usize> From<[T; M]> for SmallVec<T, N> {
+    fn from(array: [T; M]) -> Self {
+        array.into_iter().collect()
+    }
+}
+
+impl<T: PartialEq, const N: usize, const M: usize> PartialEq<SmallVec<T, M>> for SmallVec<T, N> {
+    fn eq(&self, other: &SmallVec<T, M>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for SmallVec<T, N> {}
+
+impl<T: PartialOrd, const N: usize> PartialOrd for SmallVec<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.as_slice().partial_cmp(other.as_slice())
+    }
+}
+
+impl<T: Ord, const N: usize> Ord for SmallVec<T, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl<T: Hash, const N: usize> Hash for SmallVec<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state);
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Owning iterator over the elements of a [`SmallVec`].
+pub struct IntoIter<T, const N: usize> {
+    inner: IntoIterInner<T, N>,
+}
+
+enum IntoIterInner<T, const N: usize> {
+    // The elements in `start..end` have yet to be yielded.
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        start: usize,
+        end: usize,
+    },
+    Heap(vec::IntoIter<T>),
+}
+
+impl<T, const N: usize> IntoIterator for SmallVec<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    fn into_iter(mut self) -> IntoIter<T, N> {
+        // Leave an empty vector behind for `Drop`; the elements belong to the iterator now.
+        let inner = match mem::replace(&mut self.storage, Storage::empty()) {
+            Storage::Inline { buf, len } => IntoIterInner::Inline {
+                buf,
+                start: 0,
+                end: len,
+            },
+            Storage::Heap(vec) => IntoIterInner::Heap(vec.into_iter()),
+        };
+        IntoIter { inner }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut SmallVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, start, end } => {
+                if start == end {
+                    return None;
+                }
+                *start += 1;
+                // SAFETY: the element was live, and is now outside `start..end`.
+                Some(unsafe { buf[*start - 1].assume_init_read() })
+            }
+            IntoIterInner::Heap(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match &self.inner {
+            IntoIterInner::Inline { start, end, .. } => end - start,
+            IntoIterInner::Heap(iter) => iter.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, start, end } => {
+                if start == end {
+                    return None;
+                }
+                *end -= 1;
+                // SAFETY: as in `next`.
+                Some(unsafe { buf[*end].assume_init_read() })
+            }
+            IntoIterInner::Heap(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+impl<T, const N: usize> FusedIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        if let IntoIterInner::Inline { .. } = self.inner {
+            self.for_each(drop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, rc::Rc, string::String};
+
+    #[test]
+    fn test_spills_past_inline_capacity() {
+        let mut vec: SmallVec<u32, 4> = SmallVec::new();
+        for i in 0..4 {
+            vec.push(i);
+        }
+        assert!(!vec.spilled());
+        assert_eq!(vec.capacity(), 4);
+        vec.push(4);
+        assert!(vec.spilled());
+        assert_eq!(vec.capacity(), 8);
+        assert_eq!(*vec, [0, 1, 2, 3, 4]);
+        assert_eq!(vec.pop(), Some(4));
+        assert!(vec.spilled());
+        assert_eq!(vec.into_vec(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_inline_edits() {
+        let mut vec: SmallVec<String, 8> = ["b", "d"].map(String::from).into();
+        vec.insert(0, String::from("a"));
+        vec.insert(2, String::from("c"));
+        vec.insert(4, String::from("e"));
+        assert_eq!(vec.join(""), "abcde");
+        assert_eq!(vec.remove(1), "b");
+        assert_eq!(vec.swap_remove(0), "a");
+        assert_eq!(vec.join(""), "ecd");
+        vec.retain(|s| s != "d");
+        assert_eq!(vec.join(""), "ec");
+        vec.truncate(1);
+        assert_eq!(format!("{vec:?}"), r#"["e"]"#);
+        assert!(!vec.spilled());
+    }
+
+    #[test]
+    fn test_every_element_dropped_once() {
+        let value = Rc::new(());
+        let mut inline: SmallVec<Rc<()>, 4> = (0..3).map(|_| Rc::clone(&value)).collect();
+        let heap: SmallVec<Rc<()>, 2> = (0..5).map(|_| Rc::clone(&value)).collect();
+        assert_eq!(Rc::strong_count(&value), 9);
+        inline.remove(1);
+        drop(inline.pop());
+        assert_eq!(Rc::strong_count(&value), 7);
+
+        // A partly used iterator drops what it hasn't yielded.
+        let mut iter = heap.clone().into_iter();
+        drop(iter.next());
+        drop(iter);
+        let mut iter = inline.clone().into_iter();
+        drop(iter.next_back());
+        drop(iter);
+        assert_eq!(Rc::strong_count(&value), 7);
+
+        drop((inline, heap));
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_conversions_and_iteration() {
+        let from_vec: SmallVec<i32, 4> = std::vec![1, 2, 3].into();
+        assert!(!from_vec.spilled());
+        let big: SmallVec<i32, 2> = std::vec![1, 2, 3].into();
+        assert!(big.spilled());
+        assert_eq!(from_vec, big);
+        assert!(from_vec.iter().rev().copied().eq([3, 2, 1]));
+        assert!(big.into_iter().rev().eq([3, 2, 1]));
+
+        let mut vec: SmallVec<i32, 4> = SmallVec::from(&[5, 1, 4][..]);
+        vec.sort_unstable();
+        for x in &mut vec {
+            *x *= 10;
+        }
+        assert_eq!(vec.as_slice(), [10, 40, 50]);
+        assert!(SmallVec::<i32, 4>::with_capacity(5).spilled());
+    }
+}
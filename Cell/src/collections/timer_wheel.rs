@@ -0,0 +1,445 @@
+//! Timers kept in a hierarchy of rotating wheels, in the style of the Linux kernel and tokio.
+
+use alloc::vec::Vec;
+use core::{fmt, time::Duration};
+
+use super::slot_map::{Key, SlotMap};
+
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+const TOP: usize = LEVELS - 1;
+
+/// A handle for a timer in a [`TimerWheel`], used to cancel it.
+///
+/// Handles are [`SlotMap`] keys, so the handle of a timer that already fired or was
+/// cancelled is simply ignored, even once its slot holds another timer.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(Key);
+
+/// A set of timers, each carrying a value that is handed back once its deadline passes.
+///
+/// Time is counted in ticks of a fixed length chosen in [`TimerWheel::new`], and deadlines
+/// are rounded up to whole ticks, so a timer never fires early but may fire up to a tick
+/// late. The wheel has no clock of its own: it is told the time whenever it is polled, as
+/// the [`Duration`] since some starting point, such as an `Instant` taken when it was
+/// created. Delays given to [`schedule`](TimerWheel::schedule) count from the last poll.
+///
+/// The timers are sorted into six levels of 64 slots. A slot of level 0 covers one tick, a
+/// slot of level 1 covers 64 ticks, a slot of level 2 covers 64 * 64 ticks, and so on. A
+/// timer goes into the lowest level whose slots are coarse enough to tell its deadline
+/// apart from the current time, so near timers sit in fine slots and far ones in coarse
+/// slots. When time reaches a coarse slot, its timers are moved down to finer slots (or
+/// fire, if they are due). Scheduling and cancelling are O(1), and every timer is moved at
+/// most once per level before it fires, so polling is O(1) amortized per timer, where a
+/// binary heap would take O(log n) for every operation.
+///
+/// ```
+/// use core::time::Duration;
+/// use Cell::collections::TimerWheel;
+///
+/// let mut timers = TimerWheel::new(Duration::from_millis(1));
+/// timers.schedule(Duration::from_millis(30), "second");
+/// let cancelled = timers.schedule(Duration::from_millis(10), "never");
+/// timers.schedule(Duration::from_millis(20), "first");
+/// assert_eq!(timers.cancel(cancelled), Some("never"));
+///
+/// assert!(timers.poll_expired(Duration::from_millis(15)).is_empty());
+/// assert_eq!(timers.poll_expired(Duration::from_millis(40)), ["first", "second"]);
+/// ```
+pub struct TimerWheel<T> {
+    timers: SlotMap<Timer<T>>,
+    // The first timer of each slot. The timers of a slot form a doubly linked list.
+    slots: [[Option<Key>; SLOTS]; LEVELS],
+    // Bit `s` of `occupied[l]` is set when `slots[l][s]` has any timers.
+    occupied: [u64; LEVELS],
+    tick: Duration,
+    // The time of the last poll, and the tick it falls in.
+    now: Duration,
+    current: u64,
+}
+
+struct Timer<T> {
+    deadline: u64,
+    value: T,
+    level: usize,
+    slot: usize,
+    prev: Option<Key>,
+    next: Option<Key>,
+}
+
+impl<T> TimerWheel<T> {
+    /// Creates an empty wheel that counts time in ticks of length `tick`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is zero.
+    pub fn new(tick: Duration) -> Self {
+        assert!(!tick.is_zero(), "TimerWheel tick must not be zero");
+        Self {
+            timers: SlotMap::new(),
+            slots: [[None; SLOTS]; LEVELS],
+            occupied: [0; LEVELS],
+            tick,
+            now: Duration::ZERO,
+            current: 0,
+        }
+    }
+
+    /// Returns the number of pending timers.
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Returns the time of the last poll.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Starts a timer that fires `delay` after the last poll, and returns its handle.
+    pub fn schedule(&mut self, delay: Duration, value: T) -> TimerHandle {
+        self.schedule_at(self.now.saturating_add(delay), value)
+    }
+
+    /// Starts a timer that fires at time `deadline`, and returns its handle. A deadline
+    /// that has already passed fires on the next poll.
+    pub fn schedule_at(&mut self, deadline: Duration, value: T) -> TimerHandle {
+        let key = self.timers.insert(Timer {
+            deadline: self.ticks_ceil(deadline),
+            value,
+            level: 0,
+            slot: 0,
+            prev: None,
+            next: None,
+        });
+        self.link(key);
+        TimerHandle(key)
+    }
+
+    /// Stops a timer and returns its value, or `None` if it already fired or was
+    /// cancelled.
+    pub fn cancel(&mut self, handle: TimerHandle) -> Option<T> {
+        if !self.timers.contains_key(handle.0) {
+            return None;
+        }
+        self.unlink(handle.0);
+        self.timers.remove(handle.0).map(|timer| timer.value)
+    }
+
+    /// Returns when the timer will fire, rounded up to a whole tick, or `None` if it
+    /// already fired or was cancelled.
+    pub fn deadline(&self, handle: TimerHandle) -> Option<Duration> {
+        let timer = self.timers.get(handle.0)?;
+        Some(self.duration(timer.deadline))
+    }
+
+    /// Returns the earliest deadline of all pending timers.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        // Every timer in the earliest occupied slot is due before those in any other slot,
+        // so the earliest deadline is in there.
+        let (level, slot, _) = self.next_slot()?;
+        let mut next = self.slots[level][slot];
+        let mut earliest = u64::MAX;
+        while let Some(key) = next {
+            let timer = &self.timers[key];
+            earliest = earliest.min(timer.deadline);
+            next = timer.next;
+        }
+        Some(self.duration(earliest))
+    }
+
+    /// Advances the wheel to time `now` and returns the values of the timers that are due,
+    /// in deadline order. Timers with the same deadline come out in no particular order.
+    ///
+    /// Time never goes backwards: polling with an earlier `now` than the last poll only
+    /// returns timers that are due at the last poll's time.
+    pub fn poll_expired(&mut self, now: Duration) -> Vec<T> {
+        self.now = self.now.max(now);
+        let target = self.ticks_floor(self.now);
+        let mut expired = Vec::new();
+        while let Some((level, slot, start)) = self.next_slot() {
+            if start > target {
+                break;
+            }
+            self.current = start;
+            let mut next = self.slots[level][slot].take();
+            self.occupied[level] &= !(1 << slot);
+            while let Some(key) = next {
+                let timer = &self.timers[key];
+                next = timer.next;
+                if timer.deadline <= target {
+                    let timer = self.timers.remove(key).unwrap();
+                    expired.push((timer.deadline, timer.value));
+                } else {
+                    // Relative to the new current tick, the timer belongs in a finer slot.
+                    self.link(key);
+                }
+            }
+        }
+        self.current = self.current.max(target);
+        expired.sort_by_key(|&(deadline, _)| deadline);
+        expired.into_iter().map(|(_, value)| value).collect()
+    }
+
+    // Finds the occupied slot that comes up first, and the tick it starts at.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        // A timer is only ever in a level above 0 if its deadline lies in a later slot of
+        // that level than the current tick, so every timer in a lower level is due sooner.
+        let level = self.occupied.iter().position(|&bits| bits != 0)?;
+        let shift = level * SLOT_BITS;
+        let current_slot = (self.current >> shift) as usize % SLOTS;
+        let distance = self.occupied[level]
+            .rotate_right(current_slot as u32)
+            .trailing_zeros() as u64;
+        let slot = (current_slot + distance as usize) % SLOTS;
+        let start = (self.current >> shift << shift) + (distance << shift);
+        Some((level, slot, start))
+    }
+
+    // Puts timer `key` into the slot for its deadline, as seen from the current tick.
+    fn link(&mut self, key: Key) {
+        let deadline = self.timers[key].deadline.max(self.current);
+        // The highest group of bits in which the deadline differs from the current tick
+        // picks the level; `SLOTS - 1` puts timers that are due now into level 0.
+        let differs = (deadline ^ self.current) | (SLOTS as u64 - 1);
+        let level = (63 - differs.leading_zeros() as usize) / SLOT_BITS;
+        let (level, slot) = if level < TOP {
+            (level, (deadline >> (level * SLOT_BITS)) as usize % SLOTS)
+        } else {
+            // The top level wraps around, so it only tells apart the next 63 of its slots.
+            // Anything further away waits in the last of them and is placed again once
+            // that slot comes up.
+            let shift = TOP * SLOT_BITS;
+            let ahead = ((deadline >> shift) - (self.current >> shift)).min(SLOTS as u64 - 1);
+            (TOP, ((self.current >> shift) + ahead) as usize % SLOTS)
+        };
+        let head = self.slots[level][slot].replace(key);
+        if let Some(head) = head {
+            self.timers[head].prev = Some(key);
+        }
+        let timer = &mut self.timers[key];
+        timer.level = level;
+        timer.slot = slot;
+        timer.prev = None;
+        timer.next = head;
+        self.occupied[level] |= 1 << slot;
+    }
+
+    // Takes timer `key` out of its slot.
+    fn unlink(&mut self, key: Key) {
+        let Timer {
+            level,
+            slot,
+            prev,
+            next,
+            ..
+        } = self.timers[key];
+        match prev {
+            Some(prev) => self.timers[prev].next = next,
+            None => self.slots[level][slot] = next,
+        }
+        if let Some(next) = next {
+            self.timers[next].prev = prev;
+        }
+        if self.slots[level][slot].is_none() {
+            self.occupied[level] &= !(1 << slot);
+        }
+    }
+
+    fn ticks_floor(&self, time: Duration) -> u64 {
+        u64::try_from(time.as_nanos() / self.tick.as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    fn ticks_ceil(&self, time: Duration) -> u64 {
+        u64::try_from(time.as_nanos().div_ceil(self.tick.as_nanos())).unwrap_or(u64::MAX)
+    }
+
+    fn duration(&self, ticks: u64) -> Duration {
+        let nanos = u128::from(ticks) * self.tick.as_nanos();
+        Duration::new(
+            u64::try_from(nanos / 1_000_000_000).unwrap_or(u64::MAX),
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+impl<T> fmt::Debug for TimerWheel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimerWheel")
+            .field("tick", &self.tick)
+            .field("now", &self.now)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_fires_in_deadline_order_and_never_early() {
+        let mut wheel = TimerWheel::new(ms(10));
+        wheel.schedule(ms(25), 'b');
+        wheel.schedule(ms(5), 'a');
+        wheel.schedule(ms(1000), 'd');
+        wheel.schedule(ms(700), 'c');
+        assert_eq!(wheel.next_deadline(), Some(ms(10)));
+        assert_eq!(wheel.poll_expired(ms(9)), []);
+        assert_eq!(wheel.poll_expired(ms(10)), ['a']);
+        // Rounded up to 30ms.
+        assert_eq!(wheel.poll_expired(ms(29)), []);
+        assert_eq!(wheel.poll_expired(ms(30)), ['b']);
+        assert_eq!(wheel.poll_expired(ms(5000)), ['c', 'd']);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_delays_count_from_last_poll() {
+        let mut wheel = TimerWheel::new(ms(1));
+        wheel.poll_expired(ms(100));
+        let handle = wheel.schedule(ms(50), ());
+        assert_eq!(wheel.deadline(handle), Some(ms(150)));
+        assert_eq!(wheel.poll_expired(ms(149)), []);
+        assert_eq!(wheel.poll_expired(ms(150)), [()]);
+        assert_eq!(wheel.deadline(handle), None);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut wheel = TimerWheel::new(ms(1));
+        let handles: Vec<TimerHandle> = (0..10).map(|i| wheel.schedule(ms(i * 100), i)).collect();
+        assert_eq!(wheel.cancel(handles[3]), Some(3));
+        assert_eq!(wheel.cancel(handles[3]), None);
+        assert_eq!(wheel.cancel(handles[9]), Some(9));
+        assert_eq!(wheel.len(), 8);
+        assert_eq!(wheel.poll_expired(ms(1000)), [0, 1, 2, 4, 5, 6, 7, 8]);
+        // The handle of a timer that fired is ignored.
+        assert_eq!(wheel.cancel(handles[0]), None);
+    }
+
+    #[test]
+    fn test_past_and_zero_deadlines_fire_on_next_poll() {
+        let mut wheel = TimerWheel::new(ms(1));
+        wheel.poll_expired(ms(500));
+        wheel.schedule_at(ms(10), "past");
+        wheel.schedule(Duration::ZERO, "now");
+        assert_eq!(wheel.poll_expired(ms(500)).len(), 2);
+    }
+
+    #[test]
+    fn test_cascades_through_levels() {
+        let mut wheel = TimerWheel::new(ms(1));
+        let deadlines = [
+            1,
+            63,
+            64,
+            65,
+            4095,
+            4096,
+            4097,
+            300_000,
+            20_000_000,
+            1 << 30,
+        ];
+        for (i, &deadline) in deadlines.iter().enumerate() {
+            wheel.schedule(ms(deadline), i);
+        }
+        let mut fired = vec![];
+        let mut now = 0;
+        // Poll at uneven steps so timers are moved down while time passes them by.
+        while !wheel.is_empty() {
+            now += 1 + now / 3;
+            for i in wheel.poll_expired(ms(now)) {
+                assert!(deadlines[i] <= now, "fired {} at {now}", deadlines[i]);
+                fired.push(i);
+            }
+        }
+        assert_eq!(fired, (0..deadlines.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_every_tick() {
+        let mut wheel = TimerWheel::new(ms(1));
+        for deadline in (0..5000).rev() {
+            wheel.schedule(ms(deadline), deadline);
+        }
+        for now in 0..5000 {
+            assert_eq!(wheel.poll_expired(ms(now)), [now]);
+        }
+    }
+
+    #[test]
+    fn test_deadlines_beyond_the_wheel() {
+        // The six levels together span 2^36 ticks.
+        let span = 1 << 36;
+        let tick = Duration::from_nanos(1);
+        let mut wheel = TimerWheel::new(tick);
+        let far = Duration::from_nanos(span * 3 + 5);
+        wheel.schedule(far, "far");
+        wheel.schedule(Duration::from_nanos(span / 2), "near");
+        assert_eq!(wheel.poll_expired(far - tick), ["near"]);
+        assert_eq!(wheel.poll_expired(far), ["far"]);
+        // Saturates instead of overflowing.
+        wheel.schedule(Duration::MAX, "never");
+        assert_eq!(wheel.poll_expired(far * 1000), [] as [&str; 0]);
+    }
+
+    #[test]
+    fn test_matches_a_sorted_list() {
+        // xorshift, so the test is repeatable.
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut random = move |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        let mut wheel = TimerWheel::new(Duration::from_nanos(1));
+        let mut pending: Vec<(u64, u32, TimerHandle)> = Vec::new();
+        let mut now = 0;
+        for id in 0..3000 {
+            // Mix near deadlines with ones many levels (and top-level windows) away.
+            let bits = 4 * random(11);
+            let delay = random(1 << bits);
+            let handle = wheel.schedule(Duration::from_nanos(delay), id);
+            pending.push((now + delay, id, handle));
+            if random(4) == 0 {
+                let index = random(pending.len() as u64) as usize;
+                let (_, id, handle) = pending.swap_remove(index);
+                assert_eq!(wheel.cancel(handle), Some(id));
+            }
+            if random(3) == 0 {
+                let bits = 4 * random(10);
+                now += random(1 << bits);
+                let mut due: Vec<(u64, u32)> = pending
+                    .iter()
+                    .filter(|&&(deadline, ..)| deadline <= now)
+                    .map(|&(deadline, id, _)| (deadline, id))
+                    .collect();
+                due.sort_unstable();
+                pending.retain(|&(deadline, ..)| deadline > now);
+                let mut fired = wheel.poll_expired(Duration::from_nanos(now));
+                fired.sort_unstable_by_key(|id| due.iter().position(|&(_, due)| due == *id));
+                assert_eq!(fired, due.iter().map(|&(_, id)| id).collect::<Vec<_>>());
+                assert_eq!(wheel.len(), pending.len());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "TimerWheel tick must not be zero")]
+    fn test_zero_tick_panics() {
+        TimerWheel::<()>::new(Duration::ZERO);
+    }
+}
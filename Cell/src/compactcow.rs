@@ -0,0 +1,353 @@
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{borrow::Borrow, fmt, marker::PhantomData, mem, ops::Deref, ptr, ptr::NonNull, slice};
+
+/// A clone-on-write string or slice packed into a pointer and two `u32`s: two words on
+/// 64-bit targets, where an enum of `&str` and `String` needs three.
+///
+/// There is no discriminant. A capacity of 0 means the data is borrowed, anything else
+/// means `self` owns an allocation of that capacity. An owned value with nothing allocated
+/// (an empty `String`, say) has nothing to free and is simply stored as borrowed. The
+/// price is that lengths and capacities are limited to `u32::MAX`.
+///
+/// The smaller size pays off when many values are stored and walked over, since more of them
+/// fit in a cache line. Cloning is not cheaper than for the enum: a borrowed clone copies
+/// two words where the enum copies three, which is in the noise, and an owned one still
+/// allocates. There is no `to_mut`,
+/// because the owned value only exists in pieces; mutate through `into_owned` instead.
+pub struct CompactCow<'a, B: ?Sized + Beef> {
+    ptr: NonNull<B::Elem>,
+    len: u32,
+    cap: u32,
+    marker: PhantomData<(&'a B, B::Owned)>,
+}
+
+/// The types a [`CompactCow`] can point to: `str` and slices of `Clone` elements.
+///
+/// # Safety
+///
+/// `as_parts`, `from_parts`, `owned_into_parts` and `owned_from_parts` must round-trip: the
+/// parts returned for a value have to rebuild exactly that value.
+pub unsafe trait Beef: ToOwned {
+    type Elem;
+
+    fn as_parts(&self) -> (NonNull<Self::Elem>, usize);
+
+    /// # Safety
+    ///
+    /// `ptr` and `len` must come from `as_parts` or `owned_into_parts` of a value that
+    /// lives at least as long as `'a`.
+    unsafe fn from_parts<'a>(ptr: NonNull<Self::Elem>, len: usize) -> &'a Self;
+
+    fn owned_into_parts(owned: Self::Owned) -> (NonNull<Self::Elem>, usize, usize);
+
+    /// # Safety
+    ///
+    /// The parts must come from `owned_into_parts`, and be used only once.
+    unsafe fn owned_from_parts(ptr: NonNull<Self::Elem>, len: usize, cap: usize) -> Self::Owned;
+}
+
+unsafe impl Beef for str {
+    type Elem = u8;
+
+    fn as_parts(&self) -> (NonNull<u8>, usize) {
+        (NonNull::from(self.as_bytes()).cast(), self.len())
+    }
+
+    unsafe fn from_parts<'a>(ptr: NonNull<u8>, len: usize) -> &'a str {
+        unsafe { core::str::from_utf8_unchecked(slice::from_raw_parts(ptr.as_ptr(), len)) }
+    }
+
+    fn owned_into_parts(owned: String) -> (NonNull<u8>, usize, usize) {
+        <[u8]>::owned_into_parts(owned.into_bytes())
+    }
+
+    unsafe fn owned_from_parts(ptr: NonNull<u8>, len: usize, cap: usize) -> String {
+        unsafe { String::from_utf8_unchecked(<[u8]>::owned_from_parts(ptr, len, cap)) }
+    }
+}
+
+unsafe impl<T: Clone> Beef for [T] {
+    type Elem = T;
+
+    fn as_parts(&self) -> (NonNull<T>, usize) {
+        (NonNull::from(self).cast(), self.len())
+    }
+
+    unsafe fn from_parts<'a>(ptr: NonNull<T>, len: usize) -> &'a [T] {
+        unsafe { slice::from_raw_parts(ptr.as_ptr(), len) }
+    }
+
+    fn owned_into_parts(owned: Vec<T>) -> (NonNull<T>, usize, usize) {
+        let mut owned = mem::ManuallyDrop::new(owned);
+        let (ptr, len, cap) = (owned.as_mut_ptr(), owned.len(), owned.capacity());
+        // SAFETY: a vector's pointer is never null, even without an allocation.
+        (unsafe { NonNull::new_unchecked(ptr) }, len, cap)
+    }
+
+    unsafe fn owned_from_parts(ptr: NonNull<T>, len: usize, cap: usize) -> Vec<T> {
+        unsafe { Vec::from_raw_parts(ptr.as_ptr(), len, cap) }
+    }
+}
+
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).expect("CompactCow holds at most u32::MAX elements")
+}
+
+impl<'a, B: ?Sized + Beef> CompactCow<'a, B> {
+    pub fn borrowed(borrowed: &'a B) -> Self {
+        let (ptr, len) = borrowed.as_parts();
+        Self {
+            ptr,
+            len: to_u32(len),
+            cap: 0,
+            marker: PhantomData,
+        }
+    }
+
+    // Panics if the length is above `u32::MAX`. Spare capacity above that is given back.
+    pub fn owned(owned: B::Owned) -> Self {
+        // Check the length while `owned` is still whole, so a panic drops it instead of
+        // leaking the parts.
+        let borrowed: &B = owned.borrow();
+        let len = to_u32(borrowed.as_parts().1);
+        let (ptr, _, cap) = B::owned_into_parts(owned);
+        let cap = if mem::size_of::<B::Elem>() == 0 {
+            // Nothing is allocated and a vector reports `usize::MAX`; any nonzero value
+            // marks the value as owned so the elements get dropped.
+            u32::MAX
+        } else if cap > u32::MAX as usize {
+            // SAFETY: the parts were just taken apart and aren't used again.
+            let owned = unsafe { B::owned_from_parts(ptr, len as usize, cap) };
+            // A fresh copy has no spare capacity.
+            let borrowed: &B = owned.borrow();
+            return Self::owned(borrowed.to_owned());
+        } else {
+            cap as u32
+        };
+        Self {
+            ptr,
+            len,
+            cap,
+            marker: PhantomData,
+        }
+    }
+
+    // Kept out of line, so the borrowed case of `clone` inlines into callers such as
+    // `Vec::clone` without dragging the allocation along.
+    #[cold]
+    fn clone_owned(&self) -> Self {
+        Self::owned((**self).to_owned())
+    }
+
+    #[inline]
+    pub fn is_borrowed(&self) -> bool {
+        self.cap == 0
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    pub fn into_owned(self) -> B::Owned {
+        let this = mem::ManuallyDrop::new(self);
+        if this.is_borrowed() {
+            (**this).to_owned()
+        } else {
+            // SAFETY: we own the parts and `this` won't drop them again.
+            unsafe { B::owned_from_parts(this.ptr, this.len as usize, this.cap as usize) }
+        }
+    }
+}
+
+impl<B: ?Sized + Beef> Deref for CompactCow<'_, B> {
+    type Target = B;
+
+    #[inline]
+    fn deref(&self) -> &B {
+        // SAFETY: the parts come from a borrow that outlives `'a` or from an allocation
+        // that `self` owns.
+        unsafe { B::from_parts(self.ptr, self.len as usize) }
+    }
+}
+
+impl<B: ?Sized + Beef> Drop for CompactCow<'_, B> {
+    fn drop(&mut self) {
+        if self.is_owned() {
+            // SAFETY: we own the parts, and this is the only place they're used.
+            drop(unsafe { B::owned_from_parts(self.ptr, self.len as usize, self.cap as usize) });
+        }
+    }
+}
+
+impl<B: ?Sized + Beef> Clone for CompactCow<'_, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        if self.is_borrowed() {
+            // SAFETY: with `cap == 0` the value owns nothing: `Drop` frees only owned
+            // values, and the fields are a pointer, two integers and a marker, all `Copy`.
+            // A bit copy is therefore the same value as the one built field by field, and
+            // dropping both is fine. It compiles to a single two-word copy.
+            unsafe { ptr::read(self) }
+        } else {
+            self.clone_owned()
+        }
+    }
+}
+
+// Same as for `&'a B` and `B::Owned`, the two things a `CompactCow` can hold.
+unsafe impl<B: ?Sized + Beef + Sync> Send for CompactCow<'_, B> where B::Owned: Send {}
+unsafe impl<B: ?Sized + Beef + Sync> Sync for CompactCow<'_, B> where B::Owned: Sync {}
+
+impl<B: ?Sized + Beef + fmt::Debug> fmt::Debug for CompactCow<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<B: ?Sized + Beef + fmt::Display> fmt::Display for CompactCow<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<B: ?Sized + Beef + PartialEq> PartialEq for CompactCow<'_, B> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<B: ?Sized + Beef + Eq> Eq for CompactCow<'_, B> {}
+
+impl<'a> From<&'a str> for CompactCow<'a, str> {
+    fn from(s: &'a str) -> Self {
+        Self::borrowed(s)
+    }
+}
+
+impl From<String> for CompactCow<'_, str> {
+    fn from(s: String) -> Self {
+        Self::owned(s)
+    }
+}
+
+impl<'a, T: Clone> From<&'a [T]> for CompactCow<'a, [T]> {
+    fn from(s: &'a [T]) -> Self {
+        Self::borrowed(s)
+    }
+}
+
+impl<T: Clone> From<Vec<T>> for CompactCow<'_, [T]> {
+    fn from(v: Vec<T>) -> Self {
+        Self::owned(v)
+    }
+}
+
+impl<'a, B: ?Sized + Beef> From<crate::cow::Cow<'a, B>> for CompactCow<'a, B> {
+    fn from(cow: crate::cow::Cow<'a, B>) -> Self {
+        match cow {
+            crate::cow::Cow::Borrowed(b) => Self::borrowed(b),
+            crate::cow::Cow::Owned(o) => Self::owned(o),
+        }
+    }
+}
+
+impl<'a, B: ?Sized + Beef> From<CompactCow<'a, B>> for crate::cow::Cow<'a, B> {
+    fn from(cow: CompactCow<'a, B>) -> Self {
+        if cow.is_borrowed() {
+            let cow = mem::ManuallyDrop::new(cow);
+            // SAFETY: borrowed parts point to data that lives for `'a`.
+            crate::cow::Cow::Borrowed(unsafe { B::from_parts(cow.ptr, cow.len as usize) })
+        } else {
+            crate::cow::Cow::Owned(cow.into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            mem::size_of::<CompactCow<str>>(),
+            mem::size_of::<usize>() + 8
+        );
+        #[cfg(target_pointer_width = "64")]
+        {
+            assert_eq!(mem::size_of::<CompactCow<str>>(), 16);
+            assert_eq!(mem::size_of::<CompactCow<[u64]>>(), 16);
+        }
+    }
+
+    #[test]
+    fn test_borrowed_and_owned() {
+        let s = "hello".to_string();
+        let borrowed = CompactCow::from(s.as_str());
+        assert!(borrowed.is_borrowed());
+        assert_eq!(&*borrowed, "hello");
+
+        let owned = CompactCow::<str>::from("world".to_string());
+        assert!(owned.is_owned());
+        assert_eq!(&*owned, "world");
+        assert_eq!(std::format!("{borrowed} {owned:?}"), r#"hello "world""#);
+
+        // Nothing allocated, nothing to own.
+        let empty = CompactCow::<str>::from(std::string::String::new());
+        assert!(empty.is_borrowed());
+        assert_eq!(&*empty, "");
+    }
+
+    #[test]
+    fn test_clone_and_into_owned() {
+        let data = vec![1, 2, 3];
+        let borrowed = CompactCow::from(&data[..]);
+        let copy = borrowed.clone();
+        assert!(copy.is_borrowed());
+        assert_eq!(copy, borrowed);
+
+        let owned = CompactCow::from(vec![4, 5]);
+        let copy = owned.clone();
+        assert!(copy.is_owned());
+        assert_ne!(copy.as_ptr(), owned.as_ptr());
+
+        let mut v = owned.into_owned();
+        v.push(6);
+        assert_eq!(v, [4, 5, 6]);
+        assert_eq!(borrowed.into_owned(), data);
+    }
+
+    #[test]
+    fn test_drops_owned_elements() {
+        let counter = Rc::new(());
+        let v: Vec<_> = (0..3).map(|_| Rc::clone(&counter)).collect();
+        let owned = CompactCow::from(v);
+        let copy = owned.clone();
+        assert_eq!(Rc::strong_count(&counter), 7);
+        drop(owned);
+        drop(copy);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let zsts = CompactCow::from(vec![(), (), ()]);
+        assert!(zsts.is_owned());
+        assert_eq!(zsts.len(), 3);
+    }
+
+    #[test]
+    fn test_cow_conversions() {
+        use crate::cow::Cow;
+
+        let s = "abc".to_string();
+        let compact = CompactCow::from(Cow::Borrowed(s.as_str()));
+        assert!(compact.is_borrowed());
+        let cow: Cow<str> = compact.into();
+        assert!(cow.is_borrowed());
+
+        let compact = CompactCow::<str>::from(Cow::<str>::Owned("def".to_string()));
+        assert!(compact.is_owned());
+        let cow: Cow<str> = compact.into();
+        assert!(cow.is_owned());
+        assert_eq!(&*cow, "def");
+    }
+}
@@ -0,0 +1,175 @@
+use crate::binary_heap::BinaryHeap;
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A priority queue that many threads can push into and pop from at the same time.
+///
+/// It is a [`BinaryHeap`] behind one `Mutex`, plus a `Condvar` that consumers wait on while
+/// the heap is empty. Every operation takes the lock, so this suits queues feeding a handful
+/// of workers (a priority channel or a thread pool's job queue) rather than heavily contended
+/// ones, where a sharded or skiplist-based queue would scale better.
+pub struct SyncBinaryHeap<T> {
+    heap: Mutex<BinaryHeap<T>>,
+    available: Condvar,
+}
+
+impl<T: Ord> SyncBinaryHeap<T> {
+    pub fn new() -> Self {
+        Self {
+            heap: Mutex::new(BinaryHeap::new()),
+            available: Condvar::new(),
+        }
+    }
+
+    pub fn push(&self, item: T) {
+        self.heap.lock().unwrap().push(item);
+        // Only one waiting consumer can take the new item.
+        self.available.notify_one();
+    }
+
+    // Returns the greatest element, or `None` right away if the heap is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        self.heap.lock().unwrap().pop()
+    }
+
+    // Blocks until an element is available and returns the greatest one.
+    pub fn pop_wait(&self) -> T {
+        let mut heap = self.heap.lock().unwrap();
+        loop {
+            if let Some(item) = heap.pop() {
+                return item;
+            }
+            heap = self.available.wait(heap).unwrap();
+        }
+    }
+
+    // Like `pop_wait`, but gives up and returns `None` once `timeout` has passed.
+    pub fn pop_wait_timeout(&self, timeout: Duration) -> Option<T> {
+        // A deadline too far in the future to represent never comes.
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            return Some(self.pop_wait());
+        };
+        let mut heap = self.heap.lock().unwrap();
+        loop {
+            if let Some(item) = heap.pop() {
+                return Some(item);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            heap = self.available.wait_timeout(heap, remaining).unwrap().0;
+        }
+    }
+
+    // With concurrent pushes and pops this is only a snapshot.
+    pub fn len(&self) -> usize {
+        self.heap.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn into_inner(self) -> BinaryHeap<T> {
+        self.heap.into_inner().unwrap()
+    }
+}
+
+impl<T: Ord> Default for SyncBinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> From<BinaryHeap<T>> for SyncBinaryHeap<T> {
+    fn from(heap: BinaryHeap<T>) -> Self {
+        Self {
+            heap: Mutex::new(heap),
+            available: Condvar::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Barrier, thread, vec::Vec};
+
+    #[test]
+    fn test_push_pop() {
+        let heap = SyncBinaryHeap::new();
+        assert_eq!(heap.try_pop(), None);
+        heap.push(3);
+        heap.push(7);
+        heap.push(5);
+        assert_eq!(heap.len(), 3);
+        assert_eq!(heap.try_pop(), Some(7));
+        assert_eq!(heap.pop_wait(), 5);
+        assert_eq!(heap.into_inner().into_sorted_vec(), [3]);
+    }
+
+    #[test]
+    fn test_pop_wait_blocks_until_push() {
+        let heap = SyncBinaryHeap::new();
+        thread::scope(|s| {
+            let consumer = s.spawn(|| heap.pop_wait());
+            thread::sleep(Duration::from_millis(20));
+            heap.push(42);
+            assert_eq!(consumer.join().unwrap(), 42);
+        });
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_pop_wait_timeout() {
+        let heap: SyncBinaryHeap<i32> = SyncBinaryHeap::new();
+        assert_eq!(heap.pop_wait_timeout(Duration::from_millis(10)), None);
+        heap.push(1);
+        assert_eq!(heap.pop_wait_timeout(Duration::from_millis(10)), Some(1));
+
+        thread::scope(|s| {
+            let consumer = s.spawn(|| heap.pop_wait_timeout(Duration::MAX));
+            thread::sleep(Duration::from_millis(20));
+            heap.push(2);
+            assert_eq!(consumer.join().unwrap(), Some(2));
+        });
+    }
+
+    #[test]
+    fn test_producers_and_consumers() {
+        let heap = SyncBinaryHeap::from(BinaryHeap::new());
+        let producers = 4;
+        let per_producer = 250;
+        let barrier = Barrier::new(producers + 2);
+        let mut consumed = thread::scope(|s| {
+            for p in 0..producers {
+                let (heap, barrier) = (&heap, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    for i in 0..per_producer {
+                        heap.push(p * per_producer + i);
+                    }
+                });
+            }
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        let mut got = Vec::new();
+                        for _ in 0..producers * per_producer / 2 {
+                            got.push(heap.pop_wait());
+                        }
+                        got
+                    })
+                })
+                .collect();
+            consumers
+                .into_iter()
+                .flat_map(|c| c.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        consumed.sort();
+        assert_eq!(consumed, (0..producers * per_producer).collect::<Vec<_>>());
+        assert!(heap.is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::binary_heap::BinaryHeap;
+use core::cmp::Ordering;
+use std::{
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// A queue whose items only come out once their deadline has passed, earliest deadline
+/// first.
+///
+/// The items sit in a [`BinaryHeap`] ordered by deadline, behind a `Mutex`. A consumer in
+/// [`next`](DelayQueue::next) sleeps on a `Condvar` with a timeout that ends at the earliest
+/// deadline, and an insert that brings the earliest deadline forward wakes it to sleep less.
+/// Items with the same deadline come out in the order they were inserted.
+///
+/// This is the building block for retries and timeouts: put the retry in with a backoff
+/// delay, and a worker looping on `next` runs it when it's due.
+pub struct DelayQueue<T> {
+    state: Mutex<State<T>>,
+    available: Condvar,
+}
+
+struct State<T> {
+    heap: BinaryHeap<Entry<T>>,
+    // Tie-breaker for equal deadlines.
+    next_seq: u64,
+}
+
+struct Entry<T> {
+    // `None` if the delay was too long to represent; the item never comes due.
+    deadline: Option<Instant>,
+    seq: u64,
+    value: T,
+}
+
+impl<T> Entry<T> {
+    // Sorts items that never come due after all the others.
+    fn key(&self) -> (bool, Option<Instant>, u64) {
+        (self.deadline.is_none(), self.deadline, self.seq)
+    }
+}
+
+// Our BinaryHeap is a max-heap, so the order is reversed: the earliest deadline is the
+// greatest entry. The value takes no part, so `T` doesn't have to be `Ord`.
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> State<T> {
+    // Takes the earliest item if its deadline has passed, or tells how long until it does
+    // (`None` if no item ever comes due).
+    fn pop_expired(&mut self, now: Instant) -> Result<T, Option<Duration>> {
+        match self.heap.peek().and_then(|entry| entry.deadline) {
+            Some(deadline) if deadline <= now => Ok(self.heap.pop().unwrap().value),
+            Some(deadline) => Err(Some(deadline - now)),
+            None => Err(None),
+        }
+    }
+}
+
+impl<T> DelayQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                heap: BinaryHeap::new(),
+                next_seq: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    // Makes `value` available once `delay` has passed. A delay too long to represent as an
+    // `Instant` never passes.
+    pub fn insert(&self, value: T, delay: Duration) {
+        self.push(value, Instant::now().checked_add(delay));
+    }
+
+    // Makes `value` available at `deadline`; right away if it has already passed.
+    pub fn insert_at(&self, value: T, deadline: Instant) {
+        self.push(value, Some(deadline));
+    }
+
+    fn push(&self, value: T, deadline: Option<Instant>) {
+        let mut state = self.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.heap.push(Entry {
+            deadline,
+            seq,
+            value,
+        });
+        // A sleeping consumer only needs to hear about it if it is now the earliest item;
+        // otherwise its timeout already ends before this deadline.
+        let earliest = state.heap.peek().is_some_and(|entry| entry.seq == seq);
+        drop(state);
+        if earliest {
+            self.available.notify_one();
+        }
+    }
+
+    // Takes the earliest item if its deadline has passed, without waiting.
+    pub fn pop_expired(&self) -> Option<T> {
+        self.state.lock().unwrap().pop_expired(Instant::now()).ok()
+    }
+
+    // Blocks until the earliest item is due and returns it.
+    pub fn next(&self) -> T {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match state.pop_expired(Instant::now()) {
+                Ok(value) => {
+                    self.pass_on(state);
+                    return value;
+                }
+                Err(Some(wait)) => state = self.available.wait_timeout(state, wait).unwrap().0,
+                Err(None) => state = self.available.wait(state).unwrap(),
+            }
+        }
+    }
+
+    // Like `next`, but gives up and returns `None` once `timeout` has passed.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<T> {
+        // Far enough in the future to never come, if adding overflows.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let wait = match state.pop_expired(now) {
+                Ok(value) => {
+                    self.pass_on(state);
+                    return Some(value);
+                }
+                Err(wait) => wait,
+            };
+            let left = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(now) {
+                    Some(left) if !left.is_zero() => Some(left),
+                    _ => {
+                        // The wakeup for the earliest item may have come to us; don't take
+                        // it with us.
+                        self.pass_on(state);
+                        return None;
+                    }
+                },
+                None => None,
+            };
+            state = match wait.into_iter().chain(left).min() {
+                Some(wait) => self.available.wait_timeout(state, wait).unwrap().0,
+                None => self.available.wait(state).unwrap(),
+            };
+        }
+    }
+
+    // Inserts only wake one consumer, and only for a new earliest item, so others may be
+    // asleep without a timeout while more items are queued. Whoever takes an item, or gives
+    // up waiting, hands the wakeup on, and the next consumer sets its timeout for the new
+    // earliest item.
+    fn pass_on(&self, state: MutexGuard<'_, State<T>>) {
+        let more = !state.heap.is_empty();
+        drop(state);
+        if more {
+            self.available.notify_one();
+        }
+    }
+
+    // When the earliest item is due, if there is one that ever comes due.
+    pub fn peek_deadline(&self) -> Option<Instant> {
+        self.state
+            .lock()
+            .unwrap()
+            .heap
+            .peek()
+            .and_then(|entry| entry.deadline)
+    }
+
+    // Counts the items that aren't due yet too. With concurrent users this is only a
+    // snapshot.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, vec::Vec};
+
+    #[test]
+    fn test_earliest_deadline_first() {
+        let queue = DelayQueue::new();
+        let now = Instant::now();
+        queue.insert_at("c", now - Duration::from_millis(1));
+        queue.insert_at("a", now - Duration::from_millis(3));
+        queue.insert_at("b1", now - Duration::from_millis(2));
+        queue.insert_at("b2", now - Duration::from_millis(2));
+        queue.insert("later", Duration::from_secs(60));
+        assert_eq!(queue.len(), 5);
+        let expired: Vec<_> = std::iter::from_fn(|| queue.pop_expired()).collect();
+        assert_eq!(expired, ["a", "b1", "b2", "c"]);
+        assert_eq!(queue.len(), 1);
+        assert!(queue.peek_deadline().unwrap() > Instant::now());
+    }
+
+    #[test]
+    fn test_next_waits_for_the_deadline() {
+        let queue = DelayQueue::new();
+        let start = Instant::now();
+        queue.insert(2, Duration::from_millis(40));
+        queue.insert(1, Duration::from_millis(20));
+        assert_eq!(queue.pop_expired(), None);
+        assert_eq!(queue.next(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(queue.next(), 2);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_earlier_insert_wakes_the_consumer() {
+        let queue = DelayQueue::new();
+        queue.insert("late", Duration::from_secs(60));
+        thread::scope(|s| {
+            let consumer = s.spawn(|| queue.next());
+            thread::sleep(Duration::from_millis(20));
+            queue.insert("soon", Duration::from_millis(10));
+            assert_eq!(consumer.join().unwrap(), "soon");
+        });
+    }
+
+    #[test]
+    fn test_next_timeout() {
+        let queue = DelayQueue::new();
+        assert_eq!(queue.next_timeout(Duration::from_millis(10)), None);
+        queue.insert(7, Duration::from_secs(60));
+        assert_eq!(queue.next_timeout(Duration::from_millis(10)), None);
+        queue.insert(8, Duration::from_millis(5));
+        assert_eq!(queue.next_timeout(Duration::from_millis(500)), Some(8));
+    }
+
+    #[test]
+    fn test_huge_durations_dont_overflow() {
+        let queue = DelayQueue::new();
+        queue.insert("never", Duration::MAX);
+        assert_eq!(queue.peek_deadline(), None);
+        queue.insert("soon", Duration::from_millis(5));
+        assert!(queue.peek_deadline().is_some());
+        assert_eq!(queue.next_timeout(Duration::MAX), Some("soon"));
+        assert_eq!(queue.next_timeout(Duration::from_millis(10)), None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_timed_out_consumer_passes_the_wakeup_on() {
+        let queue = DelayQueue::new();
+        let start = Instant::now();
+        thread::scope(|s| {
+            // Queued first on the condvar, so it gets the insert's wakeup, then times out
+            // before the item is due.
+            let impatient = s.spawn(|| queue.next_timeout(Duration::from_millis(50)));
+            thread::sleep(Duration::from_millis(10));
+            let patient = s.spawn(|| queue.next_timeout(Duration::from_secs(10)));
+            thread::sleep(Duration::from_millis(10));
+            queue.insert("item", Duration::from_millis(100));
+            assert_eq!(impatient.join().unwrap(), None);
+            assert_eq!(patient.join().unwrap(), Some("item"));
+        });
+        // Without the wakeup the patient consumer only notices at its own timeout.
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_every_consumer_gets_its_item() {
+        let queue = DelayQueue::new();
+        thread::scope(|s| {
+            let consumers: Vec<_> = (0..3).map(|_| s.spawn(|| queue.next())).collect();
+            thread::sleep(Duration::from_millis(20));
+            // Only the first insert is a new earliest item; the others rely on the
+            // consumers passing the wakeup on.
+            for i in 0..3 {
+                queue.insert(i, Duration::from_millis(10 * (i as u64 + 1)));
+            }
+            let mut got: Vec<_> = consumers.into_iter().map(|c| c.join().unwrap()).collect();
+            got.sort();
+            assert_eq!(got, [0, 1, 2]);
+        });
+    }
+}
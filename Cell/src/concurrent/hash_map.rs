@@ -0,0 +1,375 @@
+use crate::{
+    collections::hash_map::{self, RandomState},
+    sync::{
+        MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+use std::{boxed::Box, thread, vec::Vec};
+
+type Shard<K, V, S> = RwLock<hash_map::HashMap<K, V, S>>;
+
+/// A hash map that many threads can read and update at the same time.
+///
+/// The entries are split over a number of shards, each a [`HashMap`](hash_map::HashMap)
+/// behind its own [`RwLock`]. A key's hash picks its shard, so threads working on different
+/// keys mostly take different locks, and readers of the same shard don't block each other.
+///
+/// Since another thread may change an entry at any time, lookups can't hand out plain
+/// references. [`get`](HashMap::get) and [`get_mut`](HashMap::get_mut) return guards that keep
+/// the entry's shard locked while they live; holding one while calling back into the map for
+/// a key in the same shard can deadlock. Whole-map operations like [`len`](HashMap::len)
+/// lock one shard after another, so they only see a snapshot.
+///
+/// ```
+/// use Cell::concurrent::HashMap;
+/// use std::thread;
+///
+/// let words = HashMap::new();
+/// thread::scope(|s| {
+///     for text in ["a b a", "b c"] {
+///         let words = &words;
+///         s.spawn(move || {
+///             for word in text.split(' ') {
+///                 *words.entry(word).or_insert(0) += 1;
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(*words.get("a").unwrap(), 2);
+/// assert_eq!(*words.get("b").unwrap(), 2);
+/// assert_eq!(words.len(), 3);
+/// ```
+pub struct HashMap<K, V, S = RandomState> {
+    shards: Box<[Shard<K, V, S>]>,
+    hash_builder: S,
+}
+
+impl<K, V> HashMap<K, V, RandomState> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+
+    // `shards` is rounded up to a power of two.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K, V, S: Clone> HashMap<K, V, S> {
+    // A few shards per thread keeps two threads from picking the same one most of the time.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+        Self::with_shards_and_hasher(threads * 4, hash_builder)
+    }
+
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        let shards = (0..shards.max(1).next_power_of_two())
+            .map(|_| RwLock::new(hash_map::HashMap::with_hasher(hash_builder.clone())))
+            .collect();
+        Self {
+            shards,
+            hash_builder,
+        }
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    // With concurrent updates this is only a snapshot.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.read().unwrap().is_empty())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap().clear();
+        }
+    }
+
+    // Keeps the entries for which `f` returns true, one shard at a time.
+    pub fn retain(&self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap().retain(&mut f);
+        }
+    }
+
+    // Calls `f` on every entry, holding one shard's read lock at a time.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            for (key, value) in shard.read().unwrap().iter() {
+                f(key, value);
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> HashMap<K, V, S> {
+    fn shard<Q>(&self, key: &Q) -> &Shard<K, V, S>
+    where
+        Q: Hash + ?Sized,
+    {
+        // The shard maps pick buckets with the low bits of the same hash, so use the high
+        // ones here; otherwise every key in a shard would share its low bits too.
+        let hash = self.hash_builder.hash_one(key);
+        let bits = self.shards.len().trailing_zeros();
+        let index = hash.checked_shr(u64::BITS - bits).unwrap_or(0);
+        &self.shards[index as usize]
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).write().unwrap().insert(key, value)
+    }
+
+    // Returns a guard for the value; the key's shard stays read-locked until it is dropped.
+    pub fn get<Q>(&self, key: &Q) -> Option<MappedRwLockReadGuard<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shard(key).read().unwrap();
+        shard
+            .contains_key(key)
+            .then(|| RwLockReadGuard::map(shard, |map| map.get(key).unwrap()))
+    }
+
+    // Returns a guard for the value; the key's shard stays write-locked until it is dropped.
+    pub fn get_mut<Q>(&self, key: &Q) -> Option<MappedRwLockWriteGuard<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shard(key).write().unwrap();
+        shard
+            .contains_key(key)
+            .then(|| RwLockWriteGuard::map(shard, |map| map.get_mut(key).unwrap()))
+    }
+
+    // Returns a clone of the value, without keeping the shard locked.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).read().unwrap().get(key).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).read().unwrap().contains_key(key)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).write().unwrap().remove(key)
+    }
+
+    /// Locks the key's shard for writing, so the entry can be inspected and updated without
+    /// another thread getting in between.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S> {
+        Entry {
+            shard: self.shard(&key).write().unwrap(),
+            key,
+        }
+    }
+}
+
+impl<K, V, S: Clone + Default> Default for HashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Clone + Default> FromIterator<(K, V)> for HashMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = Self::default();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = core::iter::Flatten<std::vec::IntoIter<hash_map::HashMap<K, V, S>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let shards: Vec<_> = self
+            .shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap())
+            .collect();
+        shards.into_iter().flatten()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for shard in self.shards.iter() {
+            map.entries(shard.read().unwrap().iter());
+        }
+        map.finish()
+    }
+}
+
+/// A key together with its write-locked shard, from [`HashMap::entry`].
+pub struct Entry<'a, K, V, S> {
+    shard: RwLockWriteGuard<'a, hash_map::HashMap<K, V, S>>,
+    key: K,
+}
+
+impl<'a, K: Eq + Hash, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.shard.contains_key(&self.key)
+    }
+
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Some(value) = self.shard.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    pub fn or_insert(self, default: V) -> MappedRwLockWriteGuard<'a, V> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> MappedRwLockWriteGuard<'a, V> {
+        let key = self.key;
+        RwLockWriteGuard::map(self.shard, |map| map.entry(key).or_insert_with(default))
+    }
+
+    pub fn or_default(self) -> MappedRwLockWriteGuard<'a, V>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    // Removes the entry, returning its value if there was one.
+    pub fn remove(mut self) -> Option<V> {
+        self.shard.remove(&self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, string::String, sync::Barrier, vec};
+
+    #[test]
+    fn test_insert_get_remove() {
+        let map = HashMap::with_shards(3);
+        assert_eq!(map.shard_count(), 4);
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("a", 2), Some(1));
+        map.insert("b", 3);
+        assert_eq!(*map.get("a").unwrap(), 2);
+        *map.get_mut("b").unwrap() += 10;
+        assert_eq!(map.get_cloned("b"), Some(13));
+        assert!(map.get("c").is_none());
+        assert!(map.contains_key("a"));
+        assert_eq!(map.remove("a"), Some(2));
+        assert_eq!(map.remove("a"), None);
+        assert_eq!(map.len(), 1);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_entry() {
+        let map: HashMap<String, Vec<u32>> = HashMap::new();
+        map.entry("x".into()).or_default().push(1);
+        map.entry("x".into()).or_default().push(2);
+        let entry = map.entry("x".into()).and_modify(|v| v.push(3));
+        assert!(entry.is_occupied());
+        assert_eq!(entry.key(), "x");
+        drop(entry);
+        assert_eq!(*map.get("x").unwrap(), [1, 2, 3]);
+        assert_eq!(map.entry("x".into()).remove(), Some(vec![1, 2, 3]));
+        assert!(!map.entry("x".into()).is_occupied());
+    }
+
+    #[test]
+    fn test_keys_spread_over_shards() {
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let used = map
+            .shards
+            .iter()
+            .filter(|shard| !shard.read().unwrap().is_empty())
+            .count();
+        assert!(used * 2 > map.shard_count());
+        map.retain(|key, value| {
+            *value += 1;
+            key % 2 == 0
+        });
+        assert_eq!(map.len(), 500);
+        let mut sum = 0;
+        map.for_each(|_, value| sum += value);
+        assert_eq!(sum, (0..1000).step_by(2).map(|i| i + 1).sum::<u32>());
+        let mut entries: Vec<_> = map.into_iter().collect();
+        entries.sort();
+        assert_eq!(entries[..2], [(0, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn test_concurrent_counters() {
+        let map = HashMap::new();
+        let threads = 8;
+        let barrier = Barrier::new(threads);
+        thread::scope(|s| {
+            for t in 0..threads {
+                let (map, barrier) = (&map, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    for i in 0..1000 {
+                        *map.entry(i % 100).or_insert(0) += 1;
+                        map.insert(1000 + t * 1000 + i, t);
+                    }
+                });
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(*map.get(&i).unwrap(), threads * 10);
+        }
+        assert_eq!(map.len(), 100 + threads * 1000);
+    }
+
+    #[test]
+    fn test_debug() {
+        let map = HashMap::with_shards(1);
+        map.insert(1, "one");
+        assert_eq!(format!("{map:?}"), "{1: \"one\"}");
+    }
+}
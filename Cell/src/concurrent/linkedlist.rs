@@ -0,0 +1,260 @@
+use std::{
+    boxed::Box,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+/// A sorted, duplicate-free linked list that many threads can `insert` into, `remove` from
+/// and query at the same time.
+///
+/// Every link has its own `Mutex` and operations walk the list with hand-over-hand locking
+/// (lock coupling): the lock on the next link is taken before the lock on the current one
+/// is released. Threads working on different parts of the list therefore don't block each
+/// other, unlike a list wrapped in one big `Mutex`.
+///
+/// A node is only unlinked and freed while both the link pointing at it and its own `next`
+/// link are locked, and a walker always holds one of those two while it looks at the node.
+/// So no thread can be left holding a pointer to a freed node and no deferred reclamation is
+/// needed. A lock-free (Harris-style) variant would instead mark nodes as logically deleted
+/// and free them later through epoch or hazard-pointer reclamation.
+pub struct LinkedList<T> {
+    head: Mutex<Link<T>>,
+    len: AtomicUsize,
+}
+
+// Raw pointers rather than `Box`es: moving a `Box` asserts unique access to the whole node,
+// `next` mutex included, while another walker may still be unlocking that mutex. A node is
+// only turned back into a `Box` once it is unlinked and no walker can reach it.
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Mutex<Link<T>>,
+}
+
+// What `Mutex<Option<Box<Node<T>>>>` links would give: a value is only ever touched under
+// the lock of the link pointing at its node, so it is never shared between threads, only
+// handed from one to the next.
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Send> Sync for LinkedList<T> {}
+
+impl<T: Ord> LinkedList<T> {
+    pub const fn new() -> Self {
+        Self {
+            head: Mutex::new(None),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    // Number of elements. With concurrent writers this is only a snapshot.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Walks the list and returns the locked link whose node is the first one that is not
+    // smaller than `value` (or the locked, empty link at the end of the list).
+    fn locate(&self, value: &T) -> MutexGuard<'_, Link<T>> {
+        let mut guard = self.head.lock().unwrap();
+        loop {
+            let node = match *guard {
+                // SAFETY: the node is live while we hold the link to it, see below.
+                Some(node) if unsafe { &(*node.as_ptr()).value } < value => node,
+                _ => return guard,
+            };
+            guard = unsafe { lock_next(node) };
+        }
+    }
+
+    // Returns `true` if the value was inserted, `false` if it was already present.
+    pub fn insert(&self, value: T) -> bool {
+        let mut link = self.locate(&value);
+        // SAFETY: as in `locate`.
+        if link.is_some_and(|node| unsafe { (*node.as_ptr()).value == value }) {
+            return false;
+        }
+        let node = Box::new(Node {
+            value,
+            next: Mutex::new(*link),
+        });
+        *link = Some(NonNull::from(Box::leak(node)));
+        self.len.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    // Returns `true` if the value was present and has been removed.
+    pub fn remove(&self, value: &T) -> bool {
+        let mut link = self.locate(value);
+        let node = match *link {
+            // SAFETY: as in `locate`.
+            Some(node) if unsafe { (*node.as_ptr()).value == *value } => node,
+            _ => return false,
+        };
+        // Wait for any walker still on the node to move past it, then detach the rest of
+        // the list from it.
+        // SAFETY: we hold the link to the node, so it is live.
+        let rest = unsafe { lock_next(node) }.take();
+        *link = rest;
+        self.len.fetch_sub(1, Ordering::AcqRel);
+        drop(link);
+        // SAFETY: nothing links to the node any more, and no walker is on it: one would
+        // hold either the link we just rewrote or the node's `next`, and we held both.
+        let removed = unsafe { Box::from_raw(node.as_ptr()) };
+        // Drop the value outside of the lock.
+        drop(removed);
+        true
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        // SAFETY: as in `locate`.
+        self.locate(value)
+            .is_some_and(|node| unsafe { (*node.as_ptr()).value == *value })
+    }
+
+    // Calls `f` on every element in ascending order. Writers can keep working on the part
+    // of the list that `f` has already seen or has yet to reach.
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        let mut guard = self.head.lock().unwrap();
+        while let Some(node) = *guard {
+            // SAFETY: as in `locate`.
+            f(unsafe { &(*node.as_ptr()).value });
+            guard = unsafe { lock_next(node) };
+        }
+    }
+}
+
+// Locks the `next` link of `node`.
+//
+// SAFETY: the caller must hold the link pointing at `node`. A node can only be freed by a
+// thread that holds that link as well as the node's `next`, so it stays live while we lock
+// `next`, and for as long as the returned guard is held after the caller lets go of its
+// link. Only a shared reference to the `next` mutex is made, never to the whole node.
+unsafe fn lock_next<'a, T>(node: NonNull<Node<T>>) -> MutexGuard<'a, Link<T>> {
+    let next: &'a Mutex<Link<T>> = unsafe { &(*node.as_ptr()).next };
+    next.lock().unwrap()
+}
+
+impl<T: Ord> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // Free the nodes one at a time; with `&mut self` no other thread can be walking.
+        let mut link = take_link(&mut self.head);
+        while let Some(node) = link {
+            // SAFETY: each node is reachable from exactly one link, which we just took.
+            let mut node = unsafe { Box::from_raw(node.as_ptr()) };
+            link = take_link(&mut node.next);
+        }
+    }
+}
+
+fn take_link<T>(link: &mut Mutex<Link<T>>) -> Link<T> {
+    link.get_mut()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Barrier, thread, vec::Vec};
+
+    fn to_vec<T: Ord + Clone>(list: &LinkedList<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        list.for_each(|x| out.push(x.clone()));
+        out
+    }
+
+    #[test]
+    fn test_insert_remove_contains() {
+        let list = LinkedList::new();
+        assert!(list.insert(3));
+        assert!(list.insert(1));
+        assert!(list.insert(2));
+        assert!(!list.insert(2));
+        assert_eq!(list.len(), 3);
+        assert_eq!(to_vec(&list), [1, 2, 3]);
+
+        assert!(list.contains(&1));
+        assert!(!list.contains(&4));
+        assert!(list.remove(&2));
+        assert!(!list.remove(&2));
+        assert!(list.remove(&1));
+        assert!(list.remove(&3));
+        assert!(list.is_empty());
+        assert_eq!(to_vec(&list), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_long_list_drops_iteratively() {
+        let list = LinkedList::new();
+        for i in (0..100_000).rev() {
+            list.insert(i);
+        }
+        assert_eq!(list.len(), 100_000);
+        drop(list);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let list = LinkedList::new();
+        let threads = 8;
+        let per_thread = 200;
+        thread::scope(|s| {
+            for t in 0..threads {
+                let list = &list;
+                s.spawn(move || {
+                    // Interleave the values so the threads keep meeting in the list.
+                    for i in 0..per_thread {
+                        assert!(list.insert(i * threads + t));
+                    }
+                });
+            }
+        });
+        assert_eq!(list.len(), threads * per_thread);
+        assert_eq!(to_vec(&list), (0..threads * per_thread).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_concurrent_mixed_operations() {
+        let list = LinkedList::new();
+        for i in 0..1000 {
+            list.insert(i);
+        }
+        let barrier = Barrier::new(3);
+        thread::scope(|s| {
+            s.spawn(|| {
+                barrier.wait();
+                for i in (0..1000).step_by(2) {
+                    assert!(list.remove(&i));
+                }
+            });
+            s.spawn(|| {
+                barrier.wait();
+                for i in 1000..1500 {
+                    assert!(list.insert(i));
+                }
+            });
+            s.spawn(|| {
+                barrier.wait();
+                // Odd values are never touched by the writers.
+                for i in (1..1000).step_by(2) {
+                    assert!(list.contains(&i));
+                }
+            });
+        });
+        let expected: Vec<i32> = (1..1000).step_by(2).chain(1000..1500).collect();
+        assert_eq!(list.len(), expected.len());
+        assert_eq!(to_vec(&list), expected);
+    }
+}
@@ -0,0 +1,13 @@
+//! Data structures that can be shared between threads and mutated through `&self`.
+//!
+//! Everything in here needs the `std` feature, since it blocks on `std::sync` locks.
+
+mod binary_heap;
+mod delay_queue;
+mod hash_map;
+mod linkedlist;
+
+pub use binary_heap::SyncBinaryHeap;
+pub use delay_queue::DelayQueue;
+pub use hash_map::{Entry, HashMap};
+pub use linkedlist::LinkedList;
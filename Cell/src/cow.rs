@@ -1,4 +1,11 @@
-use std::{borrow::Borrow, ops::Deref};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, AddAssign, Deref},
+};
 
 /// Cow : Clone on write.
 /// Cow is the enum which either can be Borrowed or Owned.
@@ -9,8 +16,6 @@ use std::{borrow::Borrow, ops::Deref};
 ///
 /// Cow implements Deref which means that you can call non-mutating methods directly on the data it encloses.
 /// If mutation is desired to_mut will obtain a mutable reference to an owned value, cloning if necessary
-///
-
 pub enum Cow<'a, B: ?Sized + 'a>
 where
     B: ToOwned,
@@ -24,11 +29,11 @@ where
     B: ToOwned,
 {
     fn borrow(&self) -> &B {
-        &**self
+        self
     }
 }
 
-impl<'a, B: ?Sized + ToOwned> Deref for Cow<'_, B> {
+impl<B: ?Sized + ToOwned> Deref for Cow<'_, B> {
     type Target = B;
     fn deref(&self) -> &B {
         match *self {
@@ -66,10 +71,7 @@ impl<B: ?Sized + ToOwned> Clone for Cow<'_, B> {
 
 impl<B: ?Sized + ToOwned> Cow<'_, B> {
     pub fn is_borrowed(&self) -> bool {
-        match *self {
-            Cow::Borrowed(_) => true,
-            _ => false,
-        }
+        matches!(*self, Cow::Borrowed(_))
     }
 
     pub fn is_owned(&self) -> bool {
@@ -96,6 +98,290 @@ impl<B: ?Sized + ToOwned> Cow<'_, B> {
         }
     }
 }
+impl<B: ?Sized + ToOwned> AsRef<B> for Cow<'_, B> {
+    fn as_ref(&self) -> &B {
+        self
+    }
+}
+
+impl<B: ?Sized + ToOwned> fmt::Debug for Cow<'_, B>
+where
+    B: fmt::Debug,
+    B::Owned: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Cow::Borrowed(b) => fmt::Debug::fmt(b, f),
+            Cow::Owned(ref o) => fmt::Debug::fmt(o, f),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned> fmt::Display for Cow<'_, B>
+where
+    B: fmt::Display,
+    B::Owned: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Cow::Borrowed(b) => fmt::Display::fmt(b, f),
+            Cow::Owned(ref o) => fmt::Display::fmt(o, f),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned> Default for Cow<'_, B>
+where
+    B::Owned: Default,
+{
+    fn default() -> Self {
+        Cow::Owned(B::Owned::default())
+    }
+}
+
+// Hashes the borrowed form, so a Borrowed and an Owned value that compare equal also hash
+// the same, and a `Cow<str>` key can be looked up with a `&str`.
+impl<B: ?Sized + ToOwned + Hash> Hash for Cow<'_, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&**self, state)
+    }
+}
+
+// Comparisons look through the variant: `Borrowed("a") == Owned("a".to_string())`.
+impl<'a, 'b, B: ?Sized, C: ?Sized> PartialEq<Cow<'b, C>> for Cow<'a, B>
+where
+    B: PartialEq<C> + ToOwned,
+    C: ToOwned,
+{
+    fn eq(&self, other: &Cow<'b, C>) -> bool {
+        PartialEq::eq(&**self, &**other)
+    }
+}
+
+impl<B: ?Sized + ToOwned + Eq> Eq for Cow<'_, B> {}
+
+impl<'a, B: ?Sized + ToOwned + PartialOrd> PartialOrd for Cow<'a, B> {
+    fn partial_cmp(&self, other: &Cow<'a, B>) -> Option<Ordering> {
+        PartialOrd::partial_cmp(&**self, &**other)
+    }
+}
+
+impl<B: ?Sized + ToOwned + Ord> Ord for Cow<'_, B> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Ord::cmp(&**self, &**other)
+    }
+}
+
+// `Cow<str>` against plain strings, in both directions.
+macro_rules! impl_str_eq {
+    ($($lhs:ty, $rhs:ty;)*) => {$(
+        impl<'a, 'b> PartialEq<$rhs> for $lhs {
+            #[inline]
+            fn eq(&self, other: &$rhs) -> bool {
+                PartialEq::eq(&self[..], &other[..])
+            }
+        }
+
+        impl<'a, 'b> PartialEq<$lhs> for $rhs {
+            #[inline]
+            fn eq(&self, other: &$lhs) -> bool {
+                PartialEq::eq(&self[..], &other[..])
+            }
+        }
+    )*};
+}
+
+impl_str_eq! {
+    Cow<'a, str>, str;
+    Cow<'a, str>, &'b str;
+    Cow<'a, str>, String;
+}
+
+// `Cow<[T]>` against plain slices and vectors.
+macro_rules! impl_slice_eq {
+    ($($rhs:ty),*) => {$(
+        impl<'a, 'b, T: Clone + PartialEq<U>, U> PartialEq<$rhs> for Cow<'a, [T]> {
+            #[inline]
+            fn eq(&self, other: &$rhs) -> bool {
+                PartialEq::eq(&self[..], &other[..])
+            }
+        }
+    )*};
+}
+
+impl_slice_eq!([U], &'b [U], Vec<U>);
+
+impl<'a> Add<&'a str> for Cow<'a, str> {
+    type Output = Cow<'a, str>;
+
+    #[inline]
+    fn add(mut self, rhs: &'a str) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+// Only allocates when two non-empty strings really have to be joined. Appending to an
+// empty Cow just borrows the right-hand side.
+impl<'a> AddAssign<&'a str> for Cow<'a, str> {
+    fn add_assign(&mut self, rhs: &'a str) {
+        if self.is_empty() {
+            *self = Cow::Borrowed(rhs);
+        } else if !rhs.is_empty() {
+            if let Cow::Borrowed(lhs) = *self {
+                let mut s = String::with_capacity(lhs.len() + rhs.len());
+                s.push_str(lhs);
+                *self = Cow::Owned(s);
+            }
+            self.to_mut().push_str(rhs);
+        }
+    }
+}
+
+// Collecting always builds an owned value.
+impl<T: Clone> FromIterator<T> for Cow<'_, [T]> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Cow::Owned(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<char> for Cow<'_, str> {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        Cow::Owned(iter.into_iter().collect())
+    }
+}
+
+impl<'b> FromIterator<&'b str> for Cow<'_, str> {
+    fn from_iter<I: IntoIterator<Item = &'b str>>(iter: I) -> Self {
+        Cow::Owned(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<String> for Cow<'_, str> {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        Cow::Owned(iter.into_iter().collect())
+    }
+}
+
+// Writing into a Cow appends to it, so the first write turns a borrowed value into an
+// owned copy and later writes go straight to that copy. `write!` can then build on top of
+// borrowed text without cloning it up front.
+impl fmt::Write for Cow<'_, str> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.to_mut().push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Cow<'_, [u8]> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.to_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// A Cow serializes as the data it points to. Deserializing `Cow<str>` and `Cow<[u8]>`
+// borrows from the input whenever the deserializer can hand out borrowed data (no escapes
+// in a JSON string, for example) and falls back to an owned copy otherwise. This is what a
+// `#[serde(borrow)]` field of these types relies on for zero-copy parsing.
+#[cfg(feature = "serde")]
+impl<B: ?Sized + ToOwned + serde::Serialize> serde::Serialize for Cow<'_, B> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for Cow<'a, str> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for StrVisitor {
+            type Value = Cow<'de, str>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(
+                self,
+                v: &'de str,
+            ) -> Result<Self::Value, E> {
+                Ok(Cow::Borrowed(v))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Cow::Owned(v.to_owned()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(Cow::Owned(v))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for Cow<'a, [u8]> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Cow<'de, [u8]>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte array")
+            }
+
+            fn visit_borrowed_bytes<E: serde::de::Error>(
+                self,
+                v: &'de [u8],
+            ) -> Result<Self::Value, E> {
+                Ok(Cow::Borrowed(v))
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(
+                self,
+                v: &'de str,
+            ) -> Result<Self::Value, E> {
+                Ok(Cow::Borrowed(v.as_bytes()))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(Cow::Owned(v.to_owned()))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Cow::Owned(v.as_bytes().to_owned()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(Cow::Owned(v))
+            }
+
+            fn visit_seq<S: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: S,
+            ) -> Result<Self::Value, S::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Cow::Owned(bytes))
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -138,6 +424,165 @@ mod tests {
         assert_eq!(owned, "hello");
     }
 
+    #[test]
+    fn test_debug_display_default() {
+        let s = "hi".to_string();
+        let borrowed: Cow<str> = Cow::Borrowed(&s);
+        let owned: Cow<str> = Cow::Owned(s.clone());
+        assert_eq!(format!("{borrowed:?} {owned:?}"), r#""hi" "hi""#);
+        assert_eq!(format!("{borrowed} {owned}"), "hi hi");
+
+        let empty: Cow<str> = Cow::default();
+        assert!(empty.is_owned());
+        assert_eq!(&*empty, "");
+    }
+
+    #[test]
+    fn test_eq_and_ord() {
+        let s = "abc".to_string();
+        let borrowed: Cow<str> = Cow::Borrowed(&s);
+        let owned: Cow<str> = Cow::Owned("abd".to_string());
+        assert_eq!(borrowed, Cow::<str>::Owned("abc".to_string()));
+        assert_ne!(borrowed, owned);
+        assert!(borrowed < owned);
+        assert_eq!(borrowed.cmp(&owned), Ordering::Less);
+
+        // Against plain strings, both ways round.
+        assert_eq!(borrowed, "abc");
+        assert_eq!(borrowed, *"abc");
+        assert_eq!(borrowed, s);
+        assert_eq!("abc", borrowed);
+        assert_eq!(s, borrowed);
+
+        let v = vec![1, 2, 3];
+        let slice: Cow<[i32]> = Cow::Borrowed(&v);
+        assert_eq!(slice, v);
+        assert_eq!(slice, &v[..]);
+        assert_eq!(slice, *[1, 2, 3].as_slice());
+    }
+
+    #[test]
+    fn test_hash_map_key() {
+        use std::collections::HashMap;
+
+        let key = "k".to_string();
+        let mut map = HashMap::new();
+        map.insert(Cow::<str>::Borrowed(&key), 1);
+        map.insert(Cow::Owned("other".to_string()), 2);
+        assert_eq!(map.get(&Cow::Owned("k".to_string())), Some(&1));
+        assert_eq!(map.get("other"), Some(&2));
+    }
+
+    #[test]
+    fn test_as_ref() {
+        fn len<S: AsRef<str>>(s: S) -> usize {
+            s.as_ref().len()
+        }
+        let s = "four".to_string();
+        assert_eq!(len(Cow::<str>::Borrowed(&s)), 4);
+
+        let v = [1, 2];
+        let cow: Cow<[i32]> = Cow::Borrowed(&v);
+        let slice: &[i32] = cow.as_ref();
+        assert_eq!(slice, [1, 2]);
+    }
+
+    #[test]
+    fn test_add_str() {
+        // Nothing to join yet: stays borrowed.
+        let cow: Cow<str> = Cow::default() + "hello";
+        assert!(cow.is_borrowed());
+        let cow = cow + "";
+        assert!(cow.is_borrowed());
+
+        let mut cow = cow + " world";
+        assert!(cow.is_owned());
+        assert_eq!(cow, "hello world");
+
+        cow += "!";
+        assert_eq!(cow, "hello world!");
+
+        let mut empty: Cow<str> = Cow::Borrowed("");
+        empty += "x";
+        assert!(empty.is_borrowed());
+        assert_eq!(empty, "x");
+    }
+
+    #[test]
+    fn test_from_iterator() {
+        let squares: Cow<[u32]> = (1..4).map(|x| x * x).collect();
+        assert!(squares.is_owned());
+        assert_eq!(squares, [1, 4, 9].as_slice());
+
+        let upper: Cow<str> = "abc".chars().map(|c| c.to_ascii_uppercase()).collect();
+        assert_eq!(upper, "ABC");
+
+        let joined: Cow<str> = ["a", "b", "c"].into_iter().collect();
+        assert!(joined.is_owned());
+        assert_eq!(joined, "abc");
+
+        let joined: Cow<str> = vec!["x".to_string(), "y".to_string()].into_iter().collect();
+        assert_eq!(joined, "xy");
+    }
+
+    #[test]
+    fn test_fmt_write_promotes() {
+        use std::fmt::Write;
+
+        let mut cow: Cow<str> = Cow::Borrowed("id");
+        write!(cow, "-{}", 42).unwrap();
+        assert!(cow.is_owned());
+        assert_eq!(cow, "id-42");
+        cow.write_char('!').unwrap();
+        assert_eq!(cow, "id-42!");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_write_promotes() {
+        use std::io::Write;
+
+        let header = b"GET ".to_vec();
+        let mut cow: Cow<[u8]> = Cow::Borrowed(&header);
+        cow.write_all(b"/index").unwrap();
+        write!(cow, " HTTP/{}", 1).unwrap();
+        cow.flush().unwrap();
+        assert!(cow.is_owned());
+        assert_eq!(&*cow, b"GET /index HTTP/1");
+        assert_eq!(header, b"GET ");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_borrows_when_possible() {
+        let cow: Cow<str> = Cow::Owned("a\"b".to_string());
+        let json = serde_json::to_string(&cow).unwrap();
+        assert_eq!(json, r#""a\"b""#);
+
+        let input = r#""plain""#.to_string();
+        let plain: Cow<str> = serde_json::from_str(&input).unwrap();
+        assert!(plain.is_borrowed());
+        assert_eq!(plain, "plain");
+
+        // Unescaping needs a new buffer.
+        let escaped: Cow<str> = serde_json::from_str(&json).unwrap();
+        assert!(escaped.is_owned());
+        assert_eq!(escaped, "a\"b");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_bytes() {
+        let bytes: Cow<[u8]> = serde_json::from_str(r#""abc""#).unwrap();
+        assert!(bytes.is_borrowed());
+        assert_eq!(bytes, *b"abc".as_slice());
+
+        let bytes: Cow<[u8]> = serde_json::from_str("[1, 2, 3]").unwrap();
+        assert!(bytes.is_owned());
+        assert_eq!(bytes, [1u8, 2, 3].as_slice());
+        assert_eq!(serde_json::to_string(&bytes).unwrap(), "[1,2,3]");
+    }
+
     #[test]
     fn test_clone() {
         let s = "hello".to_string();
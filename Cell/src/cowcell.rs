@@ -0,0 +1,167 @@
+use alloc::borrow::ToOwned;
+use core::fmt;
+
+use crate::cell::Cell;
+use crate::cow::Cow;
+use crate::refcell::RefCell;
+use crate::reference::{Ref, RefMut};
+
+/// A [`Cow`] behind a [`RefCell`], for data that is shared read-only most of the time and
+/// only occasionally changed through a `&` reference.
+///
+/// Readers see the borrowed data and never pay for a copy. The first `borrow_mut` clones it
+/// into an owned value, and later writers keep changing that copy. This fits copy-on-write
+/// configuration snapshots: hand every component the same `&'a Config` wrapped in a
+/// `CowCell`, and only the ones that override something end up with their own copy.
+///
+/// Borrows are checked at runtime like `RefCell`'s. Whether the copy has been made is kept
+/// in a separate `Cell`, so `is_owned` works even while the value is mutably borrowed.
+pub struct CowCell<'a, B: ?Sized + ToOwned + 'a> {
+    cow: RefCell<Cow<'a, B>>,
+    owned: Cell<bool>,
+}
+
+impl<'a, B: ?Sized + ToOwned> CowCell<'a, B> {
+    pub fn new(borrowed: &'a B) -> Self {
+        Self::from(Cow::Borrowed(borrowed))
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        !self.is_owned()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        self.owned.get()
+    }
+
+    // Shared access to the current data, without cloning anything.
+    pub fn borrow(&self) -> Ref<'_, B> {
+        Ref::map(self.cow.borrow(), |cow| &**cow)
+    }
+
+    // Mutable access to the owned copy, cloning the borrowed data on the first call.
+    // Panics if the cell is already borrowed, like `RefCell::borrow_mut`.
+    pub fn borrow_mut(&self) -> RefMut<'_, B::Owned> {
+        let cow = self.cow.borrow_mut();
+        self.owned.set(true);
+        RefMut::map(cow, Cow::to_mut)
+    }
+
+    // Puts `cow` in the cell and returns what was there, for example to drop local changes
+    // by going back to the shared data.
+    pub fn replace(&self, cow: Cow<'a, B>) -> Cow<'a, B> {
+        let owned = cow.is_owned();
+        let old = self.cow.replace(cow);
+        self.owned.set(owned);
+        old
+    }
+
+    pub fn into_inner(self) -> Cow<'a, B> {
+        self.cow.into_inner()
+    }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<&'a B> for CowCell<'a, B> {
+    fn from(borrowed: &'a B) -> Self {
+        Self::new(borrowed)
+    }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<Cow<'a, B>> for CowCell<'a, B> {
+    fn from(cow: Cow<'a, B>) -> Self {
+        Self {
+            owned: Cell::new(cow.is_owned()),
+            cow: RefCell::new(cow),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned + fmt::Debug> fmt::Debug for CowCell<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cow.try_borrow() {
+            Ok(cow) => fmt::Debug::fmt(&**cow, f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, string::String, vec, vec::Vec};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Config {
+        retries: u32,
+        hosts: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_readers_share_writers_clone() {
+        let base = Config {
+            retries: 3,
+            hosts: vec!["a"],
+        };
+        let (reader, writer) = (CowCell::new(&base), CowCell::from(&base));
+
+        assert_eq!(reader.borrow().retries, 3);
+        assert!(reader.is_borrowed());
+
+        writer.borrow_mut().retries = 5;
+        writer.borrow_mut().hosts.push("b");
+        assert!(writer.is_owned());
+        assert_eq!(writer.borrow().hosts, ["a", "b"]);
+
+        // Neither the shared data nor the other cell saw the change.
+        assert_eq!(base.retries, 3);
+        assert_eq!(reader.borrow().retries, 3);
+        assert!(reader.is_borrowed());
+    }
+
+    #[test]
+    fn test_unsized() {
+        let cell: CowCell<str> = CowCell::new("hello");
+        let first = cell.borrow();
+        let second = cell.borrow();
+        assert_eq!(&*first, &*second);
+        drop((first, second));
+
+        cell.borrow_mut().push_str(" world");
+        assert_eq!(&*cell.borrow(), "hello world");
+        assert_eq!(format!("{cell:?}"), r#""hello world""#);
+        assert_eq!(cell.into_inner().into_owned(), "hello world");
+    }
+
+    #[test]
+    fn test_replace() {
+        let shared = [1, 2, 3];
+        let cell: CowCell<[i32]> = CowCell::new(&shared);
+        cell.borrow_mut()[0] = 10;
+
+        let old = cell.replace(Cow::Borrowed(&shared));
+        assert_eq!(&*old, [10, 2, 3]);
+        assert!(cell.is_borrowed());
+        assert_eq!(&*cell.borrow(), [1, 2, 3]);
+
+        cell.replace(Cow::Owned(vec![7]));
+        assert!(cell.is_owned());
+    }
+
+    #[test]
+    fn test_is_owned_while_borrowed() {
+        let cell: CowCell<str> = CowCell::from(Cow::Owned(String::from("x")));
+        assert!(cell.is_owned());
+        let guard = cell.borrow_mut();
+        assert!(cell.is_owned());
+        assert_eq!(format!("{cell:?}"), "<borrowed>");
+        drop(guard);
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_borrow_mut_while_reading_panics() {
+        let cell: CowCell<str> = CowCell::new("x");
+        let _reader = cell.borrow();
+        let _writer = cell.borrow_mut();
+    }
+}
@@ -0,0 +1,149 @@
+use core::{fmt, ops::Deref};
+
+/// Clone on write for types whose borrowed and owned forms are the same type.
+///
+/// `Cow<'a, B>` is written in terms of `B: ToOwned` and names the owned type as
+/// `<B as ToOwned>::Owned`, which is what makes `Cow<str>` own a `String`. For a plain
+/// `T: Clone` that indirection buys nothing and gets in the way of inference and trait
+/// bounds, so `CowLike` holds either a `&'a T` or a `T` directly.
+///
+/// It converts to and from `Cow<'a, T>` without cloning.
+pub enum CowLike<'a, T: Clone> {
+    Borrowed(&'a T),
+    Owned(T),
+}
+
+impl<T: Clone> Deref for CowLike<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        match *self {
+            CowLike::Borrowed(b) => b,
+            CowLike::Owned(ref o) => o,
+        }
+    }
+}
+
+impl<T: Clone> Clone for CowLike<'_, T> {
+    fn clone(&self) -> Self {
+        match *self {
+            CowLike::Borrowed(b) => CowLike::Borrowed(b),
+            CowLike::Owned(ref o) => CowLike::Owned(o.clone()),
+        }
+    }
+}
+
+impl<T: Clone> CowLike<'_, T> {
+    pub fn is_borrowed(&self) -> bool {
+        matches!(*self, CowLike::Borrowed(_))
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    pub fn to_mut(&mut self) -> &mut T {
+        if let CowLike::Borrowed(borrowed) = *self {
+            *self = CowLike::Owned(borrowed.clone());
+        }
+        match *self {
+            CowLike::Borrowed(_) => unreachable!(),
+            CowLike::Owned(ref mut owned) => owned,
+        }
+    }
+
+    pub fn into_owned(self) -> T {
+        match self {
+            CowLike::Borrowed(borrowed) => borrowed.clone(),
+            CowLike::Owned(owned) => owned,
+        }
+    }
+}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for CowLike<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for CowLike<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, T: Clone> From<&'a T> for CowLike<'a, T> {
+    fn from(borrowed: &'a T) -> Self {
+        CowLike::Borrowed(borrowed)
+    }
+}
+
+impl<'a, T: Clone> From<CowLike<'a, T>> for crate::cow::Cow<'a, T> {
+    fn from(cow: CowLike<'a, T>) -> Self {
+        match cow {
+            CowLike::Borrowed(b) => crate::cow::Cow::Borrowed(b),
+            CowLike::Owned(o) => crate::cow::Cow::Owned(o),
+        }
+    }
+}
+
+impl<'a, T: Clone> From<crate::cow::Cow<'a, T>> for CowLike<'a, T> {
+    fn from(cow: crate::cow::Cow<'a, T>) -> Self {
+        match cow {
+            crate::cow::Cow::Borrowed(b) => CowLike::Borrowed(b),
+            crate::cow::Cow::Owned(o) => CowLike::Owned(o),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Config {
+        retries: u32,
+        hosts: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_to_mut_clones_once() {
+        let base = Config {
+            retries: 3,
+            hosts: vec!["a"],
+        };
+        let mut cow = CowLike::from(&base);
+        assert!(cow.is_borrowed());
+        assert_eq!(cow.retries, 3);
+
+        cow.to_mut().retries = 5;
+        cow.to_mut().hosts.push("b");
+        assert!(cow.is_owned());
+        assert_eq!(cow.hosts, ["a", "b"]);
+        assert_eq!(base.retries, 3);
+        assert_eq!(cow.clone().into_owned().retries, 5);
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let x = 7;
+        assert_eq!(CowLike::Borrowed(&x).into_owned(), 7);
+        assert_eq!(CowLike::<i32>::Owned(8).into_owned(), 8);
+        assert_eq!(CowLike::Borrowed(&x), CowLike::Owned(7));
+    }
+
+    #[test]
+    fn test_cow_conversions() {
+        use crate::cow::Cow;
+
+        let x = vec![1, 2];
+        let cow: Cow<Vec<i32>> = CowLike::from(&x).into();
+        assert!(cow.is_borrowed());
+        let back = CowLike::from(cow);
+        assert!(back.is_borrowed());
+
+        let owned: Cow<Vec<i32>> = CowLike::Owned(vec![3]).into();
+        assert!(owned.is_owned());
+        assert_eq!(CowLike::from(owned).into_owned(), [3]);
+    }
+}
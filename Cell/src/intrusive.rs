@@ -0,0 +1,435 @@
+use core::{iter::FusedIterator, marker::PhantomData, ptr::NonNull};
+
+use crate::cell::Cell;
+
+/// The link fields embedded in an item.
+///
+/// Links store pointers to neighbouring *items* (not to their links), so the list never
+/// has to recover an item from the address of one of its fields.
+pub struct Link {
+    prev: Cell<Option<NonNull<()>>>,
+    next: Cell<Option<NonNull<()>>>,
+    linked: Cell<bool>,
+}
+
+impl Link {
+    pub const fn new() -> Self {
+        Self {
+            prev: Cell::new(None),
+            next: Cell::new(None),
+            linked: Cell::new(false),
+        }
+    }
+
+    // Returns true if the item is currently a member of a list through this link.
+    pub fn is_linked(&self) -> bool {
+        self.linked.get()
+    }
+
+    fn unlink(&self) {
+        self.prev.set(None);
+        self.next.set(None);
+        self.linked.set(false);
+    }
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Selects which [`Link`] of `Item` a list threads through.
+///
+/// # Safety
+///
+/// `link` must always return the same field of the given item, and no two adapters used
+/// with the same item at the same time may return the same field.
+pub unsafe trait Adapter {
+    type Item;
+
+    fn link(item: &Self::Item) -> &Link;
+}
+
+/// Declares a unit struct implementing [`Adapter`] for one `Link` field of a type.
+///
+/// `intrusive_adapter!(pub ReadyTasks = Task { ready });`
+#[macro_export]
+macro_rules! intrusive_adapter {
+    ($vis:vis $name:ident = $item:ty { $field:ident }) => {
+        $vis struct $name;
+
+        // SAFETY: the adapter always hands out the same field.
+        unsafe impl $crate::intrusive::Adapter for $name {
+            type Item = $item;
+
+            fn link(item: &$item) -> &$crate::intrusive::Link {
+                &item.$field
+            }
+        }
+    };
+}
+
+/// An intrusive doubly linked list.
+///
+/// Unlike [`LinkedList`](crate::linkedlist::LinkedList), the list does not allocate nodes.
+/// The `prev`/`next` pointers live in a [`Link`] field inside the user's own type, and the
+/// list only threads borrowed items together. An item with several `Link` fields can be
+/// a member of several lists at once, e.g. a task that sits in both the "all tasks" list
+/// and the "ready" queue of a scheduler, or a free-list of preallocated buffers.
+///
+/// Which `Link` a list uses is chosen by an [`Adapter`], usually declared with
+/// [`intrusive_adapter!`](crate::intrusive_adapter).
+///
+/// The list never owns its items: they are borrowed for `'a`, and dropping or clearing the
+/// list only resets their links.
+pub struct IntrusiveList<'a, A: Adapter> {
+    head: Option<NonNull<A::Item>>,
+    tail: Option<NonNull<A::Item>>,
+    len: usize,
+    marker: PhantomData<(&'a A::Item, A)>,
+}
+
+impl<'a, A: Adapter> IntrusiveList<'a, A> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // SAFETY (for the helpers below): every pointer stored in the list or in a link came
+    // from an `&'a A::Item`, so it stays valid for as long as the list is alive.
+
+    fn link_of(item: NonNull<A::Item>) -> &'a Link {
+        A::link(unsafe { item.as_ref() })
+    }
+
+    fn item(ptr: NonNull<()>) -> NonNull<A::Item> {
+        ptr.cast()
+    }
+
+    pub fn front(&self) -> Option<&'a A::Item> {
+        self.head.map(|item| unsafe { item.as_ref() })
+    }
+
+    pub fn back(&self) -> Option<&'a A::Item> {
+        self.tail.map(|item| unsafe { item.as_ref() })
+    }
+
+    /// Links `item` in at the front of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item is already linked through this adapter's link.
+    pub fn push_front(&mut self, item: &'a A::Item) {
+        let link = A::link(item);
+        assert!(!link.is_linked(), "item is already linked into a list");
+        let ptr = NonNull::from(item);
+
+        link.prev.set(None);
+        link.next.set(self.head.map(NonNull::cast));
+        link.linked.set(true);
+
+        match self.head {
+            None => self.tail = Some(ptr),
+            Some(head) => Self::link_of(head).prev.set(Some(ptr.cast())),
+        }
+        self.head = Some(ptr);
+        self.len += 1;
+    }
+
+    /// Links `item` in at the back of the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item is already linked through this adapter's link.
+    pub fn push_back(&mut self, item: &'a A::Item) {
+        let link = A::link(item);
+        assert!(!link.is_linked(), "item is already linked into a list");
+        let ptr = NonNull::from(item);
+
+        link.prev.set(self.tail.map(NonNull::cast));
+        link.next.set(None);
+        link.linked.set(true);
+
+        match self.tail {
+            None => self.head = Some(ptr),
+            Some(tail) => Self::link_of(tail).next.set(Some(ptr.cast())),
+        }
+        self.tail = Some(ptr);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<&'a A::Item> {
+        self.head.map(|head| {
+            unsafe { self.unlink(head) };
+            unsafe { head.as_ref() }
+        })
+    }
+
+    pub fn pop_back(&mut self) -> Option<&'a A::Item> {
+        self.tail.map(|tail| {
+            unsafe { self.unlink(tail) };
+            unsafe { tail.as_ref() }
+        })
+    }
+
+    /// Unlinks `item` from the list in O(1).
+    ///
+    /// # Safety
+    ///
+    /// `item` must currently be linked into *this* list. A link does not record which
+    /// list it belongs to, so removing an item of another list corrupts both of them.
+    pub unsafe fn remove(&mut self, item: &'a A::Item) {
+        unsafe { self.unlink(NonNull::from(item)) }
+    }
+
+    unsafe fn unlink(&mut self, item: NonNull<A::Item>) {
+        let link = Self::link_of(item);
+        debug_assert!(link.is_linked());
+        let prev = link.prev.get().map(Self::item);
+        let next = link.next.get().map(Self::item);
+
+        match prev {
+            None => self.head = next,
+            Some(prev) => Self::link_of(prev).next.set(next.map(NonNull::cast)),
+        }
+
+        match next {
+            None => self.tail = prev,
+            Some(next) => Self::link_of(next).prev.set(prev.map(NonNull::cast)),
+        }
+
+        link.unlink();
+        self.len -= 1;
+    }
+
+    // Unlinks every item, leaving their links ready to be used again.
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    pub fn iter(&self) -> Iter<'_, 'a, A> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<A: Adapter> Default for IntrusiveList<'_, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Adapter> Drop for IntrusiveList<'_, A> {
+    fn drop(&mut self) {
+        // The items outlive the list, so they must not be left pointing at each other.
+        self.clear();
+    }
+}
+
+pub struct Iter<'l, 'a, A: Adapter> {
+    head: Option<NonNull<A::Item>>,
+    tail: Option<NonNull<A::Item>>,
+    len: usize,
+    marker: PhantomData<&'l IntrusiveList<'a, A>>,
+}
+
+impl<'a, A: Adapter> Iterator for Iter<'_, 'a, A> {
+    type Item = &'a A::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head.map(|item| {
+            self.len -= 1;
+            self.head = A::link(unsafe { item.as_ref() })
+                .next
+                .get()
+                .map(NonNull::cast);
+            unsafe { item.as_ref() }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<A: Adapter> DoubleEndedIterator for Iter<'_, '_, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.tail.map(|item| {
+            self.len -= 1;
+            self.tail = A::link(unsafe { item.as_ref() })
+                .prev
+                .get()
+                .map(NonNull::cast);
+            unsafe { item.as_ref() }
+        })
+    }
+}
+
+impl<A: Adapter> ExactSizeIterator for Iter<'_, '_, A> {}
+
+impl<A: Adapter> FusedIterator for Iter<'_, '_, A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    struct Task {
+        id: u32,
+        all: Link,
+        ready: Link,
+    }
+
+    impl Task {
+        fn new(id: u32) -> Self {
+            Task {
+                id,
+                all: Link::new(),
+                ready: Link::new(),
+            }
+        }
+    }
+
+    crate::intrusive_adapter!(AllTasks = Task { all });
+    crate::intrusive_adapter!(ReadyTasks = Task { ready });
+
+    fn ids<A: Adapter<Item = Task>>(list: &IntrusiveList<'_, A>) -> Vec<u32> {
+        list.iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let (a, b, c) = (Task::new(1), Task::new(2), Task::new(3));
+        let mut list = IntrusiveList::<AllTasks>::new();
+        list.push_back(&b);
+        list.push_front(&a);
+        list.push_back(&c);
+        assert_eq!(list.len(), 3);
+        assert_eq!(ids(&list), [1, 2, 3]);
+        assert_eq!(list.front().map(|t| t.id), Some(1));
+        assert_eq!(list.back().map(|t| t.id), Some(3));
+
+        assert_eq!(list.pop_front().map(|t| t.id), Some(1));
+        assert!(!a.all.is_linked());
+        assert_eq!(list.pop_back().map(|t| t.id), Some(3));
+        assert_eq!(list.pop_back().map(|t| t.id), Some(2));
+        assert!(list.pop_front().is_none());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_membership_in_two_lists() {
+        let tasks = [Task::new(1), Task::new(2), Task::new(3)];
+        let mut all = IntrusiveList::<AllTasks>::new();
+        let mut ready = IntrusiveList::<ReadyTasks>::new();
+        for task in &tasks {
+            all.push_back(task);
+        }
+        ready.push_back(&tasks[2]);
+        ready.push_back(&tasks[0]);
+
+        assert_eq!(ids(&all), [1, 2, 3]);
+        assert_eq!(ids(&ready), [3, 1]);
+
+        assert_eq!(ready.pop_front().map(|t| t.id), Some(3));
+        assert_eq!(ids(&all), [1, 2, 3]);
+        assert!(tasks[2].all.is_linked());
+        assert!(!tasks[2].ready.is_linked());
+    }
+
+    #[test]
+    fn test_remove() {
+        let tasks = [Task::new(1), Task::new(2), Task::new(3), Task::new(4)];
+        let mut list = IntrusiveList::<AllTasks>::new();
+        for task in &tasks {
+            list.push_back(task);
+        }
+        unsafe {
+            list.remove(&tasks[1]);
+            list.remove(&tasks[3]);
+        }
+        assert_eq!(ids(&list), [1, 3]);
+        assert_eq!(list.iter().rev().map(|t| t.id).collect::<Vec<_>>(), [3, 1]);
+        unsafe { list.remove(&tasks[0]) };
+        assert_eq!(ids(&list), [3]);
+        assert_eq!(list.len(), 1);
+
+        // A removed item can be linked again.
+        list.push_front(&tasks[1]);
+        assert_eq!(ids(&list), [2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "item is already linked into a list")]
+    fn test_double_push_panics() {
+        let task = Task::new(1);
+        let mut list = IntrusiveList::<AllTasks>::new();
+        list.push_back(&task);
+        list.push_back(&task);
+    }
+
+    #[test]
+    fn test_drop_unlinks_items() {
+        let tasks = [Task::new(1), Task::new(2)];
+        {
+            let mut list = IntrusiveList::<ReadyTasks>::new();
+            list.push_back(&tasks[0]);
+            list.push_back(&tasks[1]);
+        }
+        assert!(tasks.iter().all(|t| !t.ready.is_linked()));
+
+        let mut list = IntrusiveList::<ReadyTasks>::new();
+        list.push_back(&tasks[1]);
+        assert_eq!(ids(&list), [2]);
+    }
+
+    #[test]
+    fn test_free_list() {
+        struct Buffer {
+            data: [u8; 4],
+            free: Link,
+        }
+        crate::intrusive_adapter!(FreeBuffers = Buffer { free });
+
+        let buffers: Vec<Buffer> = (0..3)
+            .map(|i| Buffer {
+                data: [i; 4],
+                free: Link::new(),
+            })
+            .collect();
+        let mut free = IntrusiveList::<FreeBuffers>::new();
+        for buffer in &buffers {
+            free.push_back(buffer);
+        }
+
+        let taken = free.pop_front().unwrap();
+        assert_eq!(taken.data, [0; 4]);
+        assert_eq!(free.len(), 2);
+        free.push_back(taken);
+        assert_eq!(free.back().map(|b| b.data[0]), Some(0));
+    }
+}
@@ -1,11 +1,50 @@
+//! Re-implementations of the standard library's cells, smart pointers and collections.
+//!
+//! The crate is `no_std` and only needs `alloc`. Pieces that depend on the standard
+//! library sit behind the `std` feature, which is enabled by default:
+//!
+//! ```toml
+//! [dependencies]
+//! Cell = { path = "../Cell", default-features = false }
+//! ```
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// The package is called `Cell`, after the first type it re-implemented.
+#![allow(non_snake_case)]
+#![feature(allocator_api)]
+#![feature(coerce_unsized)]
 #![feature(negative_impls)]
-mod BinaryHeap;
-mod cell;
-mod cow;
-mod linkedlist;
-mod once;
-mod rc;
-mod refcell;
-mod reference;
-mod syncunsafecell;
-mod unsafecell;
+#![feature(transparent_unions)]
+#![feature(unsize)]
+
+extern crate alloc;
+
+pub mod arena;
+pub mod binary_heap;
+pub mod boxed;
+pub mod cell;
+pub mod collections;
+pub mod compactcow;
+#[cfg(feature = "std")]
+pub mod concurrent;
+pub mod cow;
+pub mod cowcell;
+pub mod cowlike;
+pub mod intrusive;
+pub mod linkedlist;
+pub mod manuallydrop;
+pub mod maybeuninit;
+pub mod mow;
+pub mod once;
+pub mod owning_ref;
+pub mod pairingheap;
+pub mod rc;
+pub mod refcell;
+pub mod reference;
+pub mod safe_list;
+pub mod sync;
+pub mod syncunsafecell;
+mod sys;
+pub mod unsafecell;
+pub mod xorlinkedlist;
+
+pub use binary_heap::BinaryHeap;
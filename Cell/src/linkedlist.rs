@@ -1,29 +1,71 @@
-use std::{
+use alloc::{
+    alloc::{Allocator, Global},
+    vec::Vec,
+};
+use core::{
+    marker::PhantomData,
     mem::{self, swap, take},
     ptr::NonNull,
 };
 
-///! A doubly linked list with owned values
-///
-/// The `LinkedList` allows pushing and popping elements at either end in constant time
-///
-/// Note: it is almost always better to use [`Vec`] or [`VecDeque`] because
-/// array-based containers are generally faster
-/// more memory efficient and make better use of CPU cache.
-///
-///
+use crate::boxed::Box;
+
 struct Node<T> {
     element: T,
     next: Option<NonNull<Node<T>>>,
     prev: Option<NonNull<Node<T>>>,
 }
 
-pub struct LinkedList<T> {
+/// A doubly linked list with owned values
+///
+/// The `LinkedList` allows pushing and popping elements at either end in constant time
+///
+/// Note: it is almost always better to use [`Vec`] or [`VecDeque`](alloc::collections::VecDeque) because
+/// array-based containers are generally faster
+/// more memory efficient and make better use of CPU cache.
+///
+/// Nodes are allocated through the list's [`Allocator`]. Passing an arena or bump
+/// allocator to [`LinkedList::new_in`] keeps the nodes of one list close together,
+/// which recovers much of the cache locality a global-heap list gives up.
+pub struct LinkedList<T, A: Allocator = Global> {
     head: Option<NonNull<Node<T>>>,
     tail: Option<NonNull<Node<T>>>,
     len: usize,
+    alloc: A,
+    // The list owns its nodes, so tell the compiler that dropping a `LinkedList<T>`
+    // may drop `T`s. `NonNull` together with this marker keeps the list covariant in `T`.
+    marker: PhantomData<Box<Node<T>, A>>,
+}
+
+// Like a `Box`, the list owns its elements (and allocator), so it can move to or be shared
+// with another thread whenever they can.
+unsafe impl<T: Send, A: Allocator + Send> Send for LinkedList<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for LinkedList<T, A> {}
+
+/// A handle to one element of a [`LinkedList`], returned by
+/// [`LinkedList::push_front_handle`] and [`LinkedList::push_back_handle`].
+///
+/// It stays valid while its element is in the list it came from, and lets that element be
+/// read, moved to the front or removed in O(1), without walking the list. The list can't
+/// tell whether a handle is still one of its own, so the methods taking one are `unsafe`:
+/// keeping handles in step with the list is up to the caller, as
+/// [`LruCache`](crate::collections::LruCache) does with the handles in its map.
+pub struct NodeHandle<T> {
+    node: NonNull<Node<T>>,
 }
 
+impl<T> Clone for NodeHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeHandle<T> {}
+
+// A handle grants nothing by itself: every access goes through the list it came from.
+unsafe impl<T: Send> Send for NodeHandle<T> {}
+unsafe impl<T: Sync> Sync for NodeHandle<T> {}
+
 impl<T> Node<T> {
     fn new(element: T) -> Self {
         Self {
@@ -33,12 +75,12 @@ impl<T> Node<T> {
         }
     }
 
-    fn into_element(self: Box<Self>) -> T {
-        self.element
+    fn into_element<A: Allocator>(node: Box<Self, A>) -> T {
+        Box::into_inner(node).element
     }
 }
 
-impl<T> LinkedList<T> {
+impl<T, A: Allocator> LinkedList<T, A> {
     unsafe fn push_front_node(&mut self, node: NonNull<Node<T>>) {
         unsafe {
             // point next of the node to head
@@ -58,12 +100,12 @@ impl<T> LinkedList<T> {
         }
     }
 
-    fn pop_front_node(&mut self) -> Option<Box<Node<T>>> {
+    fn pop_front_node(&mut self) -> Option<Box<Node<T>, &A>> {
         // This method takes care not to create mutable references to whole nodes,
         // to maintain validity of aliasing pointers into `element`
 
         self.head.map(|node| unsafe {
-            let node = Box::from_raw(node.as_ptr());
+            let node = Box::from_raw_in(node.as_ptr(), &self.alloc);
 
             // update ptr: make next of the node as head.
             self.head = node.next;
@@ -92,9 +134,9 @@ impl<T> LinkedList<T> {
         }
     }
 
-    fn pop_back_node(&mut self) -> Option<Box<Node<T>>> {
+    fn pop_back_node(&mut self) -> Option<Box<Node<T>, &A>> {
         self.tail.map(|node| unsafe {
-            let node = Box::from_raw(node.as_ptr());
+            let node = Box::from_raw_in(node.as_ptr(), &self.alloc);
             self.tail = node.prev;
 
             match self.tail {
@@ -106,48 +148,103 @@ impl<T> LinkedList<T> {
         })
     }
 
-    fn unlink_node(&mut self, mut node: NonNull<Node<T>>) {
-        let node = unsafe { node.as_mut() };
+    /// Unlinks the specified node from the current list.
+    ///
+    /// Warning: this will not check that the provided node belongs to the current list.
+    ///
+    /// This method takes care not to create mutable references to `element`, to
+    /// maintain validity of aliasing pointers.
+    unsafe fn unlink_node(&mut self, node: NonNull<Node<T>>) {
+        // Only read the link fields through the raw pointer, never a `&mut Node<T>`
+        // covering `element`, which a caller may still be pointing into.
+        let (prev, next) = unsafe { ((*node.as_ptr()).prev, (*node.as_ptr()).next) };
 
         // next of the previous of the node should point to the next of node
         // the previous of the next of the node should point to the previous of the node
 
-        match node.prev {
-            None => self.head = node.next,
-            Some(prev) => unsafe { (*prev.as_ptr()).next = node.next },
+        match prev {
+            None => self.head = next,
+            Some(prev) => unsafe { (*prev.as_ptr()).next = next },
         }
 
-        match node.next {
-            None => self.tail = node.prev,
-            Some(next) => unsafe { (*next.as_ptr()).prev = node.prev },
+        match next {
+            None => self.tail = prev,
+            Some(next) => unsafe { (*next.as_ptr()).prev = prev },
         }
         self.len -= 1;
     }
+
+    // Returns the node at `at`, walking from whichever end is closer.
+    fn node_at(&self, at: usize) -> Option<NonNull<Node<T>>> {
+        if at >= self.len {
+            return None;
+        }
+        // The ends are reachable without walking at all.
+        if at == 0 {
+            return self.head;
+        }
+        if at == self.len - 1 {
+            return self.tail;
+        }
+        unsafe {
+            if at <= self.len / 2 {
+                let mut node = self.head?;
+                for _ in 0..at {
+                    node = (*node.as_ptr()).next?;
+                }
+                Some(node)
+            } else {
+                let mut node = self.tail?;
+                for _ in 0..self.len - 1 - at {
+                    node = (*node.as_ptr()).prev?;
+                }
+                Some(node)
+            }
+        }
+    }
 }
 
 impl<T> LinkedList<T> {
     #[must_use]
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<T, A: Allocator> LinkedList<T, A> {
+    /// Creates an empty list whose nodes will be allocated with `alloc`.
+    pub const fn new_in(alloc: A) -> Self {
         Self {
             head: None,
             tail: None,
             len: 0,
+            alloc,
+            marker: PhantomData,
         }
     }
 
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
     // moves all elements from `other` to the end of the list
     // This reuses all the nodes from `other` and moves them into `self`.
     // After this operation, `other` becomes empty
 
     // This operation should compute in O(1) time and O(1) memory
+    // As with std's list, the nodes of `other` are adopted as they are, so both lists
+    // must use allocators that can free each other's memory.
     pub fn append(&mut self, other: &mut Self) {
         match self.tail {
             None => swap(self, other),
-            Some(mut tail) => {
-                if let Some(mut other_head) = other.head.take() {
+            Some(tail) => {
+                if let Some(other_head) = other.head.take() {
+                    // Write the two links through the raw pointers, so no `&mut Node<T>`
+                    // covers an `element` a caller may still be pointing into.
                     unsafe {
-                        tail.as_mut().next = Some(other_head);
-                        other_head.as_mut().prev = Some(tail);
+                        (*tail.as_ptr()).next = Some(other_head);
+                        (*other_head.as_ptr()).prev = Some(tail);
                     }
                     self.tail = other.tail;
                     self.len += mem::replace(&mut other.len, 0);
@@ -165,59 +262,57 @@ impl<T> LinkedList<T> {
     }
 
     pub fn clear(&mut self) {
+        // Hand the nodes to a temporary list that borrows our allocator and let its
+        // `Drop` free them.
         drop(LinkedList {
             head: self.head.take(),
             tail: self.tail.take(),
             len: take(&mut self.len),
+            alloc: &self.alloc,
+            marker: PhantomData,
         })
     }
 
-    pub fn contains(&self, x: T) -> bool {
+    pub fn contains(&self, _x: T) -> bool {
         false // @TODO implement iter
     }
 
+    // The accessors below go through `as_ptr` and only borrow the `element` field,
+    // so no reference to a whole node (including its links) is ever created.
+
     pub fn front(&self) -> Option<&T> {
-        unsafe {
-            let refhead = self.head.as_ref();
-            refhead.map(|node| {
-                let refnode = node.as_ref();
-                &refnode.element
-            })
-        }
+        self.head.map(|node| unsafe { &(*node.as_ptr()).element })
     }
 
     pub fn front_mut(&mut self) -> Option<&mut T> {
-        unsafe {
-            let refhead = self.head.as_mut();
-            refhead.map(|node| {
-                let refnode = node.as_mut();
-                &mut refnode.element
-            })
-        }
+        self.head
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
     }
 
     pub fn back(&self) -> Option<&T> {
-        unsafe {
-            let refback = self.tail.as_ref();
-            refback.map(|node| {
-                let refnode = node.as_ref();
-                &refnode.element
-            })
-        }
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).element })
     }
 
     pub fn back_mut(&mut self) -> Option<&mut T> {
-        unsafe {
-            let refback = self.tail.as_mut();
-            refback.map(|node| {
-                let refnode = node.as_mut();
-                &mut refnode.element
-            })
-        }
+        self.tail
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    // Returns a reference to the element at `at`, or `None` if it is out of bounds.
+    // This walks from the nearer end, so it takes at most `len / 2` steps.
+    pub fn get(&self, at: usize) -> Option<&T> {
+        self.node_at(at)
+            .map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    // Returns a mutable reference to the element at `at`, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, at: usize) -> Option<&mut T> {
+        self.node_at(at)
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
     }
 
     pub fn push_front(&mut self, ele: T) {
-        let node = Box::new(Node::new(ele));
+        let node = Box::new_in(Node::new(ele), &self.alloc);
         let node_ptr = NonNull::from(Box::leak(node));
         unsafe {
             self.push_front_node(node_ptr);
@@ -225,17 +320,427 @@ impl<T> LinkedList<T> {
     }
 
     pub fn pop_front(&mut self) -> Option<T> {
-        self.pop_front_node().map(|node| node.element)
+        self.pop_front_node().map(Node::into_element)
     }
 
     pub fn push_back(&mut self, ele: T) {
-        let node = Box::new(Node::new(ele));
+        let node = Box::new_in(Node::new(ele), &self.alloc);
         let node_ptr = NonNull::from(Box::leak(node));
         self.push_back_node(node_ptr);
     }
 
     pub fn pop_back(&mut self) -> Option<T> {
-        self.pop_back_node().map(|node| node.element)
+        self.pop_back_node().map(Node::into_element)
+    }
+
+    /// Like `push_front`, but also returns a handle to the new element.
+    pub fn push_front_handle(&mut self, ele: T) -> NodeHandle<T> {
+        let node = NonNull::from(Box::leak(Box::new_in(Node::new(ele), &self.alloc)));
+        unsafe {
+            self.push_front_node(node);
+        }
+        NodeHandle { node }
+    }
+
+    /// Like `push_back`, but also returns a handle to the new element.
+    pub fn push_back_handle(&mut self, ele: T) -> NodeHandle<T> {
+        let node = NonNull::from(Box::leak(Box::new_in(Node::new(ele), &self.alloc)));
+        self.push_back_node(node);
+        NodeHandle { node }
+    }
+
+    pub fn front_handle(&self) -> Option<NodeHandle<T>> {
+        self.head.map(|node| NodeHandle { node })
+    }
+
+    pub fn back_handle(&self) -> Option<NodeHandle<T>> {
+        self.tail.map(|node| NodeHandle { node })
+    }
+
+    /// Returns the element `handle` points to.
+    ///
+    /// # Safety
+    /// `handle` must come from this list, and its element must not have been removed.
+    pub unsafe fn element(&self, handle: NodeHandle<T>) -> &T {
+        unsafe { &(*handle.node.as_ptr()).element }
+    }
+
+    /// Returns the element `handle` points to mutably.
+    ///
+    /// # Safety
+    /// As for [`element`](Self::element).
+    pub unsafe fn element_mut(&mut self, handle: NodeHandle<T>) -> &mut T {
+        unsafe { &mut (*handle.node.as_ptr()).element }
+    }
+
+    /// Relinks the node of `handle` at the front. The element stays where it is in memory,
+    /// so pointers into it (and the handle) stay valid.
+    ///
+    /// # Safety
+    /// As for [`element`](Self::element).
+    pub unsafe fn move_to_front(&mut self, handle: NodeHandle<T>) {
+        if self.head == Some(handle.node) {
+            return;
+        }
+        unsafe {
+            self.unlink_node(handle.node);
+            self.push_front_node(handle.node);
+        }
+    }
+
+    /// Removes the element `handle` points to and returns it. The handle is dangling after.
+    ///
+    /// # Safety
+    /// As for [`element`](Self::element).
+    pub unsafe fn remove_handle(&mut self, handle: NodeHandle<T>) -> T {
+        unsafe {
+            self.unlink_node(handle.node);
+            Node::into_element(Box::from_raw_in(handle.node.as_ptr(), &self.alloc))
+        }
+    }
+
+    // Swaps the elements at positions `i` and `j` by relinking their nodes, so no element
+    // is moved in memory and pointers into either element stay valid.
+    //
+    // # Panics
+    // Panics if `i` or `j` is out of bounds.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        let len = self.len;
+        let (Some(a), Some(b)) = (self.node_at(i), self.node_at(j)) else {
+            panic!("swap indices (are {i} and {j}) should be < len (is {len})");
+        };
+        if i == j {
+            return;
+        }
+        // Order the nodes so that `a` comes first.
+        let (a, b) = if i < j { (a, b) } else { (b, a) };
+
+        unsafe {
+            let a_prev = (*a.as_ptr()).prev;
+            let a_next = (*a.as_ptr()).next;
+            let b_prev = (*b.as_ptr()).prev;
+            let b_next = (*b.as_ptr()).next;
+
+            if a_next == Some(b) {
+                // a_prev <-> b <-> a <-> b_next
+                (*b.as_ptr()).prev = a_prev;
+                (*b.as_ptr()).next = Some(a);
+                (*a.as_ptr()).prev = Some(b);
+                (*a.as_ptr()).next = b_next;
+            } else {
+                // a_prev <-> b <-> a_next ... b_prev <-> a <-> b_next
+                (*b.as_ptr()).prev = a_prev;
+                (*b.as_ptr()).next = a_next;
+                (*a.as_ptr()).prev = b_prev;
+                (*a.as_ptr()).next = b_next;
+                if let Some(a_next) = a_next {
+                    (*a_next.as_ptr()).prev = Some(b);
+                }
+                if let Some(b_prev) = b_prev {
+                    (*b_prev.as_ptr()).next = Some(a);
+                }
+            }
+
+            match a_prev {
+                None => self.head = Some(b),
+                Some(prev) => (*prev.as_ptr()).next = Some(b),
+            }
+            match b_next {
+                None => self.tail = Some(a),
+                Some(next) => (*next.as_ptr()).prev = Some(a),
+            }
+        }
+    }
+
+    // Returns an iterator yielding references to the elements front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    // Returns an iterator yielding mutable references to the elements front to back.
+    // The iterator can also insert new nodes as it goes, see `IterMut::insert_next`.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, A> {
+        IterMut {
+            head: self.head,
+            tail: self.tail,
+            len: self.len,
+            list: self,
+        }
+    }
+
+    // Removes consecutive repeated elements, keeping the first of each run.
+    // On sorted data this removes all duplicates.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b)
+    }
+
+    // Removes consecutive elements that map to the same key.
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where
+        F: FnMut(&mut T) -> K,
+        K: PartialEq,
+    {
+        self.dedup_by(|a, b| key(a) == key(b))
+    }
+
+    // Removes consecutive elements for which `same_bucket(current, previous)` returns true.
+    // As with `Vec::dedup_by`, the closure gets the element that would be removed first and
+    // the last element that was kept second.
+    // This is a single O(n) pass that unlinks and frees the duplicate nodes in place.
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where
+        F: FnMut(&mut T, &mut T) -> bool,
+    {
+        let Some(mut kept) = self.head else {
+            return;
+        };
+
+        while let Some(current) = unsafe { (*kept.as_ptr()).next } {
+            // `current` and `kept` are different nodes, so these borrows never overlap.
+            let duplicate = unsafe {
+                same_bucket(
+                    &mut (*current.as_ptr()).element,
+                    &mut (*kept.as_ptr()).element,
+                )
+            };
+            if duplicate {
+                unsafe {
+                    self.unlink_node(current);
+                    drop(Box::from_raw_in(current.as_ptr(), &self.alloc));
+                }
+            } else {
+                kept = current;
+            }
+        }
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Vec<T>> for LinkedList<T> {
+    fn from(vec: Vec<T>) -> Self {
+        let mut list = LinkedList::new();
+        for ele in vec {
+            list.push_back(ele);
+        }
+        list
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for LinkedList<T> {
+    fn from(arr: [T; N]) -> Self {
+        let mut list = LinkedList::new();
+        for ele in arr {
+            list.push_back(ele);
+        }
+        list
+    }
+}
+
+// Moves the elements out front to back; the nodes are freed as they are popped.
+impl<T, A: Allocator> From<LinkedList<T, A>> for Vec<T> {
+    fn from(mut list: LinkedList<T, A>) -> Self {
+        let mut vec = Vec::with_capacity(list.len());
+        while let Some(ele) = list.pop_front() {
+            vec.push(ele);
+        }
+        vec
+    }
+}
+
+impl<T, A: Allocator> Drop for LinkedList<T, A> {
+    fn drop(&mut self) {
+        // Pop one node at a time so the boxes (and their elements) are freed in order
+        // without recursing through the `next` pointers.
+        while self.pop_front_node().is_some() {}
+    }
+}
+
+// Lists are (de)serialized as plain sequences, front to back.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, A: Allocator> serde::Serialize for LinkedList<T, A> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        let mut node = self.head;
+        while let Some(ptr) = node {
+            unsafe {
+                seq.serialize_element(&(*ptr.as_ptr()).element)?;
+                node = (*ptr.as_ptr()).next;
+            }
+        }
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for LinkedList<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ListVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: serde::Deserialize<'de>> serde::de::Visitor<'de> for ListVisitor<T> {
+            type Value = LinkedList<T>;
+
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                formatter.write_str("a sequence")
+            }
+
+            fn visit_seq<S: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: S,
+            ) -> Result<Self::Value, S::Error> {
+                let mut list = LinkedList::new();
+                while let Some(ele) = seq.next_element()? {
+                    list.push_back(ele);
+                }
+                Ok(list)
+            }
+        }
+
+        deserializer.deserialize_seq(ListVisitor(PhantomData))
+    }
+}
+
+pub struct Iter<'a, T> {
+    head: Option<NonNull<Node<T>>>,
+    tail: Option<NonNull<Node<T>>>,
+    len: usize,
+    marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head.map(|node| unsafe {
+            self.len -= 1;
+            self.head = (*node.as_ptr()).next;
+            &(*node.as_ptr()).element
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.tail.map(|node| unsafe {
+            self.len -= 1;
+            self.tail = (*node.as_ptr()).prev;
+            &(*node.as_ptr()).element
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+// Only hands out `&T`s, like `&LinkedList<T>`.
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
+
+pub struct IterMut<'a, T, A: Allocator = Global> {
+    // The list is only used to relink nodes and update `len`; elements handed out by the
+    // iterator are reached through the node pointers, never through `list`.
+    list: &'a mut LinkedList<T, A>,
+    head: Option<NonNull<Node<T>>>,
+    tail: Option<NonNull<Node<T>>>,
+    len: usize,
+}
+
+impl<'a, T, A: Allocator> Iterator for IterMut<'a, T, A> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head.map(|node| unsafe {
+            self.len -= 1;
+            self.head = (*node.as_ptr()).next;
+            &mut (*node.as_ptr()).element
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for IterMut<'a, T, A> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.tail.map(|node| unsafe {
+            self.len -= 1;
+            self.tail = (*node.as_ptr()).prev;
+            &mut (*node.as_ptr()).element
+        })
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IterMut<'_, T, A> {}
+
+impl<T, A: Allocator> IterMut<'_, T, A> {
+    // Inserts `element` right after the element most recently returned by `next`.
+    // If `next` has not been called yet the element goes to the front of the list, and
+    // once the iterator is exhausted it goes to the back.
+    //
+    // The new element is not yielded by this iterator, so a single pass can expand
+    // elements in place without revisiting what it inserted.
+    pub fn insert_next(&mut self, element: T) {
+        let Some(head) = self.head else {
+            // Nothing left to visit, so "after the last yielded element" is the back.
+            return self.list.push_back(element);
+        };
+        let Some(prev) = (unsafe { (*head.as_ptr()).prev }) else {
+            return unsafe {
+                let node = Box::new_in(Node::new(element), &self.list.alloc);
+                self.list.push_front_node(NonNull::from(Box::leak(node)));
+            };
+        };
+
+        // Only the link fields of `prev` and `head` are written, so references to their
+        // elements that were already handed out stay valid.
+        let mut node = Box::new_in(Node::new(element), &self.list.alloc);
+        node.prev = Some(prev);
+        node.next = Some(head);
+        let node = NonNull::from(Box::leak(node));
+        unsafe {
+            (*prev.as_ptr()).next = Some(node);
+            (*head.as_ptr()).prev = Some(node);
+        }
+        self.list.len += 1;
+    }
+
+    // Returns the element that the next call to `next` would return, without advancing.
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
     }
 }
 
@@ -270,3 +775,551 @@ impl<T> LinkedList<T> {
 //         None
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::alloc::{AllocError, Layout};
+    use core::cell::{Cell as StdCell, UnsafeCell};
+    use std::{rc::Rc, string::String, vec};
+
+    // These tests are written to be run under Miri as well (`cargo +nightly miri test`),
+    // which catches aliasing violations that would otherwise go unnoticed.
+
+    #[test]
+    fn test_push_pop() {
+        let mut list = LinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&3));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_front_back_mut() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+        *list.front_mut().unwrap() += 10;
+        *list.back_mut().unwrap() += 20;
+        assert_eq!(list.front(), Some(&11));
+        assert_eq!(list.back(), Some(&22));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut a = LinkedList::new();
+        let mut b = LinkedList::new();
+        a.push_back(1);
+        b.push_back(2);
+        b.push_back(3);
+        a.append(&mut b);
+        assert_eq!(a.len(), 3);
+        assert!(b.is_empty());
+        assert_eq!(a.pop_back(), Some(3));
+        assert_eq!(a.pop_back(), Some(2));
+        assert_eq!(a.pop_back(), Some(1));
+    }
+
+    #[test]
+    fn test_append_keeps_element_pointers() {
+        let mut a = LinkedList::new();
+        let mut b = LinkedList::new();
+        a.push_back(1);
+        b.push_back(2);
+        let tail: *mut i32 = a.back_mut().unwrap();
+        let other_head: *mut i32 = b.front_mut().unwrap();
+
+        // Appending writes `next` of our tail and `prev` of the other head, which must
+        // not invalidate pointers into their elements.
+        a.append(&mut b);
+
+        unsafe {
+            *tail += 10;
+            *other_head += 20;
+            assert_eq!(*tail, 11);
+            assert_eq!(*other_head, 22);
+        }
+        assert_eq!(a.pop_front(), Some(11));
+        assert_eq!(a.pop_front(), Some(22));
+    }
+
+    #[test]
+    fn test_element_pointer_survives_relinking() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        let first: *mut i32 = list.front_mut().unwrap();
+
+        // Relinking the neighbours writes to the links of the first node, which must
+        // not invalidate a pointer into its element.
+        list.push_back(2);
+        list.push_front(0);
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), Some(0));
+
+        unsafe {
+            *first += 41;
+            assert_eq!(*first, 42);
+        }
+        assert_eq!(list.front(), Some(&42));
+    }
+
+    #[test]
+    fn test_unlink_node_keeps_neighbour_pointers() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        let first: *mut i32 = list.front_mut().unwrap();
+        let last: *mut i32 = list.back_mut().unwrap();
+
+        let middle = unsafe { (*list.head.unwrap().as_ptr()).next.unwrap() };
+        unsafe {
+            list.unlink_node(middle);
+            drop(Box::from_raw(middle.as_ptr()));
+        }
+
+        assert_eq!(list.len(), 2);
+        unsafe {
+            assert_eq!(*first, 1);
+            assert_eq!(*last, 3);
+        }
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_front(), Some(3));
+    }
+
+    #[test]
+    fn test_drop_releases_elements() {
+        let counter = Rc::new(());
+        {
+            let mut list = LinkedList::new();
+            for _ in 0..5 {
+                list.push_back(Rc::clone(&counter));
+            }
+            assert_eq!(Rc::strong_count(&counter), 6);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn test_clear_releases_elements() {
+        let counter = Rc::new(());
+        let mut list = LinkedList::new();
+        list.push_back(Rc::clone(&counter));
+        list.push_front(Rc::clone(&counter));
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    // A bump allocator over a fixed buffer: every node of a list lands in one
+    // contiguous block, and deallocation is a no-op until the arena goes away.
+    struct Arena {
+        buf: UnsafeCell<[u8; 1024]>,
+        used: StdCell<usize>,
+        allocations: StdCell<usize>,
+    }
+
+    impl Arena {
+        fn new() -> Self {
+            Arena {
+                buf: UnsafeCell::new([0; 1024]),
+                used: StdCell::new(0),
+                allocations: StdCell::new(0),
+            }
+        }
+
+        fn contains(&self, ptr: *const u8) -> bool {
+            let start = self.buf.get() as *const u8;
+            let end = start.wrapping_add(1024);
+            (start..end).contains(&ptr)
+        }
+    }
+
+    unsafe impl Allocator for &Arena {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let start = self.buf.get() as *mut u8;
+            let offset = (start as usize + self.used.get()).next_multiple_of(layout.align())
+                - start as usize;
+            if offset + layout.size() > 1024 {
+                return Err(AllocError);
+            }
+            self.used.set(offset + layout.size());
+            self.allocations.set(self.allocations.get() + 1);
+            let ptr = unsafe { NonNull::new_unchecked(start.add(offset)) };
+            Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+        }
+
+        unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+    }
+
+    #[test]
+    fn test_nodes_come_from_allocator() {
+        let arena = Arena::new();
+        let mut list = LinkedList::new_in(&arena);
+        for i in 0..4 {
+            list.push_back(i);
+        }
+        list.push_front(-1);
+        assert_eq!(arena.allocations.get(), 5);
+
+        let mut node = list.head;
+        while let Some(ptr) = node {
+            assert!(arena.contains(ptr.as_ptr() as *const u8));
+            node = unsafe { (*ptr.as_ptr()).next };
+        }
+
+        assert_eq!(list.pop_front(), Some(-1));
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.len(), 3);
+        assert!(core::ptr::eq(*list.allocator(), &arena));
+    }
+
+    #[test]
+    fn test_allocator_list_drops_elements() {
+        let arena = Arena::new();
+        let counter = Rc::new(());
+        {
+            let mut list = LinkedList::new_in(&arena);
+            list.push_back(Rc::clone(&counter));
+            list.push_back(Rc::clone(&counter));
+            list.clear();
+            list.push_back(Rc::clone(&counter));
+            assert_eq!(Rc::strong_count(&counter), 2);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let list = LinkedList::from(vec![1, 2, 3]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&3));
+    }
+
+    #[test]
+    fn test_from_array() {
+        let mut list = LinkedList::from(["a", "b"]);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.pop_front(), Some("a"));
+        assert_eq!(list.pop_front(), Some("b"));
+
+        let empty: LinkedList<i32> = LinkedList::from([]);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_into_vec() {
+        let mut list = LinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(Vec::from(list), vec![1, 2, 3]);
+
+        let round_trip: Vec<String> = LinkedList::from(vec![String::from("x")]).into();
+        assert_eq!(round_trip, [String::from("x")]);
+    }
+
+    #[test]
+    fn test_into_vec_with_allocator() {
+        let arena = Arena::new();
+        let mut list = LinkedList::new_in(&arena);
+        list.push_back(1);
+        list.push_back(2);
+        assert_eq!(Vec::from(list), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut list = LinkedList::from([1, 1, 2, 3, 3, 3, 1, 4, 4]);
+        list.dedup();
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.back(), Some(&4));
+        assert_eq!(Vec::from(list), vec![1, 2, 3, 1, 4]);
+
+        let mut list: LinkedList<i32> = LinkedList::new();
+        list.dedup();
+        assert!(list.is_empty());
+
+        let mut list = LinkedList::from([7, 7, 7]);
+        list.dedup();
+        assert_eq!(list.front(), list.back());
+        assert_eq!(Vec::from(list), vec![7]);
+    }
+
+    #[test]
+    fn test_dedup_by_key() {
+        let mut list = LinkedList::from([10, 11, 20, 21, 22, 30]);
+        list.dedup_by_key(|x| *x / 10);
+        assert_eq!(Vec::from(list), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_dedup_by() {
+        let mut list = LinkedList::from(["foo", "FOO", "bar", "Bar", "baz"]);
+        list.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        assert_eq!(Vec::from(list), vec!["foo", "bar", "baz"]);
+
+        // The kept element can be updated from the duplicates it absorbs.
+        let mut list = LinkedList::from([(1, 1), (1, 2), (2, 5), (2, 1)]);
+        list.dedup_by(|current, kept| {
+            let same = current.0 == kept.0;
+            if same {
+                kept.1 += current.1;
+            }
+            same
+        });
+        assert_eq!(Vec::from(list), vec![(1, 3), (2, 6)]);
+    }
+
+    #[test]
+    fn test_dedup_drops_removed_elements() {
+        let counter = Rc::new(());
+        let mut list = LinkedList::new();
+        for _ in 0..3 {
+            list.push_back(Rc::clone(&counter));
+        }
+        list.dedup_by(|_, _| true);
+        assert_eq!(list.len(), 1);
+        assert_eq!(Rc::strong_count(&counter), 2);
+    }
+
+    // Walks the list in both directions so broken `prev` links are caught too.
+    fn check_links<T: Clone + PartialEq + core::fmt::Debug>(list: &LinkedList<T>, expected: &[T]) {
+        let mut forward = Vec::new();
+        let mut node = list.head;
+        while let Some(ptr) = node {
+            unsafe {
+                forward.push((*ptr.as_ptr()).element.clone());
+                node = (*ptr.as_ptr()).next;
+            }
+        }
+        let mut backward = Vec::new();
+        let mut node = list.tail;
+        while let Some(ptr) = node {
+            unsafe {
+                backward.push((*ptr.as_ptr()).element.clone());
+                node = (*ptr.as_ptr()).prev;
+            }
+        }
+        backward.reverse();
+        assert_eq!(forward, expected);
+        assert_eq!(backward, expected);
+        assert_eq!(list.len(), expected.len());
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut list = LinkedList::from([0, 1, 2, 3, 4]);
+        list.swap(1, 3);
+        check_links(&list, &[0, 3, 2, 1, 4]);
+        list.swap(0, 4);
+        check_links(&list, &[4, 3, 2, 1, 0]);
+        list.swap(2, 1);
+        check_links(&list, &[4, 2, 3, 1, 0]);
+        list.swap(3, 4);
+        check_links(&list, &[4, 2, 3, 0, 1]);
+        list.swap(0, 1);
+        check_links(&list, &[2, 4, 3, 0, 1]);
+        list.swap(2, 2);
+        check_links(&list, &[2, 4, 3, 0, 1]);
+
+        let mut pair = LinkedList::from(["a", "b"]);
+        pair.swap(1, 0);
+        check_links(&pair, &["b", "a"]);
+    }
+
+    #[test]
+    fn test_swap_relinks_nodes() {
+        let mut list = LinkedList::from([1, 2, 3]);
+        let first: *mut i32 = list.front_mut().unwrap();
+        list.swap(0, 2);
+        unsafe { *first *= 10 };
+        check_links(&list, &[3, 2, 10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "swap indices (are 0 and 3) should be < len (is 3)")]
+    fn test_swap_out_of_bounds() {
+        let mut list = LinkedList::from([1, 2, 3]);
+        list.swap(0, 3);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut list = LinkedList::from([1, 2, 3]);
+        for x in list.iter_mut() {
+            *x *= 2;
+        }
+        check_links(&list, &[2, 4, 6]);
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(&mut 6));
+        assert_eq!(iter.next(), Some(&mut 2));
+        assert_eq!(iter.next(), Some(&mut 4));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_iter() {
+        let list = LinkedList::from([1, 2, 3]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_handles() {
+        let mut list = LinkedList::new();
+        let a = list.push_back_handle(1);
+        let b = list.push_back_handle(2);
+        let c = list.push_front_handle(0);
+        assert_eq!(list.front_handle().map(|h| h.node), Some(c.node));
+        assert_eq!(list.back_handle().map(|h| h.node), Some(b.node));
+        let first: *mut i32 = unsafe { list.element_mut(a) };
+
+        unsafe {
+            list.move_to_front(b);
+            list.move_to_front(b);
+            *list.element_mut(c) += 10;
+            check_links(&list, &[2, 10, 1]);
+            assert_eq!(list.remove_handle(c), 10);
+            check_links(&list, &[2, 1]);
+            list.move_to_front(a);
+            check_links(&list, &[1, 2]);
+            // Relinking leaves the element where it was.
+            *first += 1;
+            assert_eq!(*list.element(a), 2);
+            assert_eq!(list.remove_handle(a), 2);
+            assert_eq!(list.remove_handle(b), 2);
+        }
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_insert_next_expands_in_place() {
+        // Replace every n with n, n * 10 in a single pass.
+        let mut list = LinkedList::from([1, 2, 3]);
+        let mut iter = list.iter_mut();
+        while let Some(x) = iter.next() {
+            let expanded = *x * 10;
+            iter.insert_next(expanded);
+        }
+        check_links(&list, &[1, 10, 2, 20, 3, 30]);
+    }
+
+    #[test]
+    fn test_insert_next_at_the_ends() {
+        let mut list = LinkedList::from([2, 3]);
+        let mut iter = list.iter_mut();
+        iter.insert_next(1);
+        assert_eq!(iter.peek_next(), Some(&mut 2));
+        assert_eq!(iter.next(), Some(&mut 2));
+        assert_eq!(iter.next(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+        iter.insert_next(4);
+        assert_eq!(iter.peek_next(), None);
+        check_links(&list, &[1, 2, 3, 4]);
+
+        let mut empty = LinkedList::new();
+        empty.iter_mut().insert_next(1);
+        check_links(&empty, &[1]);
+    }
+
+    #[test]
+    fn test_insert_next_keeps_yielded_references() {
+        let mut list = LinkedList::from([1, 2]);
+        let mut iter = list.iter_mut();
+        let first = iter.next().unwrap();
+        iter.insert_next(5);
+        let second = iter.next().unwrap();
+        *first += 100;
+        *second += 200;
+        check_links(&list, &[101, 5, 202]);
+    }
+
+    #[test]
+    fn test_insert_next_with_allocator() {
+        let arena = Arena::new();
+        let mut list = LinkedList::new_in(&arena);
+        list.push_back(1);
+        let mut iter = list.iter_mut();
+        iter.next();
+        iter.insert_next(2);
+        assert_eq!(arena.allocations.get(), 2);
+        assert_eq!(Vec::from(list), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_get() {
+        let list = LinkedList::from([0, 1, 2, 3, 4, 5]);
+        for i in 0..6 {
+            assert_eq!(list.get(i), Some(&i));
+        }
+        assert_eq!(list.get(6), None);
+        assert_eq!(LinkedList::<i32>::new().get(0), None);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut list = LinkedList::from([0, 1, 2, 3, 4]);
+        *list.get_mut(0).unwrap() = 10;
+        *list.get_mut(1).unwrap() = 11;
+        *list.get_mut(3).unwrap() = 13;
+        *list.get_mut(4).unwrap() = 14;
+        assert!(list.get_mut(5).is_none());
+        check_links(&list, &[10, 11, 2, 13, 14]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let list = LinkedList::from([String::from("a"), String::from("b")]);
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(json, r#"["a","b"]"#);
+
+        let back: LinkedList<String> = serde_json::from_str(&json).unwrap();
+        check_links(&back, &[String::from("a"), String::from("b")]);
+
+        let empty: LinkedList<i32> = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+        assert!(serde_json::from_str::<LinkedList<i32>>("{}").is_err());
+    }
+
+    #[test]
+    fn test_covariance() {
+        // Only needs to compile: a list of longer-lived references can be used
+        // where a list of shorter-lived ones is expected.
+        fn shorten<'a>(list: LinkedList<&'static str>) -> LinkedList<&'a str> {
+            list
+        }
+        fn shorten_ref<'a, 'b>(list: &'b LinkedList<&'static str>) -> &'b LinkedList<&'a str> {
+            list
+        }
+
+        let mut list = LinkedList::new();
+        list.push_back("static");
+        assert_eq!(shorten_ref(&list).front(), Some(&"static"));
+
+        let local = String::from("local");
+        let mut list = shorten(list);
+        list.push_back(&local);
+        assert_eq!(list.back(), Some(&"local"));
+    }
+}
@@ -0,0 +1,117 @@
+use core::{
+    ops::{Deref, DerefMut},
+    ptr,
+};
+
+/*
+Normally the compiler drops every value when its owner goes out of scope. ManuallyDrop<T>
+switches that off for one value: it holds a `T` but has no drop glue, so the value lives
+until someone drops it by hand, or forever.
+
+There is exactly one way to tell the compiler "don't drop this field", and that is the
+`ManuallyDrop` lang item in core; unions, for example, only accept fields without drop
+glue, and the core ManuallyDrop is what makes a field qualify. So ours wraps the core
+type for that single property, and builds the rest of the API on raw pointers.
+
+Typical uses:
+- taking a value apart in a method that consumes `self` when `Self` implements Drop
+  (`ManuallyDrop::new(self)`, then move the fields out with `ptr::read`),
+- controlling the order in which fields are dropped,
+- holding a value that may or may not have been moved out yet, like the value in Rc.
+*/
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct ManuallyDrop<T: ?Sized> {
+    value: core::mem::ManuallyDrop<T>,
+}
+
+impl<T> ManuallyDrop<T> {
+    pub const fn new(value: T) -> ManuallyDrop<T> {
+        ManuallyDrop {
+            value: core::mem::ManuallyDrop::new(value),
+        }
+    }
+
+    // Takes the value back out, so it will be dropped normally again.
+    pub const fn into_inner(slot: ManuallyDrop<T>) -> T {
+        core::mem::ManuallyDrop::into_inner(slot.value)
+    }
+
+    /// Moves the value out, leaving `slot` logically uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not use `slot` afterwards, except to let it go out of scope
+    /// (which does nothing); reading it again would duplicate the value.
+    pub unsafe fn take(slot: &mut ManuallyDrop<T>) -> T {
+        // SAFETY: `slot` holds an initialized value, and the caller promises not to use it
+        // again, so this read is the only copy that survives.
+        unsafe { ptr::read(&**slot) }
+    }
+}
+
+impl<T: ?Sized> ManuallyDrop<T> {
+    /// Runs the destructor of the value in place.
+    ///
+    /// # Safety
+    ///
+    /// The value must not be used afterwards, and this must be called at most once.
+    pub unsafe fn drop(slot: &mut ManuallyDrop<T>) {
+        // SAFETY: the caller promises the value is still there and never touched again.
+        unsafe { ptr::drop_in_place(&mut **slot) }
+    }
+}
+
+impl<T: ?Sized> Deref for ManuallyDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: ?Sized> DerefMut for ManuallyDrop<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, vec, vec::Vec};
+
+    #[test]
+    fn test_value_is_not_dropped() {
+        let rc = Rc::new(5);
+        {
+            let slot = ManuallyDrop::new(Rc::clone(&rc));
+            assert_eq!(**slot, 5);
+        }
+        assert_eq!(Rc::strong_count(&rc), 2);
+    }
+
+    #[test]
+    fn test_into_inner_and_take() {
+        let rc = Rc::new(());
+        let slot = ManuallyDrop::new(Rc::clone(&rc));
+        drop(ManuallyDrop::into_inner(slot));
+        assert_eq!(Rc::strong_count(&rc), 1);
+
+        let mut slot = ManuallyDrop::new(vec![Rc::clone(&rc)]);
+        slot.push(Rc::clone(&rc));
+        let taken: Vec<_> = unsafe { ManuallyDrop::take(&mut slot) };
+        assert_eq!(taken.len(), 2);
+        drop(taken);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn test_drop_in_place() {
+        let rc = Rc::new(());
+        let mut slot = ManuallyDrop::new(Rc::clone(&rc));
+        unsafe { ManuallyDrop::drop(&mut slot) };
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,300 @@
+use core::{fmt, mem, ptr, slice};
+
+use crate::manuallydrop::ManuallyDrop;
+
+/*
+MaybeUninit<T> is memory with room for a `T` that may or may not hold one yet. Creating a
+`T` out of uninitialized bytes is undefined behaviour even if it is never read, which
+rules out tricks like `mem::uninitialized()` or `Vec::with_capacity` + `set_len` for
+building values in place. MaybeUninit is the sanctioned way: the compiler makes no
+assumptions about its contents, and `assume_init` is where the programmer vouches that
+the bytes are now a valid `T`.
+
+It is a union of `()` and the value. A union only stores one of its fields, and reading a
+field is unsafe because the compiler can't know which one was written last, which is
+exactly the "maybe" we want. Union fields may not have drop glue, so the value sits in a
+ManuallyDrop: MaybeUninit never drops what it holds.
+
+Arrays of MaybeUninit are where it earns its keep, for example in SmallVec's inline buffer
+or when filling an array one element at a time. If filling can panic halfway through, the
+elements written so far must still be dropped; the `Guard` below is the usual drop guard
+for that.
+*/
+
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub union MaybeUninit<T> {
+    uninit: (),
+    value: core::mem::ManuallyDrop<T>,
+}
+
+impl<T> MaybeUninit<T> {
+    pub const fn new(value: T) -> MaybeUninit<T> {
+        MaybeUninit {
+            value: core::mem::ManuallyDrop::new(value),
+        }
+    }
+
+    pub const fn uninit() -> MaybeUninit<T> {
+        MaybeUninit { uninit: () }
+    }
+
+    // Memory filled with zero bytes. Whether that is a valid `T` depends on `T`: fine for
+    // integers and raw pointers, undefined behaviour for references or `NonZero`.
+    pub fn zeroed() -> MaybeUninit<T> {
+        let mut slot = MaybeUninit::uninit();
+        // SAFETY: writing bytes into our own storage; nothing claims they are a `T` yet.
+        unsafe { ptr::write_bytes(slot.as_mut_ptr(), 0, 1) };
+        slot
+    }
+
+    pub const fn uninit_array<const N: usize>() -> [MaybeUninit<T>; N] {
+        [const { MaybeUninit::uninit() }; N]
+    }
+
+    // Stores `value`, overwriting without dropping whatever was there, and returns a
+    // reference to it.
+    pub fn write(&mut self, value: T) -> &mut T {
+        *self = MaybeUninit::new(value);
+        // SAFETY: we just initialized it.
+        unsafe { self.assume_init_mut() }
+    }
+
+    pub const fn as_ptr(&self) -> *const T {
+        // A union's fields all start at its address.
+        self as *const MaybeUninit<T> as *const T
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self as *mut MaybeUninit<T> as *mut T
+    }
+
+    /// # Safety
+    ///
+    /// The value must be initialized. Like reading any union field, this trusts the
+    /// caller about which field holds the bytes.
+    pub const unsafe fn assume_init(self) -> T {
+        // SAFETY: the caller promises `value` is the initialized field.
+        unsafe { core::mem::ManuallyDrop::into_inner(self.value) }
+    }
+
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    pub const unsafe fn assume_init_ref(&self) -> &T {
+        // SAFETY: the caller promises the value is initialized.
+        unsafe { &*self.as_ptr() }
+    }
+
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    pub unsafe fn assume_init_mut(&mut self) -> &mut T {
+        // SAFETY: the caller promises the value is initialized.
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+
+    /// Copies the value out, leaving the bits in place.
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized, and unless `T: Copy` the copy left behind must
+    /// not be used again.
+    pub unsafe fn assume_init_read(&self) -> T {
+        // SAFETY: the caller promises the value is initialized.
+        unsafe { self.as_ptr().read() }
+    }
+
+    /// Drops the value in place, leaving the slot uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// The value must be initialized.
+    pub unsafe fn assume_init_drop(&mut self) {
+        // SAFETY: the caller promises the value is initialized.
+        unsafe { ptr::drop_in_place(self.as_mut_ptr()) }
+    }
+
+    /// # Safety
+    ///
+    /// Every element must be initialized.
+    pub unsafe fn array_assume_init<const N: usize>(array: [MaybeUninit<T>; N]) -> [T; N] {
+        // MaybeUninit<T> has the layout of T, so an array of one has the layout of an array
+        // of the other. `transmute` can't see that through the generic `N`, so copy the bits
+        // and forget the original, which doesn't drop anything anyway.
+        let array = ManuallyDrop::new(array);
+        // SAFETY: the layouts match, and the caller promises every element is initialized.
+        unsafe { (&*array as *const [MaybeUninit<T>; N] as *const [T; N]).read() }
+    }
+
+    /// # Safety
+    ///
+    /// Every element of `slice` must be initialized.
+    pub unsafe fn slice_assume_init_ref(slice: &[MaybeUninit<T>]) -> &[T] {
+        // SAFETY: same layout, and the caller promises the elements are initialized.
+        unsafe { slice::from_raw_parts(slice.as_ptr().cast::<T>(), slice.len()) }
+    }
+
+    /// # Safety
+    ///
+    /// Every element of `slice` must be initialized.
+    pub unsafe fn slice_assume_init_mut(slice: &mut [MaybeUninit<T>]) -> &mut [T] {
+        // SAFETY: as above.
+        unsafe { slice::from_raw_parts_mut(slice.as_mut_ptr().cast::<T>(), slice.len()) }
+    }
+
+    // Builds an array by calling `f` with each index. If `f` panics, the elements created
+    // so far are dropped before the panic goes on.
+    pub fn init_array<const N: usize, F: FnMut(usize) -> T>(mut f: F) -> [T; N] {
+        let mut array = MaybeUninit::uninit_array::<N>();
+        let mut guard = Guard {
+            slice: &mut array,
+            initialized: 0,
+        };
+        while guard.initialized < N {
+            guard.slice[guard.initialized].write(f(guard.initialized));
+            guard.initialized += 1;
+        }
+        mem::forget(guard);
+        // SAFETY: the loop initialized all `N` elements.
+        unsafe { MaybeUninit::array_assume_init(array) }
+    }
+
+    // Clones `src` into `this`, which must have the same length, and returns the now
+    // initialized slice. If a `clone` panics, the clones made so far are dropped.
+    //
+    // Whatever `this` held before is overwritten without being dropped.
+    pub fn write_slice_cloned<'a>(this: &'a mut [MaybeUninit<T>], src: &[T]) -> &'a mut [T]
+    where
+        T: Clone,
+    {
+        assert_eq!(
+            this.len(),
+            src.len(),
+            "destination and source slices have different lengths"
+        );
+        let mut guard = Guard {
+            slice: this,
+            initialized: 0,
+        };
+        for value in src {
+            guard.slice[guard.initialized].write(value.clone());
+            guard.initialized += 1;
+        }
+        let this = mem::take(&mut guard.slice);
+        mem::forget(guard);
+        // SAFETY: every element was just written.
+        unsafe { MaybeUninit::slice_assume_init_mut(this) }
+    }
+}
+
+impl<T> fmt::Debug for MaybeUninit<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The contents can't be looked at without knowing whether they are initialized.
+        f.pad("MaybeUninit<..>")
+    }
+}
+
+// Drops the first `initialized` elements of `slice` unless it is forgotten, so that a panic
+// while filling the slice doesn't leak what was already written.
+struct Guard<'a, T> {
+    slice: &'a mut [MaybeUninit<T>],
+    initialized: usize,
+}
+
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        let initialized = &mut self.slice[..self.initialized];
+        // SAFETY: exactly the first `initialized` elements have been written.
+        unsafe { ptr::drop_in_place(MaybeUninit::slice_assume_init_mut(initialized)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        format,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_write_and_assume_init() {
+        let mut slot = MaybeUninit::<String>::uninit();
+        slot.write("hello".to_string()).push('!');
+        // SAFETY: `write` initialized the slot.
+        assert_eq!(unsafe { slot.assume_init_ref() }, "hello!");
+        // SAFETY: as above; the slot is moved out of and not used again.
+        let value = unsafe { slot.assume_init() };
+        assert_eq!(value, "hello!");
+        // SAFETY: zero bytes are a valid `u64`.
+        assert_eq!(unsafe { MaybeUninit::<u64>::zeroed().assume_init() }, 0);
+        assert_eq!(format!("{:?}", MaybeUninit::new(1)), "MaybeUninit<..>");
+    }
+
+    #[test]
+    fn test_never_drops_its_value() {
+        let rc = Rc::new(());
+        let mut slot = MaybeUninit::new(Rc::clone(&rc));
+        slot.write(Rc::clone(&rc));
+        // The first clone was overwritten without being dropped.
+        assert_eq!(Rc::strong_count(&rc), 3);
+        // SAFETY: the slot holds the second clone, which is dropped once.
+        unsafe { slot.assume_init_drop() };
+        assert_eq!(Rc::strong_count(&rc), 2);
+    }
+
+    #[test]
+    fn test_arrays() {
+        let mut array = MaybeUninit::<Vec<usize>>::uninit_array::<4>();
+        for (i, slot) in array.iter_mut().enumerate() {
+            slot.write((0..i).collect());
+        }
+        // SAFETY: the loop wrote every element.
+        let array = unsafe { MaybeUninit::array_assume_init(array) };
+        assert_eq!(array[3], [0, 1, 2]);
+
+        let squares: [usize; 5] = MaybeUninit::init_array(|i| i * i);
+        assert_eq!(squares, [0, 1, 4, 9, 16]);
+    }
+
+    #[test]
+    fn test_slices() {
+        let src = ["a".to_string(), "b".to_string()];
+        let mut buf = MaybeUninit::<String>::uninit_array::<3>();
+        let written = MaybeUninit::write_slice_cloned(&mut buf[1..], &src);
+        written[0].push('!');
+        // SAFETY: `write_slice_cloned` initialized `buf[1..]`.
+        assert_eq!(
+            unsafe { MaybeUninit::slice_assume_init_ref(&buf[1..]) },
+            ["a!", "b"]
+        );
+        for slot in &mut buf[1..] {
+            // SAFETY: as above, and each slot is dropped once.
+            unsafe { slot.assume_init_drop() };
+        }
+    }
+
+    #[test]
+    fn test_guard_drops_on_panic() {
+        let rc = Rc::new(());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            MaybeUninit::init_array::<5, _>(|i| {
+                assert!(i < 3, "third element fails");
+                Rc::clone(&rc)
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "different lengths")]
+    fn test_write_slice_length_mismatch() {
+        let mut buf = MaybeUninit::<u8>::uninit_array::<2>();
+        MaybeUninit::write_slice_cloned(&mut buf, &[1]);
+    }
+}
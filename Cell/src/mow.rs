@@ -0,0 +1,158 @@
+use alloc::borrow::ToOwned;
+use core::{
+    borrow::{Borrow, BorrowMut},
+    fmt,
+    ops::Deref,
+};
+
+/// Mow : Mutate or own.
+///
+/// Like `Cow`, but with a third state for data the caller already has mutable access to.
+/// `to_mut` changes a `BorrowedMut` value in place and only clones when it starts from a
+/// shared `Borrowed` one, so code that sometimes owns `&mut` access doesn't pay for a copy
+/// it doesn't need.
+///
+/// `to_mut` hands out `&mut B`, which is enough for types like `[T]` or `Vec<T>`. To grow a
+/// `str` or slice, use `to_mut_owned`, which also has to clone a `BorrowedMut` value.
+pub enum Mow<'a, B: ?Sized + 'a>
+where
+    B: ToOwned,
+{
+    Borrowed(&'a B),
+    BorrowedMut(&'a mut B),
+    Owned(<B as ToOwned>::Owned),
+}
+
+impl<B: ?Sized + ToOwned> Deref for Mow<'_, B> {
+    type Target = B;
+    fn deref(&self) -> &B {
+        match *self {
+            Mow::Borrowed(b) => b,
+            Mow::BorrowedMut(ref b) => b,
+            Mow::Owned(ref o) => o.borrow(),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned> Mow<'_, B> {
+    pub fn is_borrowed(&self) -> bool {
+        !self.is_owned()
+    }
+
+    pub fn is_owned(&self) -> bool {
+        matches!(*self, Mow::Owned(_))
+    }
+
+    pub fn into_owned(self) -> <B as ToOwned>::Owned {
+        match self {
+            Mow::Borrowed(borrowed) => borrowed.to_owned(),
+            Mow::BorrowedMut(borrowed) => (*borrowed).to_owned(),
+            Mow::Owned(owned) => owned,
+        }
+    }
+
+    // Always ends up `Owned`, cloning from either borrowed state.
+    pub fn to_mut_owned(&mut self) -> &mut <B as ToOwned>::Owned {
+        match *self {
+            Mow::Borrowed(borrowed) => *self = Mow::Owned(borrowed.to_owned()),
+            Mow::BorrowedMut(ref borrowed) => *self = Mow::Owned((**borrowed).to_owned()),
+            Mow::Owned(_) => {}
+        }
+        match *self {
+            Mow::Owned(ref mut owned) => owned,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned> Mow<'_, B>
+where
+    B::Owned: BorrowMut<B>,
+{
+    // Mutable access to the data. Only a shared `Borrowed` value is cloned first.
+    pub fn to_mut(&mut self) -> &mut B {
+        if let Mow::Borrowed(borrowed) = *self {
+            *self = Mow::Owned(borrowed.to_owned());
+        }
+        match *self {
+            Mow::Borrowed(_) => unreachable!(),
+            Mow::BorrowedMut(ref mut borrowed) => borrowed,
+            Mow::Owned(ref mut owned) => owned.borrow_mut(),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned + fmt::Debug> fmt::Debug for Mow<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<&'a B> for Mow<'a, B> {
+    fn from(borrowed: &'a B) -> Self {
+        Mow::Borrowed(borrowed)
+    }
+}
+
+impl<'a, B: ?Sized + ToOwned> From<&'a mut B> for Mow<'a, B> {
+    fn from(borrowed: &'a mut B) -> Self {
+        Mow::BorrowedMut(borrowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{string::String, vec, vec::Vec};
+
+    #[test]
+    fn test_to_mut_from_shared_borrow_clones() {
+        let data = vec![1, 2, 3];
+        let mut mow: Mow<[i32]> = Mow::Borrowed(&data);
+        assert!(mow.is_borrowed());
+        mow.to_mut()[0] = 10;
+        assert!(mow.is_owned());
+        assert_eq!(&*mow, [10, 2, 3]);
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_to_mut_from_mutable_borrow_writes_through() {
+        let mut data = vec![1, 2, 3];
+        {
+            let mut mow: Mow<[i32]> = Mow::from(&mut data[..]);
+            mow.to_mut()[0] = 10;
+            mow.to_mut().reverse();
+            assert!(mow.is_borrowed());
+        }
+        assert_eq!(data, [3, 2, 10]);
+    }
+
+    #[test]
+    fn test_to_mut_owned() {
+        let mut s = String::from("hello");
+        let mut mow: Mow<str> = Mow::BorrowedMut(s.as_mut_str());
+        mow.to_mut().make_ascii_uppercase();
+        assert_eq!(&*mow, "HELLO");
+
+        // Growing a `str` needs an owned `String`.
+        mow.to_mut_owned().push_str(" world");
+        assert!(mow.is_owned());
+        assert_eq!(&*mow, "HELLO world");
+        drop(mow);
+        assert_eq!(s, "HELLO");
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let mut v = [1];
+        let owned: Vec<i32> = Mow::<[i32]>::from(&mut v[..]).into_owned();
+        assert_eq!(owned, [1]);
+        assert_eq!(Mow::<str>::from("x").into_owned(), "x");
+        assert_eq!(
+            Mow::<str>::Owned(String::from("y")).into_owned(),
+            String::from("y")
+        );
+        assert_eq!(std::format!("{:?}", Mow::<str>::from("z")), r#""z""#);
+    }
+}
@@ -12,7 +12,7 @@
     For thread-safe version of this struct see OnceLock
 */
 
-use std::mem;
+use core::mem;
 
 use crate::unsafecell::UnsafeCell;
 
@@ -70,7 +70,7 @@ impl<T> OnceCell<T> {
 
         // let slot = unsafe { &mut *self.inner.get() };
         // slot.insert(value);
-        let _ = unsafe { *self.inner.get() = Some(value) }; // update the value.
+        unsafe { *self.inner.get() = Some(value) }; // update the value.
         let v = unsafe { (*self.inner.get()).as_mut().unwrap_unchecked() }; //get the updated value.
         Ok(v)
     }
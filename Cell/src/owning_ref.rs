@@ -0,0 +1,419 @@
+//! An owner bundled with a reference into the data it owns.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    mem::{ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr,
+};
+
+use crate::{
+    boxed::Box,
+    rc::Rc,
+    reference::{Ref, RefMut},
+    sync::SpinLockGuard,
+};
+
+/*
+A function can't return a `String` together with a `&str` pointing into it: the reference
+would borrow from a local that moves as soon as the function returns. But the characters
+don't live in the `String` value itself, they live on the heap, and moving the `String`
+doesn't move them. OwningRef relies on exactly that: it stores the owner next to a raw
+pointer into the owner's target, and since that target stays put, the pointer stays valid
+for as long as the owner is alive and not mutated.
+
+The same goes for guards: a `Ref` points into its RefCell, so an OwningRef can keep the
+guard alive and hand out a reference to one field of the borrowed value.
+
+Keeping the target in place is not quite enough for a std `Box` or a `&mut`: every time one
+of those is moved as a value, it asserts unique access to its target again, and that
+invalidates any pointer derived from it earlier, ours included. So the owner is kept in a
+`MaybeUninit`, which is always initialized here. Moving a `MaybeUninit` moves plain bytes
+and asserts nothing, so the owner only acts as an `O` again once `into_owner` hands it out,
+and the reference dies with the `OwningRef` at that point. Because of that, the owner
+has to be dropped by hand, and taken out with `into_parts` rather than moved out of a field.
+*/
+
+/// Types whose [`Deref`] target stays at the same address when the value itself is moved.
+///
+/// # Safety
+///
+/// The reference returned by `deref` (and `deref_mut`, if implemented) must stay valid and
+/// point to the same place while `self` is moved around, as long as `self` is not dropped
+/// and not otherwise accessed.
+pub unsafe trait StableAddress: Deref {}
+
+// SAFETY: all of these keep their target on the heap, or elsewhere outside themselves. A
+// std `Box` and a `&mut` also claim their target afresh when moved, which OwningRef copes
+// with by keeping its owner in a `MaybeUninit` (see the top of the file).
+unsafe impl<T: ?Sized> StableAddress for alloc::boxed::Box<T> {}
+unsafe impl<T: ?Sized> StableAddress for Box<T> {}
+unsafe impl<T> StableAddress for Vec<T> {}
+unsafe impl StableAddress for String {}
+unsafe impl<T: ?Sized> StableAddress for alloc::rc::Rc<T> {}
+unsafe impl<T: ?Sized> StableAddress for Rc<T> {}
+unsafe impl<T: ?Sized> StableAddress for Arc<T> {}
+unsafe impl<T: ?Sized> StableAddress for &T {}
+unsafe impl<T: ?Sized> StableAddress for &mut T {}
+// SAFETY: guards point into the cell or lock they borrow from.
+unsafe impl<T: ?Sized> StableAddress for Ref<'_, T> {}
+unsafe impl<T: ?Sized> StableAddress for RefMut<'_, T> {}
+unsafe impl<T: ?Sized> StableAddress for SpinLockGuard<'_, T> {}
+#[cfg(feature = "std")]
+unsafe impl<T: ?Sized> StableAddress for crate::sync::MutexGuard<'_, T> {}
+#[cfg(feature = "std")]
+unsafe impl<T: ?Sized> StableAddress for crate::sync::RwLockReadGuard<'_, T> {}
+#[cfg(feature = "std")]
+unsafe impl<T: ?Sized> StableAddress for crate::sync::RwLockWriteGuard<'_, T> {}
+
+/// Owners whose clones deref to the very same target, so a reference into one is also a
+/// reference into the other. Not true for `Box` or `Vec`, whose clones copy the data.
+///
+/// # Safety
+///
+/// `clone` must return a value whose `deref` points to the same place as the original's.
+pub unsafe trait CloneStableAddress: StableAddress + Clone {}
+
+// SAFETY: cloning these only bumps a reference count or copies the reference.
+unsafe impl<T: ?Sized> CloneStableAddress for alloc::rc::Rc<T> {}
+unsafe impl<T: ?Sized> CloneStableAddress for Rc<T> {}
+unsafe impl<T: ?Sized> CloneStableAddress for Arc<T> {}
+unsafe impl<T: ?Sized> CloneStableAddress for &T {}
+
+/// An owner `O` together with a shared reference to a `T` inside it.
+///
+/// Starts out referring to the owner's whole target, and [`map`](OwningRef::map) narrows it
+/// down to any part of that.
+///
+/// ```
+/// use Cell::owning_ref::OwningRef;
+///
+/// fn first_line(text: String) -> OwningRef<String, str> {
+///     OwningRef::new(text).map(|text| text.lines().next().unwrap_or(""))
+/// }
+///
+/// let line = first_line(String::from("hello\nworld"));
+/// assert_eq!(&*line, "hello");
+/// assert_eq!(line.into_owner(), "hello\nworld");
+/// ```
+pub struct OwningRef<O, T: ?Sized> {
+    // Always initialized, see the top of the file.
+    owner: MaybeUninit<O>,
+    reference: *const T,
+}
+
+// It acts like an `O` next to a `&T`.
+unsafe impl<O: Send, T: ?Sized + Sync> Send for OwningRef<O, T> {}
+unsafe impl<O: Sync, T: ?Sized + Sync> Sync for OwningRef<O, T> {}
+
+impl<O: StableAddress> OwningRef<O, O::Target> {
+    pub fn new(owner: O) -> Self {
+        // Put the owner away first, so the reference is derived from where it stays.
+        let owner = MaybeUninit::new(owner);
+        // SAFETY: just initialized.
+        let reference: *const O::Target = unsafe { &**owner.assume_init_ref() };
+        OwningRef { owner, reference }
+    }
+}
+
+impl<O, T: ?Sized> OwningRef<O, T> {
+    /// Narrows the reference down to something reachable from it, e.g. a field or a
+    /// subslice. The owner comes along unchanged.
+    pub fn map<U: ?Sized, F>(self, f: F) -> OwningRef<O, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let reference: *const U = f(&*self);
+        OwningRef {
+            owner: self.into_parts().0,
+            reference,
+        }
+    }
+
+    /// Like [`map`](OwningRef::map), for projections that can fail. The owner is dropped
+    /// along with the error.
+    pub fn try_map<U: ?Sized, E, F>(self, f: F) -> Result<OwningRef<O, U>, E>
+    where
+        F: FnOnce(&T) -> Result<&U, E>,
+    {
+        let reference: *const U = f(&*self)?;
+        Ok(OwningRef {
+            owner: self.into_parts().0,
+            reference,
+        })
+    }
+
+    pub fn owner(&self) -> &O {
+        // SAFETY: the owner is initialized for as long as `self` lives.
+        unsafe { self.owner.assume_init_ref() }
+    }
+
+    pub fn into_owner(self) -> O {
+        // SAFETY: as in `owner`, and `into_parts` keeps it from being dropped here.
+        unsafe { self.into_parts().0.assume_init() }
+    }
+
+    // Takes `self` apart without running its `Drop`, leaving the owner to the caller.
+    fn into_parts(self) -> (MaybeUninit<O>, *const T) {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again, so the owner is moved, not copied.
+        (unsafe { ptr::read(&this.owner) }, this.reference)
+    }
+}
+
+impl<O, T: ?Sized> Drop for OwningRef<O, T> {
+    fn drop(&mut self) {
+        // SAFETY: the owner is initialized, and this is the last use of it.
+        unsafe { self.owner.assume_init_drop() }
+    }
+}
+
+impl<O, T: ?Sized> Deref for OwningRef<O, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: `reference` was derived from the target of `owner`, which has a stable
+        // address and is only ever accessed through shared references while we live.
+        unsafe { &*self.reference }
+    }
+}
+
+impl<O: CloneStableAddress, T: ?Sized> Clone for OwningRef<O, T> {
+    fn clone(&self) -> Self {
+        OwningRef {
+            owner: MaybeUninit::new(self.owner().clone()),
+            reference: self.reference,
+        }
+    }
+}
+
+impl<O, T: ?Sized + fmt::Debug> fmt::Debug for OwningRef<O, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwningRef").field(&&**self).finish()
+    }
+}
+
+/// An owner `O` together with a mutable reference to a `T` inside it.
+///
+/// Unlike [`OwningRef`] it can't give access to its owner while it lives: that would alias
+/// the `&mut T`. Use [`into_owner`](OwningRefMut::into_owner) to get the owner back.
+///
+/// ```
+/// use Cell::{owning_ref::OwningRefMut, refcell::RefCell};
+///
+/// struct Config {
+///     name: String,
+///     retries: u32,
+/// }
+///
+/// let cell = RefCell::new(Config { name: String::from("db"), retries: 3 });
+/// let mut retries = OwningRefMut::new(cell.borrow_mut()).map_mut(|config| &mut config.retries);
+/// *retries += 1;
+/// drop(retries);
+/// assert_eq!(cell.borrow().retries, 4);
+/// assert_eq!(cell.borrow().name, "db");
+/// ```
+pub struct OwningRefMut<O, T: ?Sized> {
+    // Always initialized, see the top of the file.
+    owner: MaybeUninit<O>,
+    reference: *mut T,
+}
+
+// It acts like an `O` next to a `&mut T`.
+unsafe impl<O: Send, T: ?Sized + Send> Send for OwningRefMut<O, T> {}
+unsafe impl<O: Sync, T: ?Sized + Sync> Sync for OwningRefMut<O, T> {}
+
+impl<O: StableAddress + DerefMut> OwningRefMut<O, O::Target> {
+    pub fn new(owner: O) -> Self {
+        // Put the owner away first, so the reference is derived from where it stays.
+        let mut owner = MaybeUninit::new(owner);
+        // SAFETY: just initialized.
+        let reference: *mut O::Target = unsafe { &mut **owner.assume_init_mut() };
+        OwningRefMut { owner, reference }
+    }
+}
+
+impl<O, T: ?Sized> OwningRefMut<O, T> {
+    /// Narrows the mutable reference down to something reachable from it.
+    pub fn map_mut<U: ?Sized, F>(mut self, f: F) -> OwningRefMut<O, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let reference: *mut U = f(&mut *self);
+        OwningRefMut {
+            owner: self.into_parts().0,
+            reference,
+        }
+    }
+
+    /// Gives up mutable access, keeping a shared reference to what `f` returns.
+    pub fn map<U: ?Sized, F>(self, f: F) -> OwningRef<O, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let reference: *const U = f(&*self);
+        OwningRef {
+            owner: self.into_parts().0,
+            reference,
+        }
+    }
+
+    pub fn try_map_mut<U: ?Sized, E, F>(mut self, f: F) -> Result<OwningRefMut<O, U>, E>
+    where
+        F: FnOnce(&mut T) -> Result<&mut U, E>,
+    {
+        let reference: *mut U = f(&mut *self)?;
+        Ok(OwningRefMut {
+            owner: self.into_parts().0,
+            reference,
+        })
+    }
+
+    pub fn into_owner(self) -> O {
+        // SAFETY: the owner is initialized, and `into_parts` keeps it from being dropped.
+        unsafe { self.into_parts().0.assume_init() }
+    }
+
+    // Takes `self` apart without running its `Drop`, leaving the owner to the caller.
+    fn into_parts(self) -> (MaybeUninit<O>, *mut T) {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again, so the owner is moved, not copied.
+        (unsafe { ptr::read(&this.owner) }, this.reference)
+    }
+}
+
+impl<O, T: ?Sized> Drop for OwningRefMut<O, T> {
+    fn drop(&mut self) {
+        // SAFETY: the owner is initialized, and this is the last use of it.
+        unsafe { self.owner.assume_init_drop() }
+    }
+}
+
+impl<O, T: ?Sized> Deref for OwningRefMut<O, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: `reference` points into the stable target of `owner`, which nobody else
+        // can reach while we hold it.
+        unsafe { &*self.reference }
+    }
+}
+
+impl<O, T: ?Sized> DerefMut for OwningRefMut<O, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as above, and `&mut self` makes this the only access.
+        unsafe { &mut *self.reference }
+    }
+}
+
+impl<O, T: ?Sized + fmt::Debug> fmt::Debug for OwningRefMut<O, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwningRefMut").field(&&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refcell::RefCell;
+    use std::{format, string::ToString, vec};
+
+    struct Person {
+        name: String,
+        age: u32,
+    }
+
+    fn name_of(cell: &RefCell<Person>) -> OwningRef<Ref<'_, Person>, str> {
+        OwningRef::new(cell.borrow()).map(|person| person.name.as_str())
+    }
+
+    #[test]
+    fn test_map_survives_moves() {
+        let owned = OwningRef::new(vec![1, 2, 3, 4]).map(|v| &v[1..3]);
+        let moved = owned;
+        let boxed = std::boxed::Box::new(moved);
+        assert_eq!(**boxed, [2, 3]);
+        assert_eq!(boxed.owner().len(), 4);
+        assert_eq!(boxed.into_owner(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_try_map() {
+        let ok = OwningRef::new(String::from("a=1"))
+            .try_map(|s| s.split_once('=').map(|kv| kv.1).ok_or(()));
+        assert_eq!(&*ok.unwrap(), "1");
+        let err = OwningRef::new(String::from("a"))
+            .try_map(|s| s.split_once('=').map(|kv| kv.1).ok_or("no ="));
+        assert_eq!(err.err(), Some("no ="));
+    }
+
+    #[test]
+    fn test_guard_as_owner() {
+        let cell = RefCell::new(Person {
+            name: "Ada".to_string(),
+            age: 36,
+        });
+        let name = name_of(&cell);
+        assert_eq!(&*name, "Ada");
+        assert!(cell.try_borrow_mut().is_err());
+        drop(name);
+
+        let mut age = OwningRefMut::new(cell.borrow_mut()).map_mut(|person| &mut person.age);
+        *age += 1;
+        let age = age.map(|age| age);
+        assert_eq!(*age, 37);
+        drop(age);
+        assert_eq!(cell.borrow().age, 37);
+    }
+
+    #[test]
+    fn test_shared_owner_clones() {
+        let rc = Rc::new(vec![String::from("x"), String::from("y")]);
+        let second = OwningRef::new(rc.clone()).map(|v| v[1].as_str());
+        let copy = second.clone();
+        drop(second);
+        assert_eq!(&*copy, "y");
+        assert_eq!(format!("{copy:?}"), "OwningRef(\"y\")");
+    }
+
+    #[test]
+    fn test_owning_ref_mut_box() {
+        let mut first = OwningRefMut::new(Box::new([1, 2, 3])).map_mut(|a| &mut a[0]);
+        *first = 10;
+        let result: Result<OwningRefMut<_, i32>, ()> =
+            OwningRefMut::new(first.into_owner()).try_map_mut(|a| Ok(&mut a[2]));
+        let last = result.unwrap();
+        assert_eq!(*last, 3);
+        assert_eq!(*last.into_owner(), [10, 2, 3]);
+    }
+
+    // A std `Box` or a `&mut` asserts unique access to its target whenever it is moved,
+    // which must not invalidate the reference stored next to it.
+    #[test]
+    fn test_std_box_owner() {
+        let nine = OwningRef::new(std::boxed::Box::new(9)).map(|x| x);
+        let moved = nine;
+        assert_eq!(*moved, 9);
+        assert_eq!(**moved.owner(), 9);
+
+        let last = OwningRefMut::new(std::boxed::Box::new([1, 2, 3])).map_mut(|a| &mut a[2]);
+        let mut moved = last;
+        *moved += 1;
+        assert_eq!(*moved, 4);
+        let last = moved.map(|x| x);
+        assert_eq!(*last, 4);
+        assert_eq!(*last.into_owner(), [1, 2, 4]);
+    }
+
+    #[test]
+    fn test_mut_ref_owner() {
+        let mut values = [1, 2, 3];
+        let mut first = OwningRefMut::new(&mut values).map_mut(|a| &mut a[0]);
+        *first = 10;
+        let first = first.map(|x| x);
+        assert_eq!(*first, 10);
+        drop(first);
+        assert_eq!(values, [10, 2, 3]);
+    }
+}
@@ -0,0 +1,465 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{marker::PhantomData, mem, ptr::NonNull};
+
+/// A max-heap stored as a tree of individually allocated nodes, the mergeable counterpart
+/// of the array-backed binary heap.
+///
+/// Every node keeps its children in a linked list. `push` and `meld` just link one root
+/// under the other in O(1). `pop` removes the root and merges its children back together in
+/// two passes (pairwise left to right, then folding right to left), which is O(log n)
+/// amortized.
+///
+/// `push` returns a [`Handle`] to the new element. `increase_key` uses it to cut the element's
+/// subtree out of the tree and meld it back at the root in O(1) amortized time. It's the
+/// max-heap form of the classic decrease-key.
+pub struct PairingHeap<T> {
+    root: Link<T>,
+    len: usize,
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    element: T,
+    // Leftmost child.
+    child: Link<T>,
+    // Next sibling to the right.
+    next: Link<T>,
+    // The parent for a leftmost child, the sibling to the left otherwise, `None` for a root.
+    prev: Link<T>,
+}
+
+/// Refers to an element pushed into a [`PairingHeap`], for use with
+/// [`PairingHeap::increase_key`]. It stays valid until the element is popped or the heap is
+/// dropped, and moves along with the element when its heap is melded into another one.
+pub struct Handle<T> {
+    node: NonNull<Node<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T: Ord> PairingHeap<T> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            root: None,
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.root.map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn push(&mut self, element: T) -> Handle<T> {
+        // Compare before allocating, so a panicking `Ord` leaves nothing behind.
+        let root_wins = self
+            .root
+            .map(|root| unsafe { (*root.as_ptr()).element >= element });
+        let node = NonNull::from(Box::leak(Box::new(Node {
+            element,
+            child: None,
+            next: None,
+            prev: None,
+        })));
+        self.root = Some(match (self.root, root_wins) {
+            (Some(root), Some(root_wins)) => unsafe { link(root, node, root_wins) },
+            _ => node,
+        });
+        self.len += 1;
+        Handle { node }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        // Detach the root before `merge_pairs` calls `Ord`, which may panic: the heap must
+        // not point at the freed root then. It's left empty, and `merge_pairs` frees the
+        // children.
+        let root = self.root.take()?;
+        let len = mem::take(&mut self.len);
+        unsafe {
+            let root = Box::from_raw(root.as_ptr());
+            self.root = merge_pairs(root.child);
+            self.len = len - 1;
+            Some(root.element)
+        }
+    }
+
+    // Moves all elements of `other` into `self` in O(1). Handles into `other` keep working
+    // and now refer to elements of `self`.
+    pub fn meld(&mut self, mut other: Self) {
+        if let Some(other_root) = other.root {
+            // Compare while `other` still owns its tree, so it is freed if `Ord` panics.
+            let root_wins = self.root.map(|root| unsafe { geq(root, other_root) });
+            other.root = None;
+            self.root = Some(match (self.root, root_wins) {
+                (Some(root), Some(root_wins)) => unsafe { link(root, other_root, root_wins) },
+                _ => other_root,
+            });
+            self.len += mem::take(&mut other.len);
+        }
+    }
+
+    /// Returns the element the handle refers to.
+    ///
+    /// # Safety
+    ///
+    /// The element of `handle` must still be in this heap: not popped, and its heap not
+    /// dropped or melded into a different one than `self`.
+    pub unsafe fn get(&self, handle: Handle<T>) -> &T {
+        unsafe { &(*handle.node.as_ptr()).element }
+    }
+
+    /// Replaces the element of `handle` with a greater (or equal) one.
+    ///
+    /// Panics if `element` is smaller than the current one. Making an element smaller would
+    /// mean sifting it down through its subtree, which a pairing heap can't do cheaply; pop
+    /// and push it again instead.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`get`](Self::get).
+    pub unsafe fn increase_key(&mut self, handle: Handle<T>, element: T) {
+        let node = handle.node.as_ptr();
+        unsafe {
+            assert!(
+                element >= (*node).element,
+                "the new element must not be smaller than the current one"
+            );
+            (*node).element = element;
+
+            let Some(prev) = (*node).prev else {
+                // Already the root.
+                return;
+            };
+            // Compare before the subtree is cut out, so a panicking `Ord` can't leave it
+            // detached from the heap.
+            let root = self.root.unwrap();
+            let root_wins = geq(root, handle.node);
+            // Cut the subtree out of its sibling list and meld it with the root.
+            if (*prev.as_ptr()).child == Some(handle.node) {
+                (*prev.as_ptr()).child = (*node).next;
+            } else {
+                (*prev.as_ptr()).next = (*node).next;
+            }
+            if let Some(next) = (*node).next {
+                (*next.as_ptr()).prev = Some(prev);
+            }
+            (*node).prev = None;
+            (*node).next = None;
+            self.root = Some(link(root, handle.node, root_wins));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        drop(PairingHeap {
+            root: self.root.take(),
+            len: mem::take(&mut self.len),
+            marker: PhantomData,
+        });
+    }
+}
+
+// Whether `a` may be the parent of `b`. The only place that calls `Ord`, so callers compare
+// first and relink after: a panic then leaves the tree as it was.
+//
+// SAFETY: `a` and `b` must be live.
+unsafe fn geq<T: Ord>(a: NonNull<Node<T>>, b: NonNull<Node<T>>) -> bool {
+    unsafe { (*a.as_ptr()).element >= (*b.as_ptr()).element }
+}
+
+// Links two roots, making `b` the leftmost child of `a` if `a_wins` (from `geq(a, b)`) and
+// the other way round otherwise, and returns the new root.
+//
+// SAFETY: `a` and `b` must be distinct live roots (no `prev` and no `next`).
+unsafe fn link<T>(a: NonNull<Node<T>>, b: NonNull<Node<T>>, a_wins: bool) -> NonNull<Node<T>> {
+    unsafe {
+        let (parent, child) = if a_wins { (a, b) } else { (b, a) };
+        let first = (*parent.as_ptr()).child;
+        (*child.as_ptr()).next = first;
+        (*child.as_ptr()).prev = Some(parent);
+        if let Some(first) = first {
+            (*first.as_ptr()).prev = Some(child);
+        }
+        (*parent.as_ptr()).child = Some(child);
+        parent
+    }
+}
+
+// The sibling lists `merge_pairs` works through. Whenever it compares, every node is in
+// exactly one of them, so if `Ord` panics, dropping this frees all of them.
+struct Merge<T> {
+    current: Link<T>,
+    pairs: Link<T>,
+    root: Link<T>,
+}
+
+impl<T> Drop for Merge<T> {
+    fn drop(&mut self) {
+        for list in [self.current.take(), self.pairs.take(), self.root.take()] {
+            unsafe { free(list) };
+        }
+    }
+}
+
+// Merges a list of siblings into a single root with the standard two passes.
+//
+// SAFETY: `first` must be the head of a sibling list whose parent is being discarded.
+unsafe fn merge_pairs<T: Ord>(first: Link<T>) -> Link<T> {
+    let mut merge = Merge {
+        current: first,
+        pairs: None,
+        root: None,
+    };
+    unsafe {
+        // Left to right: link neighbours in pairs, stacking the results through `next`.
+        while let Some(a) = merge.current {
+            let merged = match (*a.as_ptr()).next {
+                None => {
+                    merge.current = take_next(a);
+                    a
+                }
+                Some(b) => {
+                    let a_wins = geq(a, b);
+                    take_next(a);
+                    merge.current = take_next(b);
+                    link(a, b, a_wins)
+                }
+            };
+            (*merged.as_ptr()).next = merge.pairs;
+            merge.pairs = Some(merged);
+        }
+
+        // Right to left: fold the pairs into one tree.
+        while let Some(pair) = merge.pairs {
+            merge.root = Some(match merge.root {
+                None => {
+                    merge.pairs = take_next(pair);
+                    pair
+                }
+                Some(root) => {
+                    let root_wins = geq(root, pair);
+                    merge.pairs = take_next(pair);
+                    link(root, pair, root_wins)
+                }
+            });
+        }
+        merge.root.take()
+    }
+}
+
+// Frees the trees in a sibling list, with an explicit stack; recursing could overflow on
+// deep trees.
+//
+// SAFETY: nothing else may own or use the nodes afterwards.
+unsafe fn free<T>(first: Link<T>) {
+    let mut stack: Vec<NonNull<Node<T>>> = first.into_iter().collect();
+    while let Some(node) = stack.pop() {
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        stack.extend(node.child);
+        stack.extend(node.next);
+    }
+}
+
+// Detaches `node` from its siblings, returning its former `next`.
+//
+// SAFETY: `node` must be live.
+unsafe fn take_next<T>(node: NonNull<Node<T>>) -> Link<T> {
+    unsafe {
+        (*node.as_ptr()).prev = None;
+        (*node.as_ptr()).next.take()
+    }
+}
+
+impl<T: Ord> Default for PairingHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for PairingHeap<T> {
+    fn drop(&mut self) {
+        unsafe { free(self.root.take()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, vec, vec::Vec};
+
+    fn drain<T: Ord>(heap: &mut PairingHeap<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Some(x) = heap.pop() {
+            out.push(x);
+        }
+        out
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut heap = PairingHeap::new();
+        assert!(heap.is_empty());
+        assert_eq!(heap.pop(), None);
+        for x in [5, 1, 8, 3, 3, 9, 2] {
+            heap.push(x);
+        }
+        assert_eq!(heap.len(), 7);
+        assert_eq!(heap.peek(), Some(&9));
+        assert_eq!(drain(&mut heap), [9, 8, 5, 3, 3, 2, 1]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_interleaved() {
+        let mut heap = PairingHeap::new();
+        let mut reference = vec![];
+        for i in 0..500usize {
+            let x = (i * 7919 + 13) % 101;
+            heap.push(x);
+            reference.push(x);
+            if i % 3 == 0 {
+                reference.sort();
+                assert_eq!(heap.pop(), reference.pop());
+            }
+        }
+        reference.sort_by(|a, b| b.cmp(a));
+        assert_eq!(drain(&mut heap), reference);
+    }
+
+    #[test]
+    fn test_meld() {
+        let mut a = PairingHeap::new();
+        let mut b = PairingHeap::new();
+        a.push(4);
+        a.push(1);
+        b.push(6);
+        let handle = b.push(2);
+        b.push(3);
+        a.meld(b);
+        assert_eq!(a.len(), 5);
+
+        // Handles survive the meld.
+        unsafe {
+            assert_eq!(*a.get(handle), 2);
+            a.increase_key(handle, 7);
+        }
+        assert_eq!(drain(&mut a), [7, 6, 4, 3, 1]);
+
+        a.meld(PairingHeap::new());
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn test_increase_key() {
+        let mut heap = PairingHeap::new();
+        let handles: Vec<_> = (0..20).map(|x| heap.push(x * 10)).collect();
+        // Pop once so the tree has some depth.
+        assert_eq!(heap.pop(), Some(190));
+
+        unsafe {
+            heap.increase_key(handles[3], 35);
+            heap.increase_key(handles[0], 500);
+            assert_eq!(heap.peek(), Some(&500));
+            // The root can be increased as well.
+            heap.increase_key(handles[0], 600);
+            heap.increase_key(handles[5], 50);
+        }
+        let mut expected: Vec<_> = (1..19).map(|x| x * 10).collect();
+        expected[2] = 35;
+        expected.push(600);
+        expected.sort_by(|a, b| b.cmp(a));
+        assert_eq!(drain(&mut heap), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be smaller")]
+    fn test_increase_key_rejects_smaller() {
+        let mut heap = PairingHeap::new();
+        let handle = heap.push(5);
+        unsafe { heap.increase_key(handle, 4) };
+    }
+
+    #[test]
+    fn test_pop_survives_panicking_ord() {
+        use core::cell::Cell;
+        use core::cmp::Ordering;
+
+        std::thread_local!(static PANIC: Cell<bool> = const { Cell::new(false) });
+
+        #[derive(PartialEq, Eq)]
+        struct Touchy(u32);
+
+        impl PartialOrd for Touchy {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Touchy {
+            fn cmp(&self, other: &Self) -> Ordering {
+                assert!(!PANIC.get(), "comparison failed");
+                self.0.cmp(&other.0)
+            }
+        }
+
+        // Pushed largest first, so the root ends up with several children to merge.
+        let mut heap = PairingHeap::new();
+        for i in (0..8).rev() {
+            heap.push(Touchy(i));
+        }
+        PANIC.set(true);
+        let popped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| heap.pop()));
+        assert!(popped.is_err());
+        PANIC.set(false);
+        // The root was freed exactly once, the heap doesn't refer to it any more, and the
+        // other elements were freed rather than leaked (Miri checks both).
+        assert!(heap.is_empty());
+        assert_eq!(heap.len(), 0);
+        heap.push(Touchy(1));
+        assert_eq!(heap.pop().map(|t| t.0), Some(1));
+    }
+
+    #[test]
+    fn test_drop_releases_elements() {
+        let counter = Rc::new(());
+        let mut heap = PairingHeap::new();
+        for i in 0..100 {
+            heap.push((i, Rc::clone(&counter)));
+        }
+        heap.pop();
+        assert_eq!(Rc::strong_count(&counter), 100);
+        heap.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(heap.is_empty());
+
+        let mut long = PairingHeap::new();
+        for i in 0..100_000 {
+            long.push(i);
+        }
+        drop(long);
+    }
+}
@@ -1,9 +1,34 @@
-use std::{marker::PhantomData, ops::Deref, ptr::NonNull};
+use alloc::{
+    alloc::{handle_alloc_error, Allocator, Global, Layout},
+    string::String,
+    vec::Vec,
+};
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Deref,
+    ptr::{self, NonNull},
+};
 
-use crate::cell::Cell;
+use crate::{boxed::Box, cell::Cell, manuallydrop::ManuallyDrop};
+
+// `repr(C)` fixes the field order, so the layout of an `RcInner<[T]>` can be worked out
+// before it is allocated (see `Rc::allocate_for_slice`).
+#[repr(C)]
+struct RcInner<T: ?Sized> {
+    refcount: Cell<usize>,
+    // Number of `Weak`s, plus one shared by all the `Rc`s while any of them is alive.
+    // The allocation is freed once this reaches zero.
+    weak: Cell<usize>,
+    // Dropped by hand when the last `Rc` goes away, possibly long before the allocation
+    // itself is freed by the last `Weak`.
+    value: ManuallyDrop<T>,
+}
 
 /// Single threaded reference counting pointers. `Rc` stands for Reference Counted.
-/// The Type Rc<T> provides shared ownership of a value of type `T` allocated in the heap
+/// The Type `Rc<T>` provides shared ownership of a value of type `T` allocated in the heap
 /// Invoking `Clone` on `Rc` produces a new pointer to the same allocation in the heap
 /// When the last `Rc` pointer to a given allocation is destroyed, the value stored in that allocation is also droped
 ///
@@ -15,25 +40,33 @@ use crate::cell::Cell;
 /// between threads and consequently `Rc` does not implement `Send`. As a result the Rust compiler will check
 /// at compile time that you are not sending `Rc`s between threads. If you need multi-threaded atomic
 /// reference counting use sync::Arc
-
-struct RcInner<T: ?Sized> {
-    refcount: Cell<usize>,
-    value: T,
-}
-
 pub struct Rc<T: ?Sized> {
     inner: NonNull<RcInner<T>>,
     _marker: PhantomData<RcInner<T>>, // PhantomData tells the compiler that when we drop Rc, check the Inner T if is dropped.
 }
 
-impl<T> !Sync for Rc<T> {}
-impl<T> !Send for Rc<T> {}
+impl<T: ?Sized> !Sync for Rc<T> {}
+impl<T: ?Sized> !Send for Rc<T> {}
+
+/// `Weak` is a version of `Rc` that holds a non-owning reference to the managed allocation.
+/// The allocation is accessed by calling `upgrade`, which returns `None` once the value has
+/// been dropped.
+///
+/// A `Weak` does not keep the value alive, only the allocation. This makes it the tool for
+/// breaking reference cycles, e.g. the `prev` pointers of a doubly linked list built out of `Rc`s.
+pub struct Weak<T: ?Sized> {
+    inner: NonNull<RcInner<T>>,
+}
+
+impl<T: ?Sized> !Sync for Weak<T> {}
+impl<T: ?Sized> !Send for Weak<T> {}
 
 impl<T> Rc<T> {
     pub fn new(v: T) -> Self {
         let inner = Box::new(RcInner {
-            value: v,
+            value: ManuallyDrop::new(v),
             refcount: Cell::new(1),
+            weak: Cell::new(1),
         });
         Rc {
             // SAFETY: Box does not give us a Null pointer.
@@ -41,11 +74,152 @@ impl<T> Rc<T> {
             _marker: PhantomData,
         }
     }
+
+    // Returns the inner value if `this` is the only strong reference,
+    // otherwise gives `this` back.
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        if Rc::strong_count(&this) != 1 {
+            return Err(this);
+        }
+        let this = ManuallyDrop::new(this);
+        let ptr = this.inner.as_ptr();
+        unsafe {
+            (*ptr).refcount.set(0);
+            // SAFETY: we were the last `Rc`, so nobody else can reach the value and the
+            // refcount of 0 stops `Weak::upgrade` from handing it out again.
+            let value = ManuallyDrop::take(&mut (*ptr).value);
+            release_weak(this.inner);
+            Ok(value)
+        }
+    }
+}
+
+impl<T> Rc<[T]> {
+    // Allocates an `RcInner<[T]>` with room for `len` elements and both counts set to one.
+    // The elements are left uninitialized for the caller to write.
+    fn allocate_for_slice(len: usize) -> NonNull<RcInner<[T]>> {
+        let layout = Layout::new::<RcInner<()>>()
+            .extend(Layout::array::<T>(len).expect("slice too large for an Rc"))
+            .expect("slice too large for an Rc")
+            .0
+            .pad_to_align();
+        let memory = Global
+            .allocate(layout)
+            .unwrap_or_else(|_| handle_alloc_error(layout));
+        // A slice pointer with the right length gives the `RcInner<[T]>` pointer its
+        // metadata; the address is the start of the allocation.
+        let inner =
+            ptr::slice_from_raw_parts_mut(memory.as_ptr().cast::<T>(), len) as *mut RcInner<[T]>;
+        // SAFETY: the allocation has the layout of an `RcInner<[T]>` of `len` elements,
+        // since `repr(C)` lays the counts out first and the slice after them, just like
+        // the `RcInner<()>` extended by the array above.
+        unsafe {
+            (&raw mut (*inner).refcount).write(Cell::new(1));
+            (&raw mut (*inner).weak).write(Cell::new(1));
+            NonNull::new_unchecked(inner)
+        }
+    }
+
+    // SAFETY: the elements of `inner` must have been initialized.
+    unsafe fn from_inner(inner: NonNull<RcInner<[T]>>) -> Self {
+        Rc {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Copy> From<&[T]> for Rc<[T]> {
+    fn from(slice: &[T]) -> Self {
+        let inner = Rc::allocate_for_slice(slice.len());
+        // SAFETY: the new allocation has room for exactly `slice.len()` elements, and
+        // `T: Copy` means copying the bits is all it takes to duplicate them.
+        unsafe {
+            let elements = (&raw mut (*inner.as_ptr()).value).cast::<T>();
+            ptr::copy_nonoverlapping(slice.as_ptr(), elements, slice.len());
+            Rc::from_inner(inner)
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Rc<[T]> {
+    fn from(mut vec: Vec<T>) -> Self {
+        let inner = Rc::allocate_for_slice(vec.len());
+        // SAFETY: the elements are moved into the new allocation, and setting the vec's
+        // length to zero keeps it from dropping them again; it only frees its buffer.
+        unsafe {
+            let elements = (&raw mut (*inner.as_ptr()).value).cast::<T>();
+            ptr::copy_nonoverlapping(vec.as_ptr(), elements, vec.len());
+            vec.set_len(0);
+            Rc::from_inner(inner)
+        }
+    }
+}
+
+impl From<&str> for Rc<str> {
+    fn from(s: &str) -> Self {
+        let bytes = ManuallyDrop::new(Rc::<[u8]>::from(s.as_bytes()));
+        Rc {
+            // SAFETY: `str` has the same layout and metadata as `[u8]`, and the bytes
+            // were copied from a `str`, so they are valid UTF-8. The count owned by
+            // `bytes` moves to the new `Rc`.
+            inner: unsafe { NonNull::new_unchecked(bytes.inner.as_ptr() as *mut RcInner<str>) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl From<String> for Rc<str> {
+    fn from(s: String) -> Self {
+        Rc::from(s.as_str())
+    }
+}
+
+impl<T: ?Sized> Rc<T> {
+    fn inner(&self) -> &RcInner<T> {
+        // SAFETY: the allocation lives at least as long as any Rc pointing to it.
+        unsafe { self.inner.as_ref() }
+    }
+
+    // Creates a new `Weak` pointer to this allocation.
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let inner = this.inner();
+        inner.weak.set(inner.weak.get() + 1);
+        Weak { inner: this.inner }
+    }
+
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner().refcount.get()
+    }
+
+    pub fn weak_count(this: &Self) -> usize {
+        // Leave out the weak reference shared by the strong pointers.
+        this.inner().weak.get() - 1
+    }
+
+    // Returns true if both `Rc`s point to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        core::ptr::addr_eq(this.inner.as_ptr(), other.inner.as_ptr())
+    }
+}
+
+// Drops one weak reference and frees the allocation if it was the last one.
+//
+// SAFETY: `inner` must be a live allocation and the caller must own one of its weak references.
+unsafe fn release_weak<T: ?Sized>(inner: NonNull<RcInner<T>>) {
+    let weak = unsafe { &(*inner.as_ptr()).weak };
+    let w = weak.get() - 1;
+    weak.set(w);
+    if w == 0 {
+        // SAFETY: no Rc or Weak is left. The value was already dropped (or moved out),
+        // and `ManuallyDrop` keeps the Box from dropping it a second time.
+        drop(unsafe { Box::from_raw(inner.as_ptr()) });
+    }
 }
 
-impl<T> Clone for Rc<T> {
+impl<T: ?Sized> Clone for Rc<T> {
     fn clone(&self) -> Self {
-        let inner = unsafe { self.inner.as_ref() };
+        let inner = self.inner();
         inner.refcount.set(inner.refcount.get() + 1);
         Rc {
             inner: self.inner,
@@ -54,28 +228,96 @@ impl<T> Clone for Rc<T> {
     }
 }
 
-impl<T> Deref for Rc<T> {
+impl<T: ?Sized> Deref for Rc<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         // SAFETY: self.inner is a Box that is only deallocated when the last Rc goes away
         // we have an Rc, therefore the Box has not been deallocated, so deref is fine.
-        &unsafe { self.inner.as_ref() }.value
+        &self.inner().value
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for Rc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for Rc<T> {}
+
+impl<T: ?Sized + Hash> Hash for Rc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: ?Sized> Borrow<T> for Rc<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Rc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for Rc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
     }
 }
 
 impl<T: ?Sized> Drop for Rc<T> {
     fn drop(&mut self) {
-        let inner = unsafe { self.inner.as_ref() };
+        let inner = self.inner();
         let c = inner.refcount.get();
+        inner.refcount.set(c - 1);
         if c == 1 {
-            drop(inner);
             // SAFETY: we are the only reference left, and we are being dropped.
             // therefore, after us, there will be no Rc's and no reference to T.
-            let _ = unsafe { Box::from_raw(self.inner.as_ptr()) };
-        } else {
-            // there are other Rc's so don't drop the Box!.
-            inner.refcount.set(c - 1);
+            unsafe {
+                ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value);
+                // The strong pointers are gone, so give up the weak reference they shared.
+                release_weak(self.inner);
+            }
+        }
+        // otherwise there are other Rc's so don't drop the value!.
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    // Attempts to get an `Rc` to the value, returning `None` if it has already been dropped.
+    pub fn upgrade(&self) -> Option<Rc<T>> {
+        let inner = unsafe { self.inner.as_ref() };
+        let c = inner.refcount.get();
+        if c == 0 {
+            return None;
         }
+        inner.refcount.set(c + 1);
+        Some(Rc {
+            inner: self.inner,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn strong_count(&self) -> usize {
+        unsafe { self.inner.as_ref() }.refcount.get()
+    }
+}
+
+impl<T: ?Sized> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        let inner = unsafe { self.inner.as_ref() };
+        inner.weak.set(inner.weak.get() + 1);
+        Weak { inner: self.inner }
+    }
+}
+
+impl<T: ?Sized> Drop for Weak<T> {
+    fn drop(&mut self) {
+        unsafe { release_weak(self.inner) }
     }
 }
 
@@ -95,6 +337,7 @@ impl<T: ?Sized> Drop for Rc<T> {
 mod tests {
 
     use super::*;
+    use std::{string::String, vec};
 
     #[test]
     fn test_rc_new() {
@@ -140,4 +383,98 @@ mod tests {
         }
         assert!(dropped.get());
     }
+
+    #[test]
+    fn test_rc_counts() {
+        let rc1 = Rc::new(5);
+        let rc2 = rc1.clone();
+        let weak = Rc::downgrade(&rc1);
+        assert_eq!(Rc::strong_count(&rc1), 2);
+        assert_eq!(Rc::weak_count(&rc1), 1);
+        assert!(Rc::ptr_eq(&rc1, &rc2));
+        assert!(!Rc::ptr_eq(&rc1, &Rc::new(5)));
+        drop(rc2);
+        drop(weak);
+        assert_eq!(Rc::strong_count(&rc1), 1);
+        assert_eq!(Rc::weak_count(&rc1), 0);
+    }
+
+    #[test]
+    fn test_weak_upgrade() {
+        let rc = Rc::new(String::from("hello"));
+        let weak = Rc::downgrade(&rc);
+        let weak2 = weak.clone();
+        assert_eq!(weak.upgrade().as_deref().map(String::as_str), Some("hello"));
+        drop(rc);
+        assert_eq!(weak.strong_count(), 0);
+        assert!(weak.upgrade().is_none());
+        assert!(weak2.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_weak_does_not_keep_value_alive() {
+        let dropped = Rc::new(Cell::new(false));
+        struct SetOnDrop(Rc<Cell<bool>>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let rc = Rc::new(SetOnDrop(dropped.clone()));
+        let weak = Rc::downgrade(&rc);
+        drop(rc);
+        // The value is dropped right away even though the allocation is kept for `weak`.
+        assert!(dropped.get());
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_try_unwrap() {
+        let rc = Rc::new(String::from("only"));
+        let weak = Rc::downgrade(&rc);
+        let other = rc.clone();
+        let rc = Rc::try_unwrap(rc).unwrap_err();
+        drop(other);
+        assert_eq!(Rc::try_unwrap(rc).ok(), Some(String::from("only")));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_rc_str() {
+        let hello = Rc::<str>::from("hello");
+        let weak = Rc::downgrade(&hello);
+        let other = hello.clone();
+        assert_eq!(&*hello, "hello");
+        assert_eq!(hello, Rc::from(String::from("hello")));
+        assert_eq!(std::format!("{hello}/{other:?}"), "hello/\"hello\"");
+        drop(hello);
+        drop(other);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(&*Rc::<str>::from(""), "");
+    }
+
+    #[test]
+    fn test_rc_slice() {
+        let copied = Rc::<[u16]>::from(&[1, 2, 3][..]);
+        assert_eq!(*copied, [1, 2, 3]);
+
+        let dropped = Rc::new(Cell::new(0));
+        struct CountDrop(Rc<Cell<usize>>);
+        impl Drop for CountDrop {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let moved =
+            Rc::<[CountDrop]>::from(vec![CountDrop(dropped.clone()), CountDrop(dropped.clone())]);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(dropped.get(), 0);
+        drop(moved);
+        assert_eq!(dropped.get(), 2);
+
+        // The header is padded up to the alignment of the elements.
+        let wide = Rc::<[u128]>::from(vec![u128::MAX; 3]);
+        assert_eq!(*wide, [u128::MAX; 3]);
+    }
 }
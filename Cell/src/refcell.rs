@@ -1,6 +1,6 @@
-use std::fmt::Error;
-use std::marker::PhantomData;
-use std::ptr::NonNull;
+use core::fmt::Error;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
 
 use crate::cell::Cell;
 use crate::reference::{BorrowRef, BorrowRefMut, Ref, RefMut};
@@ -9,16 +9,6 @@ use crate::unsafecell::UnsafeCell;
 type BorrowFlag = isize;
 const UNUSED: BorrowFlag = 0;
 
-#[inline(always)]
-fn is_reading(x: BorrowFlag) -> bool {
-    x > UNUSED
-}
-
-#[inline(always)]
-fn is_writing(x: BorrowFlag) -> bool {
-    x < UNUSED
-}
-
 /*
 RefCell uses Rust's lifetimes to implement "dynamic borrowing" a process whereby one can claim temporary,
 exclusive, mutable access to the inner value. Borrows for RefCell are tracked at runtime, unlike Rust's native reference types
@@ -55,11 +45,11 @@ impl<T> RefCell<T> {
     // without deinitalizing either one.
 
     pub fn replace(&self, t: T) -> T {
-        std::mem::replace(&mut *self.borrow_mut(), t)
+        core::mem::replace(&mut *self.borrow_mut(), t)
     }
 
     pub fn swap(&self, other: &Self) {
-        std::mem::swap(&mut *self.borrow_mut(), &mut *other.borrow_mut());
+        core::mem::swap(&mut *self.borrow_mut(), &mut *other.borrow_mut());
     }
 }
 
@@ -101,6 +91,12 @@ impl<T: ?Sized> RefCell<T> {
             Err(_) => panic!("RefCell<T> already borrowed"),
         }
     }
+
+    // Returns a mutable reference to the wrapped value. No borrow flag is needed:
+    // `&mut self` already proves that no `Ref` or `RefMut` is alive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -112,6 +108,13 @@ mod tests {
         assert_eq!(*cell.borrow(), 5);
     }
 
+    #[test]
+    fn test_get_mut() {
+        let mut cell = RefCell::new(5);
+        *cell.get_mut() += 1;
+        assert_eq!(*cell.borrow(), 6);
+    }
+
     #[test]
     fn test_replace() {
         let cell = RefCell::new(5);
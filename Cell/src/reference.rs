@@ -1,7 +1,5 @@
 use crate::cell::Cell;
-use core::borrow;
-use std::{
-    borrow::Borrow,
+use core::{
     marker::PhantomData,
     ops::{Deref, DerefMut},
     ptr::NonNull,
@@ -32,6 +30,20 @@ impl<T: ?Sized> Deref for Ref<'_, T> {
     }
 }
 
+impl<'b, T: ?Sized> Ref<'b, T> {
+    // Makes a new `Ref` for a component of the borrowed data, e.g. a field of a struct.
+    // The `RefCell` stays immutably borrowed until the returned `Ref` is dropped.
+    pub fn map<U: ?Sized, F>(orig: Ref<'b, T>, f: F) -> Ref<'b, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        Ref {
+            value: NonNull::from(f(&*orig)),
+            borrow: orig.borrow,
+        }
+    }
+}
+
 pub struct BorrowRefMut<'b> {
     borrow: &'b Cell<BorrowFlag>,
 }
@@ -57,6 +69,21 @@ impl<T: ?Sized> DerefMut for RefMut<'_, T> {
     }
 }
 
+impl<'b, T: ?Sized> RefMut<'b, T> {
+    // Makes a new `RefMut` for a component of the borrowed data.
+    // The `RefCell` stays mutably borrowed until the returned `RefMut` is dropped.
+    pub fn map<U: ?Sized, F>(mut orig: RefMut<'b, T>, f: F) -> RefMut<'b, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        RefMut {
+            value: NonNull::from(f(&mut *orig)),
+            borrow: orig.borrow,
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<'b> BorrowRef<'b> {
     pub fn new(borrow: &'b Cell<BorrowFlag>) -> Option<BorrowRef<'b>> {
         let b = borrow.get().wrapping_add(1);
@@ -134,7 +161,7 @@ mod tests {
     fn test_borrow_ref_clone() {
         let cell = Cell::new(UNUSED);
         let borrow_ref = BorrowRef::new(&cell).unwrap();
-        let borrow_ref_clone = borrow_ref.clone();
+        let _borrow_ref_clone = borrow_ref.clone();
         assert_eq!(cell.get(), 2);
     }
 
@@ -142,7 +169,7 @@ mod tests {
     fn test_borrow_ref_drop() {
         let cell = Cell::new(UNUSED);
         {
-            let borrow_ref = BorrowRef::new(&cell).unwrap();
+            let _borrow_ref = BorrowRef::new(&cell).unwrap();
             assert_eq!(cell.get(), 1);
         }
         assert_eq!(cell.get(), UNUSED);
@@ -160,7 +187,7 @@ mod tests {
     fn test_borrow_ref_mut_drop() {
         let cell = Cell::new(UNUSED);
         {
-            let borrow_ref_mut = BorrowRefMut::new(&cell).unwrap();
+            let _borrow_ref_mut = BorrowRefMut::new(&cell).unwrap();
             assert_eq!(cell.get(), UNUSED - 1);
         }
         assert_eq!(cell.get(), UNUSED);
@@ -192,4 +219,36 @@ mod tests {
         *ref_mut_value = 43;
         assert_eq!(*ref_mut_value, 43);
     }
+
+    #[test]
+    fn test_ref_map() {
+        let pair = (1, 2);
+        let cell = Cell::new(UNUSED);
+        let whole = Ref {
+            value: NonNull::from(&pair),
+            borrow: BorrowRef::new(&cell).unwrap(),
+        };
+        let second = Ref::map(whole, |pair| &pair.1);
+        assert_eq!(*second, 2);
+        assert_eq!(cell.get(), 1);
+        drop(second);
+        assert_eq!(cell.get(), UNUSED);
+    }
+
+    #[test]
+    fn test_ref_mut_map() {
+        let mut pair = (1, 2);
+        let cell = Cell::new(UNUSED);
+        let whole = RefMut {
+            value: NonNull::from(&mut pair),
+            borrow: BorrowRefMut::new(&cell).unwrap(),
+            marker: PhantomData,
+        };
+        let mut first = RefMut::map(whole, |pair| &mut pair.0);
+        *first = 10;
+        assert_eq!(cell.get(), UNUSED - 1);
+        drop(first);
+        assert_eq!(cell.get(), UNUSED);
+        assert_eq!(pair, (10, 2));
+    }
 }
@@ -0,0 +1,316 @@
+use crate::{
+    linkedlist,
+    rc::{Rc, Weak},
+    refcell::RefCell,
+    reference::{Ref, RefMut},
+};
+
+/// A doubly linked list built only from the crate's own `Rc`, `Weak` and `RefCell`.
+///
+/// It offers the same API as [`linkedlist::LinkedList`] without a single `unsafe` block:
+/// `next` pointers are strong `Rc`s, `prev` pointers are `Weak`s so the two directions don't
+/// form reference cycles, and every node sits in a `RefCell` so neighbours can relink it.
+/// Element accessors hand out `Ref`/`RefMut` guards instead of plain references.
+///
+/// The price is an extra allocation header and runtime borrow checks per node, which makes
+/// this list slower than the pointer-based one. The two can be converted into each other
+/// with `From`, moving the elements without cloning them.
+pub struct LinkedList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+
+struct Node<T> {
+    element: T,
+    next: Link<T>,
+    prev: Option<Weak<RefCell<Node<T>>>>,
+}
+
+impl<T> Node<T> {
+    fn new(element: T) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Node {
+            element,
+            next: None,
+            prev: None,
+        }))
+    }
+
+    // Takes the element out of a node that is no longer linked anywhere.
+    fn into_element(node: Rc<RefCell<Self>>) -> T {
+        match Rc::try_unwrap(node) {
+            Ok(node) => node.into_inner().element,
+            Err(_) => unreachable!("an unlinked node has no other strong references"),
+        }
+    }
+}
+
+impl<T> LinkedList<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    pub fn front(&self) -> Option<Ref<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.element))
+    }
+
+    pub fn front_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.element))
+    }
+
+    pub fn back(&self) -> Option<Ref<'_, T>> {
+        self.tail
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.element))
+    }
+
+    pub fn back_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.tail
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.element))
+    }
+
+    pub fn push_front(&mut self, ele: T) {
+        let node = Node::new(ele);
+        match self.head.take() {
+            None => self.tail = Some(Rc::clone(&node)),
+            Some(old_head) => {
+                old_head.borrow_mut().prev = Some(Rc::downgrade(&node));
+                node.borrow_mut().next = Some(old_head);
+            }
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.take().map(|old_head| {
+            let next = old_head.borrow_mut().next.take();
+            match next {
+                None => self.tail = None,
+                Some(new_head) => {
+                    new_head.borrow_mut().prev = None;
+                    self.head = Some(new_head);
+                }
+            }
+            self.len -= 1;
+            Node::into_element(old_head)
+        })
+    }
+
+    pub fn push_back(&mut self, ele: T) {
+        let node = Node::new(ele);
+        match self.tail.take() {
+            None => self.head = Some(Rc::clone(&node)),
+            Some(old_tail) => {
+                node.borrow_mut().prev = Some(Rc::downgrade(&old_tail));
+                old_tail.borrow_mut().next = Some(Rc::clone(&node));
+            }
+        }
+        self.tail = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.tail.take().map(|old_tail| {
+            let prev = old_tail.borrow_mut().prev.take().and_then(|p| p.upgrade());
+            match prev {
+                None => self.head = None,
+                Some(new_tail) => {
+                    // Drops the last strong reference to `old_tail` besides ours.
+                    new_tail.borrow_mut().next = None;
+                    self.tail = Some(new_tail);
+                }
+            }
+            self.len -= 1;
+            Node::into_element(old_tail)
+        })
+    }
+
+    // Moves all elements from `other` to the end of the list, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        let Some(other_head) = other.head.take() else {
+            return;
+        };
+        match self.tail.take() {
+            None => self.head = Some(other_head),
+            Some(tail) => {
+                other_head.borrow_mut().prev = Some(Rc::downgrade(&tail));
+                tail.borrow_mut().next = Some(other_head);
+            }
+        }
+        self.tail = other.tail.take();
+        self.len += core::mem::take(&mut other.len);
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // Dropping the head would otherwise drop the whole `next` chain recursively.
+        self.clear();
+    }
+}
+
+impl<T> From<linkedlist::LinkedList<T>> for LinkedList<T> {
+    fn from(mut list: linkedlist::LinkedList<T>) -> Self {
+        let mut safe = LinkedList::new();
+        while let Some(ele) = list.pop_front() {
+            safe.push_back(ele);
+        }
+        safe
+    }
+}
+
+impl<T> From<LinkedList<T>> for linkedlist::LinkedList<T> {
+    fn from(mut safe: LinkedList<T>) -> Self {
+        let mut list = linkedlist::LinkedList::new();
+        while let Some(ele) = safe.pop_front() {
+            list.push_back(ele);
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    fn to_vec<T: Clone>(list: &LinkedList<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut node = list.head.clone();
+        while let Some(current) = node {
+            out.push(current.borrow().element.clone());
+            node = current.borrow().next.clone();
+        }
+        out
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut list = LinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(to_vec(&list), [1, 2, 3]);
+        assert_eq!(*list.front().unwrap(), 1);
+        assert_eq!(*list.back().unwrap(), 3);
+
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+        assert!(list.front().is_none());
+    }
+
+    #[test]
+    fn test_front_back_mut() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+        *list.front_mut().unwrap() += 10;
+        *list.back_mut().unwrap() += 20;
+        assert_eq!(to_vec(&list), [11, 22]);
+    }
+
+    #[test]
+    fn test_prev_links_are_weak() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        let head = list.head.clone().unwrap();
+        // One from the list, one from us: the next node only points back weakly.
+        assert_eq!(Rc::strong_count(&head), 2);
+        assert_eq!(Rc::weak_count(&head), 1);
+        drop(head);
+
+        // Walk backwards through the weak links.
+        let mut out = Vec::new();
+        let mut node = list.tail.clone();
+        while let Some(current) = node {
+            out.push(current.borrow().element);
+            node = current.borrow().prev.as_ref().and_then(|p| p.upgrade());
+        }
+        assert_eq!(out, [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut a = LinkedList::new();
+        let mut b = LinkedList::new();
+        a.push_back(1);
+        b.push_back(2);
+        b.push_back(3);
+        a.append(&mut b);
+        assert!(b.is_empty());
+        assert_eq!(a.len(), 3);
+        assert_eq!(to_vec(&a), [1, 2, 3]);
+        assert_eq!(a.pop_back(), Some(3));
+        assert_eq!(a.pop_back(), Some(2));
+
+        let mut empty = LinkedList::new();
+        empty.append(&mut a);
+        assert_eq!(to_vec(&empty), [1]);
+    }
+
+    #[test]
+    fn test_drop_releases_elements() {
+        let counter = Rc::new(());
+        {
+            let mut list = LinkedList::new();
+            for _ in 0..3 {
+                list.push_back(Rc::clone(&counter));
+            }
+            assert_eq!(Rc::strong_count(&counter), 4);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let mut long = LinkedList::new();
+        for i in 0..100_000 {
+            long.push_back(i);
+        }
+        drop(long);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let unsafe_list = linkedlist::LinkedList::from(vec![1, 2, 3]);
+        let safe = LinkedList::from(unsafe_list);
+        assert_eq!(to_vec(&safe), [1, 2, 3]);
+
+        let back = linkedlist::LinkedList::from(safe);
+        assert_eq!(Vec::from(back), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::sys::hint;
+
+/// Exponential backoff for spin loops.
+///
+/// Two kinds of loop need it. A compare-and-swap that failed because another thread changed
+/// the value just before should retry soon, but not all at once with every other thread
+/// that failed: [`spin`](Backoff::spin) waits twice as long each time. A thread waiting for
+/// another one to finish something should wait longer and longer, and eventually give its
+/// time slice away: [`snooze`](Backoff::snooze) spins at first and then yields to the OS
+/// scheduler (with the `std` feature; without it, it keeps spinning).
+///
+/// Once [`is_completed`](Backoff::is_completed) returns true, further snoozing is unlikely to
+/// help, and a blocking primitive should go to sleep instead.
+///
+/// ```
+/// use Cell::sync::Backoff;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// fn fetch_mul(a: &AtomicUsize, b: usize) -> usize {
+///     let mut backoff = Backoff::new();
+///     let mut current = a.load(Ordering::Relaxed);
+///     loop {
+///         match a.compare_exchange_weak(current, current * b, Ordering::AcqRel, Ordering::Relaxed) {
+///             Ok(previous) => return previous,
+///             Err(actual) => current = actual,
+///         }
+///         backoff.spin();
+///     }
+/// }
+///
+/// let a = AtomicUsize::new(6);
+/// assert_eq!(fetch_mul(&a, 7), 6);
+/// assert_eq!(a.load(Ordering::Relaxed), 42);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    step: u32,
+    spin_limit: u32,
+    yield_limit: u32,
+}
+
+impl Backoff {
+    /// The default for how many times the wait doubles: up to 2^6 spin hints per call.
+    pub const SPIN_LIMIT: u32 = 6;
+    /// The default for how many steps there are in total, yields included.
+    pub const YIELD_LIMIT: u32 = 10;
+
+    pub const fn new() -> Self {
+        Self::with_limits(Self::SPIN_LIMIT, Self::YIELD_LIMIT)
+    }
+
+    // A call spins at most 2^`spin_limit` times, and `is_completed` turns true after
+    // `yield_limit` snoozes. `yield_limit` is raised to `spin_limit` if it is lower.
+    pub const fn with_limits(spin_limit: u32, yield_limit: u32) -> Self {
+        Self {
+            step: 0,
+            spin_limit,
+            yield_limit: if yield_limit < spin_limit {
+                spin_limit
+            } else {
+                yield_limit
+            },
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+
+    /// Backs off after a failed compare-and-swap: spins for a number of iterations that
+    /// doubles with every call, up to 2^`spin_limit`. Never yields.
+    pub fn spin(&mut self) {
+        for _ in 0..1u64 << self.step.min(self.spin_limit) {
+            hint::spin_loop();
+        }
+        if self.step <= self.spin_limit {
+            self.step += 1;
+        }
+    }
+
+    /// Backs off while waiting for another thread: spins like [`spin`](Backoff::spin) for
+    /// the first steps, and yields the thread once the spinning limit is reached.
+    pub fn snooze(&mut self) {
+        if self.step <= self.spin_limit {
+            for _ in 0..1u64 << self.step {
+                hint::spin_loop();
+            }
+        } else {
+            #[cfg(feature = "std")]
+            crate::sys::thread::yield_now();
+            #[cfg(not(feature = "std"))]
+            for _ in 0..1u64 << self.spin_limit {
+                hint::spin_loop();
+            }
+        }
+        if self.step <= self.yield_limit {
+            self.step += 1;
+        }
+    }
+
+    // True once snoozing has run its course; a caller that can block should do so now.
+    pub fn is_completed(&self) -> bool {
+        self.step > self.yield_limit
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    };
+
+    #[test]
+    fn test_snooze_completes() {
+        let mut backoff = Backoff::with_limits(2, 5);
+        let mut snoozes = 0;
+        while !backoff.is_completed() {
+            backoff.snooze();
+            snoozes += 1;
+        }
+        assert_eq!(snoozes, 6);
+        backoff.reset();
+        assert!(!backoff.is_completed());
+    }
+
+    #[test]
+    fn test_spin_never_completes() {
+        let mut backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.spin();
+        }
+        assert!(!backoff.is_completed());
+        // A yield limit below the spin limit is raised to it.
+        assert_eq!(Backoff::with_limits(4, 1).yield_limit, 4);
+    }
+
+    #[test]
+    fn test_wait_for_flag() {
+        let flag = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut backoff = Backoff::new();
+                while !flag.load(Ordering::Acquire) {
+                    backoff.snooze();
+                }
+            });
+            flag.store(true, Ordering::Release);
+        });
+    }
+}
@@ -0,0 +1,287 @@
+// Bookkeeping for finding deadlocks among the crate's `Mutex` and `RwLock`.
+//
+// The locks call these hooks with the address of their state word, which identifies the
+// lock. With the `deadlock_detection` feature, the hooks record which threads hold which
+// locks and which lock every blocked thread is waiting for; `check_deadlocks` turns that
+// into a wait-for graph and looks for cycles. Without the feature they do nothing and
+// compile away.
+
+#[cfg(feature = "deadlock_detection")]
+pub use imp::{check_deadlocks, DeadlockedThread};
+
+// Records that the current thread took the lock. A thread holding several read locks on
+// the same `RwLock` is recorded once per lock.
+#[inline]
+pub(super) fn acquire<S>(state: &S) {
+    #[cfg(feature = "deadlock_detection")]
+    imp::acquire(key(state));
+    #[cfg(not(feature = "deadlock_detection"))]
+    let _ = state;
+}
+
+// Records that the current thread released the lock.
+#[inline]
+pub(super) fn release<S>(state: &S) {
+    #[cfg(feature = "deadlock_detection")]
+    imp::release(key(state));
+    #[cfg(not(feature = "deadlock_detection"))]
+    let _ = state;
+}
+
+// Marks the current thread as blocked on a lock until the returned value is dropped. Locks
+// call this right before they first go to sleep, so that spinning briefly doesn't pay for
+// capturing a backtrace.
+#[must_use]
+pub(super) struct Waiting(());
+
+impl Waiting {
+    #[inline]
+    pub(super) fn start<S>(state: &S) -> Self {
+        #[cfg(feature = "deadlock_detection")]
+        imp::wait_start(key(state));
+        #[cfg(not(feature = "deadlock_detection"))]
+        let _ = state;
+        Waiting(())
+    }
+}
+
+#[cfg(feature = "deadlock_detection")]
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        imp::wait_end();
+    }
+}
+
+#[cfg(feature = "deadlock_detection")]
+fn key<S>(state: &S) -> usize {
+    state as *const S as usize
+}
+
+#[cfg(feature = "deadlock_detection")]
+mod imp {
+    use std::{
+        backtrace::Backtrace,
+        collections::{HashMap, HashSet},
+        sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+        thread::{self, Thread, ThreadId},
+        vec::Vec,
+    };
+
+    /// A thread that is part of a deadlock found by [`check_deadlocks`].
+    #[derive(Clone, Debug)]
+    pub struct DeadlockedThread {
+        thread: Thread,
+        backtrace: Arc<Backtrace>,
+    }
+
+    impl DeadlockedThread {
+        pub fn thread_id(&self) -> ThreadId {
+            self.thread.id()
+        }
+
+        pub fn name(&self) -> Option<&str> {
+            self.thread.name()
+        }
+
+        // Where the thread was when it went to sleep waiting for the lock.
+        pub fn backtrace(&self) -> &Backtrace {
+            &self.backtrace
+        }
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        // Lock -> the threads holding it, once per guard.
+        holders: HashMap<usize, Vec<ThreadId>>,
+        // Blocked thread -> the lock it waits for.
+        waiting: HashMap<ThreadId, (usize, DeadlockedThread)>,
+    }
+
+    // A std mutex, so that recording a lock doesn't go through the locks being recorded.
+    static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Default::default);
+
+    fn registry() -> MutexGuard<'static, Registry> {
+        // The bookkeeping is updated in single steps, so it stays valid after a panic.
+        REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn acquire(key: usize) {
+        let id = thread::current().id();
+        registry().holders.entry(key).or_default().push(id);
+    }
+
+    pub fn release(key: usize) {
+        let id = thread::current().id();
+        let mut registry = registry();
+        if let Some(holders) = registry.holders.get_mut(&key) {
+            if let Some(i) = holders.iter().position(|&holder| holder == id) {
+                holders.swap_remove(i);
+            }
+            if holders.is_empty() {
+                registry.holders.remove(&key);
+            }
+        }
+    }
+
+    pub fn wait_start(key: usize) {
+        let thread = thread::current();
+        // Captured before taking the registry lock; it is by far the slowest part.
+        let backtrace = Arc::new(Backtrace::force_capture());
+        registry()
+            .waiting
+            .insert(thread.id(), (key, DeadlockedThread { thread, backtrace }));
+    }
+
+    pub fn wait_end() {
+        let id = thread::current().id();
+        registry().waiting.remove(&id);
+    }
+
+    /// Looks for threads that are deadlocked on the crate's [`Mutex`](super::super::Mutex)
+    /// and [`RwLock`](super::super::RwLock), and returns one list of threads per cycle
+    /// found. Every thread in a cycle is blocked on a lock held by the next one, and the
+    /// last by the first.
+    ///
+    /// Meant for debugging: call it from a watchdog thread when a test hangs, and print the
+    /// backtraces. Needs the `deadlock_detection` feature, which makes every lock
+    /// operation record itself in a global table.
+    pub fn check_deadlocks() -> Vec<Vec<DeadlockedThread>> {
+        let registry = registry();
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        for &id in registry.waiting.keys() {
+            let mut path = Vec::new();
+            find_cycles(&registry, id, &mut path, &mut visited, &mut cycles);
+        }
+        cycles
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .iter()
+                    .map(|id| registry.waiting[id].1.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Depth-first search from `id` along "waits for a lock held by" edges. A thread that
+    // is already on the current path closes a cycle.
+    fn find_cycles(
+        registry: &Registry,
+        id: ThreadId,
+        path: &mut Vec<ThreadId>,
+        visited: &mut HashSet<ThreadId>,
+        cycles: &mut Vec<Vec<ThreadId>>,
+    ) {
+        if let Some(start) = path.iter().position(|&on_path| on_path == id) {
+            cycles.push(path[start..].to_vec());
+            return;
+        }
+        if !visited.insert(id) {
+            return;
+        }
+        // Threads that aren't blocked can't be part of a cycle.
+        let Some((key, _)) = registry.waiting.get(&id) else {
+            return;
+        };
+        path.push(id);
+        for &holder in registry.holders.get(key).into_iter().flatten() {
+            find_cycles(registry, holder, path, visited, cycles);
+        }
+        path.pop();
+    }
+}
+
+#[cfg(all(test, feature = "deadlock_detection"))]
+mod tests {
+    use super::*;
+    use crate::sync::{Mutex, RwLock};
+    use std::{
+        sync::{Arc, Barrier},
+        thread::{self, ThreadId},
+        time::Duration,
+        vec::Vec,
+    };
+
+    // Polls until a cycle made of exactly `threads` shows up.
+    fn wait_for_cycle(threads: &[ThreadId]) -> Vec<DeadlockedThread> {
+        loop {
+            let found = check_deadlocks().into_iter().find(|cycle| {
+                cycle.len() == threads.len()
+                    && cycle.iter().all(|t| threads.contains(&t.thread_id()))
+            });
+            if let Some(cycle) = found {
+                return cycle;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_finds_lock_order_inversion() {
+        // The threads stay deadlocked; they are left behind when the test binary exits.
+        let locks = Arc::new((Mutex::new(()), Mutex::new(())));
+        let barrier = Arc::new(Barrier::new(2));
+        let spawn = |first_a: bool| {
+            let (locks, barrier) = (Arc::clone(&locks), Arc::clone(&barrier));
+            thread::Builder::new()
+                .name(if first_a { "a-then-b" } else { "b-then-a" }.into())
+                .spawn(move || {
+                    let (first, second) = if first_a {
+                        (&locks.0, &locks.1)
+                    } else {
+                        (&locks.1, &locks.0)
+                    };
+                    let _first = first.lock().unwrap();
+                    barrier.wait();
+                    let _second = second.lock().unwrap();
+                })
+                .unwrap()
+                .thread()
+                .id()
+        };
+        let threads = [spawn(true), spawn(false)];
+
+        let cycle = wait_for_cycle(&threads);
+        let mut names: Vec<_> = cycle.iter().map(|t| t.name().unwrap()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["a-then-b", "b-then-a"]);
+    }
+
+    #[test]
+    fn test_finds_read_then_write_on_one_thread() {
+        let lock: &'static RwLock<()> = std::boxed::Box::leak(std::boxed::Box::default());
+        let thread = thread::spawn(move || {
+            let _read = lock.read().unwrap();
+            let _write = lock.write().unwrap();
+        });
+        let cycle = wait_for_cycle(&[thread.thread().id()]);
+        assert_eq!(cycle.len(), 1);
+    }
+
+    #[test]
+    fn test_contention_is_not_a_deadlock() {
+        let lock = Mutex::new(0);
+        thread::scope(|s| {
+            let mut threads = Vec::new();
+            for _ in 0..4 {
+                threads.push(
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            *lock.lock().unwrap() += 1;
+                        }
+                    })
+                    .thread()
+                    .id(),
+                );
+            }
+            for _ in 0..20 {
+                assert!(check_deadlocks()
+                    .iter()
+                    .flatten()
+                    .all(|t| !threads.contains(&t.thread_id())));
+            }
+        });
+        assert_eq!(*lock.lock().unwrap(), 4000);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// Wakes up threads or tasks waiting for something to happen.
+///
+/// A waiter first calls `listen`, then checks whatever condition it is waiting for, and only
+/// then blocks on the returned [`EventListener`]. Because the listener is registered before
+/// the check, a notification sent in between is not lost: it is recorded in the listener and
+/// `wait` returns right away. Notifications sent while nobody is listening are dropped.
+///
+/// A listener can be waited on by blocking the thread (`wait`, `wait_timeout`) or awaited as
+/// a future, so the same event can connect threads and async tasks without a `Condvar`.
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use Cell::sync::Event;
+///
+/// let ready = AtomicBool::new(false);
+/// let event = Event::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+///         ready.store(true, Ordering::SeqCst);
+///         event.notify_all();
+///     });
+///     while !ready.load(Ordering::SeqCst) {
+///         let listener = event.listen();
+///         if ready.load(Ordering::SeqCst) {
+///             break;
+///         }
+///         listener.wait();
+///     }
+/// });
+/// ```
+pub struct Event {
+    // Listeners in the order they registered, so `notify_one` is fair.
+    listeners: Mutex<VecDeque<Arc<Waiter>>>,
+}
+
+struct Waiter {
+    state: Mutex<WaiterState>,
+}
+
+#[derive(Default)]
+struct WaiterState {
+    notified: Option<Notified>,
+    wake: Option<Wake>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Notified {
+    One,
+    All,
+}
+
+enum Wake {
+    Thread(Thread),
+    Task(Waker),
+}
+
+impl Waiter {
+    fn notify(&self, how: Notified) {
+        let mut state = self.state.lock().unwrap();
+        state.notified = Some(how);
+        match state.wake.take() {
+            Some(Wake::Thread(thread)) => thread.unpark(),
+            Some(Wake::Task(waker)) => waker.wake(),
+            None => {}
+        }
+    }
+}
+
+impl Event {
+    pub fn new() -> Self {
+        Self {
+            listeners: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // Registers a listener that the next `notify_one` or `notify_all` will wake.
+    pub fn listen(&self) -> EventListener<'_> {
+        let waiter = Arc::new(Waiter {
+            state: Mutex::new(WaiterState::default()),
+        });
+        self.listeners
+            .lock()
+            .unwrap()
+            .push_back(Arc::clone(&waiter));
+        EventListener {
+            event: self,
+            waiter,
+            done: false,
+        }
+    }
+
+    // Wakes the listener that has been registered the longest. Returns whether there was one.
+    pub fn notify_one(&self) -> bool {
+        let waiter = self.listeners.lock().unwrap().pop_front();
+        match waiter {
+            Some(waiter) => {
+                waiter.notify(Notified::One);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Wakes every registered listener and returns how many there were.
+    pub fn notify_all(&self) -> usize {
+        let waiters = core::mem::take(&mut *self.listeners.lock().unwrap());
+        let count = waiters.len();
+        for waiter in waiters {
+            waiter.notify(Notified::All);
+        }
+        count
+    }
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A registration with an [`Event`], created by [`Event::listen`].
+///
+/// If a listener that was picked by `notify_one` is dropped before it is waited on, the
+/// notification is handed on to the next listener instead of being lost.
+pub struct EventListener<'a> {
+    event: &'a Event,
+    waiter: Arc<Waiter>,
+    done: bool,
+}
+
+impl EventListener<'_> {
+    pub fn is_notified(&self) -> bool {
+        self.waiter.state.lock().unwrap().notified.is_some()
+    }
+
+    // Blocks the current thread until the listener is notified.
+    pub fn wait(mut self) {
+        self.wait_until(None);
+    }
+
+    // Like `wait`, but gives up after `timeout`. Returns whether the listener was notified.
+    pub fn wait_timeout(mut self, timeout: Duration) -> bool {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(&mut self, deadline: Option<Instant>) -> bool {
+        loop {
+            {
+                let mut state = self.waiter.state.lock().unwrap();
+                if state.notified.is_some() {
+                    self.done = true;
+                    return true;
+                }
+                state.wake = Some(Wake::Thread(thread::current()));
+            }
+            match deadline {
+                None => thread::park(),
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) => thread::park_timeout(remaining),
+                    None => return false,
+                },
+            }
+        }
+    }
+}
+
+impl Future for EventListener<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.waiter.state.lock().unwrap();
+        if state.notified.is_some() {
+            drop(state);
+            self.done = true;
+            return Poll::Ready(());
+        }
+        state.wake = Some(Wake::Task(cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for EventListener<'_> {
+    fn drop(&mut self) {
+        let mut listeners = self.event.listeners.lock().unwrap();
+        if let Some(i) = listeners.iter().position(|w| Arc::ptr_eq(w, &self.waiter)) {
+            listeners.remove(i);
+            return;
+        }
+        drop(listeners);
+        // Already notified. A `notify_one` that this listener never consumed goes to the
+        // next one in line, otherwise it would be lost.
+        let notified = self.waiter.state.lock().unwrap().notified;
+        if !self.done && notified == Some(Notified::One) {
+            self.event.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        boxed::Box,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_notify_before_wait_is_not_lost() {
+        let event = Event::new();
+        let listener = event.listen();
+        assert!(!listener.is_notified());
+        assert!(event.notify_one());
+        assert!(listener.is_notified());
+        listener.wait();
+        // Nobody is listening any more.
+        assert!(!event.notify_one());
+    }
+
+    #[test]
+    fn test_notify_one_is_fifo() {
+        let event = Event::new();
+        let first = event.listen();
+        let second = event.listen();
+        event.notify_one();
+        assert!(first.is_notified());
+        assert!(!second.is_notified());
+        assert!(!second.wait_timeout(Duration::from_millis(10)));
+        first.wait();
+    }
+
+    #[test]
+    fn test_dropped_listener_passes_notification_on() {
+        let event = Event::new();
+        let first = event.listen();
+        let second = event.listen();
+        event.notify_one();
+        drop(first);
+        assert!(second.is_notified());
+        second.wait();
+
+        // Dropping a listener that was never notified just unregisters it.
+        drop(event.listen());
+        assert_eq!(event.notify_all(), 0);
+    }
+
+    #[test]
+    fn test_notify_all_wakes_threads() {
+        let event = Event::new();
+        let flag = AtomicBool::new(false);
+        let woken = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while !flag.load(Ordering::SeqCst) {
+                        let listener = event.listen();
+                        if flag.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        listener.wait();
+                    }
+                    woken.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            thread::sleep(Duration::from_millis(20));
+            flag.store(true, Ordering::SeqCst);
+            event.notify_all();
+        });
+        assert_eq!(woken.load(Ordering::SeqCst), 4);
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl std::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_listener_as_future() {
+        let event = Event::new();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut listeners: Vec<_> = (0..2).map(|_| Box::pin(event.listen())).collect();
+        assert!(listeners[0].as_mut().poll(&mut cx).is_pending());
+        assert!(listeners[1].as_mut().poll(&mut cx).is_pending());
+        assert_eq!(event.notify_all(), 2);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert!(listeners[0].as_mut().poll(&mut cx).is_ready());
+        assert!(listeners[1].as_mut().poll(&mut cx).is_ready());
+    }
+}
@@ -0,0 +1,209 @@
+//! Waiting on a 32-bit atomic until another thread changes it.
+//!
+//! On Linux these are the `futex` system calls, so a primitive built on them needs nothing
+//! besides its `AtomicU32`: the kernel keeps the queue of waiting threads. Elsewhere they
+//! fall back to the [`parking`](super::parking) table, keyed by the atomic's address, which
+//! behaves the same.
+//!
+//! Wakeups may be spurious, so callers always re-check the atomic in a loop.
+
+use std::time::Duration;
+
+use crate::sys::atomic::AtomicU32;
+
+/// Blocks while `futex` holds `expected`, until [`wake_one`] or [`wake_all`] is called on it
+/// or `timeout` passes. Returns `false` only if it timed out.
+///
+/// If the value is already different, returns right away. The check and going to sleep are
+/// atomic with respect to wakeups, so a wake that follows a change of the value can't be
+/// missed.
+pub fn wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+    imp::wait(futex, expected, timeout)
+}
+
+/// Wakes one thread waiting on `futex`.
+pub fn wake_one(futex: &AtomicU32) {
+    imp::wake(futex, 1);
+}
+
+/// Wakes every thread waiting on `futex`.
+pub fn wake_all(futex: &AtomicU32) {
+    imp::wake(futex, i32::MAX);
+}
+
+#[cfg(all(target_os = "linux", not(loom)))]
+mod imp {
+    use core::{
+        ptr,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+    use std::{io, time::Duration};
+
+    pub fn wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+        // An absolute deadline on the monotonic clock, so that retrying after `EINTR`
+        // doesn't restart the timeout.
+        let deadline = timeout.and_then(|timeout| {
+            let now = monotonic_now();
+            let nanos = now.tv_nsec as u64 + u64::from(timeout.subsec_nanos());
+            let secs = (now.tv_sec as u64)
+                .checked_add(timeout.as_secs())?
+                .checked_add(nanos / 1_000_000_000)?;
+            Some(libc::timespec {
+                tv_sec: libc::time_t::try_from(secs).ok()?,
+                tv_nsec: (nanos % 1_000_000_000) as _,
+            })
+        });
+
+        loop {
+            if futex.load(Ordering::Relaxed) != expected {
+                return true;
+            }
+            // SAFETY: the futex word is a live `AtomicU32`, and the timespec (if any) lives
+            // on this stack frame for the duration of the call.
+            let result = unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    futex.as_ptr(),
+                    libc::FUTEX_WAIT_BITSET | libc::FUTEX_PRIVATE_FLAG,
+                    expected,
+                    deadline.as_ref().map_or(ptr::null(), |d| d as *const _),
+                    ptr::null::<u32>(),
+                    !0u32,
+                )
+            };
+            if result >= 0 {
+                return true;
+            }
+            match io::Error::last_os_error().raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::ETIMEDOUT) => return false,
+                // EAGAIN: the value had already changed.
+                _ => return true,
+            }
+        }
+    }
+
+    pub fn wake(futex: &AtomicU32, count: i32) {
+        // SAFETY: waking takes no pointers besides the futex word.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                futex.as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                count,
+            );
+        }
+    }
+
+    fn monotonic_now() -> libc::timespec {
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `now` is a valid timespec to write into.
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+        now
+    }
+}
+
+#[cfg(not(any(target_os = "linux", loom)))]
+mod imp {
+    use super::super::parking::{self, ParkResult};
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, Instant};
+
+    pub fn wait(futex: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        let validate = || futex.load(Ordering::Relaxed) == expected;
+        parking::park(key(futex), validate, deadline) != ParkResult::TimedOut
+    }
+
+    pub fn wake(futex: &AtomicU32, count: i32) {
+        if count == 1 {
+            parking::unpark_one(key(futex), |_| {});
+        } else {
+            parking::unpark_all(key(futex));
+        }
+    }
+
+    fn key(futex: &AtomicU32) -> usize {
+        futex as *const AtomicU32 as usize
+    }
+}
+
+// Loom can't block a thread on a value, but yielding tells it this thread can't make
+// progress until another one runs. That is a spurious wakeup as far as callers know.
+#[cfg(loom)]
+mod imp {
+    use crate::sys::{
+        atomic::{AtomicU32, Ordering},
+        thread,
+    };
+    use std::time::Duration;
+
+    pub fn wait(futex: &AtomicU32, expected: u32, _timeout: Option<Duration>) -> bool {
+        if futex.load(Ordering::Relaxed) == expected {
+            thread::yield_now();
+        }
+        true
+    }
+
+    pub fn wake(_futex: &AtomicU32, _count: i32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::Ordering, thread, time::Instant};
+
+    #[test]
+    fn test_wait_returns_if_value_differs() {
+        let futex = AtomicU32::new(1);
+        assert!(wait(&futex, 0, None));
+    }
+
+    #[test]
+    fn test_wait_times_out() {
+        let futex = AtomicU32::new(0);
+        let start = Instant::now();
+        assert!(!wait(&futex, 0, Some(Duration::from_millis(20))));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_wake_all() {
+        let futex = AtomicU32::new(0);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while futex.load(Ordering::Acquire) == 0 {
+                        wait(&futex, 0, None);
+                    }
+                });
+            }
+            thread::sleep(Duration::from_millis(20));
+            futex.store(1, Ordering::Release);
+            wake_all(&futex);
+        });
+    }
+
+    #[test]
+    fn test_wake_one_at_a_time() {
+        let futex = AtomicU32::new(0);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for round in 1..=100 {
+                    let mut seen = futex.load(Ordering::Acquire);
+                    while seen < round {
+                        wait(&futex, seen, None);
+                        seen = futex.load(Ordering::Acquire);
+                    }
+                }
+            });
+            for _ in 0..100 {
+                futex.fetch_add(1, Ordering::Release);
+                wake_one(&futex);
+            }
+        });
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A single-use gate that opens once `count_down` has been called `n` times.
+///
+/// Unlike a `Barrier`, the threads counting down don't wait for each other, and the latch
+/// can't be reset. It's meant for startup: every worker calls `count_down` once it is ready,
+/// and a coordinator blocks in `wait` until all of them are. Once the count reaches zero,
+/// `wait` returns right away forever after.
+pub struct CountDownLatch {
+    count: Mutex<usize>,
+    zero: Condvar,
+}
+
+impl CountDownLatch {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: Mutex::new(count),
+            zero: Condvar::new(),
+        }
+    }
+
+    // Decrements the count, releasing every waiter when it reaches zero. Counting down an
+    // open latch does nothing.
+    pub fn count_down(&self) {
+        let mut count = self.count.lock().unwrap();
+        if *count > 0 {
+            *count -= 1;
+            if *count == 0 {
+                self.zero.notify_all();
+            }
+        }
+    }
+
+    // The number of `count_down` calls still needed to open the latch.
+    pub fn count(&self) -> usize {
+        *self.count.lock().unwrap()
+    }
+
+    // Blocks until the count reaches zero.
+    pub fn wait(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = self.zero.wait(count).unwrap();
+        }
+    }
+
+    // Like `wait`, but gives up after `timeout`. Returns whether the latch is open.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return false;
+            };
+            count = self.zero.wait_timeout(count, remaining).unwrap().0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_coordinator_waits_for_workers() {
+        let workers = 8;
+        let latch = CountDownLatch::new(workers);
+        let ready = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| {
+                    ready.fetch_add(1, Ordering::Relaxed);
+                    latch.count_down();
+                });
+            }
+            latch.wait();
+            assert_eq!(ready.load(Ordering::Relaxed), workers);
+        });
+        assert_eq!(latch.count(), 0);
+    }
+
+    #[test]
+    fn test_open_latch_stays_open() {
+        let latch = CountDownLatch::new(1);
+        assert!(!latch.wait_timeout(Duration::from_millis(10)));
+        latch.count_down();
+        latch.count_down();
+        assert_eq!(latch.count(), 0);
+        latch.wait();
+        assert!(latch.wait_timeout(Duration::ZERO));
+        assert!(CountDownLatch::new(0).wait_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_many_waiters() {
+        let latch = CountDownLatch::new(1);
+        thread::scope(|s| {
+            let waiters: Vec<_> = (0..4).map(|_| s.spawn(|| latch.wait())).collect();
+            thread::sleep(Duration::from_millis(10));
+            latch.count_down();
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        });
+    }
+}
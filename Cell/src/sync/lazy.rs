@@ -0,0 +1,289 @@
+use core::{cell::UnsafeCell, fmt, mem::MaybeUninit, ops::Deref};
+
+use super::Once;
+use crate::sys::const_fn;
+
+/// A cell that is written at most once and can be shared between threads, the thread-safe
+/// counterpart of [`OnceCell`](crate::once::OnceCell).
+///
+/// A [`Once`] guards the write: the first `get_or_init` runs its closure, threads that
+/// arrive meanwhile wait for it, and afterwards every `get` is a single load.
+pub struct OnceLock<T> {
+    once: Once,
+    // Initialized once `once` has completed.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: sharing the cell shares `&T` between threads, and any thread may be the one that
+// writes the value, so `T` has to be both `Sync` and `Send`.
+unsafe impl<T: Sync + Send> Sync for OnceLock<T> {}
+unsafe impl<T: Send> Send for OnceLock<T> {}
+
+impl<T> OnceLock<T> {
+    const_fn! {
+        pub const fn new() -> Self {
+            Self {
+                once: Once::new(),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            // SAFETY: a completed `once` means the value was written, and it is never
+            // written again.
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.once.is_completed() {
+            // SAFETY: as in `get`, and `&mut self` makes the access exclusive.
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    // Stores `value` if the cell is empty, otherwise hands it back. Blocks while another
+    // thread is initializing the cell.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    // Returns the value, initializing it with `f` first if the cell is empty. Only one
+    // thread runs its `f`; the others block until it is done. If `f` panics, the cell stays
+    // empty and later calls panic too.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        self.once.call_once(|| {
+            // SAFETY: `call_once` runs at most one closure, so nobody else is writing or
+            // reading the value yet.
+            unsafe { (*self.value.get()).write(f()) };
+        });
+        // SAFETY: `call_once` returned, so the value is initialized.
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+
+    pub fn into_inner(mut self) -> Option<T> {
+        if !self.once.is_completed() {
+            return None;
+        }
+        // Reset the `Once` so that `Drop` doesn't drop the value we move out.
+        self.once = Once::new();
+        // SAFETY: the value was initialized, and we just made sure it isn't dropped again.
+        Some(unsafe { self.value.get_mut().assume_init_read() })
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            // SAFETY: the value was initialized and is dropped only here.
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<T> for OnceLock<T> {
+    fn from(value: T) -> Self {
+        let cell = Self::new();
+        let _ = cell.set(value);
+        cell
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("OnceLock");
+        match self.get() {
+            Some(value) => d.field(value),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
+
+/// A value that is computed by `F` the first time it is dereferenced, from any thread.
+///
+/// Built on [`OnceLock`], so concurrent first uses block until one of them has run the
+/// initializer. This is what [`lazy_static!`](crate::lazy_static) expands to; with the
+/// default `F`, it can be a `static`.
+pub struct LazyLock<T, F = fn() -> T> {
+    cell: OnceLock<T>,
+    // Taken by whichever thread runs the initializer, under the cell's `Once`.
+    init: UnsafeCell<Option<F>>,
+}
+
+// SAFETY: `init` is only touched inside the `Once`, by one thread, which may not be the one
+// that created the `LazyLock`.
+unsafe impl<T: Sync + Send, F: Send> Sync for LazyLock<T, F> {}
+
+impl<T, F: FnOnce() -> T> LazyLock<T, F> {
+    const_fn! {
+        pub const fn new(init: F) -> Self {
+            Self {
+                cell: OnceLock::new(),
+                init: UnsafeCell::new(Some(init)),
+            }
+        }
+    }
+
+    // Runs the initializer if nobody has yet, and returns the value. Panics if an earlier
+    // initializer panicked.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| {
+            // SAFETY: we are inside the cell's `Once`, so no other thread touches `init`.
+            let init = unsafe { (*this.init.get()).take() };
+            init.expect("LazyLock initializer already taken")()
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for LazyLock<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("LazyLock");
+        match self.cell.get() {
+            Some(value) => d.field(value),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
+
+/// Declares `static`s that are initialized on first use, with the syntax of the
+/// `lazy_static` crate.
+///
+/// Every `static ref NAME: Type = expr;` becomes a `static NAME: LazyLock<Type>` that runs
+/// `expr` the first time it is dereferenced.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// Cell::lazy_static! {
+///     static ref PRIMES: HashMap<u32, bool> = (2..20).map(|n| (n, is_prime(n))).collect();
+/// }
+///
+/// fn is_prime(n: u32) -> bool {
+///     (2..n).all(|d| n % d != 0)
+/// }
+///
+/// assert!(PRIMES[&13]);
+/// assert!(!PRIMES[&15]);
+/// ```
+#[macro_export]
+macro_rules! lazy_static {
+    ($($(#[$attr:meta])* $vis:vis static ref $name:ident : $ty:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::sync::LazyLock<$ty> = $crate::sync::LazyLock::new(|| $init);
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        format, panic,
+        string::String,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_once_lock() {
+        let cell = OnceLock::new();
+        assert!(cell.get().is_none());
+        assert_eq!(format!("{cell:?}"), "OnceLock(<uninit>)");
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(*cell.get_or_init(|| 3), 1);
+        assert_eq!(format!("{cell:?}"), "OnceLock(1)");
+        assert_eq!(cell.into_inner(), Some(1));
+    }
+
+    #[test]
+    fn test_once_lock_initializes_once_across_threads() {
+        let calls = AtomicUsize::new(0);
+        let cell = OnceLock::new();
+        let values: Vec<usize> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let (cell, calls) = (&cell, &calls);
+                    s.spawn(move || {
+                        *cell.get_or_init(|| {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            thread::sleep(std::time::Duration::from_millis(10));
+                            i
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(values.iter().all(|&v| v == values[0]));
+    }
+
+    static INIT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    crate::lazy_static! {
+        /// The greeting, built once.
+        static ref GREETING: String = {
+            INIT_CALLS.fetch_add(1, Ordering::SeqCst);
+            String::from("hello")
+        };
+        pub(crate) static ref SQUARES: Vec<u32> = (0..5).map(|n| n * n).collect();
+    }
+
+    #[test]
+    fn test_lazy_static() {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert_eq!(*GREETING, "hello"));
+            }
+        });
+        assert_eq!(INIT_CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(*SQUARES, [0, 1, 4, 9, 16]);
+    }
+
+    #[test]
+    fn test_lazy_lock_poisoned_by_panicking_init() {
+        let lazy: LazyLock<i32> = LazyLock::new(|| panic!("boom"));
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| *lazy)).is_err());
+        assert_eq!(format!("{lazy:?}"), "LazyLock(<uninit>)");
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| *lazy)).is_err());
+    }
+}
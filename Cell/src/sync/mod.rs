@@ -0,0 +1,56 @@
+//! Synchronization primitives for coordinating threads.
+//!
+//! Blocking primitives park the calling thread and need the `std` feature. [`SpinLock`]
+//! busy-waits instead and [`AtomicOnceBox`] never waits at all, so both work without it, as
+//! does [`Backoff`], the helper every spin loop in here uses.
+//!
+//! With the `deadlock_detection` feature, `Mutex` and `RwLock` keep track of which
+//! thread holds and waits for which lock, and `check_deadlocks` reports the threads that
+//! wait on each other in a cycle.
+
+mod backoff;
+#[cfg(feature = "std")]
+mod deadlock;
+#[cfg(feature = "std")]
+mod event;
+#[cfg(feature = "std")]
+pub mod futex;
+#[cfg(feature = "std")]
+mod latch;
+#[cfg(feature = "std")]
+mod lazy;
+#[cfg(feature = "std")]
+mod mutex;
+#[cfg(feature = "std")]
+mod once;
+mod once_box;
+#[cfg(feature = "std")]
+pub mod parking;
+#[cfg(feature = "std")]
+mod rcu;
+#[cfg(feature = "std")]
+mod rwlock;
+mod spinlock;
+
+pub use backoff::Backoff;
+#[cfg(feature = "deadlock_detection")]
+pub use deadlock::{check_deadlocks, DeadlockedThread};
+#[cfg(feature = "std")]
+pub use event::{Event, EventListener};
+#[cfg(feature = "std")]
+pub use latch::CountDownLatch;
+#[cfg(feature = "std")]
+pub use lazy::{LazyLock, OnceLock};
+#[cfg(feature = "std")]
+pub use mutex::{MappedMutexGuard, Mutex, MutexGuard};
+#[cfg(feature = "std")]
+pub use once::{Once, OnceState};
+pub use once_box::AtomicOnceBox;
+#[cfg(feature = "std")]
+pub use rcu::RcuCell;
+#[cfg(feature = "std")]
+pub use rwlock::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
+    RwLockUpgradeableReadGuard, RwLockWriteGuard,
+};
+pub use spinlock::{SpinLock, SpinLockGuard};
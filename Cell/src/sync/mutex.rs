@@ -0,0 +1,507 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+use std::{
+    sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+    thread,
+};
+
+use super::{deadlock, futex, Backoff};
+use crate::sys::{
+    atomic::{AtomicU32, Ordering},
+    const_fn,
+};
+
+const LOCKED: u32 = 1;
+// Set while threads may be waiting for the lock, so unlocking knows to wake one.
+const CONTENDED: u32 = 2;
+const POISONED: u32 = 4;
+
+/// A mutual exclusion lock whose whole state, poison flag included, is a single `u32`.
+///
+/// Waiting threads don't need a queue inside the mutex: they sleep on the state word with
+/// [`futex::wait`], which the kernel backs on Linux. An uncontended `lock` and `unlock` are
+/// a single atomic operation each; a contended `lock` spins briefly before it sleeps.
+///
+/// Like `std::sync::Mutex`, the mutex is poisoned when a thread panics while holding it, and
+/// `lock` then returns the guard wrapped in a [`PoisonError`]. Callers that don't care can
+/// use `lock_unpoisoned`, which neither reports nor sets the poison flag and skips the
+/// `thread::panicking` checks that go with it.
+pub struct Mutex<T: ?Sized> {
+    state: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out access to `T` to one thread at a time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// Access to the data of a locked [`Mutex`]. The lock is released when the guard drops.
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+    poison: PoisonOnPanic,
+    marker: PhantomData<*const ()>,
+}
+
+/// A [`MutexGuard`] narrowed down to part of the data by [`MutexGuard::map`].
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MappedMutexGuard<'a, T: ?Sized> {
+    state: &'a AtomicU32,
+    value: NonNull<T>,
+    poison: PoisonOnPanic,
+    marker: PhantomData<(&'a mut T, *const ())>,
+}
+
+// SAFETY: sharing a guard only shares `&T`.
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MappedMutexGuard<'_, T> {}
+
+// Whether dropping a guard should poison the mutex if the thread is panicking by then.
+#[derive(Clone, Copy)]
+struct PoisonOnPanic(bool);
+
+impl PoisonOnPanic {
+    fn new() -> Self {
+        // A thread that locks while already unwinding doesn't poison the mutex; only a
+        // panic that starts while the guard is held does.
+        Self(!thread::panicking())
+    }
+
+    fn release(self, state: &AtomicU32) {
+        if self.0 && thread::panicking() {
+            state.fetch_or(POISONED, Ordering::Relaxed);
+        }
+        unlock(state);
+    }
+}
+
+impl<T> Mutex<T> {
+    const_fn! {
+        pub const fn new(value: T) -> Self {
+            Self {
+                state: AtomicU32::new(0),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    // Blocks until the lock is free, then takes it.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & LOCKED != 0
+            || self
+                .state
+                .compare_exchange(state, state | LOCKED, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            self.lock_slow();
+        }
+        self.guard()
+    }
+
+    // Like `lock`, but ignores poisoning: it returns the guard even if the mutex is
+    // poisoned, and a panic while holding that guard doesn't poison it.
+    pub fn lock_unpoisoned(&self) -> MutexGuard<'_, T> {
+        match self.lock() {
+            Ok(mut guard) => {
+                guard.poison = PoisonOnPanic(false);
+                guard
+            }
+            Err(err) => {
+                let mut guard = err.into_inner();
+                guard.poison = PoisonOnPanic(false);
+                guard
+            }
+        }
+    }
+
+    // Takes the lock if it is free, without blocking.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED != 0 {
+                return Err(TryLockError::WouldBlock);
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return self.guard().map_err(TryLockError::Poisoned),
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & POISONED != 0
+    }
+
+    pub fn clear_poison(&self) {
+        self.state.fetch_and(!POISONED, Ordering::Relaxed);
+    }
+
+    // No locking needed, `&mut self` already guarantees exclusive access.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    // Wraps the lock we just took in a guard, reporting poison.
+    fn guard(&self) -> LockResult<MutexGuard<'_, T>> {
+        deadlock::acquire(&self.state);
+        let guard = MutexGuard {
+            lock: self,
+            poison: PoisonOnPanic::new(),
+            marker: PhantomData,
+        };
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    #[cold]
+    fn lock_slow(&self) {
+        // How long `lock` retries before it sleeps, as long as nobody else is waiting.
+        let mut backoff = Backoff::new();
+        // Once we have slept, other threads may still be asleep too. We can't tell, so we
+        // take the lock as contended and our unlock wakes the next one.
+        let mut slept = 0;
+        let mut waiting = None;
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED == 0 {
+                match self.state.compare_exchange_weak(
+                    state,
+                    state | LOCKED | slept,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(s) => state = s,
+                }
+                continue;
+            }
+
+            // Spin for a while if nobody is asleep yet; the lock is often released quickly.
+            if state & CONTENDED == 0 && !backoff.is_completed() {
+                backoff.snooze();
+                state = self.state.load(Ordering::Relaxed);
+                continue;
+            }
+
+            // Announce that we are going to sleep, so the unlocking thread wakes us.
+            if state & CONTENDED == 0 {
+                if let Err(s) = self.state.compare_exchange_weak(
+                    state,
+                    state | CONTENDED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            waiting.get_or_insert_with(|| deadlock::Waiting::start(&self.state));
+            futex::wait(&self.state, state | CONTENDED, None);
+            slept = CONTENDED;
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+}
+
+fn unlock(state: &AtomicU32) {
+    deadlock::release(state);
+    let state_before = state.fetch_and(!(LOCKED | CONTENDED), Ordering::Release);
+    if state_before & CONTENDED != 0 {
+        // The woken thread takes the lock as contended, so the other sleepers are
+        // woken in turn.
+        futex::wake_one(state);
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(TryLockError::Poisoned(err)) => d.field("data", &&**err.get_ref()),
+            Err(TryLockError::WouldBlock) => d.field("data", &format_args!("<locked>")),
+        };
+        d.field("poisoned", &self.is_poisoned());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: holding the guard means holding the lock.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: holding the guard means holding the lock.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.poison.release(&self.lock.state);
+    }
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    // Narrows the guard down to the part of the data `f` returns, such as a field. The
+    // mutex stays locked until the returned guard is dropped.
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        // If `f` panics, `orig` is still dropped normally and unlocks the mutex.
+        let value = NonNull::from(f(&mut *orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedMutexGuard {
+            state: &orig.lock.state,
+            value,
+            poison: orig.poison,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> MappedMutexGuard<'a, T> {
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let value = NonNull::from(f(&mut *orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedMutexGuard {
+            state: orig.state,
+            value,
+            poison: orig.poison,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for MappedMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer was derived from the data of the mutex we hold locked.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MappedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as above, and the guard is the only way to reach it.
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for MappedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.poison.release(self.state);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MappedMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, mem::size_of, panic, string::String, sync::Arc};
+
+    #[test]
+    fn test_lock_and_try_lock() {
+        let mutex = Mutex::new(1);
+        {
+            let mut guard = mutex.lock().unwrap();
+            *guard += 1;
+            assert!(matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)));
+            assert_eq!(
+                format!("{mutex:?}"),
+                "Mutex { data: <locked>, poisoned: false, .. }"
+            );
+        }
+        assert_eq!(*mutex.try_lock().unwrap(), 2);
+        assert_eq!(mutex.into_inner().unwrap(), 2);
+        assert_eq!(size_of::<Mutex<()>>(), 4);
+    }
+
+    #[test]
+    fn test_contended_counter() {
+        let mutex = Mutex::new(0usize);
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        *mutex.lock().unwrap() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*mutex.lock().unwrap(), 16000);
+    }
+
+    #[test]
+    fn test_waiters_are_woken() {
+        // Holding the lock long enough makes the other threads sleep rather than spin.
+        let mutex = Mutex::new(Vec::new());
+        std::thread::scope(|s| {
+            let guard = mutex.lock().unwrap();
+            for i in 0..4 {
+                let mutex = &mutex;
+                s.spawn(move || mutex.lock().unwrap().push(i));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            drop(guard);
+        });
+        let mut values = mutex.into_inner().unwrap();
+        values.sort();
+        assert_eq!(values, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_mapped_guard() {
+        struct Stats {
+            hits: u32,
+            name: String,
+        }
+        let mutex = Mutex::new(Stats {
+            hits: 0,
+            name: String::from("cache"),
+        });
+        {
+            let mut hits = MutexGuard::map(mutex.lock().unwrap(), |s| &mut s.hits);
+            *hits += 1;
+            assert!(matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)));
+        }
+        let name = MutexGuard::map(mutex.lock().unwrap(), |s| &mut s.name);
+        let mut first = MappedMutexGuard::map(name, |name| &mut name[..1]);
+        first.make_ascii_uppercase();
+        assert_eq!(format!("{first:?}"), r#""C""#);
+        drop(first);
+        let stats = mutex.lock().unwrap();
+        assert_eq!((stats.hits, stats.name.as_str()), (1, "Cache"));
+    }
+
+    #[test]
+    fn test_lock_unpoisoned() {
+        let mutex = Arc::new(Mutex::new(0));
+        let _ = {
+            let mutex = Arc::clone(&mutex);
+            std::thread::spawn(move || {
+                let _guard = mutex.lock_unpoisoned();
+                panic!("doesn't poison");
+            })
+            .join()
+        };
+        assert!(!mutex.is_poisoned());
+
+        let _ = {
+            let mutex = Arc::clone(&mutex);
+            std::thread::spawn(move || {
+                let _guard = MutexGuard::map(mutex.lock().unwrap(), |x| x);
+                panic!("a mapped guard still poisons");
+            })
+            .join()
+        };
+        assert!(mutex.is_poisoned());
+        *mutex.lock_unpoisoned() += 1;
+        assert_eq!(*mutex.lock_unpoisoned(), 1);
+        assert!(mutex.is_poisoned());
+    }
+
+    #[test]
+    fn test_poisoning() {
+        let mutex = Arc::new(Mutex::new(0));
+        let result = {
+            let mutex = Arc::clone(&mutex);
+            std::thread::spawn(move || {
+                let _guard = mutex.lock().unwrap();
+                panic!("boom");
+            })
+            .join()
+        };
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+
+        let guard = mutex.lock().unwrap_err().into_inner();
+        assert_eq!(*guard, 0);
+        drop(guard);
+        assert!(matches!(mutex.try_lock(), Err(TryLockError::Poisoned(_))));
+
+        mutex.clear_poison();
+        assert!(mutex.lock().is_ok());
+
+        // Locking while already panicking doesn't poison.
+        let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            struct LockOnDrop<'a>(&'a Mutex<i32>);
+            impl Drop for LockOnDrop<'_> {
+                fn drop(&mut self) {
+                    drop(self.0.lock());
+                }
+            }
+            let _lock_on_drop = LockOnDrop(&mutex);
+            panic!("unwinding");
+        }));
+        assert!(!mutex.is_poisoned());
+    }
+}
@@ -0,0 +1,190 @@
+use super::futex;
+use crate::sys::{
+    atomic::{AtomicU32, Ordering},
+    const_fn,
+};
+
+const INCOMPLETE: u32 = 0;
+const RUNNING: u32 = 1;
+// Running, and other threads are asleep waiting for it to finish.
+const QUEUED: u32 = 2;
+const COMPLETE: u32 = 3;
+// The initializer panicked.
+const POISONED: u32 = 4;
+
+/// Runs a one-time global initialization, the thread-safe counterpart of `OnceCell`.
+///
+/// The first `call_once` runs its closure; threads that call it meanwhile sleep on the
+/// state word until the closure finishes, and later calls return right away. The state is a
+/// single `u32`.
+///
+/// If the closure panics, the `Once` is poisoned and later `call_once`s panic too.
+/// `call_once_force` can still run an initializer on a poisoned `Once`.
+pub struct Once {
+    state: AtomicU32,
+}
+
+/// Passed to the closure of [`Once::call_once_force`].
+#[derive(Debug)]
+pub struct OnceState {
+    poisoned: bool,
+}
+
+impl OnceState {
+    // Whether an earlier initializer panicked.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+}
+
+// Poisons the `Once` unless it is defused, so a panicking initializer doesn't leave
+// other threads waiting forever.
+struct CompletionGuard<'a> {
+    state: &'a AtomicU32,
+    set_state_on_drop_to: u32,
+}
+
+impl Drop for CompletionGuard<'_> {
+    fn drop(&mut self) {
+        if self
+            .state
+            .swap(self.set_state_on_drop_to, Ordering::Release)
+            == QUEUED
+        {
+            futex::wake_all(self.state);
+        }
+    }
+}
+
+impl Once {
+    const_fn! {
+        pub const fn new() -> Self {
+            Self {
+                state: AtomicU32::new(INCOMPLETE),
+            }
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    // Runs `f` if no `call_once` has completed yet, otherwise does nothing. Blocks while
+    // another thread is running its closure. Panics if the `Once` is poisoned.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        if self.is_completed() {
+            return;
+        }
+        let mut f = Some(f);
+        self.call(false, &mut |_| f.take().unwrap()());
+    }
+
+    // Like `call_once`, but also runs `f` on a poisoned `Once`, telling it so.
+    pub fn call_once_force(&self, f: impl FnOnce(&OnceState)) {
+        if self.is_completed() {
+            return;
+        }
+        let mut f = Some(f);
+        self.call(true, &mut |state| f.take().unwrap()(state));
+    }
+
+    // Not generic, so the waiting logic is only compiled once.
+    #[cold]
+    fn call(&self, ignore_poison: bool, f: &mut dyn FnMut(&OnceState)) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            match state {
+                COMPLETE => return,
+                POISONED if !ignore_poison => {
+                    panic!("Once instance has previously been poisoned")
+                }
+                INCOMPLETE | POISONED => {
+                    if let Err(s) = self.state.compare_exchange_weak(
+                        state,
+                        RUNNING,
+                        Ordering::Acquire,
+                        Ordering::Acquire,
+                    ) {
+                        state = s;
+                        continue;
+                    }
+                    let mut guard = CompletionGuard {
+                        state: &self.state,
+                        set_state_on_drop_to: POISONED,
+                    };
+                    f(&OnceState {
+                        poisoned: state == POISONED,
+                    });
+                    guard.set_state_on_drop_to = COMPLETE;
+                    return;
+                }
+                _ => {
+                    if state == RUNNING {
+                        if let Err(s) = self.state.compare_exchange_weak(
+                            RUNNING,
+                            QUEUED,
+                            Ordering::Relaxed,
+                            Ordering::Acquire,
+                        ) {
+                            state = s;
+                            continue;
+                        }
+                    }
+                    futex::wait(&self.state, QUEUED, None);
+                    state = self.state.load(Ordering::Acquire);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for Once {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Once").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{panic, sync::atomic::AtomicUsize, thread, time::Duration};
+
+    #[test]
+    fn test_runs_once() {
+        let once = Once::new();
+        let runs = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    once.call_once(|| {
+                        thread::sleep(Duration::from_millis(20));
+                        runs.fetch_add(1, Ordering::SeqCst);
+                    });
+                    // Nobody gets past `call_once` before the closure is done.
+                    assert_eq!(runs.load(Ordering::SeqCst), 1);
+                });
+            }
+        });
+        assert!(once.is_completed());
+        once.call_once(|| unreachable!());
+    }
+
+    #[test]
+    fn test_poisoning() {
+        static ONCE: Once = Once::new();
+        assert!(panic::catch_unwind(|| ONCE.call_once(|| panic!("init failed"))).is_err());
+        assert!(!ONCE.is_completed());
+        assert!(panic::catch_unwind(|| ONCE.call_once(|| {})).is_err());
+
+        let mut saw_poison = false;
+        ONCE.call_once_force(|state| saw_poison = state.is_poisoned());
+        assert!(saw_poison);
+        assert!(ONCE.is_completed());
+    }
+}
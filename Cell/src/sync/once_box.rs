@@ -0,0 +1,196 @@
+use alloc::boxed::Box;
+use core::{
+    fmt,
+    marker::PhantomData,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// A lazily allocated value behind a single pointer, initialized without any locking.
+///
+/// `get_or_init` allocates the value and tries to install it with one compare-and-swap. If
+/// several threads race, they may all run their initializer, but only one box is installed;
+/// the others are freed and everybody gets the winner. That makes it a good fit for global
+/// caches whose initializer is cheap and side-effect free, where a `Once` that blocks the
+/// losers would be overkill. It needs an allocator but not `std`.
+pub struct AtomicOnceBox<T> {
+    ptr: AtomicPtr<T>,
+    // We own a `Box<T>`, for drop check and auto traits.
+    marker: PhantomData<Box<T>>,
+}
+
+// SAFETY: sharing the cell shares `&T` between threads and lets any of them install the
+// value, so `T` has to be both `Sync` and `Send`.
+unsafe impl<T: Send + Sync> Sync for AtomicOnceBox<T> {}
+
+impl<T> AtomicOnceBox<T> {
+    pub const fn new() -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        // SAFETY: a non-null pointer is an installed box, which lives as long as `self`.
+        unsafe { self.ptr.load(Ordering::Acquire).as_ref() }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: as in `get`, and `&mut self` makes the access exclusive.
+        unsafe { self.ptr.get_mut().as_mut() }
+    }
+
+    // Installs `value` if the cell is empty, otherwise hands it back.
+    pub fn set(&self, value: Box<T>) -> Result<(), Box<T>> {
+        let new = Box::into_raw(value);
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(()),
+            // SAFETY: we still own `new`, nobody else has seen it.
+            Err(_) => Err(unsafe { Box::from_raw(new) }),
+        }
+    }
+
+    // Returns the value, allocating it with `f` first if the cell is empty. `f` may run on
+    // several threads at once; all but one result are dropped.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        match self.get_or_try_init(|| Ok::<T, core::convert::Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    // Like `get_or_init`, but leaves the cell empty if `f` fails.
+    pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+        let new = Box::into_raw(Box::new(f()?));
+        match self
+            .ptr
+            .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            // SAFETY: we just installed `new`, and it lives as long as `self`.
+            Ok(_) => Ok(unsafe { &*new }),
+            Err(winner) => {
+                // Another thread got there first; ours was never shared.
+                drop(unsafe { Box::from_raw(new) });
+                // SAFETY: the winner's box is installed for good.
+                Ok(unsafe { &*winner })
+            }
+        }
+    }
+
+    pub fn into_inner(mut self) -> Option<Box<T>> {
+        self.take()
+    }
+
+    // Empties the cell. Needs `&mut self`, since shared references may point into the box.
+    pub fn take(&mut self) -> Option<Box<T>> {
+        let ptr = core::mem::replace(self.ptr.get_mut(), ptr::null_mut());
+        // SAFETY: a non-null pointer is a box we own.
+        (!ptr.is_null()).then(|| unsafe { Box::from_raw(ptr) })
+    }
+}
+
+impl<T> Drop for AtomicOnceBox<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+impl<T> Default for AtomicOnceBox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Box<T>> for AtomicOnceBox<T> {
+    fn from(value: Box<T>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(value)),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AtomicOnceBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("AtomicOnceBox");
+        match self.get() {
+            Some(value) => d.field(value),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        format,
+        sync::{atomic::AtomicUsize, Arc},
+        thread,
+        vec::Vec,
+    };
+
+    #[test]
+    fn test_set_and_get() {
+        let cell = AtomicOnceBox::new();
+        assert!(cell.get().is_none());
+        assert_eq!(format!("{cell:?}"), "AtomicOnceBox(<uninit>)");
+        assert!(cell.set(Box::new(1)).is_ok());
+        assert_eq!(*cell.set(Box::new(2)).unwrap_err(), 2);
+        assert_eq!(cell.get_or_init(|| 3), &1);
+        assert_eq!(format!("{cell:?}"), "AtomicOnceBox(1)");
+        assert_eq!(cell.into_inner().map(|b| *b), Some(1));
+    }
+
+    #[test]
+    fn test_failed_init_leaves_cell_empty() {
+        let mut cell = AtomicOnceBox::new();
+        assert_eq!(cell.get_or_try_init(|| Err("nope")), Err("nope"));
+        assert!(cell.get().is_none());
+        assert_eq!(cell.get_or_try_init(|| Ok::<_, ()>(5)), Ok(&5));
+        *cell.get_mut().unwrap() += 1;
+        assert_eq!(cell.take().map(|b| *b), Some(6));
+        assert!(cell.get().is_none());
+    }
+
+    #[test]
+    fn test_racing_initializers_agree() {
+        // Count live values: every losing box must be freed.
+        struct Counted(usize, Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        let live = Arc::new(AtomicUsize::new(0));
+        let cell = AtomicOnceBox::new();
+        let seen: Vec<usize> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let (cell, live) = (&cell, &live);
+                    s.spawn(move || {
+                        cell.get_or_init(|| {
+                            live.fetch_add(1, Ordering::SeqCst);
+                            Counted(i, Arc::clone(live))
+                        })
+                        .0
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(seen.iter().all(|&i| i == seen[0]));
+        assert_eq!(live.load(Ordering::SeqCst), 1);
+        drop(cell);
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+}
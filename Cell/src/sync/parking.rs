@@ -0,0 +1,257 @@
+//! A global table of wait queues, keyed by address.
+//!
+//! Blocking primitives don't need a queue of their own: a thread that has to wait parks
+//! itself under some key (usually the address of the primitive's state word), and whoever
+//! changes the state unparks the threads waiting under that key. This is what lets
+//! [`RwLock`](super::RwLock) get away with a single word of state, and it is what
+//! [`futex`](super::futex) falls back to on platforms without futexes.
+//!
+//! The table has a fixed number of buckets, each a short spinlock-protected queue. Different
+//! keys may share a bucket; threads waiting under one key are never woken for another.
+//!
+//! The `validate` callback of [`park`] runs with the bucket locked, and so does the callback
+//! of [`unpark_one`]. A primitive that updates its state from the unpark callback and checks
+//! it from `validate` can't lose a wakeup: either the waiter sees the update and doesn't park,
+//! or it is already queued when the unpark happens.
+
+use std::{
+    cell::Cell as StdCell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, Thread},
+    time::Instant,
+};
+
+use super::SpinLock;
+
+/// How a call to [`park`] ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParkResult {
+    /// Another thread unparked this one.
+    Unparked,
+    /// `validate` returned `false`, so the thread never parked.
+    Invalid,
+    /// The timeout passed before anyone unparked the thread.
+    TimedOut,
+}
+
+/// What [`unpark_one`] found in the queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnparkResult {
+    /// The number of threads that were unparked, 0 or 1.
+    pub unparked_threads: usize,
+    /// Whether threads are still parked under the same key.
+    pub have_more_threads: bool,
+}
+
+const BUCKETS: usize = 64;
+
+type Queue = VecDeque<(usize, Arc<Parker>)>;
+
+static TABLE: [SpinLock<Queue>; BUCKETS] = [const { SpinLock::new(VecDeque::new()) }; BUCKETS];
+
+struct Parker {
+    thread: Thread,
+    // Set, with the bucket locked, when the thread is taken out of the queue.
+    unparked: AtomicBool,
+}
+
+thread_local! {
+    // Every thread reuses one parker; it can only be parked under one key at a time.
+    static PARKER: StdCell<Option<Arc<Parker>>> = const { StdCell::new(None) };
+}
+
+fn bucket(key: usize) -> &'static SpinLock<Queue> {
+    // Fibonacci hashing: spreads nearby addresses over the whole table.
+    let hash = (key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    &TABLE[(hash >> (64 - BUCKETS.trailing_zeros())) as usize]
+}
+
+fn current_parker() -> Arc<Parker> {
+    PARKER.with(|cell| {
+        let parker = cell.take().unwrap_or_else(|| {
+            Arc::new(Parker {
+                thread: thread::current(),
+                unparked: AtomicBool::new(false),
+            })
+        });
+        cell.set(Some(Arc::clone(&parker)));
+        parker
+    })
+}
+
+/// Parks the current thread under `key` until another thread unparks it or `timeout` passes.
+///
+/// `validate` runs with the key's bucket locked, right before the thread is queued. If it
+/// returns `false`, the thread doesn't park and `park` returns [`ParkResult::Invalid`].
+/// Callers use it to re-check that the thing they are waiting for still hasn't happened.
+pub fn park(key: usize, validate: impl FnOnce() -> bool, timeout: Option<Instant>) -> ParkResult {
+    let parker = current_parker();
+    parker.unparked.store(false, Ordering::Relaxed);
+    {
+        let mut queue = bucket(key).lock();
+        if !validate() {
+            return ParkResult::Invalid;
+        }
+        queue.push_back((key, Arc::clone(&parker)));
+    }
+
+    loop {
+        if parker.unparked.load(Ordering::Acquire) {
+            return ParkResult::Unparked;
+        }
+        match timeout {
+            None => thread::park(),
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) => thread::park_timeout(remaining),
+                None => {
+                    let mut queue = bucket(key).lock();
+                    if let Some(i) = queue.iter().position(|(_, p)| Arc::ptr_eq(p, &parker)) {
+                        queue.remove(i);
+                        return ParkResult::TimedOut;
+                    }
+                    // Someone unparked us while the timeout ran out. The flag is set with the
+                    // bucket locked, so it is visible now.
+                    return ParkResult::Unparked;
+                }
+            },
+        }
+    }
+}
+
+/// Unparks the thread that has been parked under `key` the longest, if there is one.
+///
+/// `callback` runs with the bucket still locked, after the thread has been taken out of the
+/// queue but before it is woken, and is told whether more threads are waiting. Any state it
+/// changes is seen by the `validate` of every later [`park`] under the same key.
+pub fn unpark_one(key: usize, callback: impl FnOnce(UnparkResult)) -> UnparkResult {
+    let mut queue = bucket(key).lock();
+    let parker = queue
+        .iter()
+        .position(|(k, _)| *k == key)
+        .and_then(|i| queue.remove(i))
+        .map(|(_, parker)| parker);
+    let result = UnparkResult {
+        unparked_threads: parker.is_some() as usize,
+        have_more_threads: parker.is_some() && queue.iter().any(|(k, _)| *k == key),
+    };
+    callback(result);
+    if let Some(parker) = &parker {
+        parker.unparked.store(true, Ordering::Release);
+    }
+    drop(queue);
+    if let Some(parker) = parker {
+        parker.thread.unpark();
+    }
+    result
+}
+
+/// Unparks every thread parked under `key` and returns how many there were.
+pub fn unpark_all(key: usize) -> usize {
+    let mut woken = Vec::new();
+    {
+        let mut queue = bucket(key).lock();
+        queue.retain(|(k, parker)| {
+            if *k != key {
+                return true;
+            }
+            parker.unparked.store(true, Ordering::Release);
+            woken.push(Arc::clone(parker));
+            false
+        });
+    }
+    for parker in &woken {
+        parker.thread.unpark();
+    }
+    woken.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, time::Duration, vec::Vec};
+
+    // Waits until `count` threads are parked under `key`.
+    fn wait_for_parked(key: usize, count: usize) {
+        while bucket(key).lock().iter().filter(|(k, _)| *k == key).count() < count {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_invalid_does_not_park() {
+        let key = 0x1000;
+        assert_eq!(park(key, || false, None), ParkResult::Invalid);
+        assert_eq!(unpark_one(key, |_| {}), UnparkResult::default());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let key = 0x2000;
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert_eq!(park(key, || true, Some(deadline)), ParkResult::TimedOut);
+        assert_eq!(unpark_all(key), 0);
+    }
+
+    #[test]
+    fn test_unpark_one_in_order() {
+        let key = 0x3000;
+        let order = SpinLock::new(Vec::new());
+        thread::scope(|s| {
+            for i in 0..3 {
+                let order = &order;
+                s.spawn(move || {
+                    assert_eq!(park(key, || true, None), ParkResult::Unparked);
+                    order.lock().push(i);
+                });
+                wait_for_parked(key, i + 1);
+            }
+            let mut results = Vec::new();
+            for i in 0..3 {
+                results.push(unpark_one(key, |_| {}));
+                while order.lock().len() <= i {
+                    thread::yield_now();
+                }
+            }
+            assert!(results[0].have_more_threads && results[1].have_more_threads);
+            assert_eq!(
+                results[2],
+                UnparkResult {
+                    unparked_threads: 1,
+                    have_more_threads: false
+                }
+            );
+        });
+        assert_eq!(*order.lock(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_unpark_all_only_wakes_its_key() {
+        let (key, other) = (0x4000, 0x4000 + BUCKETS * 8);
+        let woken = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    park(key, || true, None);
+                    woken.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            s.spawn(|| {
+                park(other, || true, None);
+                woken.fetch_add(10, Ordering::SeqCst);
+            });
+            wait_for_parked(key, 3);
+            wait_for_parked(other, 1);
+            assert_eq!(unpark_all(key), 3);
+            while woken.load(Ordering::SeqCst) < 3 {
+                thread::yield_now();
+            }
+            assert_eq!(woken.load(Ordering::SeqCst), 3);
+            assert_eq!(unpark_all(other), 1);
+        });
+        assert_eq!(woken.load(Ordering::SeqCst), 13);
+    }
+}
@@ -0,0 +1,220 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+use std::sync::Arc;
+
+use super::{Backoff, Mutex};
+
+/// A shared value that readers can snapshot without ever blocking, in the style of
+/// read-copy-update.
+///
+/// `load` hands out an `Arc` of the current version, so a reader keeps a consistent
+/// snapshot for as long as it likes. `update` builds the next version from the current one
+/// and swaps it in; readers that started before the swap keep the old version, later ones
+/// see the new one. Writers are serialized by a mutex, readers never touch it.
+///
+/// The tricky part is the moment between a reader loading the pointer and taking its
+/// reference count: a writer must not drop the old version in that window. Readers mark
+/// that window in one of two counters, picked by the current grace-period epoch. A writer
+/// swaps the pointer, moves to the next epoch, and waits for the old epoch's counter to
+/// drain before it gives up its own reference to the old version. The wait only covers
+/// readers that are mid-`load`, which takes a few instructions; snapshots held afterwards
+/// don't delay anyone.
+///
+/// Best for read-mostly state such as configuration or routing tables, where an update may
+/// copy the whole value.
+pub struct RcuCell<T> {
+    // Owns one strong count of the current version, from `Arc::into_raw`.
+    current: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    // Readers inside `load`, by the parity of the epoch they entered in.
+    readers: [AtomicUsize; 2],
+    writer: Mutex<()>,
+}
+
+// SAFETY: the cell hands out `Arc<T>`s to any thread, which needs `T: Send + Sync`.
+unsafe impl<T: Send + Sync> Send for RcuCell<T> {}
+unsafe impl<T: Send + Sync> Sync for RcuCell<T> {}
+
+impl<T> RcuCell<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: AtomicPtr::new(Arc::into_raw(Arc::new(value)).cast_mut()),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: Mutex::new(()),
+        }
+    }
+
+    // A snapshot of the current version. Never blocks, even while a writer is updating.
+    pub fn load(&self) -> Arc<T> {
+        let readers = self.enter();
+        let ptr = self.current.load(Ordering::SeqCst);
+        // SAFETY: `ptr` came from `Arc::into_raw`, and the writer that replaces it keeps
+        // its own count until we leave the read section, so the count can't reach zero
+        // before we add ours.
+        let snapshot = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        // SeqCst like every other access to the counters; with Release, Miri's weak-memory
+        // emulation finds an order in which the writer frees `ptr` under our feet.
+        readers.fetch_sub(1, Ordering::SeqCst);
+        snapshot
+    }
+
+    // Replaces the value with `f(&current)`. Updates are serialized, so `f` always sees
+    // the version the previous update installed.
+    pub fn update(&self, f: impl FnOnce(&T) -> T) {
+        let _writer = self.writer.lock_unpoisoned();
+        // SAFETY: only writers replace the pointer, and we hold the writer lock, so the
+        // cell's own count keeps it alive.
+        let next = f(unsafe { &*self.current.load(Ordering::Acquire) });
+        self.install(next);
+    }
+
+    // Replaces the value without looking at the current one.
+    pub fn store(&self, value: T) {
+        let _writer = self.writer.lock_unpoisoned();
+        self.install(value);
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // SAFETY: the cell owns one count, and `&mut self` rules out concurrent loads.
+        let mut current = unsafe { Arc::from_raw(*self.current.get_mut()) };
+        let value = Arc::get_mut(&mut current).map(|value| value as *mut T);
+        let _ = Arc::into_raw(current);
+        // SAFETY: the value stays alive as long as the cell, and no snapshot exists.
+        value.map(|value| unsafe { &mut *value })
+    }
+
+    // The current version. Once the cell is gone, the `Arc` is unique unless snapshots
+    // are still around.
+    pub fn into_inner(self) -> Arc<T> {
+        self.load()
+    }
+
+    // Marks the start of a read section and returns the counter to decrement at its end.
+    fn enter(&self) -> &AtomicUsize {
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let readers = &self.readers[epoch % 2];
+            readers.fetch_add(1, Ordering::SeqCst);
+            // If a writer moved on to the next epoch meanwhile, it may already have
+            // waited for this counter; register again under the new epoch.
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                return readers;
+            }
+            // SeqCst, as in `load`.
+            readers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // Swaps in `value` and drops the cell's count of the old version once no reader can
+    // still be about to take its own. Called with the writer lock held.
+    fn install(&self, value: T) {
+        let next = Arc::into_raw(Arc::new(value)).cast_mut();
+        let old = self.current.swap(next, Ordering::SeqCst);
+
+        // Readers that enter from now on see the new epoch, and with it the new pointer.
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+        let mut backoff = Backoff::new();
+        // SeqCst, to pair with the reader's `fetch_add` then `epoch` load in `enter`: with
+        // a weaker load we could read a stale 0 while that reader still sees the old epoch.
+        while self.readers[epoch % 2].load(Ordering::SeqCst) != 0 {
+            backoff.snooze();
+        }
+        // SAFETY: every reader that could have loaded `old` has taken its count by now.
+        drop(unsafe { Arc::from_raw(old) });
+    }
+}
+
+impl<T> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        // SAFETY: the cell owns one count, and `&mut self` rules out readers mid-load.
+        drop(unsafe { Arc::from_raw(*self.current.get_mut()) });
+    }
+}
+
+impl<T: Default> Default for RcuCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for RcuCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcuCell")
+            .field("value", &self.load())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, thread, vec, vec::Vec};
+
+    #[test]
+    fn test_snapshots_survive_updates() {
+        let cell = RcuCell::new(vec![1, 2]);
+        let before = cell.load();
+        cell.update(|v| {
+            let mut v = v.clone();
+            v.push(3);
+            v
+        });
+        assert_eq!(*before, [1, 2]);
+        assert_eq!(*cell.load(), [1, 2, 3]);
+        // The cell dropped its count of the old version; only our snapshot is left.
+        assert_eq!(Arc::strong_count(&before), 1);
+
+        cell.store(Vec::new());
+        assert!(cell.load().is_empty());
+        assert_eq!(format!("{cell:?}"), "RcuCell { value: [] }");
+    }
+
+    #[test]
+    fn test_get_mut_and_into_inner() {
+        let mut cell = RcuCell::new(1);
+        *cell.get_mut().unwrap() += 1;
+        let snapshot = cell.load();
+        assert!(cell.get_mut().is_none());
+        drop(snapshot);
+        assert_eq!(*cell.into_inner(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writers() {
+        // Every version is a pair whose halves are equal; a torn or freed read would show.
+        let cell = RcuCell::new((0usize, 0usize));
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        cell.update(|&(a, b)| (a + 1, b + 1));
+                    }
+                });
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    for _ in 0..5000 {
+                        let snapshot = cell.load();
+                        assert_eq!(snapshot.0, snapshot.1);
+                        assert!(snapshot.0 >= last);
+                        last = snapshot.0;
+                    }
+                });
+            }
+        });
+        assert_eq!(*cell.load(), (2000, 2000));
+    }
+}
@@ -0,0 +1,850 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+    thread,
+};
+
+use super::{deadlock, parking};
+
+const WRITER: usize = 1;
+// Set while threads may be parked waiting for the lock.
+const PARKED: usize = 2;
+// Held by the one upgradeable reader, who is also counted as a reader.
+const UPGRADEABLE: usize = 4;
+const POISONED: usize = 8;
+// The reader count is kept in the remaining bits.
+const READER: usize = 16;
+// The bits that don't say who holds the lock.
+const FLAGS: usize = PARKED | POISONED;
+
+/// A reader-writer lock whose whole state, poison flag included, is a single word.
+///
+/// Any number of readers or one writer can hold the lock. The state word keeps the writer
+/// bit, a "threads are parked" bit, the poison flag and the reader count; blocked threads
+/// park in the global [`parking`] table under its address, and the thread releasing the
+/// lock wakes all of them to try again.
+///
+/// One of the readers can hold an upgradeable read lock instead, which it can later turn
+/// into write access without unlocking in between. Since only one thread can hold it at a
+/// time, no other writer can get in first, so whatever the reader checked still holds once
+/// it writes. That avoids the check-unlock-relock race of read-mostly caches.
+///
+/// Readers are let in whenever no writer holds the lock, so a steady stream of readers can
+/// keep a writer waiting. Like `std::sync::RwLock`, a panic while holding write access
+/// poisons the lock. `read_unpoisoned` and `write_unpoisoned` opt out of poisoning, the same
+/// as `Mutex::lock_unpoisoned`.
+pub struct RwLock<T: ?Sized> {
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: readers on several threads share `&T`, so `T` has to be `Sync` too.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// Shared access to the data of an [`RwLock`], released when the guard drops.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    marker: PhantomData<*const ()>,
+}
+
+/// Exclusive access to the data of an [`RwLock`], released when the guard drops.
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    // Whether a panic while the guard is held poisons the lock.
+    poison: bool,
+    marker: PhantomData<*const ()>,
+}
+
+/// Shared access to the data of an [`RwLock`] that can be upgraded to exclusive access.
+/// Created by [`RwLock::upgradeable_read`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockUpgradeableReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+    marker: PhantomData<*const ()>,
+}
+
+/// An [`RwLockReadGuard`] narrowed down to part of the data by [`RwLockReadGuard::map`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct MappedRwLockReadGuard<'a, T: ?Sized> {
+    state: &'a AtomicUsize,
+    value: NonNull<T>,
+    marker: PhantomData<(&'a T, *const ())>,
+}
+
+/// An [`RwLockWriteGuard`] narrowed down to part of the data by [`RwLockWriteGuard::map`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct MappedRwLockWriteGuard<'a, T: ?Sized> {
+    state: &'a AtomicUsize,
+    poison: bool,
+    value: NonNull<T>,
+    marker: PhantomData<(&'a mut T, *const ())>,
+}
+
+// SAFETY: sharing a guard only shares `&T`.
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockUpgradeableReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MappedRwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MappedRwLockWriteGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    // Blocks until no writer holds the lock, then takes shared access.
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        if !self.try_lock_shared() {
+            self.lock_shared_slow();
+        }
+        self.read_guard()
+    }
+
+    // Like `read`, but returns the guard even if the lock is poisoned.
+    pub fn read_unpoisoned(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        if self.try_lock_shared() {
+            self.read_guard().map_err(TryLockError::Poisoned)
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    // Blocks until no writer or other upgradeable reader holds the lock, then takes shared
+    // access that can be upgraded later. Plain readers can still come and go meanwhile.
+    pub fn upgradeable_read(&self) -> LockResult<RwLockUpgradeableReadGuard<'_, T>> {
+        let can_lock = |state| state & (WRITER | UPGRADEABLE) == 0;
+        let lock = |state: usize| {
+            state
+                .checked_add(READER | UPGRADEABLE)
+                .expect("RwLock reader count overflowed")
+        };
+        if !self.try_transition(can_lock, lock) {
+            self.wait_until(can_lock, lock);
+        }
+        self.upgradeable_guard()
+    }
+
+    pub fn try_upgradeable_read(&self) -> TryLockResult<RwLockUpgradeableReadGuard<'_, T>> {
+        let can_lock = |state| state & (WRITER | UPGRADEABLE) == 0;
+        if self.try_transition(can_lock, |state| state + (READER | UPGRADEABLE)) {
+            self.upgradeable_guard().map_err(TryLockError::Poisoned)
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    // Blocks until nobody holds the lock, then takes exclusive access.
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        if self
+            .state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_exclusive_slow();
+        }
+        self.write_guard()
+    }
+
+    // Like `write`, but ignores poisoning: it returns the guard even if the lock is
+    // poisoned, and a panic while holding that guard doesn't poison it.
+    pub fn write_unpoisoned(&self) -> RwLockWriteGuard<'_, T> {
+        let mut guard = self.write().unwrap_or_else(PoisonError::into_inner);
+        guard.poison = false;
+        guard
+    }
+
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & !FLAGS != 0 {
+                return Err(TryLockError::WouldBlock);
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | WRITER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return self.write_guard().map_err(TryLockError::Poisoned),
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & POISONED != 0
+    }
+
+    pub fn clear_poison(&self) {
+        self.state.fetch_and(!POISONED, Ordering::Relaxed);
+    }
+
+    // No locking needed, `&mut self` already guarantees exclusive access.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn read_guard(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        deadlock::acquire(&self.state);
+        let guard = RwLockReadGuard {
+            lock: self,
+            marker: PhantomData,
+        };
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    fn upgradeable_guard(&self) -> LockResult<RwLockUpgradeableReadGuard<'_, T>> {
+        deadlock::acquire(&self.state);
+        let guard = RwLockUpgradeableReadGuard {
+            lock: self,
+            marker: PhantomData,
+        };
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    fn write_guard(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        deadlock::acquire(&self.state);
+        let guard = RwLockWriteGuard {
+            lock: self,
+            // A thread that locks while already unwinding doesn't poison the lock.
+            poison: !thread::panicking(),
+            marker: PhantomData,
+        };
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    fn try_lock_shared(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 {
+                return false;
+            }
+            let new = state
+                .checked_add(READER)
+                .expect("RwLock reader count overflowed");
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    // Moves the state to `lock(state)` if `can_lock` accepts it, without waiting.
+    fn try_transition(
+        &self,
+        can_lock: impl Fn(usize) -> bool,
+        lock: impl Fn(usize) -> usize,
+    ) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while can_lock(state) {
+            match self.state.compare_exchange_weak(
+                state,
+                lock(state),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    #[cold]
+    fn lock_shared_slow(&self) {
+        self.wait_until(|state| state & WRITER == 0, |state| state + READER);
+    }
+
+    #[cold]
+    fn lock_exclusive_slow(&self) {
+        self.wait_until(|state| state & !FLAGS == 0, |state| state | WRITER);
+    }
+
+    // Waits until `can_lock` accepts the state, then moves it to `lock(state)`.
+    fn wait_until(&self, can_lock: impl Fn(usize) -> bool, lock: impl Fn(usize) -> usize) {
+        let mut waiting = None;
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if can_lock(state) {
+                match self.state.compare_exchange_weak(
+                    state,
+                    lock(state),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(s) => state = s,
+                }
+                continue;
+            }
+
+            if state & PARKED == 0 {
+                if let Err(s) = self.state.compare_exchange_weak(
+                    state,
+                    state | PARKED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            let validate = || {
+                let state = self.state.load(Ordering::Relaxed);
+                state & PARKED != 0 && !can_lock(state)
+            };
+            waiting.get_or_insert_with(|| deadlock::Waiting::start(&self.state));
+            parking::park(key(&self.state), validate, None);
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+}
+
+fn key(state: &AtomicUsize) -> usize {
+    state as *const AtomicUsize as usize
+}
+
+fn unlock_shared(state: &AtomicUsize, upgradeable: usize) {
+    deadlock::release(state);
+    let old = state.fetch_sub(READER | upgradeable, Ordering::Release);
+    // While readers hold the lock, writers and upgradeable readers may be parked. A writer
+    // can go once the last reader leaves, an upgrade once only the upgrading reader is
+    // left, and an upgradeable reader once the current one leaves.
+    if old & PARKED != 0 && (old / READER <= 2 || upgradeable != 0) {
+        wake_parked(state, PARKED);
+    }
+}
+
+fn unlock_exclusive(state: &AtomicUsize, poison: bool) {
+    deadlock::release(state);
+    if poison && thread::panicking() {
+        state.fetch_or(POISONED, Ordering::Relaxed);
+    }
+    // Only the parked and poison bits can be set besides ours; the slow path keeps the
+    // poison bit.
+    if state
+        .compare_exchange(WRITER, 0, Ordering::Release, Ordering::Relaxed)
+        .is_err()
+    {
+        wake_parked(state, WRITER | PARKED);
+    }
+}
+
+// Clears the `clear` bits, which include the parked bit, and lets every parked thread try
+// again if there were any. Clearing the bit first means a thread that is just about to
+// park fails its validation instead.
+#[cold]
+fn wake_parked(state: &AtomicUsize, clear: usize) {
+    if state.fetch_and(!clear, Ordering::Release) & PARKED != 0 {
+        parking::unpark_all(key(state));
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(TryLockError::Poisoned(err)) => d.field("data", &&**err.get_ref()),
+            Err(TryLockError::WouldBlock) => d.field("data", &format_args!("<locked>")),
+        };
+        d.field("poisoned", &self.is_poisoned());
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: holding a read guard means no writer exists.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unlock_shared(&self.lock.state, 0);
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: holding the write guard means holding the lock exclusively.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: holding the write guard means holding the lock exclusively.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        unlock_exclusive(&self.lock.state, self.poison);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    // Narrows the guard down to the part of the data `f` returns. The lock stays
+    // read-locked until the returned guard is dropped.
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRwLockReadGuard<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let value = NonNull::from(f(&*orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedRwLockReadGuard {
+            state: &orig.lock.state,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> MappedRwLockReadGuard<'a, T> {
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRwLockReadGuard<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let value = NonNull::from(f(&*orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedRwLockReadGuard {
+            state: orig.state,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    // Narrows the guard down to the part of the data `f` returns. The lock stays
+    // write-locked until the returned guard is dropped.
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockWriteGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let value = NonNull::from(f(&mut *orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedRwLockWriteGuard {
+            state: &orig.lock.state,
+            poison: orig.poison,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> MappedRwLockWriteGuard<'a, T> {
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockWriteGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let value = NonNull::from(f(&mut *orig));
+        let orig = ManuallyDrop::new(orig);
+        MappedRwLockWriteGuard {
+            state: orig.state,
+            poison: orig.poison,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for MappedRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer was derived from the data of the lock we hold read-locked.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> Drop for MappedRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unlock_shared(self.state, 0);
+    }
+}
+
+impl<T: ?Sized> Deref for MappedRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer was derived from the data of the lock we hold write-locked.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MappedRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as above, and the guard is the only way to reach it.
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for MappedRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        unlock_exclusive(self.state, self.poison);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MappedRwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MappedRwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> RwLockUpgradeableReadGuard<'a, T> {
+    // Turns the guard into write access, waiting for the other readers to leave. No writer
+    // can get in between.
+    pub fn upgrade(this: Self) -> RwLockWriteGuard<'a, T> {
+        let lock = ManuallyDrop::new(this).lock;
+        let can_upgrade = |state| state & !FLAGS == READER | UPGRADEABLE;
+        let upgrade = |state| state - (READER | UPGRADEABLE) + WRITER;
+        if !lock.try_transition(can_upgrade, upgrade) {
+            // While waiting, our own read lock doesn't count as held by us, so that
+            // deadlock detection only sees the other readers we wait for.
+            deadlock::release(&lock.state);
+            lock.wait_until(can_upgrade, upgrade);
+            deadlock::acquire(&lock.state);
+        }
+        RwLockWriteGuard {
+            lock,
+            poison: !thread::panicking(),
+            marker: PhantomData,
+        }
+    }
+
+    // Upgrades if no other reader holds the lock right now, otherwise hands the guard
+    // back.
+    pub fn try_upgrade(this: Self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        let can_upgrade = |state| state & !FLAGS == READER | UPGRADEABLE;
+        let upgrade = |state| state - (READER | UPGRADEABLE) + WRITER;
+        if this.lock.try_transition(can_upgrade, upgrade) {
+            let lock = ManuallyDrop::new(this).lock;
+            Ok(RwLockWriteGuard {
+                lock,
+                poison: !thread::panicking(),
+                marker: PhantomData,
+            })
+        } else {
+            Err(this)
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockUpgradeableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: holding a read guard means no writer exists.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockUpgradeableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unlock_shared(&self.lock.state, UPGRADEABLE);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockUpgradeableReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{mem::size_of, string::String, sync::Arc, time::Duration, vec::Vec};
+
+    #[test]
+    fn test_readers_share_writer_excludes() {
+        let lock = RwLock::new(5);
+        {
+            let a = lock.read().unwrap();
+            let b = lock.try_read().unwrap();
+            assert_eq!(*a + *b, 10);
+            assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        }
+        {
+            let mut w = lock.write().unwrap();
+            *w += 1;
+            assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+            assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        }
+        assert_eq!(lock.into_inner().unwrap(), 6);
+        assert_eq!(size_of::<RwLock<()>>(), size_of::<usize>());
+    }
+
+    #[test]
+    fn test_writer_waits_for_readers() {
+        let lock = RwLock::new(0);
+        std::thread::scope(|s| {
+            let reader = lock.read().unwrap();
+            let writer = s.spawn(|| *lock.write().unwrap() += 1);
+            std::thread::sleep(Duration::from_millis(30));
+            assert_eq!(*reader, 0);
+            drop(reader);
+            writer.join().unwrap();
+        });
+        assert_eq!(*lock.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mixed_contention() {
+        let lock = RwLock::new((0usize, 0usize));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        let mut w = lock.write().unwrap();
+                        w.0 += 1;
+                        w.1 += 1;
+                    }
+                });
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        let r = lock.read().unwrap();
+                        assert_eq!(r.0, r.1);
+                    }
+                });
+            }
+        });
+        assert_eq!(*lock.read().unwrap(), (2000, 2000));
+    }
+
+    #[test]
+    fn test_upgradeable_read() {
+        let lock = RwLock::new(1);
+        let upgradeable = lock.upgradeable_read().unwrap();
+        // Plain readers are still let in, other upgradeable readers and writers aren't.
+        let reader = lock.read().unwrap();
+        assert!(matches!(
+            lock.try_upgradeable_read(),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+
+        let upgradeable = RwLockUpgradeableReadGuard::try_upgrade(upgradeable).unwrap_err();
+        drop(reader);
+        let mut writer = RwLockUpgradeableReadGuard::try_upgrade(upgradeable).unwrap();
+        *writer += 1;
+        assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+        drop(writer);
+
+        let upgradeable = lock.try_upgradeable_read().unwrap();
+        assert_eq!(*upgradeable, 2);
+        drop(upgradeable);
+        assert!(lock.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_upgrade_waits_for_readers() {
+        let lock = RwLock::new(Vec::new());
+        std::thread::scope(|s| {
+            let reader = lock.read().unwrap();
+            let upgrader = s.spawn(|| {
+                let guard = lock.upgradeable_read().unwrap();
+                if guard.is_empty() {
+                    RwLockUpgradeableReadGuard::upgrade(guard).push("filled");
+                }
+            });
+            std::thread::sleep(Duration::from_millis(30));
+            assert!(reader.is_empty());
+            drop(reader);
+            upgrader.join().unwrap();
+        });
+        assert_eq!(*lock.read().unwrap(), ["filled"]);
+    }
+
+    #[test]
+    fn test_cache_fill_happens_once() {
+        // Every thread checks the cache and fills it if it is empty. With an upgradeable
+        // read nobody can fill it between another thread's check and its write.
+        let lock = RwLock::new(None);
+        let fills = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let (lock, fills) = (&lock, &fills);
+                s.spawn(move || {
+                    let guard = lock.upgradeable_read().unwrap();
+                    if guard.is_none() {
+                        fills.fetch_add(1, Ordering::SeqCst);
+                        *RwLockUpgradeableReadGuard::upgrade(guard) = Some(i);
+                    }
+                });
+                s.spawn(|| {
+                    let _ = *lock.read().unwrap();
+                });
+            }
+        });
+        assert_eq!(fills.load(Ordering::SeqCst), 1);
+        assert!(lock.read().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_mapped_guards() {
+        let lock = RwLock::new((String::from("name"), 1));
+        {
+            let name = RwLockReadGuard::map(lock.read().unwrap(), |pair| &pair.0);
+            let name = MappedRwLockReadGuard::map(name, |s| s.as_str());
+            let count = RwLockReadGuard::map(lock.read().unwrap(), |pair| &pair.1);
+            assert_eq!((&*name, *count), ("name", 1));
+            assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        }
+        {
+            let mut count = RwLockWriteGuard::map(lock.write().unwrap(), |pair| &mut pair.1);
+            *count += 1;
+            let mut count = MappedRwLockWriteGuard::map(count, |c| c);
+            *count += 1;
+            assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+        }
+        assert_eq!(lock.read().unwrap().1, 3);
+    }
+
+    #[test]
+    fn test_unpoisoned() {
+        let lock = Arc::new(RwLock::new(0));
+        let _ = {
+            let lock = Arc::clone(&lock);
+            std::thread::spawn(move || {
+                let _guard = lock.write_unpoisoned();
+                panic!("doesn't poison");
+            })
+            .join()
+        };
+        assert!(!lock.is_poisoned());
+
+        let _ = {
+            let lock = Arc::clone(&lock);
+            std::thread::spawn(move || {
+                let _guard = RwLockWriteGuard::map(lock.write().unwrap(), |x| x);
+                panic!("a mapped guard still poisons");
+            })
+            .join()
+        };
+        assert!(lock.is_poisoned());
+        *lock.write_unpoisoned() += 1;
+        assert_eq!(*lock.read_unpoisoned(), 1);
+        assert!(lock.is_poisoned());
+    }
+
+    #[test]
+    fn test_write_panic_poisons() {
+        let lock = Arc::new(RwLock::new(1));
+        let _ = {
+            let lock = Arc::clone(&lock);
+            std::thread::spawn(move || {
+                let _guard = lock.read().unwrap();
+                panic!("readers don't poison");
+            })
+            .join()
+        };
+        assert!(!lock.is_poisoned());
+
+        let _ = {
+            let lock = Arc::clone(&lock);
+            std::thread::spawn(move || {
+                let _guard = lock.write().unwrap();
+                panic!("writers do");
+            })
+            .join()
+        };
+        assert!(lock.is_poisoned());
+        assert_eq!(*lock.read().unwrap_err().into_inner(), 1);
+        // The poison bit shares the state word but doesn't keep anyone out.
+        assert!(matches!(lock.try_write(), Err(TryLockError::Poisoned(_))));
+        assert!(lock.upgradeable_read().is_err());
+        lock.clear_poison();
+        assert!(lock.write().is_ok());
+    }
+}
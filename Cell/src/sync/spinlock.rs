@@ -0,0 +1,192 @@
+// The crate's own `UnsafeCell` is a plain wrapper the compiler knows nothing about, so
+// writing through it from several threads would be undefined behaviour. Locks use the real
+// one.
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::Backoff;
+
+/// A mutual exclusion lock that busy-waits instead of putting the thread to sleep.
+///
+/// Waiting threads spin on a plain load until the lock looks free and only then try to take
+/// it (test-and-test-and-set), so they don't keep bouncing the cache line between cores
+/// while it is held. Between attempts they back off exponentially.
+///
+/// Spinning burns CPU for as long as the lock is held, so this is only a good choice for
+/// very short critical sections, or where there is no OS to park a thread with: it needs
+/// neither `std` nor an allocator. Anything that might hold the lock for a while should
+/// use a blocking mutex instead.
+pub struct SpinLock<T: ?Sized> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out access to `T` to one thread at a time, which is what `Mutex`
+// requires too.
+unsafe impl<T: ?Sized + Send> Send for SpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinLock<T> {}
+
+/// Access to the data of a locked [`SpinLock`]. The lock is released when the guard drops.
+#[must_use = "if unused the SpinLock will immediately unlock"]
+pub struct SpinLockGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock<T>,
+    // Unlocking on another thread is fine for a spinlock, but keep the guard `!Send` like
+    // `MutexGuard` so code can switch between the two.
+    marker: PhantomData<*const ()>,
+}
+
+// SAFETY: sharing the guard only shares `&T`.
+unsafe impl<T: ?Sized + Sync> Sync for SpinLockGuard<'_, T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinLock<T> {
+    // Spins until the lock is free, then takes it.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            // Wait for the lock to look free with plain loads, which don't need the cache
+            // line exclusively.
+            while self.locked.load(Ordering::Relaxed) {
+                backoff.snooze();
+            }
+        }
+    }
+
+    // Takes the lock if it is free, without spinning.
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinLockGuard {
+                lock: self,
+                marker: PhantomData,
+            })
+    }
+
+    // Only a snapshot: another thread may take or release the lock right after.
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    // No locking needed, `&mut self` already guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Default> Default for SpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for SpinLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SpinLock");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: ?Sized> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: holding the guard means holding the lock.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: holding the guard means holding the lock.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinLockGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, thread};
+
+    #[test]
+    fn test_lock_and_try_lock() {
+        let lock = SpinLock::new(1);
+        {
+            let mut guard = lock.lock();
+            *guard += 1;
+            assert!(lock.is_locked());
+            assert!(lock.try_lock().is_none());
+            assert_eq!(format!("{lock:?}"), "SpinLock { data: <locked> }");
+        }
+        assert!(!lock.is_locked());
+        assert_eq!(*lock.try_lock().unwrap(), 2);
+        assert_eq!(format!("{lock:?}"), "SpinLock { data: 2 }");
+        assert_eq!(lock.into_inner(), 2);
+    }
+
+    #[test]
+    fn test_counter_across_threads() {
+        let lock = SpinLock::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        *lock.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*lock.lock(), 8000);
+    }
+
+    #[test]
+    fn test_unsized() {
+        let mut lock: SpinLock<[i32; 3]> = SpinLock::default();
+        lock.get_mut()[1] = 5;
+        let lock: &SpinLock<[i32]> = &lock;
+        lock.lock()[2] = 7;
+        assert_eq!(&*lock.lock(), [0, 5, 7]);
+    }
+}
@@ -0,0 +1,38 @@
+//! The atomics, spin hints and thread functions that [`Mutex`](crate::sync::Mutex),
+//! [`Once`](crate::sync::Once) and [`OnceLock`](crate::sync::OnceLock) are built on.
+//!
+//! Normally these are just core's and std's. Built with `RUSTFLAGS="--cfg loom"`, they are
+//! the mocks from the [`loom`](https://docs.rs/loom) crate instead, which runs a test once for
+//! every way its threads can interleave and every value a relaxed load is allowed to see. The
+//! cfg works for downstream crates too: their loom tests model our primitives along with
+//! their own code.
+//!
+//! Loom atomics can't be created in a `const fn`, so under loom the constructors built on
+//! them are plain functions (see `const_fn!` below), and those types can't be put in `static`s.
+//! Loom has no futex either; [`futex::wait`](crate::sync::futex::wait) yields to the model
+//! instead of sleeping.
+
+#[cfg(not(loom))]
+pub(crate) use core::hint;
+#[cfg(loom)]
+pub(crate) use loom::{hint, sync::atomic, thread};
+#[cfg(all(feature = "std", not(loom)))]
+pub(crate) use std::{sync::atomic, thread};
+
+#[cfg(all(loom, not(feature = "std")))]
+compile_error!("loom needs the `std` feature");
+#[cfg(all(loom, feature = "deadlock_detection"))]
+compile_error!("`deadlock_detection` keeps its registry in a static, which loom doesn't allow");
+
+// Declares a `const fn`, or a plain `fn` under loom.
+#[cfg(feature = "std")]
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const fn $($rest)*
+        #[cfg(loom)]
+        $(#[$attr])* $vis fn $($rest)*
+    };
+}
+#[cfg(feature = "std")]
+pub(crate) use const_fn;
@@ -1,18 +1,26 @@
+// `repr(transparent)` is what makes the pointer casts below (and Cell's on top of them)
+// valid: without it the layout of a struct, even with one field, isn't guaranteed.
+// The value sits in core's `UnsafeCell`, the lang item that tells the compiler (and Miri)
+// that it may be written through a shared reference. A plain field would make every
+// write through `get()` UB.
 #[derive(Debug)]
+#[repr(transparent)]
 pub struct UnsafeCell<T: ?Sized> {
-    value: T,
+    value: core::cell::UnsafeCell<T>,
 }
 
 impl<T: ?Sized> !Sync for UnsafeCell<T> {}
 
 impl<T> UnsafeCell<T> {
     pub const fn new(value: T) -> Self {
-        Self { value }
+        Self {
+            value: core::cell::UnsafeCell::new(value),
+        }
     }
 
     // unwraps the value, consuming the cell.
     pub fn into_inner(self) -> T {
-        self.value
+        self.value.into_inner()
     }
 }
 
@@ -20,21 +28,14 @@ impl<T: ?Sized> UnsafeCell<T> {
     // Gets a mutable pointer to the wrapped value.
 
     pub const fn get(&self) -> *mut T {
-        // the case converts self into a raw, immutable pointer of an UnsafeCell<T>
-
-        // cast changes the pointer from *const UnsafeCell<T> to *const T
-        // Since UnsafeCell<T> has the same in-memory representation as its inner type T, this case is valid.
-
-        // *const T as *mut T
-        // This final cast converts the immutable pointer *const T to a mutable pointer *mut T.
-        // while casting from *const T to *mut T is allowed, it doen't inherently make the data mutable
-        // to safely mutate the data, you must ensure that no other referneces(mutable or immutable)
-        // to the data exist.
-        self as *const UnsafeCell<T> as *const T as *mut T
+        // The pointer comes from core's `UnsafeCell`, so it keeps write permission even
+        // though we only have `&self`. To safely mutate the data, you must ensure that no
+        // other references(mutable or immutable) to the data exist.
+        self.value.get()
     }
 
     pub const fn get_mut(&mut self) -> &mut T {
-        &mut self.value
+        self.value.get_mut()
     }
 
     pub const fn from_mut(value: &mut T) -> &mut UnsafeCell<T> {
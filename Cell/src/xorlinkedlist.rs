@@ -0,0 +1,469 @@
+use alloc::boxed::Box;
+use core::{
+    marker::PhantomData,
+    mem::{swap, take},
+    ptr::{self, NonNull},
+};
+
+/// A doubly linked list that stores a single link word per node.
+///
+/// Every node keeps `address(prev) ^ address(next)` instead of two pointers, with a missing
+/// neighbour counting as address 0. Knowing one neighbour of a node is enough to recover
+/// the other, so the list can still be walked in both directions, but only from an end or
+/// from a [`Cursor`] that remembers where it came from. Random relinking through a lone
+/// node pointer is not possible.
+///
+/// This saves one word per node compared to [`LinkedList`](crate::linkedlist::LinkedList)
+/// and otherwise offers the same API shape.
+///
+/// Link words are built with `expose_provenance` and turned back into pointers with
+/// `with_exposed_provenance_mut`, which keeps the pointer round trip well defined.
+struct Node<T> {
+    element: T,
+    link: usize,
+}
+
+pub struct XorLinkedList<T> {
+    head: Option<NonNull<Node<T>>>,
+    tail: Option<NonNull<Node<T>>>,
+    len: usize,
+    marker: PhantomData<Box<Node<T>>>,
+}
+
+fn addr<T>(node: Option<NonNull<Node<T>>>) -> usize {
+    node.map_or(0, |node| node.as_ptr().expose_provenance())
+}
+
+fn from_addr<T>(addr: usize) -> Option<NonNull<Node<T>>> {
+    NonNull::new(ptr::with_exposed_provenance_mut(addr))
+}
+
+// Given a node and one of its neighbours, returns the other neighbour.
+//
+// SAFETY: `node` must be a live node of a list.
+unsafe fn other<T>(
+    node: NonNull<Node<T>>,
+    neighbour: Option<NonNull<Node<T>>>,
+) -> Option<NonNull<Node<T>>> {
+    from_addr(unsafe { (*node.as_ptr()).link } ^ addr(neighbour))
+}
+
+impl<T> XorLinkedList<T> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn clear(&mut self) {
+        drop(XorLinkedList {
+            head: self.head.take(),
+            tail: self.tail.take(),
+            len: take(&mut self.len),
+            marker: PhantomData,
+        })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn push_front(&mut self, ele: T) {
+        let node = NonNull::from(Box::leak(Box::new(Node {
+            element: ele,
+            link: addr(self.head),
+        })));
+        match self.head {
+            None => self.tail = Some(node),
+            // The old head had no previous node (0), so xor-ing the new node in is enough.
+            Some(head) => unsafe { (*head.as_ptr()).link ^= addr(Some(node)) },
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, ele: T) {
+        let node = NonNull::from(Box::leak(Box::new(Node {
+            element: ele,
+            link: addr(self.tail),
+        })));
+        match self.tail {
+            None => self.head = Some(node),
+            Some(tail) => unsafe { (*tail.as_ptr()).link ^= addr(Some(node)) },
+        }
+        self.tail = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.map(|node| unsafe {
+            let node = Box::from_raw(node.as_ptr());
+            self.head = from_addr(node.link);
+            match self.head {
+                None => self.tail = None,
+                Some(head) => (*head.as_ptr()).link ^= addr(Some(NonNull::from(&*node))),
+            }
+            self.len -= 1;
+            node.element
+        })
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.tail.map(|node| unsafe {
+            let node = Box::from_raw(node.as_ptr());
+            self.tail = from_addr(node.link);
+            match self.tail {
+                None => self.head = None,
+                Some(tail) => (*tail.as_ptr()).link ^= addr(Some(NonNull::from(&*node))),
+            }
+            self.len -= 1;
+            node.element
+        })
+    }
+
+    // Moves all elements from `other` to the end of the list in O(1), leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        match self.tail {
+            None => swap(self, other),
+            Some(tail) => {
+                if let Some(other_head) = other.head.take() {
+                    unsafe {
+                        (*tail.as_ptr()).link ^= addr(Some(other_head));
+                        (*other_head.as_ptr()).link ^= addr(Some(tail));
+                    }
+                    self.tail = other.tail.take();
+                    self.len += take(&mut other.len);
+                }
+            }
+        }
+    }
+
+    // Returns a cursor positioned on the first element (or on the "ghost" position
+    // between the ends when the list is empty).
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor {
+            pos: Position::front(self),
+            list: self,
+        }
+    }
+
+    // Returns a cursor positioned on the last element.
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        Cursor {
+            pos: Position::back(self),
+            list: self,
+        }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            pos: Position::front(self),
+            list: self,
+        }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            pos: Position::back(self),
+            list: self,
+        }
+    }
+}
+
+impl<T> Default for XorLinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for XorLinkedList<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+// Where a cursor is. Walking needs the current node plus the node before it; the list
+// ends are joined by a "ghost" position (`current == None`) like std's cursors.
+struct Position<T> {
+    prev: Option<NonNull<Node<T>>>,
+    current: Option<NonNull<Node<T>>>,
+    index: usize,
+}
+
+impl<T> Position<T> {
+    fn front(list: &XorLinkedList<T>) -> Self {
+        Position {
+            prev: None,
+            current: list.head,
+            index: 0,
+        }
+    }
+
+    fn back(list: &XorLinkedList<T>) -> Self {
+        Position {
+            prev: list.tail.and_then(|tail| unsafe { other(tail, None) }),
+            current: list.tail,
+            index: list.len.saturating_sub(1),
+        }
+    }
+
+    fn index(&self) -> Option<usize> {
+        self.current.map(|_| self.index)
+    }
+
+    fn move_next(&mut self, list: &XorLinkedList<T>) {
+        match self.current {
+            // From the ghost position we wrap around to the front.
+            None => *self = Position::front(list),
+            Some(current) => {
+                let next = unsafe { other(current, self.prev) };
+                self.prev = Some(current);
+                self.current = next;
+                self.index = if next.is_some() { self.index + 1 } else { 0 };
+            }
+        }
+    }
+
+    fn move_prev(&mut self, list: &XorLinkedList<T>) {
+        match (self.current, self.prev) {
+            // From the ghost position we wrap around to the back.
+            (None, _) => *self = Position::back(list),
+            // At the head there is nothing before us, so step onto the ghost.
+            (Some(_), None) => {
+                self.current = None;
+                self.index = 0;
+            }
+            (Some(current), Some(prev)) => {
+                self.prev = unsafe { other(prev, Some(current)) };
+                self.current = Some(prev);
+                self.index -= 1;
+            }
+        }
+    }
+}
+
+/// A read-only cursor over an [`XorLinkedList`].
+pub struct Cursor<'a, T> {
+    list: &'a XorLinkedList<T>,
+    pos: Position<T>,
+}
+
+impl<'a, T> Cursor<'a, T> {
+    // Returns the index of the current element, or `None` on the ghost position.
+    pub fn index(&self) -> Option<usize> {
+        self.pos.index()
+    }
+
+    pub fn current(&self) -> Option<&'a T> {
+        self.pos
+            .current
+            .map(|node| unsafe { &(*node.as_ptr()).element })
+    }
+
+    pub fn move_next(&mut self) {
+        self.pos.move_next(self.list);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.pos.move_prev(self.list);
+    }
+}
+
+/// A cursor over an [`XorLinkedList`] that can modify the element it points at.
+pub struct CursorMut<'a, T> {
+    list: &'a mut XorLinkedList<T>,
+    pos: Position<T>,
+}
+
+impl<T> CursorMut<'_, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.pos.index()
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.pos
+            .current
+            .map(|node| unsafe { &mut (*node.as_ptr()).element })
+    }
+
+    pub fn move_next(&mut self) {
+        self.pos.move_next(self.list);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.pos.move_prev(self.list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, vec::Vec};
+
+    fn forward<T: Clone>(list: &XorLinkedList<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut cursor = list.cursor_front();
+        while let Some(x) = cursor.current() {
+            out.push(x.clone());
+            cursor.move_next();
+        }
+        out
+    }
+
+    fn backward<T: Clone>(list: &XorLinkedList<T>) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut cursor = list.cursor_back();
+        while let Some(x) = cursor.current() {
+            out.push(x.clone());
+            cursor.move_prev();
+        }
+        out
+    }
+
+    #[test]
+    fn test_node_has_a_single_link_word() {
+        assert_eq!(
+            core::mem::size_of::<Node<u64>>(),
+            core::mem::size_of::<u64>() + core::mem::size_of::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut list = XorLinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&3));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_walk_both_ways() {
+        let mut list = XorLinkedList::new();
+        for i in 0..5 {
+            list.push_back(i);
+        }
+        list.push_front(-1);
+        assert_eq!(forward(&list), [-1, 0, 1, 2, 3, 4]);
+        assert_eq!(backward(&list), [4, 3, 2, 1, 0, -1]);
+
+        *list.front_mut().unwrap() = 10;
+        *list.back_mut().unwrap() = 40;
+        assert_eq!(list.pop_back(), Some(40));
+        assert_eq!(forward(&list), [10, 0, 1, 2, 3]);
+        assert_eq!(backward(&list), [3, 2, 1, 0, 10]);
+    }
+
+    #[test]
+    fn test_cursor_wraps_through_ghost() {
+        let mut list = XorLinkedList::new();
+        list.push_back('a');
+        list.push_back('b');
+        list.push_back('c');
+
+        let mut cursor = list.cursor_front();
+        assert_eq!((cursor.index(), cursor.current()), (Some(0), Some(&'a')));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!((cursor.index(), cursor.current()), (Some(2), Some(&'c')));
+        cursor.move_prev();
+        assert_eq!((cursor.index(), cursor.current()), (Some(1), Some(&'b')));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!((cursor.index(), cursor.current()), (None, None));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&'a'));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        cursor.move_prev();
+        assert_eq!((cursor.index(), cursor.current()), (Some(2), Some(&'c')));
+        cursor.move_prev();
+        assert_eq!((cursor.index(), cursor.current()), (Some(1), Some(&'b')));
+
+        let empty: XorLinkedList<i32> = XorLinkedList::new();
+        let mut cursor = empty.cursor_back();
+        assert_eq!(cursor.current(), None);
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut list = XorLinkedList::new();
+        for i in 1..=3 {
+            list.push_back(i);
+        }
+        let mut cursor = list.cursor_back_mut();
+        while let Some(x) = cursor.current() {
+            *x *= 100;
+            cursor.move_prev();
+        }
+        assert_eq!(forward(&list), [100, 200, 300]);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut a = XorLinkedList::new();
+        let mut b = XorLinkedList::new();
+        a.push_back(1);
+        b.push_back(2);
+        b.push_back(3);
+        a.append(&mut b);
+        assert!(b.is_empty());
+        assert_eq!(a.len(), 3);
+        assert_eq!(forward(&a), [1, 2, 3]);
+        assert_eq!(backward(&a), [3, 2, 1]);
+
+        let mut empty = XorLinkedList::new();
+        empty.append(&mut a);
+        assert_eq!(forward(&empty), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_drop_and_clear_release_elements() {
+        let counter = Rc::new(());
+        let mut list = XorLinkedList::new();
+        for _ in 0..4 {
+            list.push_back(Rc::clone(&counter));
+        }
+        list.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        list.push_front(Rc::clone(&counter));
+        drop(list);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,92 @@
+//! Model-checks the blocking primitives under every interleaving loom can find.
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo +nightly test --release --test loom
+//! ```
+#![cfg(loom)]
+
+use loom::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use Cell::sync::{Mutex, Once, OnceLock};
+
+#[test]
+fn mutex_increments_are_not_lost() {
+    loom::model(|| {
+        let mutex = Arc::new(Mutex::new(0));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || *mutex.lock().unwrap() += 1)
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*mutex.lock().unwrap(), 2);
+    });
+}
+
+#[test]
+fn mutex_guards_non_atomic_data() {
+    loom::model(|| {
+        let mutex = Arc::new(Mutex::new((0, 0)));
+        let writer = {
+            let mutex = Arc::clone(&mutex);
+            thread::spawn(move || {
+                let mut pair = mutex.lock().unwrap();
+                pair.0 += 1;
+                pair.1 += 1;
+            })
+        };
+        // Either both halves were written or neither.
+        let (a, b) = *mutex.lock().unwrap();
+        assert_eq!(a, b);
+        writer.join().unwrap();
+    });
+}
+
+#[test]
+fn once_runs_exactly_once() {
+    loom::model(|| {
+        let once = Arc::new(Once::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let (once, calls) = (Arc::clone(&once), Arc::clone(&calls));
+                thread::spawn(move || {
+                    once.call_once(|| {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                    });
+                    // Returning from `call_once` means the closure has finished.
+                    assert_eq!(calls.load(Ordering::Relaxed), 1);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(once.is_completed());
+    });
+}
+
+#[test]
+fn once_lock_publishes_its_value() {
+    loom::model(|| {
+        let cell = Arc::new(OnceLock::new());
+        let threads: Vec<_> = (0..2)
+            .map(|i| {
+                let cell = Arc::clone(&cell);
+                thread::spawn(move || *cell.get_or_init(|| i))
+            })
+            .collect();
+        let seen: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(seen[0], seen[1]);
+        assert_eq!(cell.get(), Some(&seen[0]));
+    });
+}
@@ -0,0 +1,326 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::{channel, threadpool::ThreadPool, Receiver, SendError, Sender};
+
+/*
+An actor is a piece of state that only ever runs on one thread at a time and that the rest of
+the program talks to by sending it messages. Nobody else touches the state, so it needs no
+locks; the mailbox (one of our channels) is the only synchronization, and messages from one
+sender are handled in the order they were sent.
+
+spawn_actor runs the actor's message loop as a job on a ThreadPool, so every running actor
+occupies one worker until it stops, and a pool needs at least as many workers as there are
+actors alive at once. Dropping the pool waits for its actors to stop, like any other job.
+
+The mailbox is bounded. Without a bound, a producer that is faster than the actor fills the
+mailbox until memory runs out; with one, `send` blocks while the mailbox is full, and the
+producer is slowed down to the actor's pace. That is backpressure.
+*/
+
+/// State that handles messages of type [`Actor::Msg`] one at a time, on a pool worker.
+///
+/// `started` runs before the first message and `stopped` after the last one, when the actor
+/// was asked to [`stop`](Address::stop) or every [`Address`] is gone. If `handle` panics, the
+/// actor stops on the spot: `stopped` isn't called and the remaining messages are dropped.
+pub trait Actor: Send + 'static {
+    type Msg: Send + 'static;
+
+    fn started(&mut self) {}
+
+    fn handle(&mut self, msg: Self::Msg);
+
+    fn stopped(&mut self) {}
+}
+
+enum Envelope<M> {
+    Msg(M),
+    Stop,
+}
+
+struct Mailbox {
+    state: Mutex<MailboxState>,
+    not_full: Condvar,
+    capacity: usize,
+}
+
+struct MailboxState {
+    // Messages sent but not yet taken out by the actor. `Stop` doesn't count.
+    queued: usize,
+    // No more messages are taken: `stop` was called, or the actor has stopped.
+    closed: bool,
+    stopped: bool,
+}
+
+/// Where messages for an actor go. Cloning it gives another producer its own address.
+pub struct Address<M> {
+    sender: Sender<Envelope<M>>,
+    mailbox: Arc<Mailbox>,
+}
+
+// Like our Sender, an Address can be cloned whether or not `M` can.
+impl<M> Clone for Address<M> {
+    fn clone(&self) -> Self {
+        Address {
+            sender: self.sender.clone(),
+            mailbox: Arc::clone(&self.mailbox),
+        }
+    }
+}
+
+impl<M> Address<M> {
+    // Puts `msg` in the mailbox, blocking while it's full. Gives the message back if the
+    // actor has been asked to stop or has stopped, since nobody would ever handle it.
+    pub fn send(&mut self, msg: M) -> Result<(), M> {
+        let mut state = self.mailbox.state.lock().unwrap();
+        while state.queued == self.mailbox.capacity && !state.closed {
+            state = self.mailbox.not_full.wait(state).unwrap();
+        }
+        if state.closed {
+            return Err(msg);
+        }
+        // Still under the lock, so the mailbox can't be closed in between. It is closed
+        // before the actor drops its receiver, so this doesn't fail either.
+        if let Err(SendError(Envelope::Msg(msg))) = self.sender.send(Envelope::Msg(msg)) {
+            return Err(msg);
+        }
+        state.queued += 1;
+        Ok(())
+    }
+
+    // Asks the actor to stop once it has handled the messages sent before this. Doesn't
+    // wait for it, and never blocks on a full mailbox. Messages sent afterwards, from any
+    // address of the actor, are given back by `send`.
+    pub fn stop(&mut self) {
+        let mut state = self.mailbox.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        state.closed = true;
+        // Under the lock, so every message taken before comes ahead of `Stop`. If the
+        // receiver is gone, the actor has stopped already.
+        let _ = self.sender.send(Envelope::Stop);
+        drop(state);
+        // Senders waiting for room would only end up behind `Stop`.
+        self.mailbox.not_full.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.mailbox.state.lock().unwrap().stopped
+    }
+}
+
+// Starts `actor` on one of `pool`'s workers. `send` blocks once `capacity` messages are
+// waiting in its mailbox. Panics if `capacity` is zero.
+pub fn spawn_actor<A: Actor>(pool: &ThreadPool, actor: A, capacity: usize) -> Address<A::Msg> {
+    assert!(
+        capacity > 0,
+        "a mailbox needs room for at least one message"
+    );
+    let (sender, receiver) = channel();
+    let mailbox = Arc::new(Mailbox {
+        state: Mutex::new(MailboxState {
+            queued: 0,
+            closed: false,
+            stopped: false,
+        }),
+        not_full: Condvar::new(),
+        capacity,
+    });
+    let shared = Arc::clone(&mailbox);
+    pool.execute(move || run(actor, receiver, &shared));
+    Address { sender, mailbox }
+}
+
+fn run<A: Actor>(mut actor: A, mut receiver: Receiver<Envelope<A::Msg>>, mailbox: &Mailbox) {
+    let _stopped = StopOnDrop(mailbox);
+    actor.started();
+    // `None` means every Address is gone; nothing can arrive any more.
+    while let Some(Envelope::Msg(msg)) = receiver.recv() {
+        let mut state = mailbox.state.lock().unwrap();
+        state.queued -= 1;
+        drop(state);
+        mailbox.not_full.notify_one();
+        actor.handle(msg);
+    }
+    actor.stopped();
+}
+
+// Marks the mailbox closed and stopped however the loop ends, a panicking `handle`
+// included, and wakes the senders blocked on a full mailbox so they get their messages back.
+struct StopOnDrop<'a>(&'a Mailbox);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.closed = true;
+        state.stopped = true;
+        drop(state);
+        self.0.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Log {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Actor for Log {
+        type Msg = u32;
+
+        fn started(&mut self) {
+            self.events.lock().unwrap().push("started".to_string());
+        }
+
+        fn handle(&mut self, msg: u32) {
+            assert!(msg != 13, "unlucky message");
+            self.events.lock().unwrap().push(msg.to_string());
+        }
+
+        fn stopped(&mut self) {
+            self.events.lock().unwrap().push("stopped".to_string());
+        }
+    }
+
+    #[test]
+    fn handles_messages_in_order() {
+        let pool = ThreadPool::new(1);
+        let log = Log::default();
+        let events = Arc::clone(&log.events);
+        let mut address = spawn_actor(&pool, log, 4);
+        for i in 0..10 {
+            address.send(i).unwrap();
+        }
+        address.stop();
+        pool.join();
+        let expected: Vec<String> = ["started"]
+            .into_iter()
+            .map(String::from)
+            .chain((0..10).map(|i| i.to_string()))
+            .chain(["stopped".to_string()])
+            .collect();
+        assert_eq!(*events.lock().unwrap(), expected);
+        assert!(address.is_stopped());
+        assert_eq!(address.send(10), Err(10));
+    }
+
+    #[test]
+    fn stops_when_every_address_is_dropped() {
+        let pool = ThreadPool::new(1);
+        let log = Log::default();
+        let events = Arc::clone(&log.events);
+        let mut address = spawn_actor(&pool, log, 1);
+        let mut other = address.clone();
+        address.send(1).unwrap();
+        drop(address);
+        other.send(2).unwrap();
+        drop(other);
+        pool.join();
+        assert_eq!(*events.lock().unwrap(), ["started", "1", "2", "stopped"]);
+    }
+
+    // Handles each message once the gate is open.
+    struct Slow(Arc<Mutex<()>>);
+
+    impl Actor for Slow {
+        type Msg = ();
+
+        fn handle(&mut self, _: ()) {
+            drop(self.0.lock().unwrap());
+        }
+    }
+
+    #[test]
+    fn full_mailbox_blocks_the_sender() {
+        let pool = ThreadPool::new(1);
+        let gate = Arc::new(Mutex::new(()));
+        let closed = gate.lock().unwrap();
+        let mut address = spawn_actor(&pool, Slow(Arc::clone(&gate)), 1);
+        // The first message is taken out and blocks in `handle`, the second fills the
+        // mailbox, and the third has to wait.
+        address.send(()).unwrap();
+        address.send(()).unwrap();
+        let sent = Arc::new(AtomicBool::new(false));
+        let sender = {
+            let (mut address, sent) = (address.clone(), Arc::clone(&sent));
+            thread::spawn(move || {
+                address.send(()).unwrap();
+                sent.store(true, Ordering::SeqCst);
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!sent.load(Ordering::SeqCst));
+        drop(closed);
+        sender.join().unwrap();
+        assert!(sent.load(Ordering::SeqCst));
+        address.stop();
+    }
+
+    #[test]
+    fn stop_refuses_later_messages() {
+        let pool = ThreadPool::new(1);
+        let gate = Arc::new(Mutex::new(()));
+        let closed = gate.lock().unwrap();
+        let mut address = spawn_actor(&pool, Slow(Arc::clone(&gate)), 2);
+        address.send(()).unwrap();
+        address.send(()).unwrap();
+        address.stop();
+        // The actor is still busy and hasn't seen `Stop`, and there is room in the mailbox,
+        // but whatever comes after `Stop` would be dropped unhandled.
+        assert!(!address.is_stopped());
+        assert_eq!(address.send(()), Err(()));
+        assert_eq!(address.clone().send(()), Err(()));
+        drop(closed);
+        pool.join();
+        assert!(address.is_stopped());
+    }
+
+    #[test]
+    fn stop_wakes_blocked_senders() {
+        let pool = ThreadPool::new(1);
+        let gate = Arc::new(Mutex::new(()));
+        let closed = gate.lock().unwrap();
+        let mut address = spawn_actor(&pool, Slow(Arc::clone(&gate)), 1);
+        address.send(()).unwrap();
+        address.send(()).unwrap();
+        let (mut done, mut result) = channel();
+        {
+            let mut address = address.clone();
+            thread::spawn(move || done.send(address.send(())).unwrap());
+        }
+        thread::sleep(Duration::from_millis(50));
+        address.stop();
+        // The actor is still busy, and the waiting message would end up behind `Stop`.
+        assert_eq!(result.recv_timeout(Duration::from_secs(5)), Ok(Err(())));
+        drop(closed);
+        pool.join();
+    }
+
+    #[test]
+    fn panic_in_handle_stops_the_actor() {
+        let pool = ThreadPool::new(1);
+        let log = Log::default();
+        let events = Arc::clone(&log.events);
+        let mut address = spawn_actor(&pool, log, 8);
+        address.send(13).unwrap();
+        pool.join();
+        assert_eq!(pool.panic_count(), 1);
+        assert!(address.is_stopped());
+        assert_eq!(address.send(1), Err(1));
+        assert_eq!(*events.lock().unwrap(), ["started"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one message")]
+    fn zero_capacity() {
+        spawn_actor(&ThreadPool::new(1), Log::default(), 0);
+    }
+}
@@ -0,0 +1,62 @@
+use std::{error::Error, fmt};
+
+/// Returned by [`Sender::send`](crate::Sender::send) and
+/// [`send_all`](crate::Sender::send_all) when the receiver is gone, with what couldn't be
+/// sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+// Not derived, so that `T` doesn't need to be Debug for `send(..).unwrap()`.
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Why [`Receiver::try_recv`](crate::Receiver::try_recv) returned without a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    // No message right now, but senders are still around and may send one.
+    Empty,
+    // The queue is empty and every sender is gone, so none will ever arrive.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => f.write_str("receiving on a closed channel"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+/// Why [`Receiver::recv_timeout`](crate::Receiver::recv_timeout) returned without a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    // Nothing arrived in time; senders are still around.
+    Timeout,
+    // The queue is empty and every sender is gone.
+    Disconnected,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting on a channel"),
+            RecvTimeoutError::Disconnected => f.write_str("receiving on a closed channel"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
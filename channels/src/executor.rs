@@ -0,0 +1,370 @@
+use std::{
+    future::{poll_fn, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{
+    channel,
+    task::{self, ArcWake, Parker},
+    Receiver, Sender,
+};
+
+/*
+A Future does nothing on its own: somebody has to call poll() on it until it returns Ready.
+When it returns Pending it has handed the Waker from the Context to whatever it waits for,
+and that thing calls wake() once the future can make progress. An executor is the loop
+that polls futures, and decides what "waking" means: for block_on it unparks the thread
+that is blocked, for the Executor it puts the task back on the run queue.
+*/
+
+// Runs `future` to completion on the current thread, parking it whenever the future is
+// waiting.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let parker = Parker::new();
+    let waker = parker.unparker().waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        parker.park();
+    }
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A pool of worker threads that run spawned futures.
+///
+/// The run queue is one of our channels, shared by the workers like in
+/// [`ThreadPool`](crate::threadpool::ThreadPool). Every spawned future becomes a task, and a
+/// task's waker sends the task back into the channel, so whichever worker receives it next
+/// polls it again. A task is only ever queued once at a time, however often it is woken.
+///
+/// A task that panics is stopped and its [`JoinHandle`] returns the panic; the worker goes
+/// on with other tasks. Dropping the executor waits until every spawned task has finished,
+/// then shuts the workers down.
+pub struct Executor {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    // Our Sender needs `&mut self`, and wakers can fire from any thread. The Option is
+    // taken when the executor shuts down, which closes the channel.
+    sender: Mutex<Option<Sender<Arc<Task>>>>,
+    // Tasks spawned but not finished yet, so Drop can wait for them.
+    active: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Shared {
+    fn schedule(&self, task: Arc<Task>) {
+        // After shutdown every task has finished, so there is nothing left to run.
+        if let Some(sender) = self.sender.lock().unwrap().as_mut() {
+            // The workers hold the receiver until shutdown takes the sender.
+            sender
+                .send(task)
+                .unwrap_or_else(|_| unreachable!("the executor's workers are gone"));
+        }
+    }
+}
+
+struct Task {
+    // None once the future has completed.
+    future: Mutex<Option<BoxFuture>>,
+    // Set while the task sits in the run queue, so waking it again doesn't queue it twice.
+    scheduled: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if !arc_self.scheduled.swap(true, Ordering::AcqRel) {
+            arc_self.shared.schedule(Arc::clone(arc_self));
+        }
+    }
+}
+
+impl Executor {
+    // Panics if `threads` is zero.
+    pub fn new(threads: usize) -> Executor {
+        assert!(threads > 0, "an Executor needs at least one worker");
+        let (sender, receiver) = channel::<Arc<Task>>();
+        let shared = Arc::new(Shared {
+            sender: Mutex::new(Some(sender)),
+            active: Mutex::new(0),
+            idle: Condvar::new(),
+        });
+        let workers = (0..threads)
+            .map(|i| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("executor-worker-{i}"))
+                    .spawn(move || run(receiver))
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        Executor { shared, workers }
+    }
+
+    // Starts running `future` on the workers. The returned handle can be awaited, or
+    // joined from outside the executor, to get its output.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(JoinState {
+            result: None,
+            waker: None,
+        }));
+        let join = Arc::clone(&state);
+        let mut future = Box::pin(future);
+        let task = async move {
+            // Catch a panic from any poll of the future, so it ends up in the JoinHandle
+            // instead of unwinding through the worker.
+            let result = poll_fn(|cx| {
+                match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                    Ok(Poll::Pending) => Poll::Pending,
+                    Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+                    Err(panic) => Poll::Ready(Err(panic)),
+                }
+            })
+            .await;
+            let mut state = join.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        };
+
+        *self.shared.active.lock().unwrap() += 1;
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(Box::pin(task))),
+            scheduled: AtomicBool::new(true),
+            shared: Arc::clone(&self.shared),
+        });
+        self.shared.schedule(task);
+        JoinHandle { state }
+    }
+
+    // Blocks until every task spawned so far has finished.
+    pub fn join(&self) {
+        let mut active = self.shared.active.lock().unwrap();
+        while *active > 0 {
+            active = self.shared.idle.wait(active).unwrap();
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+fn run(mut receiver: Receiver<Arc<Task>>) {
+    loop {
+        let Some(task) = receiver.recv() else {
+            // The executor was dropped and the queue is drained.
+            return;
+        };
+        // Clear the flag before polling: a wake that comes in during the poll has to queue
+        // the task again, or it would be lost.
+        task.scheduled.store(false, Ordering::Release);
+        let waker = task::waker(Arc::clone(&task));
+        let mut slot = task.future.lock().unwrap();
+        let Some(future) = slot.as_mut() else {
+            // Woken after it completed.
+            continue;
+        };
+        if future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready()
+        {
+            *slot = None;
+            drop(slot);
+            let mut active = task.shared.active.lock().unwrap();
+            *active -= 1;
+            if *active == 0 {
+                task.shared.idle.notify_all();
+            }
+        }
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        self.join();
+        // That was the only Sender, so the workers see the channel close and exit.
+        drop(self.shared.sender.lock().unwrap().take());
+        for worker in self.workers.drain(..) {
+            worker.join().expect("executor worker panicked");
+        }
+    }
+}
+
+/// The output of a task started with [`Executor::spawn`].
+///
+/// Await it from another future, or call [`JoinHandle::join`] to block on it. Either gives
+/// `Err` with the panic payload if the task panicked, like `std::thread::JoinHandle`.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+struct JoinState<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T> JoinHandle<T> {
+    // Blocks the current thread until the task has finished.
+    pub fn join(self) -> thread::Result<T> {
+        block_on(self)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = thread::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::AtomicUsize,
+        time::{Duration, Instant},
+    };
+
+    // A future that is ready once another thread has finished sleeping.
+    fn sleep(duration: Duration) -> impl Future<Output = ()> {
+        let state = Arc::new(Mutex::new((false, None::<Waker>)));
+        let timer = Arc::clone(&state);
+        thread::spawn(move || {
+            thread::sleep(duration);
+            let mut state = timer.lock().unwrap();
+            state.0 = true;
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+        poll_fn(move |cx| {
+            let mut state = state.lock().unwrap();
+            if state.0 {
+                Poll::Ready(())
+            } else {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+
+    // A future that wakes itself and returns Pending `times` times before it is ready.
+    fn yield_times(mut times: usize) -> impl Future<Output = usize> {
+        let mut polls = 0;
+        poll_fn(move |cx| {
+            polls += 1;
+            if times == 0 {
+                return Poll::Ready(polls);
+            }
+            times -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+
+    #[test]
+    fn block_on_ready() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+
+    #[test]
+    fn block_on_waits_for_wakeup() {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(block_on(yield_times(5)), 6);
+    }
+
+    #[test]
+    fn spawn_runs_tasks_on_workers() {
+        let executor = Executor::new(4);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..50)
+            .map(|i| {
+                let counter = Arc::clone(&counter);
+                executor.spawn(async move {
+                    sleep(Duration::from_millis(1)).await;
+                    let polls = yield_times(3).await;
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    i * polls
+                })
+            })
+            .collect();
+        let results: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, (0..50).map(|i| i * 4).collect::<Vec<_>>());
+        executor.join();
+        assert_eq!(counter.load(Ordering::SeqCst), 50);
+    }
+
+    #[test]
+    fn tasks_can_await_each_other() {
+        let executor = Executor::new(2);
+        let first = executor.spawn(async {
+            sleep(Duration::from_millis(10)).await;
+            "first"
+        });
+        let second = executor.spawn(async move { format!("{} then second", first.await.unwrap()) });
+        assert_eq!(second.join().unwrap(), "first then second");
+    }
+
+    #[test]
+    fn panics_are_returned_by_join() {
+        let executor = Executor::new(1);
+        let failed = executor.spawn(async {
+            yield_times(1).await;
+            panic!("task failed");
+        });
+        let fine = executor.spawn(async { 7 });
+        let panic = failed.join().unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"task failed"));
+        assert_eq!(fine.join().unwrap(), 7);
+    }
+
+    #[test]
+    fn drop_waits_for_tasks() {
+        let done = Arc::new(AtomicBool::new(false));
+        let executor = Executor::new(1);
+        let flag = Arc::clone(&done);
+        executor.spawn(async move {
+            sleep(Duration::from_millis(10)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+        drop(executor);
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one worker")]
+    fn zero_workers() {
+        Executor::new(0);
+    }
+}
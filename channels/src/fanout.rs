@@ -0,0 +1,124 @@
+use std::thread;
+
+use crate::{channel, Receiver, SendError, Sender};
+
+/*
+Fan-out splits one stream of messages into several. Receiver::tee hands the receiver to a
+router thread that receives every message and passes it on to n new channels, whose
+receivers it returns. The router is built from nothing but our own Sender and Receiver, so
+it inherits their close semantics: when the upstream channel closes, the router drops its
+downstream senders and every downstream receiver sees the close after the messages that
+were already routed; when every downstream receiver is gone, the router drops the upstream
+receiver, and the upstream senders see it as disconnected.
+
+A downstream receiver that is dropped early simply stops getting messages. The others
+carry on: broadcast goes on cloning to them, round-robin skips the missing one.
+*/
+
+/// What [`Receiver::tee`] does with each message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanoutPolicy {
+    // Every downstream receiver gets its own clone of every message.
+    Broadcast,
+    // Each message goes to one downstream receiver, taking turns.
+    RoundRobin,
+}
+
+impl<T: Clone + Send + 'static> Receiver<T> {
+    // Routes everything this receiver gets to `n` new receivers on a thread of its own,
+    // following `policy`. Panics if `n` is zero.
+    pub fn tee(self, n: usize, policy: FanoutPolicy) -> Vec<Receiver<T>> {
+        assert!(n > 0, "a tee needs at least one downstream receiver");
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..n).map(|_| channel()).unzip();
+        thread::Builder::new()
+            .name("channels-tee".to_string())
+            .spawn(move || route(self, senders, policy))
+            .expect("failed to spawn the tee thread");
+        receivers
+    }
+}
+
+fn route<T: Clone>(
+    mut upstream: Receiver<T>,
+    mut downstream: Vec<Sender<T>>,
+    policy: FanoutPolicy,
+) {
+    let mut next = 0;
+    // An upstream close ends the loop; every downstream receiver gone ends it too.
+    while !downstream.is_empty() {
+        let Some(msg) = upstream.recv() else {
+            return;
+        };
+        match policy {
+            FanoutPolicy::Broadcast => {
+                downstream.retain_mut(|tx| tx.send(msg.clone()).is_ok());
+            }
+            FanoutPolicy::RoundRobin => {
+                let mut msg = msg;
+                // The turn passes on from whoever got the last message. A failed send
+                // gives the message back, and that receiver leaves the rotation.
+                while !downstream.is_empty() {
+                    next %= downstream.len();
+                    match downstream[next].send(msg) {
+                        Ok(()) => {
+                            next += 1;
+                            break;
+                        }
+                        Err(SendError(back)) => {
+                            msg = back;
+                            downstream.remove(next);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcast_clones_to_everyone() {
+        let (mut tx, rx) = channel();
+        let outputs = rx.tee(3, FanoutPolicy::Broadcast);
+        tx.send_all(0..5).unwrap();
+        drop(tx);
+        for mut rx in outputs {
+            assert_eq!(rx.iter().collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let (mut tx, rx) = channel();
+        let mut outputs = rx.tee(3, FanoutPolicy::RoundRobin);
+        tx.send_all(0..6).unwrap();
+        drop(tx);
+        let got: Vec<Vec<_>> = outputs.iter_mut().map(|rx| rx.iter().collect()).collect();
+        assert_eq!(got, [[0, 3], [1, 4], [2, 5]]);
+    }
+
+    #[test]
+    fn round_robin_skips_dropped_receivers() {
+        let (mut tx, rx) = channel();
+        let mut outputs = rx.tee(2, FanoutPolicy::RoundRobin);
+        drop(outputs.remove(0));
+        tx.send_all(0..4).unwrap();
+        drop(tx);
+        assert_eq!(outputs[0].iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn upstream_disconnects_when_every_output_is_gone() {
+        let (mut tx, rx) = channel();
+        drop(rx.tee(2, FanoutPolicy::Broadcast));
+        // The router only notices on its next message, and then lets go of the receiver.
+        tx.send(1).unwrap();
+        while !tx.is_disconnected() {
+            thread::yield_now();
+        }
+        assert_eq!(tx.send(2), Err(SendError(2)));
+    }
+}
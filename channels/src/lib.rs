@@ -1,8 +1,40 @@
 use std::{
     collections::VecDeque,
-    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
 };
 
+use metrics::Counters;
+use sys::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+use wait::{Line, Signal, Turn};
+
+pub mod actor;
+mod error;
+pub mod executor;
+mod fanout;
+pub mod lockfree;
+mod metrics;
+mod peek;
+#[cfg(feature = "async")]
+mod stream;
+pub mod sync;
+mod sys;
+pub mod task;
+pub mod threadlocal;
+pub mod threadpool;
+mod wait;
+
+pub use error::{RecvTimeoutError, SendError, TryRecvError};
+pub use fanout::FanoutPolicy;
+#[cfg(feature = "metrics")]
+pub use metrics::Stats;
+pub use peek::Peek;
+#[cfg(feature = "async")]
+pub use stream::Recv;
+pub use wait::WaitStrategy;
+
 // A Mutex is boolean semaphore effectively
 // Arc is needed to have a shared inner datastructure for both sender and receiver.
 
@@ -44,56 +76,410 @@ impl<T> Drop for Sender<T> {
     fn drop(&mut self) {
         let mut inner = self.shared.inner.lock().unwrap();
         inner.senders -= 1;
-
-        if inner.senders == 0 {
-            self.shared.available.notify_one(); // If it was the last one , notifiy if the receiver is waiting so wakes up.
+        if inner.senders > 0 {
+            return;
         }
+        // Messages the receivers left behind for `reclaim`; nobody is left to take them now.
+        // Dropped after the lock is released, in case dropping a message uses this channel.
+        let unread = if inner.receivers == 0 {
+            std::mem::take(&mut inner.queue)
+        } else {
+            VecDeque::new()
+        };
+        // If it was the last one, wake every waiting receiver so they all see the channel is closed.
+        self.shared.wake_receivers(inner);
+        drop(unread);
     }
 }
 
 impl<T> Sender<T> {
-    pub fn send(&mut self, t: T) {
+    // Hands `t` back in the error if the receiver is gone: nobody would ever take it out of
+    // the queue, so enqueueing it would only leak it.
+    pub fn send(&mut self, t: T) -> Result<(), SendError<T>> {
         let mut inner = self.shared.inner.lock().unwrap(); // What if the thread failed to access the lock.
+        if inner.closed_for_send() {
+            return Err(SendError(t));
+        }
         inner.queue.push_back(t);
+        // Counted before the lock is released, so a receiver can't take the message and
+        // decrement the count first.
+        let depth = self.shared.len.fetch_add(1, Ordering::Relaxed) + 1;
+        self.shared.counters.sent(1, depth);
+        // The receiver that has waited longest gets it, see wait.rs.
+        let next = self.shared.available.first(&inner.line, 1);
+        #[cfg(feature = "async")]
+        let task = inner.wakers.pop_front();
         drop(inner); //drops the lock, when other notify wakes up the other thread it can take the lock immediately.
 
         // and if any thread is in sleep and is waiting for the data
-        // we will use the notify_one method to wake it up.
-        self.shared.available.notify_one();
+        // we will wake it up.
+        self.shared.available.wake(next);
+        #[cfg(feature = "async")]
+        if let Some((_, waker)) = task {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /*
+    Sends every item of `items` under a single lock, so they end up next to each other in the
+    queue, in order. The items are collected before taking the lock: the iterator may be
+    arbitrary code, and if it used this channel while we held the lock, it would deadlock.
+    If the receiver is gone, nothing is sent and the collected items are handed back.
+    */
+    pub fn send_all<I: IntoIterator<Item = T>>(
+        &mut self,
+        items: I,
+    ) -> Result<(), SendError<Vec<T>>> {
+        let batch: Vec<T> = items.into_iter().collect();
+        let mut inner = self.shared.inner.lock().unwrap();
+        if inner.closed_for_send() {
+            return Err(SendError(batch));
+        }
+        let n = batch.len();
+        inner.queue.extend(batch);
+        let depth = self.shared.len.fetch_add(n, Ordering::Relaxed) + n;
+        self.shared.counters.sent(n, depth);
+        // One receiver per message can make progress: the first n in line.
+        let next = self.shared.available.first(&inner.line, n);
+        #[cfg(feature = "async")]
+        let tasks: stream::Wakers = {
+            let n = n.min(inner.wakers.len());
+            inner.wakers.drain(..n).collect()
+        };
+        drop(inner);
+        self.shared.available.wake(next);
+        #[cfg(feature = "async")]
+        stream::wake_all(tasks);
+        Ok(())
+    }
+
+    // Messages sent but not received yet. Only a snapshot, other threads may send or
+    // receive right after.
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Whether every receiver is gone or the channel was closed, so that any further send
+    // would fail. Once true, it stays true: a closed channel can't be opened again.
+    pub fn is_disconnected(&self) -> bool {
+        self.shared.inner.lock().unwrap().closed_for_send()
+    }
+
+    /*
+    The messages nobody received: once every receiver is gone, whatever they left unread
+    stays in the channel instead of being dropped, and any sender can take it back here,
+    oldest first, to retry or log it. Empty while a receiver is still alive. If no sender
+    reclaims them, they are dropped with the last Sender.
+    */
+    pub fn reclaim(&self) -> VecDeque<T> {
+        let mut inner = self.shared.inner.lock().unwrap();
+        if inner.receivers > 0 {
+            return VecDeque::new();
+        }
+        std::mem::take(&mut inner.queue)
+    }
+
+    // Closes the channel for everyone without dropping this handle, see `Receiver::close`.
+    pub fn close(&self) {
+        self.shared.close();
+    }
+
+    // A handle that can send later, through `upgrade`, without keeping the channel open.
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/*
+A WeakSender holds on to the shared state like a Sender, but it isn't counted in `senders`,
+so it doesn't keep the receivers waiting: once the last real Sender is dropped the channel
+is closed, weak senders or not. It can't send by itself; `upgrade` turns it into a Sender
+while the channel is still open, and never reopens a closed one, since receivers that saw
+the close have already returned None.
+
+The Arc keeps the queue's memory alive as long as a WeakSender exists, but not the channel.
+*/
+
+/// A [`Sender`] that doesn't count towards keeping the channel open, made by
+/// [`Sender::downgrade`].
+pub struct WeakSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// Same as Sender: cloneable whatever `T` is.
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> Self {
+        WeakSender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> WeakSender<T> {
+    // A real Sender, if some Sender and some Receiver are still alive and the channel
+    // wasn't closed.
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        let mut inner = self.shared.inner.lock().unwrap();
+        if inner.closed_for_recv() || inner.closed_for_send() {
+            return None;
+        }
+        inner.senders += 1;
+        drop(inner);
+        Some(Sender {
+            shared: Arc::clone(&self.shared),
+        })
     }
 }
 
 pub struct Receiver<T> {
     shared: Arc<Shared<T>>,
     buffer: VecDeque<T>,
+    // Our place among the waiting tasks while we are in `poll_next`.
+    #[cfg(feature = "async")]
+    key: Option<u64>,
+}
+
+/*
+Cloning the Receiver turns the channel into an MPMC queue: every message goes to exactly
+one of the receivers, whichever asks first. Workers of a pool can each hold their own clone
+instead of sharing one Receiver behind a Mutex.
+*/
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.inner.lock().unwrap().receivers += 1;
+        self.shared.cloned.store(true, Ordering::Relaxed);
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            buffer: VecDeque::new(),
+            #[cfg(feature = "async")]
+            key: None,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.shared.inner.lock().unwrap();
+        inner.receivers -= 1;
+        #[cfg(feature = "async")]
+        if let Some(key) = self.key.take() {
+            // The others are woken below anyway if they have something to receive.
+            inner.unregister(key);
+        }
+        if inner.receivers > 0 {
+            /*
+            Our buffer holds messages that were sent before everything still in the queue,
+            and the other receivers can still take them. Put them back in front.
+            */
+            if !self.buffer.is_empty() {
+                self.buffer.append(&mut inner.queue);
+                std::mem::swap(&mut self.buffer, &mut inner.queue);
+                self.shared.wake_receivers(inner);
+            }
+            return;
+        }
+        // Nobody can receive these any more, so they no longer count in `len`. Our buffer
+        // goes back in front of the queue, and the senders can `reclaim` the lot.
+        self.buffer.append(&mut inner.queue);
+        std::mem::swap(&mut self.buffer, &mut inner.queue);
+        self.shared
+            .len
+            .fetch_sub(inner.queue.len(), Ordering::Relaxed);
+        if inner.senders > 0 {
+            return;
+        }
+        // No sender left to reclaim them either. Take them out to drop them after the lock
+        // is released, in case dropping a message sends on this channel.
+        let unread = std::mem::take(&mut inner.queue);
+        drop(inner);
+        drop(unread);
+    }
 }
 
 impl<T> Receiver<T> {
     pub fn recv(&mut self) -> Option<T> {
         if let Some(t) = self.buffer.pop_front() {
-            return Some(t);
+            return Some(self.received_from_buffer(t));
         }
-        let mut inner = self.shared.inner.lock().unwrap();
+        let inner = self.shared.inner.lock().unwrap();
         /*
         queue.pop_front().unwrap()
         pop_front returns and option and what if there is no element is the queue.
         what practically we want to do is to wait till there is some data to receive.
-        For that we use Condvar in Inner
+        For that we wait in line on the Signal in Shared, see wait.rs
          */
-        loop {
-            match inner.queue.pop_front() {
-                Some(t) => {
-                    if !inner.queue.is_empty() {
-                        std::mem::swap(&mut self.buffer, &mut inner.queue);
-                    }
-                    return Some(t);
-                } // releases the mutex
-                None if inner.senders == 0 => return None,
-                None => {
-                    inner = self.shared.available.wait(inner).unwrap(); // wait requires you give up the guard and then wait, if it wakes up it take the mutex lock for you
-                }
+        // gives up the guard while waiting, and takes the mutex lock again for us when it wakes up
+        let (mut inner, _) = self.shared.wait_turn(inner, None);
+        // None only once the channel is finished. Counted before the lock is released, or
+        // another receiver could see the channel closed with this message still in `len`.
+        let t = inner.take(&mut self.buffer).map(|t| self.received(t));
+        self.shared.pass_on(&inner);
+        t
+    }
+
+    /*
+    Like recv, but never waits: when there is nothing to take it says why. `Empty` means a
+    message may still come, `Disconnected` that none ever will, which is the cue for a polling
+    loop to stop checking.
+    */
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        if let Some(t) = self.buffer.pop_front() {
+            return Ok(self.received_from_buffer(t));
+        }
+        let mut inner = self.shared.inner.lock().unwrap();
+        match inner.take(&mut self.buffer) {
+            Some(t) => Ok(self.received(t)),
+            None if self.shared.finished(&inner) => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /*
+    Like recv, but waits at most `timeout`. The Condvar may wake us up early, either
+    spuriously or because another message was taken already, so we wait against a fixed
+    deadline rather than restarting the full timeout on every wakeup.
+    */
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        if let Some(t) = self.buffer.pop_front() {
+            return Ok(self.received_from_buffer(t));
+        }
+        // Far enough in the future to never come, if adding overflows.
+        let deadline = Instant::now().checked_add(timeout);
+        let inner = self.shared.inner.lock().unwrap();
+        let (mut inner, turn) = self.shared.wait_turn(inner, deadline);
+        // A message queued when our time ran out belongs to someone ahead of us in line.
+        let t = match turn {
+            Turn::Ready => inner.take(&mut self.buffer).map(|t| self.received(t)),
+            Turn::TimedOut => None,
+        };
+        self.shared.pass_on(&inner);
+        match t {
+            Some(t) => Ok(t),
+            None if self.shared.finished(&inner) => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /*
+    Messages sent but not received yet, by any receiver. Those that sit in a receiver's
+    private buffer count too: they are still waiting to be received, even though they have
+    left the shared queue. Only a snapshot, like Sender::len.
+    */
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // How many senders are still alive. Zero means nothing new can arrive; the queued
+    // messages can still be received.
+    pub fn sender_count(&self) -> usize {
+        self.shared.inner.lock().unwrap().senders
+    }
+
+    /*
+    Blocks like `recv` until there is at least one message, then takes up to `max` of them
+    with a single lock. An empty Vec means the channel is closed. Panics if `max` is zero,
+    since an empty batch would look the same as a closed channel.
+    */
+    pub fn recv_batch(&mut self, max: usize) -> Vec<T> {
+        assert!(max > 0, "a batch needs room for at least one message");
+        let from_buffer = max.min(self.buffer.len());
+        let mut batch: Vec<T> = self.buffer.drain(..from_buffer).collect();
+        if batch.len() < max {
+            let mut inner = self.shared.inner.lock().unwrap();
+            if batch.is_empty() {
+                inner = self.shared.wait_turn(inner, None).0;
             }
+            let n = (max - batch.len()).min(inner.queue.len());
+            batch.extend(inner.queue.drain(..n));
+            // Under the lock, like `received`.
+            self.shared.len.fetch_sub(n, Ordering::Relaxed);
+            self.shared.pass_on(&inner);
         }
+        self.shared.unbuffer(from_buffer);
+        self.shared.counters.received(batch.len());
+        batch
+    }
+
+    // Moves every message that is queued right now into `out`, with a single lock and
+    // without blocking. Returns how many there were.
+    pub fn drain_into(&mut self, out: &mut Vec<T>) -> usize {
+        let from_buffer = self.buffer.len();
+        out.extend(self.buffer.drain(..));
+        let mut inner = self.shared.inner.lock().unwrap();
+        let from_queue = inner.queue.len();
+        out.extend(inner.queue.drain(..));
+        // Under the lock, like `received`.
+        self.shared.len.fetch_sub(from_queue, Ordering::Relaxed);
+        drop(inner);
+        self.shared.unbuffer(from_buffer);
+        self.shared.counters.received(from_buffer + from_queue);
+        from_buffer + from_queue
+    }
+
+    // Borrows the receiver for a `for` loop that blocks on every message and ends once the
+    // channel is closed. The receiver is still ours afterwards.
+    pub fn iter(&mut self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    // Like `iter`, but never blocks: it ends as soon as nothing is queued.
+    pub fn try_iter(&mut self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
+    /*
+    Gives up receiving and returns every message that hasn't been received yet, oldest
+    first: our buffer and everything in the queue, which the other receivers then won't
+    get either. Unlike dropping the receiver, nothing is left behind for `Sender::reclaim`.
+    */
+    pub fn into_pending(mut self) -> VecDeque<T> {
+        let mut pending = std::mem::take(&mut self.buffer);
+        let from_buffer = pending.len();
+        let mut inner = self.shared.inner.lock().unwrap();
+        pending.append(&mut inner.queue);
+        // Under the lock, like `received`.
+        self.shared
+            .len
+            .fetch_sub(pending.len() - from_buffer, Ordering::Relaxed);
+        drop(inner);
+        self.shared.unbuffer(from_buffer);
+        pending
+    }
+
+    /*
+    Closes the channel without dropping any handle, for shutdown when the handles live in
+    structs that outlive it. From then on every send fails, and the receivers get the
+    messages that were already sent, then None, just as if every Sender had been dropped.
+    Both sides can close; closing twice does nothing.
+    */
+    pub fn close(&self) {
+        self.shared.close();
+    }
+
+    // Every message a receive takes out of the queue passes through here to be taken off
+    // the count.
+    fn received(&self, t: T) -> T {
+        self.shared.len.fetch_sub(1, Ordering::Relaxed);
+        self.shared.counters.received(1);
+        t
+    }
+
+    // The same for a message from our buffer, without the lock held.
+    fn received_from_buffer(&self, t: T) -> T {
+        self.shared.unbuffer(1);
+        self.shared.counters.received(1);
+        t
     }
 }
 
@@ -109,11 +495,55 @@ impl<T> Receiver<T> {
 struct Inner<T> {
     queue: VecDeque<T>,
     senders: usize,
+    // Like `senders`: once the last receiver is dropped, send refuses new messages.
+    receivers: usize,
+    // Set by `close`; then it's as if both counts had dropped to zero.
+    closed: bool,
+    // Receivers blocked waiting for a message, served in this order, see wait.rs.
+    line: Line,
+    next_ticket: u64,
+    #[cfg(feature = "async")]
+    wakers: stream::Wakers,
+    #[cfg(feature = "async")]
+    next_key: u64,
+}
+
+impl<T> Inner<T> {
+    fn closed_for_send(&self) -> bool {
+        self.receivers == 0 || self.closed
+    }
+
+    // No new message can arrive. The queued ones can still be received.
+    fn closed_for_recv(&self) -> bool {
+        self.senders == 0 || self.closed
+    }
+
+    /*
+    Takes the next message for a receiver holding the lock. With a single receiver, the rest
+    of the queue moves into its buffer at the same time, so its following recvs don't need
+    the lock at all. With more than one, that would hide those messages from the others while
+    they sit idle, so every message is taken out on its own instead.
+    */
+    fn take(&mut self, buffer: &mut VecDeque<T>) -> Option<T> {
+        let t = self.queue.pop_front()?;
+        if self.receivers == 1 && !self.queue.is_empty() {
+            std::mem::swap(buffer, &mut self.queue);
+        }
+        Some(t)
+    }
 }
 
 struct Shared<T> {
     inner: Mutex<Inner<T>>,
-    available: Condvar,
+    available: Signal,
+    // The queue plus every receiver's buffer. Receiving from the buffer doesn't take the
+    // lock, so this can't be in Inner.
+    len: AtomicUsize,
+    // Set once a Receiver is cloned; until then no other receiver can be waiting on our
+    // buffer, see `unbuffer`.
+    cloned: AtomicBool,
+    // Only counts with the `metrics` feature.
+    counters: Counters,
     /*
     the condvar needs to be outside the mutex, imagine you're currently holding the mutex and  u relalize you to
     wake other people up , the person u wake up has to take the mutex, but you are currently holding the mutex and they try to take the mutex
@@ -121,6 +551,53 @@ struct Shared<T> {
     */
 }
 
+impl<T> Shared<T> {
+    fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        // Wake every waiting receiver so they all see the channel is closed.
+        self.wake_receivers(inner);
+    }
+
+    // Wakes every waiting receiver, threads and tasks, after releasing the lock.
+    fn wake_receivers(
+        &self,
+        #[cfg_attr(not(feature = "async"), allow(unused_mut))] mut inner: MutexGuard<'_, Inner<T>>,
+    ) {
+        let everyone = self.available.first(&inner.line, usize::MAX);
+        #[cfg(feature = "async")]
+        let tasks = std::mem::take(&mut inner.wakers);
+        drop(inner);
+        self.available.wake(everyone);
+        #[cfg(feature = "async")]
+        stream::wake_all(tasks);
+    }
+
+    /*
+    Whether a receiver that found the queue empty has seen the end. The channel being closed
+    isn't enough: a receiver that was cloned while it had buffered messages still holds them,
+    and hands them back to the queue if it's dropped before receiving them, so the others
+    must not give up while the count says something is left somewhere.
+    */
+    fn finished(&self, inner: &Inner<T>) -> bool {
+        inner.closed_for_recv() && self.len.load(Ordering::Relaxed) == 0
+    }
+
+    /*
+    Takes `n` messages off the count that a receiver took out of its own buffer, without the
+    lock. If that was the last of them, another receiver may be waiting in `finished`'s
+    sense for exactly these, so it must be woken to see the end. Taking the lock before
+    notifying means a receiver that read the old count is already asleep, and the notify
+    can't be lost. A channel that was never cloned has only one receiver, so it skips this.
+    */
+    fn unbuffer(&self, n: usize) {
+        let before = self.len.fetch_sub(n, Ordering::Relaxed);
+        if n > 0 && before == n && self.cloned.load(Ordering::Relaxed) {
+            self.wake_receivers(self.inner.lock().unwrap());
+        }
+    }
+}
+
 impl<T> Iterator for Receiver<T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
@@ -128,15 +605,56 @@ impl<T> Iterator for Receiver<T> {
     }
 }
 
+/// Blocking iterator returned by [`Receiver::iter`].
+pub struct Iter<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv()
+    }
+}
+
+/// Non-blocking iterator returned by [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    channel_with_strategy(WaitStrategy::Condvar)
+}
+
+// Like `channel`, but receivers wait the way `strategy` says: trade CPU for latency with
+// Park or Spin.
+pub fn channel_with_strategy<T>(strategy: WaitStrategy) -> (Sender<T>, Receiver<T>) {
     let inner = Inner {
         queue: VecDeque::default(),
         senders: 1,
+        receivers: 1,
+        closed: false,
+        line: Line::new(),
+        next_ticket: 0,
+        #[cfg(feature = "async")]
+        wakers: stream::Wakers::new(),
+        #[cfg(feature = "async")]
+        next_key: 0,
     };
 
     let shared = Shared {
         inner: Mutex::new(inner),
-        available: Condvar::new(),
+        available: Signal::new(strategy),
+        len: AtomicUsize::new(0),
+        cloned: AtomicBool::new(false),
+        counters: Counters::default(),
     };
 
     let shared = Arc::new(shared);
@@ -147,6 +665,8 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
         Receiver {
             shared: shared.clone(),
             buffer: VecDeque::default(),
+            #[cfg(feature = "async")]
+            key: None,
         },
     )
 }
@@ -158,7 +678,7 @@ mod tests {
     #[test]
     fn ping_pong() {
         let (mut tx, mut rx) = channel();
-        tx.send(42);
+        tx.send(42).unwrap();
         assert_eq!(rx.recv(), Some(42));
     }
 
@@ -169,11 +689,282 @@ mod tests {
         assert_eq!(rx.recv(), None);
     }
 
+    #[test]
+    fn try_recv_never_blocks() {
+        let (mut tx, mut rx) = channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        drop(tx);
+        // Messages sent before the last sender left still come out first.
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout_waits_at_most_the_timeout() {
+        let (mut tx, mut rx) = channel();
+        let start = Instant::now();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(20)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            tx.send(7).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(7));
+        sender.join().unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn cloned_receivers_share_the_messages() {
+        let (mut tx, rx) = channel();
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut rx = rx.clone();
+                std::thread::spawn(move || {
+                    let mut got = Vec::new();
+                    while let Some(t) = rx.recv() {
+                        got.push(t);
+                    }
+                    got
+                })
+            })
+            .collect();
+        drop(rx);
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+        // Dropping the last sender has to wake every worker, not just one.
+        drop(tx);
+        let mut all: Vec<_> = workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect();
+        all.sort();
+        assert_eq!(all, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dropped_receiver_returns_its_buffer() {
+        let (mut tx, mut rx) = channel();
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        // Alone, the receiver took 1 and 2 into its buffer along with 0.
+        assert_eq!(rx.recv(), Some(0));
+        let mut rx2 = rx.clone();
+        tx.send(3).unwrap();
+        drop(rx);
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Ok(3));
+        drop(rx2);
+        assert_eq!(tx.send(4), Err(SendError(4)));
+    }
+
+    #[test]
+    fn len_counts_buffered_messages() {
+        let (mut tx, mut rx) = channel();
+        assert!(tx.is_empty() && rx.is_empty());
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(tx.len(), 5);
+        // The other four move into the receiver's buffer, but they still count.
+        assert_eq!(rx.recv(), Some(0));
+        assert_eq!((tx.len(), rx.len()), (4, 4));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.recv_timeout(Duration::ZERO), Ok(2));
+        assert_eq!(tx.len(), 2);
+        let rx2 = rx.clone();
+        drop(rx);
+        assert_eq!(tx.len(), 2);
+        drop(rx2);
+        assert!(tx.is_empty());
+    }
+
     #[test]
     fn closed_rx() {
         let (mut tx, rx) = channel::<i32>();
         drop(rx);
-        tx.send(42);
-        // assert_eq!(rx.recv(), None);
+        assert_eq!(tx.send(42), Err(SendError(42)));
+    }
+
+    #[test]
+    fn iter_borrows_the_receiver() {
+        let (mut tx, mut rx) = channel();
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(rx.try_iter().next(), None);
+        tx.send(3).unwrap();
+        drop(tx);
+        let mut got = Vec::new();
+        for i in rx.iter() {
+            got.push(i);
+        }
+        assert_eq!(got, [3]);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn batches() {
+        let (mut tx, mut rx) = channel();
+        tx.send_all(0..5).unwrap();
+        tx.send(5).unwrap();
+        assert_eq!(rx.len(), 6);
+        assert_eq!(rx.recv_batch(2), [0, 1]);
+        let mut out = vec![-1];
+        assert_eq!(rx.drain_into(&mut out), 4);
+        assert_eq!(out, [-1, 2, 3, 4, 5]);
+        assert_eq!(rx.drain_into(&mut out), 0);
+        assert!(rx.is_empty());
+
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            tx.send_all([6, 7]).unwrap();
+        });
+        // Waits for the first message, and takes whatever else came with it.
+        assert_eq!(rx.recv_batch(10), [6, 7]);
+        sender.join().unwrap();
+        assert!(rx.recv_batch(10).is_empty());
+    }
+
+    #[test]
+    fn send_all_to_a_closed_channel() {
+        let (mut tx, rx) = channel();
+        drop(rx);
+        assert_eq!(tx.send_all([1, 2]), Err(SendError(vec![1, 2])));
+    }
+
+    #[test]
+    fn close_lets_the_receiver_drain() {
+        let (mut tx, mut rx) = channel();
+        let weak = tx.downgrade();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        tx.close();
+        assert!(tx.is_disconnected());
+        assert_eq!(tx.send(3), Err(SendError(3)));
+        assert!(weak.upgrade().is_none());
+        assert_eq!(rx.iter().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        // Closing twice is fine.
+        rx.close();
+    }
+
+    #[test]
+    fn close_wakes_a_waiting_receiver() {
+        let (tx, mut rx) = channel::<()>();
+        let rx2 = rx.clone();
+        let waiter = std::thread::spawn(move || rx.recv());
+        std::thread::sleep(Duration::from_millis(10));
+        // The receiving side can close too, while every handle is still alive.
+        rx2.close();
+        assert_eq!(waiter.join().unwrap(), None);
+        assert!(tx.is_disconnected());
+    }
+
+    #[test]
+    fn into_pending_returns_the_unread_messages() {
+        let (mut tx, mut rx) = channel();
+        tx.send_all(0..4).unwrap();
+        // Moves the rest of the queue into the buffer.
+        assert_eq!(rx.recv(), Some(0));
+        tx.send(4).unwrap();
+        assert_eq!(rx.into_pending(), [1, 2, 3, 4]);
+        assert!(tx.is_empty() && tx.is_disconnected());
+        assert!(tx.reclaim().is_empty());
+    }
+
+    #[test]
+    fn senders_reclaim_what_the_receiver_left() {
+        let (mut tx, mut rx) = channel();
+        tx.send_all(0..3).unwrap();
+        assert_eq!(rx.recv(), Some(0));
+        assert!(tx.reclaim().is_empty());
+        drop(rx);
+        assert!(tx.is_empty());
+        assert_eq!(tx.send(3), Err(SendError(3)));
+        assert_eq!(tx.reclaim(), [1, 2]);
+        assert!(tx.reclaim().is_empty());
+    }
+
+    #[test]
+    fn unreclaimed_messages_drop_with_the_last_sender() {
+        let (mut tx, rx) = channel();
+        let message = Arc::new(());
+        tx.send(Arc::clone(&message)).unwrap();
+        let weak = tx.downgrade();
+        drop(rx);
+        assert_eq!(Arc::strong_count(&message), 2);
+        // The weak sender keeps the shared state alive, but not the messages.
+        drop(tx);
+        assert_eq!(Arc::strong_count(&message), 1);
+        drop(weak);
+    }
+
+    #[test]
+    fn buffered_messages_delay_the_end_for_clones() {
+        let (mut tx, mut rx) = channel();
+        tx.send_all(0..3).unwrap();
+        drop(tx);
+        // The lone receiver takes 0 and buffers 1 and 2, then gets a clone.
+        assert_eq!(rx.recv(), Some(0));
+        let mut rx2 = rx.clone();
+        // The channel is closed and its queue is empty, but rx still has messages.
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+        let waiter = std::thread::spawn(move || rx2.recv());
+        std::thread::sleep(Duration::from_millis(10));
+        // Once rx has received them all, the waiting clone sees the end.
+        assert_eq!(rx.iter().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(waiter.join().unwrap(), None);
+    }
+
+    #[test]
+    fn weak_sender_does_not_keep_the_channel_open() {
+        let (tx, mut rx) = channel();
+        let weak = tx.downgrade();
+        let mut upgraded = weak.upgrade().unwrap();
+        assert_eq!(rx.sender_count(), 2);
+        upgraded.send(1).unwrap();
+        drop(upgraded);
+        drop(tx);
+        // Only the weak sender is left, so the channel is closed.
+        assert_eq!(rx.recv(), Some(1));
+        assert_eq!(rx.recv(), None);
+        assert!(weak.clone().upgrade().is_none());
+    }
+
+    #[test]
+    fn weak_sender_needs_a_receiver() {
+        let (tx, rx) = channel::<()>();
+        let weak = tx.downgrade();
+        drop(rx);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn disconnection_is_visible_without_sending() {
+        let (tx, rx) = channel::<i32>();
+        let tx2 = tx.clone();
+        let rx2 = rx.clone();
+        assert_eq!(rx.sender_count(), 2);
+        drop(tx2);
+        assert_eq!(rx2.sender_count(), 1);
+        drop(rx);
+        assert!(!tx.is_disconnected());
+        drop(rx2);
+        assert!(tx.is_disconnected());
     }
 }
@@ -0,0 +1,150 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/*
+CPUs don't move single bytes between their caches, they move whole cache lines. If two
+atomics that different threads write to share a line, every write by one thread invalidates
+the line in the other thread's cache, even though they never touch the same variable. That
+is false sharing, and on a busy queue it can cost more than the atomic operations themselves.
+
+CachePadded<T> aligns its value to the size of a cache line, and since a type's size is
+always a multiple of its alignment, nothing else can end up on the same line.
+
+The line size depends on the architecture:
+- x86_64 and powerpc64 have 64-byte lines, but the prefetcher pulls them in pairs, and
+  Apple's aarch64 chips have 128-byte lines; 128 bytes covers all of them.
+- s390x has 256-byte lines.
+- 32-bit arm, mips and riscv mostly have 32-byte lines.
+- 64 bytes everywhere else.
+*/
+
+/// Pads and aligns a value to the length of a cache line, so that it doesn't share one with
+/// its neighbours.
+#[cfg_attr(
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64",
+    ),
+    repr(align(128))
+)]
+#[cfg_attr(target_arch = "s390x", repr(align(256)))]
+#[cfg_attr(
+    any(
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "riscv32",
+    ),
+    repr(align(32))
+)]
+#[cfg_attr(
+    not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64",
+        target_arch = "s390x",
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "riscv32",
+    )),
+    repr(align(64))
+)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    pub const fn new(value: T) -> CachePadded<T> {
+        CachePadded { value }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    fn from(value: T) -> CachePadded<T> {
+        CachePadded::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachePadded")
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        mem,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    #[test]
+    fn fills_a_whole_line() {
+        let align = mem::align_of::<CachePadded<u8>>();
+        assert!(align >= 32 && align.is_power_of_two());
+        assert_eq!(mem::size_of::<CachePadded<u8>>(), align);
+        assert_eq!(mem::size_of::<CachePadded<[u8; 129]>>() % align, 0);
+
+        // Two padded values next to each other never share a line.
+        let pair = [CachePadded::new(1u8), CachePadded::new(2u8)];
+        let first = &*pair[0] as *const u8 as usize;
+        let second = &*pair[1] as *const u8 as usize;
+        assert!(second - first >= align);
+    }
+
+    #[test]
+    fn derefs_to_the_value() {
+        let mut padded = CachePadded::from(vec![1, 2]);
+        padded.push(3);
+        assert_eq!(padded.len(), 3);
+        assert_eq!(padded.into_inner(), [1, 2, 3]);
+        assert_eq!(
+            format!("{:?}", CachePadded::new(7)),
+            "CachePadded { value: 7 }"
+        );
+    }
+
+    #[test]
+    fn counters_on_separate_lines() {
+        let counters: [CachePadded<AtomicUsize>; 4] = Default::default();
+        thread::scope(|s| {
+            for counter in &counters {
+                s.spawn(move || {
+                    for _ in 0..1000 {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert!(counters
+            .iter()
+            .all(|counter| counter.load(Ordering::Relaxed) == 1000));
+    }
+}
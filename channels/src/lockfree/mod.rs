@@ -0,0 +1,19 @@
+/*
+Lock-free counterparts to the Mutex-based channel in lib.rs. Instead of taking a lock,
+every operation reads the current state, prepares a change, and installs it with a single
+compare-and-swap, retrying if another thread got there first. No thread ever waits for
+another one to release anything, so a thread that gets descheduled mid-operation can't
+hold everybody else up.
+
+Removed nodes can't be freed right away because other threads may still be reading them;
+the reclaim module takes care of that with hazard pointers.
+*/
+
+mod cache_padded;
+mod queue;
+pub mod reclaim;
+mod stack;
+
+pub use cache_padded::CachePadded;
+pub use queue::Queue;
+pub use stack::Stack;
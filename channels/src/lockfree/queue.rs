@@ -0,0 +1,251 @@
+use std::{mem::MaybeUninit, ptr};
+
+use super::{
+    reclaim::{self, HazardPointer},
+    CachePadded,
+};
+use crate::sys::atomic::{AtomicPtr, Ordering};
+
+/// A lock-free multi-producer, multi-consumer FIFO queue (the Michael-Scott queue).
+///
+/// The queue is a singly linked list that always starts with a sentinel node. `head` points
+/// at the sentinel and the first value sits in the node after it; `tail` points at the last
+/// node or, briefly, at the one before it. `push` links a node after the last one and then
+/// swings `tail`; `pop` swings `head` to the next node, which becomes the new sentinel once
+/// its value is moved out.
+///
+/// Producers only touch `tail` and consumers only touch `head`, so unlike our Mutex-backed
+/// channel, a push and a pop never get in each other's way. The two pointers are padded to
+/// separate cache lines so that they don't slow each other down through false sharing either.
+pub struct Queue<T> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+    tail: CachePadded<AtomicPtr<Node<T>>>,
+}
+
+struct Node<T> {
+    // Uninitialized in the sentinel: either the very first one, or a node whose value has
+    // already been popped.
+    value: MaybeUninit<T>,
+    // Null while the node is last, set exactly once when the next node is linked.
+    next: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: MaybeUninit<T>) -> *mut Node<T> {
+        Box::into_raw(Box::new(Node {
+            value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+// Values move between threads through the queue, so `T: Send` is all we need.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+    pub fn new() -> Queue<T> {
+        let sentinel = Node::new(MaybeUninit::uninit());
+        Queue {
+            head: CachePadded::new(AtomicPtr::new(sentinel)),
+            tail: CachePadded::new(AtomicPtr::new(sentinel)),
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let node = Node::new(MaybeUninit::new(value));
+        let hazard = HazardPointer::new();
+        loop {
+            let tail = hazard.protect(&self.tail);
+            // SAFETY: the hazard pointer keeps `tail` alive.
+            let next = unsafe { &(*tail).next }.load(Ordering::Acquire);
+            if !next.is_null() {
+                // Another push linked its node but hasn't moved `tail` yet. Help it along
+                // instead of waiting for it; that's what keeps the queue lock-free.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            // SAFETY: as above.
+            let linked = unsafe { &(*tail).next }.compare_exchange(
+                ptr::null_mut(),
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            );
+            if linked.is_ok() {
+                // If this fails, someone already helped us.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, node, Ordering::Release, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        // One for the sentinel we read `next` from, one for the node we take the value out of.
+        let head_hazard = HazardPointer::new();
+        let next_hazard = HazardPointer::new();
+        loop {
+            let head = head_hazard.protect(&self.head);
+            // SAFETY: the hazard pointer keeps `head` alive.
+            let next = next_hazard.protect(unsafe { &(*head).next });
+            /*
+            `next` is only safe to use if it hadn't been popped and freed before we protected
+            it. It can't have been while `head` is still the sentinel: it would have to
+            become the sentinel first.
+            */
+            if self.head.load(Ordering::Acquire) != head {
+                continue;
+            }
+            if next.is_null() {
+                return None;
+            }
+            let tail = self.tail.load(Ordering::Acquire);
+            if head == tail {
+                // `tail` is lagging behind a push that is still in progress; move it along
+                // so that it never points at a node we are about to retire.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                // SAFETY: our CAS made `next` the sentinel, so we are the only one taking its
+                // value, and `head` is unreachable now and ours to retire. Its own value was
+                // taken when it became the sentinel, and `MaybeUninit` won't drop it again.
+                unsafe {
+                    let value = (*next).value.assume_init_read();
+                    head_hazard.reset();
+                    next_hazard.reset();
+                    reclaim::retire(head);
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    // Only a snapshot: other threads may push or pop right after.
+    pub fn is_empty(&self) -> bool {
+        let hazard = HazardPointer::new();
+        let head = hazard.protect(&self.head);
+        // SAFETY: the hazard pointer keeps `head` alive.
+        unsafe { &(*head).next }.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // `&mut self` means nobody else uses the queue, so free the nodes directly. The
+        // first one is the sentinel and has no value.
+        // SAFETY: every node came from `Node::new`, and only the ones after the sentinel
+        // hold a value.
+        unsafe {
+            let sentinel = Box::from_raw(self.head.load(Ordering::Relaxed));
+            let mut node = sentinel.next.load(Ordering::Relaxed);
+            while !node.is_null() {
+                let mut boxed = Box::from_raw(node);
+                boxed.value.assume_init_drop();
+                node = boxed.next.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{atomic::AtomicUsize, Arc, Mutex},
+        thread,
+    };
+
+    #[test]
+    fn fifo() {
+        let queue = Queue::new();
+        assert!(queue.is_empty());
+        queue.push(1);
+        queue.push(2);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.pop(), Some(1));
+        queue.push(3);
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn mpmc_keeps_each_producers_order() {
+        const PRODUCERS: usize = 4;
+        const PER_PRODUCER: usize = 2000;
+        let queue = Queue::new();
+        let received = Mutex::new(Vec::new());
+        let remaining = AtomicUsize::new(PRODUCERS * PER_PRODUCER);
+        thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                let queue = &queue;
+                s.spawn(move || {
+                    for i in 0..PER_PRODUCER {
+                        queue.push((p, i));
+                    }
+                });
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut mine = Vec::new();
+                    while remaining.load(Ordering::SeqCst) > 0 {
+                        match queue.pop() {
+                            Some(item) => {
+                                remaining.fetch_sub(1, Ordering::SeqCst);
+                                mine.push(item);
+                            }
+                            None => thread::yield_now(),
+                        }
+                    }
+                    received.lock().unwrap().push(mine);
+                });
+            }
+        });
+
+        let received = received.into_inner().unwrap();
+        // Every consumer saw each producer's values in the order they were pushed.
+        for mine in &received {
+            for p in 0..PRODUCERS {
+                let from_p: Vec<_> = mine.iter().filter(|(q, _)| *q == p).collect();
+                assert!(from_p.windows(2).all(|w| w[0].1 < w[1].1));
+            }
+        }
+        let total: usize = received.iter().map(Vec::len).sum();
+        assert_eq!(total, PRODUCERS * PER_PRODUCER);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn values_dropped_once() {
+        let value = Arc::new(());
+        let queue = Queue::new();
+        for _ in 0..10 {
+            queue.push(Arc::clone(&value));
+        }
+        for _ in 0..4 {
+            drop(queue.pop());
+        }
+        reclaim::collect();
+        assert_eq!(Arc::strong_count(&value), 7);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
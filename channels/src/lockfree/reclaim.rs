@@ -0,0 +1,268 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    mem, ptr,
+    sync::{
+        atomic::{self, AtomicBool, AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+use crate::sys;
+
+/*
+The hard part of a lock-free structure isn't the CAS loop, it's knowing when a removed node
+can be freed. Another thread may have loaded the same pointer just before we unlinked it and
+be about to read through it. Freeing it right away would be a use-after-free, and reusing the
+address would make that thread's CAS succeed when it shouldn't (the ABA problem).
+
+Hazard pointers solve that: before a thread dereferences a shared pointer, it publishes it
+in a hazard slot that every thread can see. A removed node is only "retired", and it's freed
+later by a scan that skips every pointer currently published in some slot.
+*/
+
+// One published pointer. Slots are never freed; a thread that is done with its slot marks
+// it inactive so another thread can claim it.
+struct Slot {
+    active: AtomicBool,
+    ptr: AtomicPtr<()>,
+    // Written once before the slot is published, never changed after.
+    next: *const Slot,
+}
+
+// All slots ever created, as a push-only linked list.
+static SLOTS: AtomicPtr<Slot> = AtomicPtr::new(ptr::null_mut());
+
+// A retired node, type-erased so nodes of different structures can sit in one list.
+struct Retired {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+// The retired node is only freed by whichever thread finds it unprotected; `retire`
+// requires that to be fine.
+unsafe impl Send for Retired {}
+
+// Retired nodes left behind by threads that exited while some of them were still protected.
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+// A thread scans its retired list once it gets this long, so every scan frees many nodes.
+const SCAN_THRESHOLD: usize = 64;
+
+struct RetiredList(RefCell<Vec<Retired>>);
+
+impl Drop for RetiredList {
+    fn drop(&mut self) {
+        // The thread is exiting. Free what we can and leave the rest to other threads.
+        let mut retired = mem::take(self.0.get_mut());
+        scan(&mut retired);
+        ORPHANS.lock().unwrap().append(&mut retired);
+    }
+}
+
+thread_local! {
+    static RETIRED: RetiredList = const { RetiredList(RefCell::new(Vec::new())) };
+}
+
+/// A hazard slot owned by the current thread. Whatever pointer it protects won't be freed
+/// by [`retire`] until it is [`reset`](HazardPointer::reset) or dropped.
+pub struct HazardPointer {
+    slot: &'static Slot,
+}
+
+impl HazardPointer {
+    // Claims a free slot, or adds a new one if every slot is taken.
+    pub fn new() -> HazardPointer {
+        let mut current = SLOTS.load(Ordering::Acquire);
+        // SAFETY: slots are leaked, so every pointer in the list stays valid forever.
+        while let Some(slot) = unsafe { current.as_ref() } {
+            if !slot.active.load(Ordering::Relaxed)
+                && slot
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return HazardPointer { slot };
+            }
+            current = slot.next as *mut Slot;
+        }
+
+        let slot = Box::into_raw(Box::new(Slot {
+            active: AtomicBool::new(true),
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            next: ptr::null(),
+        }));
+        let mut head = SLOTS.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: nobody else can see the slot until the CAS below publishes it.
+            unsafe { (*slot).next = head };
+            match SLOTS.compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(h) => head = h,
+            }
+        }
+        // SAFETY: the slot is never freed.
+        HazardPointer {
+            slot: unsafe { &*slot },
+        }
+    }
+
+    // Loads `src` and protects the pointer it holds. The returned pointer stays valid to
+    // dereference until this hazard pointer protects something else, as long as whoever
+    // removes it from `src` frees it through `retire`.
+    pub fn protect<T>(&self, src: &sys::atomic::AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(Ordering::Relaxed);
+        loop {
+            self.slot.ptr.store(ptr.cast(), Ordering::SeqCst);
+            // Publishing isn't enough on its own: the node may have been removed and
+            // scanned between our load and our store. If `src` still holds it, it hadn't
+            // been removed when we published, so any later scan will see our slot.
+            let again = src.load(Ordering::SeqCst);
+            if again == ptr {
+                return ptr;
+            }
+            ptr = again;
+        }
+    }
+
+    pub fn reset(&self) {
+        self.slot.ptr.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+impl Default for HazardPointer {
+    fn default() -> Self {
+        HazardPointer::new()
+    }
+}
+
+impl Drop for HazardPointer {
+    fn drop(&mut self) {
+        self.reset();
+        self.slot.active.store(false, Ordering::Release);
+    }
+}
+
+/// Frees `ptr` as a `Box<T>` once no hazard pointer protects it any more.
+///
+/// # Safety
+///
+/// `ptr` must come from `Box::into_raw`, must already be unreachable for threads that
+/// haven't protected it yet, and must not be retired twice. It may be freed on any thread.
+pub unsafe fn retire<T>(ptr: *mut T) {
+    unsafe fn drop_box<T>(ptr: *mut ()) {
+        drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+    }
+    let retired = Retired {
+        ptr: ptr.cast(),
+        drop: drop_box::<T>,
+    };
+    let pushed = RETIRED.try_with(|list| {
+        let mut list = list.0.borrow_mut();
+        list.push(retired);
+        list.len() >= SCAN_THRESHOLD
+    });
+    match pushed {
+        Ok(true) => collect(),
+        Ok(false) => {}
+        // The thread-local list is already gone because the thread is exiting.
+        Err(_) => ORPHANS.lock().unwrap().push(Retired {
+            ptr: ptr.cast(),
+            drop: drop_box::<T>,
+        }),
+    }
+}
+
+/// Frees every node retired by this thread, and every orphaned one, that no hazard pointer
+/// protects. [`retire`] calls it now and then on its own; call it directly to release
+/// memory sooner, for example after a burst of removals.
+pub fn collect() {
+    // Take the list out first: freeing a node may drop a value whose own Drop retires more.
+    let Ok(mut retired) = RETIRED.try_with(|list| mem::take(&mut *list.0.borrow_mut())) else {
+        return;
+    };
+    if let Ok(mut orphans) = ORPHANS.try_lock() {
+        retired.append(&mut orphans);
+    }
+    scan(&mut retired);
+    let _ = RETIRED.try_with(|list| list.0.borrow_mut().append(&mut retired));
+}
+
+// Frees the nodes in `retired` that aren't protected, keeping the rest.
+fn scan(retired: &mut Vec<Retired>) {
+    // Pairs with the SeqCst accesses in `protect`: every node in the list was removed
+    // before this point, so a thread that protects one after it will see it gone.
+    atomic::fence(Ordering::SeqCst);
+    let mut hazards = HashSet::new();
+    let mut current = SLOTS.load(Ordering::Acquire);
+    // SAFETY: slots are never freed.
+    while let Some(slot) = unsafe { current.as_ref() } {
+        let ptr = slot.ptr.load(Ordering::SeqCst);
+        if !ptr.is_null() {
+            hazards.insert(ptr);
+        }
+        current = slot.next as *mut Slot;
+    }
+
+    let (keep, free): (Vec<_>, Vec<_>) = mem::take(retired)
+        .into_iter()
+        .partition(|r| hazards.contains(&r.ptr));
+    *retired = keep;
+    for r in free {
+        // SAFETY: the node was retired, so it's unreachable, and nobody protects it.
+        unsafe { (r.drop)(r.ptr) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    struct CountDrops(Arc<AtomicUsize>);
+
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn protected_nodes_outlive_retire() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let node = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+        let shared = AtomicPtr::new(node);
+
+        let hazard = HazardPointer::new();
+        assert_eq!(hazard.protect(&shared), node);
+        shared.store(ptr::null_mut(), Ordering::SeqCst);
+        unsafe { retire(node) };
+        collect();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        drop(hazard);
+        collect();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn exiting_thread_leaves_protected_nodes_behind() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let node = Box::into_raw(Box::new(CountDrops(Arc::clone(&drops))));
+        let shared = AtomicPtr::new(node);
+        let hazard = HazardPointer::new();
+        hazard.protect(&shared);
+
+        let node = node as usize;
+        std::thread::spawn(move || unsafe { retire(node as *mut CountDrops) })
+            .join()
+            .unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // The node now sits in the orphan list, and any thread's scan can free it.
+        drop(hazard);
+        while drops.load(Ordering::SeqCst) == 0 {
+            collect();
+        }
+    }
+}
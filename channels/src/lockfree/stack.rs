@@ -0,0 +1,190 @@
+use std::{mem::ManuallyDrop, ptr};
+
+use super::reclaim::{self, HazardPointer};
+use crate::sys::{
+    atomic::{AtomicPtr, Ordering},
+    const_fn,
+};
+
+/// A lock-free LIFO stack (Treiber's stack).
+///
+/// The stack is a singly linked list and `head` points at the top node. `push` links a new
+/// node in front of the current head and swings `head` to it with a CAS; `pop` swings `head`
+/// to the second node. If the CAS fails, another thread changed the top in the meantime,
+/// and we start over with the new one.
+pub struct Stack<T> {
+    head: AtomicPtr<Node<T>>,
+}
+
+struct Node<T> {
+    // `pop` moves the value out before the node is retired, so freeing the node later must
+    // not drop it again.
+    value: ManuallyDrop<T>,
+    // Never changes once the node is pushed.
+    next: *mut Node<T>,
+}
+
+// Values move between threads through the stack, so `T: Send` is all we need, just like for
+// our channel.
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+impl<T> Stack<T> {
+    const_fn! {
+        pub const fn new() -> Stack<T> {
+            Stack {
+                head: AtomicPtr::new(ptr::null_mut()),
+            }
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(Node {
+            value: ManuallyDrop::new(value),
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the node isn't shared until the CAS succeeds.
+            unsafe { (*node).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let hazard = HazardPointer::new();
+        loop {
+            /*
+            Protecting the head before reading `next` is what makes this safe: without it
+            another thread could pop and free the node between our load and our read. It
+            also rules out ABA, because the node can't be freed and its address handed out
+            again while we still hold it.
+            */
+            let head = hazard.protect(&self.head);
+            if head.is_null() {
+                return None;
+            }
+            // SAFETY: the hazard pointer keeps `head` alive.
+            let next = unsafe { (*head).next };
+            if self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                hazard.reset();
+                // SAFETY: our CAS unlinked the node, so we are the only one taking its
+                // value, and nobody can reach it any more to retire it again.
+                unsafe {
+                    let value = ptr::read(&*(*head).value);
+                    reclaim::retire(head);
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    // Only a snapshot: other threads may push or pop right after.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Stack::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // `&mut self` means no other thread is using the stack, so the nodes can be freed
+        // directly without going through `retire`. (A relaxed load rather than `get_mut`,
+        // which loom's atomics don't have.)
+        let mut node = self.head.load(Ordering::Relaxed);
+        while !node.is_null() {
+            // SAFETY: every node in the list came from `Box::into_raw` in `push`.
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { ManuallyDrop::drop(&mut boxed.value) };
+            node = boxed.next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{atomic::AtomicUsize, Arc},
+        thread,
+    };
+
+    #[test]
+    fn lifo() {
+        let stack = Stack::new();
+        assert!(stack.is_empty());
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+        stack.push(4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn concurrent_push_pop() {
+        let stack = Stack::new();
+        let popped = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for t in 0..4 {
+                let stack = &stack;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        stack.push(t * 1000 + i);
+                    }
+                });
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut sum = 0;
+                    for _ in 0..1000 {
+                        loop {
+                            if let Some(v) = stack.pop() {
+                                sum += v;
+                                break;
+                            }
+                            thread::yield_now();
+                        }
+                    }
+                    popped.fetch_add(sum, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(stack.is_empty());
+        assert_eq!(popped.load(Ordering::SeqCst), (0..4000).sum());
+    }
+
+    #[test]
+    fn values_dropped_once() {
+        let value = Arc::new(());
+        let stack = Stack::new();
+        for _ in 0..10 {
+            stack.push(Arc::clone(&value));
+        }
+        for _ in 0..5 {
+            drop(stack.pop());
+        }
+        reclaim::collect();
+        assert_eq!(Arc::strong_count(&value), 6);
+        drop(stack);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
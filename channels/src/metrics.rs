@@ -0,0 +1,135 @@
+/*
+Counters for watching a channel under load, with the `metrics` feature. They live in the
+shared state, next to `len`, and every handle can read them, so a benchmark can report
+backpressure without wrapping each send and recv.
+
+Every counter is a relaxed atomic, bumped where the channel already does the work: a send
+counts while it still holds the lock, a receive where it takes the message off `len`. They
+don't order anything, and a snapshot taken while other threads are busy may be a message
+or two apart between counters.
+
+Without the feature, Counters is an empty struct whose methods do nothing, so the call
+sites don't need a cfg each and the channel pays nothing for them.
+*/
+
+#[cfg(feature = "metrics")]
+use crate::{
+    sys::atomic::{AtomicU64, AtomicUsize, Ordering},
+    Receiver, Sender,
+};
+
+/// What a channel has done since it was created, returned by [`Sender::stats`] and
+/// [`Receiver::stats`].
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    // Messages sent, through `send` or `send_all`.
+    pub sent: u64,
+    // Messages handed out by any receive: recv and friends, batches, drains and `Peek::take`.
+    // Not the ones returned by `into_pending` or `Sender::reclaim`.
+    pub received: u64,
+    // How often a receiver found nothing and had to wait, as a thread or as a task.
+    pub waits: u64,
+    // The most messages that were waiting to be received at once, counted like `len`.
+    pub max_depth: usize,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+pub(crate) struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    waits: AtomicU64,
+    max_depth: AtomicUsize,
+}
+
+#[cfg(feature = "metrics")]
+impl Counters {
+    // `depth` is `len` right after the messages were added.
+    pub(crate) fn sent(&self, n: usize, depth: usize) {
+        self.sent.fetch_add(n as u64, Ordering::Relaxed);
+        self.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    pub(crate) fn received(&self, n: usize) {
+        self.received.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn waited(&self) {
+        self.waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            waits: self.waits.load(Ordering::Relaxed),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[derive(Default)]
+pub(crate) struct Counters {}
+
+#[cfg(not(feature = "metrics"))]
+impl Counters {
+    pub(crate) fn sent(&self, _n: usize, _depth: usize) {}
+
+    pub(crate) fn received(&self, _n: usize) {}
+
+    pub(crate) fn waited(&self) {}
+}
+
+#[cfg(feature = "metrics")]
+impl<T> Sender<T> {
+    // A snapshot of the channel's counters, the same for every handle.
+    pub fn stats(&self) -> Stats {
+        self.shared.counters.stats()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<T> Receiver<T> {
+    // A snapshot of the channel's counters, the same for every handle.
+    pub fn stats(&self) -> Stats {
+        self.shared.counters.stats()
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use crate::channel;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn counts_traffic() {
+        let (mut tx, mut rx) = channel();
+        assert_eq!(rx.stats(), Stats::default());
+        tx.send_all(0..4).unwrap();
+        tx.send(4).unwrap();
+        assert_eq!(rx.recv(), Some(0));
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.recv_batch(2), [2, 3]);
+        let mut rest = Vec::new();
+        rx.drain_into(&mut rest);
+        let stats = tx.stats();
+        assert_eq!((stats.sent, stats.received), (5, 5));
+        assert_eq!(stats.max_depth, 5);
+        assert_eq!(stats.waits, 0);
+    }
+
+    #[test]
+    fn counts_waits() {
+        let (mut tx, mut rx) = channel();
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(()).unwrap();
+        });
+        assert_eq!(rx.recv(), Some(()));
+        sender.join().unwrap();
+        assert!(rx.stats().waits >= 1);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::VecDeque, ops::Deref};
+
+use crate::{
+    sys::{atomic::Ordering, MutexGuard},
+    Inner, Receiver, Shared,
+};
+
+/*
+Peeking lets a consumer look at the next message before deciding whether to take it. Where
+that message lives decides how the guard holds on to it:
+
+- a lone receiver moves the queue into its private buffer, as recv does, and the guard
+  borrows the buffer. Nobody else can receive it anyway, and the lock is free again.
+- with several receivers, the message must stay where the others can get it, so the guard
+  keeps the lock and borrows the front of the shared queue. Senders and the other receivers
+  block until the guard is dropped, so don't hold it for longer than the inspection takes.
+
+Dropping the guard leaves the message where it was; Peek::take receives it.
+*/
+
+/// The next message of a [`Receiver`], borrowed by [`Receiver::peek`] or
+/// [`Receiver::try_peek`] without receiving it.
+pub struct Peek<'a, T> {
+    front: Front<'a, T>,
+    shared: &'a Shared<T>,
+}
+
+enum Front<'a, T> {
+    Buffer(&'a mut VecDeque<T>),
+    Queue(MutexGuard<'a, Inner<T>>),
+}
+
+impl<T> Receiver<T> {
+    // Blocks like `recv` until there is a message, and borrows it instead of taking it.
+    // `None` once the channel is closed.
+    pub fn peek(&mut self) -> Option<Peek<'_, T>> {
+        self.peek_front(true)
+    }
+
+    // Like `peek`, but returns `None` right away if nothing is queued.
+    pub fn try_peek(&mut self) -> Option<Peek<'_, T>> {
+        self.peek_front(false)
+    }
+
+    fn peek_front(&mut self, block: bool) -> Option<Peek<'_, T>> {
+        let Receiver { shared, buffer, .. } = self;
+        if buffer.is_empty() {
+            let mut inner = shared.inner.lock().unwrap();
+            if block {
+                inner = shared.wait_turn(inner, None).0;
+            }
+            // We may leave the message where it is, so the next in line gets a look too.
+            shared.pass_on(&inner);
+            if inner.queue.is_empty() {
+                return None;
+            }
+            if inner.receivers > 1 {
+                return Some(Peek {
+                    front: Front::Queue(inner),
+                    shared,
+                });
+            }
+            std::mem::swap(buffer, &mut inner.queue);
+        }
+        Some(Peek {
+            front: Front::Buffer(buffer),
+            shared,
+        })
+    }
+}
+
+impl<T> Peek<'_, T> {
+    // Receives the message after all.
+    pub fn take(self) -> T {
+        let t = match self.front {
+            Front::Buffer(buffer) => {
+                let t = buffer.pop_front();
+                self.shared.unbuffer(1);
+                self.shared.counters.received(1);
+                t
+            }
+            Front::Queue(mut inner) => {
+                let t = inner.queue.pop_front();
+                self.shared.len.fetch_sub(1, Ordering::Relaxed);
+                self.shared.counters.received(1);
+                t
+            }
+        };
+        t.expect("a Peek always has a message in front")
+    }
+}
+
+impl<T> Deref for Peek<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        let front = match &self.front {
+            Front::Buffer(buffer) => buffer.front(),
+            Front::Queue(inner) => inner.queue.front(),
+        };
+        front.expect("a Peek always has a message in front")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel;
+    use std::thread;
+
+    #[test]
+    fn peek_then_take() {
+        let (mut tx, mut rx) = channel();
+        assert!(rx.try_peek().is_none());
+        tx.send_all([1, 2]).unwrap();
+        assert_eq!(*rx.peek().unwrap(), 1);
+        // Still there.
+        assert_eq!(*rx.try_peek().unwrap(), 1);
+        assert_eq!(rx.peek().unwrap().take(), 1);
+        assert_eq!(rx.len(), 1);
+        assert_eq!(rx.recv(), Some(2));
+        drop(tx);
+        assert!(rx.peek().is_none());
+    }
+
+    #[test]
+    fn peeked_message_stays_available_to_others() {
+        let (mut tx, mut rx) = channel();
+        let mut other = rx.clone();
+        tx.send("for other").unwrap();
+        let peeked = rx.peek().unwrap();
+        assert_eq!(*peeked, "for other");
+        drop(peeked);
+        assert_eq!(other.try_recv(), Ok("for other"));
+        drop(rx);
+    }
+
+    #[test]
+    fn peek_waits_for_a_message() {
+        let (mut tx, mut rx) = channel();
+        let sender = thread::spawn(move || tx.send(5).unwrap());
+        assert_eq!(*rx.peek().unwrap(), 5);
+        sender.join().unwrap();
+        assert_eq!(rx.try_recv(), Ok(5));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{Inner, Receiver};
+
+/*
+With the `async` feature, a Receiver can also be waited on from a task. Blocking recv puts
+the thread to sleep on the Condvar; poll_next can't, so it stores the task's Waker in the
+shared state instead, and returns Pending. A send takes the oldest waker out and wakes it,
+next to the notify_one for sleeping threads, and the last Sender wakes every task so they
+all see the close.
+
+A send wakes one task for its message. If that task is dropped before it polls again, the
+message is still queued and nobody else was woken for it, so whoever drops it passes the
+wakeup on to the next task in line.
+
+Every Receiver (not every future) has its own key into the list of wakers: polling again
+only replaces the waker, and the task keeps its place in line.
+*/
+
+// Tasks waiting in poll_next, oldest first, by the key of their receiver.
+pub(crate) type Wakers = std::collections::VecDeque<(u64, Waker)>;
+
+// Wakes them after the lock is released: a waker may run executor code that takes locks of
+// its own.
+pub(crate) fn wake_all(wakers: Wakers) {
+    for (_, waker) in wakers {
+        waker.wake();
+    }
+}
+
+impl<T> Inner<T> {
+    // Forgets the waker of a receiver that stops waiting. If a send has taken it out
+    // already, the message it was woken for goes unreceived: returns the next task to
+    // wake for it.
+    pub(crate) fn unregister(&mut self, key: u64) -> Option<Waker> {
+        let before = self.wakers.len();
+        self.wakers.retain(|(k, _)| *k != key);
+        if self.wakers.len() == before && !self.queue.is_empty() {
+            return self.wakers.pop_front().map(|(_, waker)| waker);
+        }
+        None
+    }
+}
+
+impl<T> Receiver<T> {
+    // Like `recv`, but for a task: the future completes with the next message, or with
+    // `None` once the channel is closed.
+    pub fn recv_async(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    /*
+    The next message if there is one, Ready(None) if the channel is closed, or Pending with
+    `cx`'s waker stored until a send or the close. The same contract as `Stream::poll_next`
+    from the futures crate, which a wrapper can forward to.
+    */
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(t) = self.buffer.pop_front() {
+            return Poll::Ready(Some(self.received_from_buffer(t)));
+        }
+        let mut inner = self.shared.inner.lock().unwrap();
+        let ready = match inner.take(&mut self.buffer) {
+            Some(t) => Some(self.received(t)),
+            None if self.shared.finished(&inner) => None,
+            None => {
+                let key = *self.key.get_or_insert_with(|| {
+                    inner.next_key += 1;
+                    inner.next_key
+                });
+                match inner.wakers.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, waker)) => waker.clone_from(cx.waker()),
+                    None => inner.wakers.push_back((key, cx.waker().clone())),
+                }
+                self.shared.counters.waited();
+                return Poll::Pending;
+            }
+        };
+        // Done waiting, whether or not a send woke us for this.
+        if let Some(key) = self.key.take() {
+            inner.wakers.retain(|(k, _)| *k != key);
+        }
+        Poll::Ready(ready)
+    }
+}
+
+/// Future returned by [`Receiver::recv_async`].
+pub struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().receiver.poll_next(cx)
+    }
+}
+
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        // No key means the future never waited, or has got its message.
+        let Some(key) = self.receiver.key.take() else {
+            return;
+        };
+        let next = self.receiver.shared.inner.lock().unwrap().unregister(key);
+        if let Some(waker) = next {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        channel,
+        executor::{block_on, Executor},
+        task::waker_fn,
+        Sender,
+    };
+    use std::{future::poll_fn, thread, time::Duration};
+
+    #[test]
+    fn recv_async_waits_for_a_send() {
+        let executor = Executor::new(2);
+        let (mut tx, mut rx) = channel();
+        let handle = executor.spawn(async move {
+            let mut got = Vec::new();
+            while let Some(i) = rx.recv_async().await {
+                got.push(i);
+            }
+            got
+        });
+        for i in 0..5 {
+            thread::sleep(Duration::from_millis(5));
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        assert_eq!(handle.join().unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn poll_next_as_a_stream() {
+        let (mut tx, mut rx) = channel();
+        let sender = thread::spawn(move || {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+        });
+        let sum = block_on(async {
+            let mut sum = 0;
+            while let Some(i) = poll_fn(|cx| rx.poll_next(cx)).await {
+                sum += i;
+            }
+            sum
+        });
+        sender.join().unwrap();
+        assert_eq!(sum, 4950);
+    }
+
+    #[test]
+    fn dropped_future_passes_the_wakeup_on() {
+        let (mut tx, mut first) = channel();
+        let mut second = first.clone();
+        let waker = waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let mut waiting = Box::pin(first.recv_async());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        let handle = thread::spawn(move || block_on(second.recv_async()));
+        // Wait until the second task is in line too, behind the first.
+        while waiting_tasks(&tx) < 2 {
+            thread::yield_now();
+        }
+        tx.send(7).unwrap();
+        // The send woke the first future, which goes away without receiving.
+        drop(waiting);
+        assert_eq!(handle.join().unwrap(), Some(7));
+        drop(first);
+
+        fn waiting_tasks(tx: &Sender<i32>) -> usize {
+            tx.shared.inner.lock().unwrap().wakers.len()
+        }
+    }
+
+    #[test]
+    fn close_wakes_every_task() {
+        let executor = Executor::new(2);
+        let (tx, rx) = channel::<()>();
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let mut rx = rx.clone();
+                executor.spawn(async move { rx.recv_async().await })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+        drop(tx);
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), None);
+        }
+    }
+}
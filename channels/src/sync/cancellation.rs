@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// A flag for asking work to stop, shared by everyone who clones the token.
+///
+/// Cancellation is cooperative: [`cancel`](CancellationToken::cancel) only sets the flag, and
+/// the work checks [`is_cancelled`](CancellationToken::is_cancelled) between steps, or waits
+/// for it with the blocking [`wait`](CancellationToken::wait) or with
+/// [`cancelled`](CancellationToken::cancelled)`.await` on the executor. The token is `Send`
+/// and `Sync`, so it can be moved into thread pool jobs, spawned tasks and scoped threads
+/// alike.
+///
+/// [`child_token`](CancellationToken::child_token) creates a token that is cancelled together
+/// with its parent but can also be cancelled on its own, without affecting the parent. That
+/// way one part of a shutdown can be stopped early while the whole tree still stops with the
+/// root.
+#[derive(Clone)]
+pub struct CancellationToken {
+    node: Arc<Node>,
+}
+
+struct Node {
+    // Set once, under the lock, so `is_cancelled` doesn't need to take it.
+    cancelled: AtomicBool,
+    state: Mutex<State>,
+    // For threads blocked in `wait`.
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    // A parent doesn't keep its children alive: a dropped child has nothing to cancel.
+    children: Vec<Weak<Node>>,
+    // Tasks waiting in `cancelled().await`, by the key of their future.
+    wakers: HashMap<u64, Waker>,
+    next_key: u64,
+}
+
+impl Node {
+    fn new(cancelled: bool) -> Arc<Node> {
+        Arc::new(Node {
+            cancelled: AtomicBool::new(cancelled),
+            state: Mutex::new(State::default()),
+            condvar: Condvar::new(),
+        })
+    }
+
+    fn cancel(&self) {
+        // One node at a time, with no lock held, so a deep tree can't deadlock, and from a
+        // worklist rather than by recursing, so it can't overflow the stack either.
+        let mut pending = self.cancel_node();
+        while let Some(node) = pending.pop() {
+            pending.extend(node.cancel_node());
+        }
+    }
+
+    // Cancels this node but not its descendants, and returns the children still alive.
+    // Returns none if the node was already cancelled, since its children were then too.
+    fn cancel_node(&self) -> Vec<Arc<Node>> {
+        let (children, wakers) = {
+            let mut state = self.state.lock().unwrap();
+            if self.cancelled.swap(true, Ordering::Release) {
+                return Vec::new();
+            }
+            (
+                std::mem::take(&mut state.children),
+                std::mem::take(&mut state.wakers),
+            )
+        };
+        self.condvar.notify_all();
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+        children.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            node: Node::new(false),
+        }
+    }
+
+    // A token that is cancelled when this one is, or when it is cancelled itself. Already
+    // cancelled if this one is.
+    pub fn child_token(&self) -> CancellationToken {
+        let mut state = self.node.state.lock().unwrap();
+        if self.is_cancelled() {
+            return CancellationToken {
+                node: Node::new(true),
+            };
+        }
+        let child = Node::new(false);
+        // Forget the children that were dropped meanwhile, or the list would only grow.
+        state.children.retain(|child| child.strong_count() > 0);
+        state.children.push(Arc::downgrade(&child));
+        CancellationToken { node: child }
+    }
+
+    // Cancels this token and all of its descendants. Cancelling twice does nothing.
+    pub fn cancel(&self) {
+        self.node.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.node.cancelled.load(Ordering::Acquire)
+    }
+
+    // Blocks the thread until the token is cancelled.
+    pub fn wait(&self) {
+        let mut state = self.node.state.lock().unwrap();
+        while !self.is_cancelled() {
+            state = self.node.condvar.wait(state).unwrap();
+        }
+    }
+
+    // Like `wait`, but gives up after `timeout`. Returns whether the token was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        // A deadline too far in the future to represent never comes.
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            self.wait();
+            return true;
+        };
+        let mut state = self.node.state.lock().unwrap();
+        while !self.is_cancelled() {
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                return false;
+            };
+            state = self.node.condvar.wait_timeout(state, left).unwrap().0;
+        }
+        true
+    }
+
+    // Returns a future that completes once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    // Cancels the token when the guard is dropped, for example when the scope that owns
+    // the guard unwinds from a panic.
+    pub fn drop_guard(self) -> DropGuard {
+        DropGuard { token: Some(self) }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        CancellationToken::new()
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("is_cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Future returned by [`CancellationToken::cancelled`].
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    // Where our waker is stored while we wait.
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let mut state = this.token.node.state.lock().unwrap();
+        // `cancel` sets the flag under this lock, so checking again here means we can't
+        // store our waker just after it took them all.
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let key = *this.key.get_or_insert_with(|| {
+            state.next_key += 1;
+            state.next_key
+        });
+        state.wakers.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token.node.state.lock().unwrap().wakers.remove(&key);
+        }
+    }
+}
+
+/// Cancels its token when dropped, unless [`disarm`](DropGuard::disarm)ed first.
+pub struct DropGuard {
+    token: Option<CancellationToken>,
+}
+
+impl DropGuard {
+    // Gives the token back without cancelling it.
+    pub fn disarm(mut self) -> CancellationToken {
+        self.token.take().expect("only taken here or in drop")
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{executor::Executor, threadpool::ThreadPool};
+    use std::{
+        sync::atomic::AtomicUsize,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn children_follow_their_parent() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let grandchild = child.child_token();
+        let sibling = root.child_token();
+
+        child.cancel();
+        assert!(child.is_cancelled() && grandchild.is_cancelled());
+        assert!(!root.is_cancelled() && !sibling.is_cancelled());
+
+        root.cancel();
+        assert!(sibling.is_cancelled());
+        assert!(root.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn dropped_children_are_forgotten() {
+        let root = CancellationToken::new();
+        for _ in 0..100 {
+            drop(root.child_token());
+        }
+        let _kept = root.child_token();
+        assert_eq!(root.node.state.lock().unwrap().children.len(), 1);
+    }
+
+    #[test]
+    fn wait_blocks_until_cancelled() {
+        let token = CancellationToken::new();
+        assert!(!token.wait_timeout(Duration::from_millis(10)));
+        thread::scope(|s| {
+            let child = token.child_token();
+            let waiter = s.spawn(move || {
+                child.wait();
+                Instant::now()
+            });
+            thread::sleep(Duration::from_millis(20));
+            let cancelled_at = Instant::now();
+            token.cancel();
+            assert!(waiter.join().unwrap() >= cancelled_at);
+        });
+        assert!(token.wait_timeout(Duration::ZERO));
+        assert!(token.wait_timeout(Duration::MAX));
+    }
+
+    #[test]
+    fn deep_trees_cancel_without_recursing() {
+        let root = CancellationToken::new();
+        let mut chain = vec![root.child_token()];
+        for _ in 0..100_000 {
+            let child = chain.last().unwrap().child_token();
+            chain.push(child);
+        }
+        root.cancel();
+        assert!(chain.iter().all(CancellationToken::is_cancelled));
+    }
+
+    #[test]
+    fn stops_thread_pool_jobs() {
+        let pool = ThreadPool::new(4);
+        let token = CancellationToken::new();
+        let steps = Arc::new(AtomicUsize::new(0));
+        for _ in 0..4 {
+            let (token, steps) = (token.clone(), Arc::clone(&steps));
+            pool.execute(move || {
+                while !token.is_cancelled() {
+                    steps.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+        }
+        thread::sleep(Duration::from_millis(20));
+        token.cancel();
+        pool.join();
+        assert!(steps.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn cancelled_future_wakes_tasks() {
+        let executor = Executor::new(2);
+        let token = CancellationToken::new();
+        let handles: Vec<_> = (0..3)
+            .map(|i| {
+                let token = token.child_token();
+                executor.spawn(async move {
+                    token.cancelled().await;
+                    i
+                })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+        token.cancel();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, [0, 1, 2]);
+        assert!(token.node.state.lock().unwrap().wakers.is_empty());
+    }
+
+    #[test]
+    fn drop_guard_cancels_unless_disarmed() {
+        let token = CancellationToken::new();
+        let guard = token.clone().drop_guard();
+        let token2 = guard.disarm();
+        assert!(!token.is_cancelled());
+
+        let result = thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = token2.drop_guard();
+                panic!("worker failed");
+            })
+            .join()
+        });
+        assert!(result.is_err());
+        assert!(token.is_cancelled());
+    }
+}
@@ -0,0 +1,21 @@
+/*
+Async counterparts to Condvar and friends. A blocking primitive puts the thread to sleep;
+these return a future instead, and keep the Waker of every task that is waiting. Whoever
+makes progress possible takes the wakers out and wakes them, and the executor polls those
+tasks again. Nobody spins and no worker thread is blocked while a task waits.
+
+Each primitive keeps its state and its queue of waiters behind one std Mutex. The lock is
+only ever held for a few instructions and never across an await, so it is fine to take it
+from inside poll.
+
+CancellationToken is the exception that can be waited for both ways: it is meant to be
+handed to threads and tasks alike, so it has a blocking `wait` next to `cancelled().await`.
+*/
+
+mod cancellation;
+mod notify;
+mod semaphore;
+
+pub use cancellation::{CancellationToken, Cancelled, DropGuard};
+pub use notify::{Notified, Notify};
+pub use semaphore::{Acquire, Semaphore, SemaphorePermit};
@@ -0,0 +1,274 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Wakes tasks waiting for an event, like a Condvar for futures.
+///
+/// A task waits with `notify.notified().await`. [`notify_one`](Notify::notify_one) wakes the
+/// task that has been waiting longest; if nobody is waiting it leaves a permit behind, so the
+/// next `notified()` completes right away. There is at most one permit, however often
+/// `notify_one` is called. [`notify_waiters`](Notify::notify_waiters) wakes every task that is
+/// waiting at that moment and leaves no permit.
+///
+/// A `Notified` future only joins the queue when it is first polled; a notification that
+/// reaches it and is then dropped unawaited is passed on to the next waiter.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+struct State {
+    permit: bool,
+    waiters: VecDeque<Arc<Mutex<Waiter>>>,
+}
+
+struct Waiter {
+    waker: Option<Waker>,
+    notified: Option<Wakeup>,
+}
+
+#[derive(Clone, Copy)]
+enum Wakeup {
+    One,
+    All,
+}
+
+impl State {
+    // Returns the waker to call once the lock is released.
+    fn notify_one(&mut self) -> Option<Waker> {
+        match self.waiters.pop_front() {
+            Some(waiter) => {
+                let mut waiter = waiter.lock().unwrap();
+                waiter.notified = Some(Wakeup::One);
+                waiter.waker.take()
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+impl Notify {
+    pub const fn new() -> Notify {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Returns a future that completes once this is notified.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter: None,
+            done: false,
+        }
+    }
+
+    pub fn notify_one(&self) {
+        let waker = self.state.lock().unwrap().notify_one();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub fn notify_waiters(&self) {
+        let waiters = std::mem::take(&mut self.state.lock().unwrap().waiters);
+        for waiter in waiters {
+            let waker = {
+                let mut waiter = waiter.lock().unwrap();
+                waiter.notified = Some(Wakeup::All);
+                waiter.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+/// Future returned by [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    // Our place in the queue, from the first Pending until we are notified.
+    waiter: Option<Arc<Mutex<Waiter>>>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(());
+        }
+        let mut state = this.notify.state.lock().unwrap();
+        match &this.waiter {
+            None if state.permit => {
+                state.permit = false;
+                this.done = true;
+                Poll::Ready(())
+            }
+            None => {
+                let waiter = Arc::new(Mutex::new(Waiter {
+                    waker: Some(cx.waker().clone()),
+                    notified: None,
+                }));
+                state.waiters.push_back(Arc::clone(&waiter));
+                this.waiter = Some(waiter);
+                Poll::Pending
+            }
+            Some(waiter) => {
+                let mut waiter = waiter.lock().unwrap();
+                if waiter.notified.is_some() {
+                    drop(waiter);
+                    this.waiter = None;
+                    this.done = true;
+                    Poll::Ready(())
+                } else {
+                    // The task may have moved to another waker since the last poll.
+                    waiter.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        let waker = {
+            let mut state = self.notify.state.lock().unwrap();
+            let notified = waiter.lock().unwrap().notified;
+            match notified {
+                None => {
+                    state.waiters.retain(|other| !Arc::ptr_eq(other, &waiter));
+                    None
+                }
+                // notify_one picked us, but nobody will see it: give it to somebody else.
+                Some(Wakeup::One) => state.notify_one(),
+                Some(Wakeup::All) => None,
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{block_on, Executor},
+        task::waker_fn,
+    };
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = waker_fn(|| {});
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn permit_is_stored() {
+        let notify = Notify::new();
+        notify.notify_one();
+        notify.notify_one();
+        block_on(notify.notified());
+        // Only one permit, however many notifications.
+        assert!(poll_once(&mut notify.notified()).is_pending());
+    }
+
+    #[test]
+    fn notify_one_wakes_in_order() {
+        let notify = Notify::new();
+        let mut first = notify.notified();
+        let mut second = notify.notified();
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+        notify.notify_one();
+        assert!(poll_once(&mut second).is_pending());
+        assert!(poll_once(&mut first).is_ready());
+        notify.notify_one();
+        assert!(poll_once(&mut second).is_ready());
+    }
+
+    #[test]
+    fn dropped_waiter_passes_notification_on() {
+        let notify = Notify::new();
+        let mut first = notify.notified();
+        let mut second = notify.notified();
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+        notify.notify_one();
+        drop(first);
+        assert!(poll_once(&mut second).is_ready());
+        // A waiter dropped before being notified just leaves the queue.
+        let mut third = notify.notified();
+        assert!(poll_once(&mut third).is_pending());
+        drop(third);
+        notify.notify_one();
+        assert!(poll_once(&mut notify.notified()).is_ready());
+    }
+
+    #[test]
+    fn notify_waiters_wakes_everyone() {
+        let notify = Arc::new(Notify::new());
+        let woken = Arc::new(AtomicUsize::new(0));
+        let executor = Executor::new(2);
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let notify = Arc::clone(&notify);
+                let woken = Arc::clone(&woken);
+                executor.spawn(async move {
+                    notify.notified().await;
+                    woken.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        while notify.state.lock().unwrap().waiters.len() < 5 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        notify.notify_waiters();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(woken.load(Ordering::SeqCst), 5);
+        // notify_waiters leaves no permit behind.
+        assert!(poll_once(&mut notify.notified()).is_pending());
+    }
+
+    #[test]
+    fn notify_from_another_thread() {
+        let notify = Arc::new(Notify::new());
+        let notifier = Arc::clone(&notify);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            notifier.notify_one();
+        });
+        block_on(notify.notified());
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Limits how many tasks can be in some section at once.
+///
+/// The semaphore holds a number of permits. [`acquire`](Semaphore::acquire) waits until it
+/// can take one, and gives it back as a [`SemaphorePermit`] that returns it when dropped.
+///
+/// Waiters are served strictly in order: a task that asks for a permit while others are
+/// queued waits behind them even if permits are free, so a task wanting many permits
+/// can't be starved by a stream of tasks wanting one.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+struct State {
+    permits: usize,
+    waiters: VecDeque<Arc<Mutex<Waiter>>>,
+}
+
+struct Waiter {
+    needed: usize,
+    // Set once the permits were taken from the semaphore for this waiter.
+    granted: bool,
+    waker: Option<Waker>,
+}
+
+impl State {
+    // Hands permits to waiters from the front of the queue for as long as there are enough.
+    // Returns the wakers to call once the lock is released.
+    fn grant(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        while let Some(front) = self.waiters.front() {
+            let mut waiter = front.lock().unwrap();
+            if waiter.needed > self.permits {
+                break;
+            }
+            self.permits -= waiter.needed;
+            waiter.granted = true;
+            wakers.extend(waiter.waker.take());
+            drop(waiter);
+            self.waiters.pop_front();
+        }
+        wakers
+    }
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().unwrap().permits
+    }
+
+    // Adds `n` permits, waking the waiters they are enough for.
+    pub fn add_permits(&self, n: usize) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.permits += n;
+            state.grant()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    // Waits for `n` permits at once. Never completes if the semaphore never holds that many.
+    pub fn acquire_many(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits: n,
+            waiter: None,
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    // Takes `n` permits if they are free and nobody is queued for them.
+    pub fn try_acquire_many(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.waiters.is_empty() || state.permits < n {
+            return None;
+        }
+        state.permits -= n;
+        Some(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+}
+
+/// Future returned by [`Semaphore::acquire`] and [`Semaphore::acquire_many`].
+///
+/// Dropping it gives up its place in the queue, and returns the permits if they had already
+/// been granted.
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    waiter: Option<Arc<Mutex<Waiter>>>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<SemaphorePermit<'a>> {
+        let this = self.get_mut();
+        let mut state = this.semaphore.state.lock().unwrap();
+        let ready = match &this.waiter {
+            None if state.waiters.is_empty() && state.permits >= this.permits => {
+                state.permits -= this.permits;
+                true
+            }
+            None => {
+                let waiter = Arc::new(Mutex::new(Waiter {
+                    needed: this.permits,
+                    granted: false,
+                    waker: Some(cx.waker().clone()),
+                }));
+                state.waiters.push_back(Arc::clone(&waiter));
+                this.waiter = Some(waiter);
+                false
+            }
+            Some(waiter) => {
+                let mut waiter = waiter.lock().unwrap();
+                if !waiter.granted {
+                    // The task may have moved to another waker since the last poll.
+                    waiter.waker = Some(cx.waker().clone());
+                }
+                waiter.granted
+            }
+        };
+        drop(state);
+        if !ready {
+            return Poll::Pending;
+        }
+        this.waiter = None;
+        Poll::Ready(SemaphorePermit {
+            semaphore: this.semaphore,
+            permits: this.permits,
+        })
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        let wakers = {
+            let mut state = self.semaphore.state.lock().unwrap();
+            if waiter.lock().unwrap().granted {
+                state.permits += self.permits;
+            } else {
+                state.waiters.retain(|other| !Arc::ptr_eq(other, &waiter));
+            }
+            // Either way the waiters behind us may be able to go now.
+            state.grant()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Permits taken from a [`Semaphore`], given back when this is dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    // Keeps the permits out of the semaphore for good.
+    pub fn forget(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{block_on, Executor},
+        task::waker_fn,
+    };
+    use std::{
+        future::poll_fn,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = waker_fn(|| {});
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    // Lets the executor run other tasks before this one continues.
+    async fn yield_now() {
+        let mut yielded = false;
+        poll_fn(|cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    #[test]
+    fn permits_are_returned_on_drop() {
+        let semaphore = Semaphore::new(2);
+        let a = block_on(semaphore.acquire());
+        let b = semaphore.try_acquire().unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+        assert!(semaphore.try_acquire().is_none());
+        drop(a);
+        assert_eq!(semaphore.available_permits(), 1);
+        b.forget();
+        assert_eq!(semaphore.available_permits(), 1);
+        let many = semaphore.try_acquire_many(1).unwrap();
+        assert_eq!(many.num_permits(), 1);
+    }
+
+    #[test]
+    fn waiters_are_served_in_order() {
+        let semaphore = Semaphore::new(2);
+        let held = semaphore.try_acquire_many(2).unwrap();
+        let mut big = semaphore.acquire_many(2);
+        let mut small = semaphore.acquire();
+        assert!(poll_once(&mut big).is_pending());
+        assert!(poll_once(&mut small).is_pending());
+        semaphore.add_permits(1);
+        // One permit is free, but `small` is queued behind `big`.
+        assert!(poll_once(&mut small).is_pending());
+        assert!(semaphore.try_acquire().is_none());
+        drop(held);
+        let Poll::Ready(big_permit) = poll_once(&mut big) else {
+            panic!("the big waiter should have its permits");
+        };
+        let Poll::Ready(small_permit) = poll_once(&mut small) else {
+            panic!("the small waiter should have its permit");
+        };
+        assert_eq!(big_permit.num_permits() + small_permit.num_permits(), 3);
+        assert_eq!(semaphore.available_permits(), 0);
+    }
+
+    #[test]
+    fn dropped_waiter_leaves_the_queue() {
+        let semaphore = Semaphore::new(1);
+        let held = semaphore.try_acquire().unwrap();
+        let mut first = semaphore.acquire();
+        let mut second = semaphore.acquire();
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+        drop(held);
+        // `first` was granted the permit but never took it; dropping it hands it on.
+        drop(first);
+        assert!(poll_once(&mut second).is_ready());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn limits_concurrent_tasks() {
+        let semaphore = Arc::new(Semaphore::new(3));
+        let current = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let executor = Executor::new(4);
+        for _ in 0..40 {
+            let semaphore = Arc::clone(&semaphore);
+            let current = Arc::clone(&current);
+            let peak = Arc::clone(&peak);
+            executor.spawn(async move {
+                let _permit = semaphore.acquire().await;
+                let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                yield_now().await;
+                current.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        executor.join();
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+}
@@ -0,0 +1,33 @@
+/*
+The synchronization types the channel and the lock-free structures are built on.
+
+Normally they are std's. Compiled with `RUSTFLAGS="--cfg loom"` they come from the loom
+crate instead, whose mocks run a test once for every way its threads can interleave (and for
+every value each relaxed load is allowed to return), so a missing wakeup or a too-weak
+ordering shows up as a failing test rather than a rare production hang. The cfg is visible
+to downstream crates as well, so their own loom tests check our types along with their code.
+
+Three things don't carry over:
+- loom atomics can't be created in a `const fn`, so under loom `const_fn!` turns those
+  constructors into plain functions, and the types can't live in `static`s;
+- the hazard pointer registry in `lockfree::reclaim` is global and outlives a single loom
+  run, so it stays on std. Loom checks the structures' own pointers, not the slots;
+- loom has no `park_timeout`, so the Park and Spin wait strategies in `wait` park with
+  std. Only the default Condvar strategy is model-checked.
+*/
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{atomic, Arc, Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{atomic, Arc, Condvar, Mutex, MutexGuard};
+
+// Declares a `const fn`, or a plain `fn` under loom.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const fn $($rest)*
+        #[cfg(loom)]
+        $(#[$attr])* $vis fn $($rest)*
+    };
+}
+pub(crate) use const_fn;
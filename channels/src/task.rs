@@ -0,0 +1,254 @@
+use std::{
+    cell::Cell,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    sync::{Arc, Condvar, Mutex},
+    task::{RawWaker, RawWakerVTable, Waker},
+    time::Duration,
+};
+
+/*
+A Waker is a data pointer plus a table of four functions: clone, wake, wake_by_ref and drop.
+Almost every waker wants the data to be an Arc, so that cloning bumps the count and the last
+drop frees it. ArcWake is the one method you actually have to write; waker() fills in the
+table for any type implementing it, so a custom future only needs to say what waking means.
+*/
+
+/// A type that can be woken through an `Arc`, and so turned into a [`Waker`] with
+/// [`waker`].
+pub trait ArcWake: Send + Sync {
+    fn wake_by_ref(arc_self: &Arc<Self>);
+
+    // Wakes and gives up this reference. Override it if consuming the Arc saves a clone.
+    fn wake(self: Arc<Self>) {
+        Self::wake_by_ref(&self);
+    }
+}
+
+// Creates a Waker that calls `W`'s ArcWake methods. The Waker owns one strong count.
+pub fn waker<W: ArcWake + 'static>(wake: Arc<W>) -> Waker {
+    let data = Arc::into_raw(wake).cast::<()>();
+    // SAFETY: `data` came from Arc::into_raw for a `W`, which is what every function in the
+    // vtable for `W` expects, and the Waker owns the strong count it holds.
+    unsafe { Waker::from_raw(RawWaker::new(data, vtable::<W>())) }
+}
+
+fn vtable<W: ArcWake + 'static>() -> &'static RawWakerVTable {
+    &RawWakerVTable::new(
+        clone_raw::<W>,
+        wake_raw::<W>,
+        wake_by_ref_raw::<W>,
+        drop_raw::<W>,
+    )
+}
+
+// In each of these `data` is a pointer from Arc::into_raw for a `W` that still owns its
+// strong count; the Waker contract makes sure the count is given back exactly once.
+
+unsafe fn clone_raw<W: ArcWake + 'static>(data: *const ()) -> RawWaker {
+    // SAFETY: the new RawWaker owns the count we add here.
+    unsafe { Arc::increment_strong_count(data.cast::<W>()) };
+    RawWaker::new(data, vtable::<W>())
+}
+
+unsafe fn wake_raw<W: ArcWake + 'static>(data: *const ()) {
+    // SAFETY: waking by value consumes the waker, so its count moves into this Arc.
+    let arc = unsafe { Arc::from_raw(data.cast::<W>()) };
+    ArcWake::wake(arc);
+}
+
+unsafe fn wake_by_ref_raw<W: ArcWake + 'static>(data: *const ()) {
+    // SAFETY: the waker keeps its count, so this Arc must not be dropped.
+    let arc = ManuallyDrop::new(unsafe { Arc::from_raw(data.cast::<W>()) });
+    ArcWake::wake_by_ref(&arc);
+}
+
+unsafe fn drop_raw<W: ArcWake + 'static>(data: *const ()) {
+    // SAFETY: the waker is going away, and its count with it.
+    drop(unsafe { Arc::from_raw(data.cast::<W>()) });
+}
+
+struct WakeFn<F>(F);
+
+impl<F: Fn() + Send + Sync> ArcWake for WakeFn<F> {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        (arc_self.0)();
+    }
+}
+
+// Creates a Waker that calls `f` every time it is woken. Handy for tests, and for bridging
+// to code that has its own way of being notified.
+pub fn waker_fn<F: Fn() + Send + Sync + 'static>(f: F) -> Waker {
+    waker(Arc::new(WakeFn(f)))
+}
+
+/// Blocks a thread until an [`Unparker`] wakes it.
+///
+/// Works like `thread::park`, but isn't tied to a thread: the parker can be created
+/// anywhere and moved to the thread that waits on it. There is a single token: `unpark`
+/// sets it, `park` waits for it and clears it. An `unpark` that comes before the `park`
+/// therefore isn't lost, but several of them only let one `park` through.
+///
+/// Only the owner parks, so `Parker` is `Send` but not `Sync`; hand out [`Unparker`]s, or a
+/// [`Waker`] made from one, to whoever has to wake it.
+pub struct Parker {
+    unparker: Unparker,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// Wakes the [`Parker`] it was created from. Cheap to clone and usable from any thread.
+#[derive(Clone)]
+pub struct Unparker {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    notified: Mutex<bool>,
+    available: Condvar,
+}
+
+impl Parker {
+    pub fn new() -> Parker {
+        Parker {
+            unparker: Unparker {
+                inner: Arc::new(Inner {
+                    notified: Mutex::new(false),
+                    available: Condvar::new(),
+                }),
+            },
+            _not_sync: PhantomData,
+        }
+    }
+
+    // Blocks until the token is available, then takes it.
+    pub fn park(&self) {
+        let inner = &self.unparker.inner;
+        let mut notified = inner.notified.lock().unwrap();
+        while !*notified {
+            notified = inner.available.wait(notified).unwrap();
+        }
+        *notified = false;
+    }
+
+    // Like park, but gives up after `timeout`. Returns whether it was unparked.
+    pub fn park_timeout(&self, timeout: Duration) -> bool {
+        let inner = &self.unparker.inner;
+        let notified = inner.notified.lock().unwrap();
+        let (mut notified, _) = inner
+            .available
+            .wait_timeout_while(notified, timeout, |notified| !*notified)
+            .unwrap();
+        std::mem::replace(&mut *notified, false)
+    }
+
+    pub fn unparker(&self) -> &Unparker {
+        &self.unparker
+    }
+}
+
+impl Default for Parker {
+    fn default() -> Self {
+        Parker::new()
+    }
+}
+
+impl Unparker {
+    // Makes the token available, waking the parker if it is parked.
+    pub fn unpark(&self) {
+        ArcWake::wake_by_ref(&self.inner);
+    }
+
+    // A Waker that unparks the parker, for polling a future from a thread that parks.
+    pub fn waker(&self) -> Waker {
+        waker(Arc::clone(&self.inner))
+    }
+}
+
+impl ArcWake for Inner {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        *arc_self.notified.lock().unwrap() = true;
+        arc_self.available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Instant,
+    };
+
+    struct Counter(AtomicUsize);
+
+    impl ArcWake for Counter {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn arc_wake_counts_references() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = waker(Arc::clone(&counter));
+        assert_eq!(Arc::strong_count(&counter), 2);
+        let clone = waker.clone();
+        assert_eq!(Arc::strong_count(&counter), 3);
+        waker.wake_by_ref();
+        clone.wake();
+        assert_eq!(Arc::strong_count(&counter), 2);
+        waker.wake();
+        assert_eq!(Arc::strong_count(&counter), 1);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn waker_fn_calls_closure() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = Arc::clone(&count);
+        let waker = waker_fn(move || {
+            inner.fetch_add(1, Ordering::SeqCst);
+        });
+        waker.wake_by_ref();
+        let clone = waker.clone();
+        drop(waker);
+        clone.wake();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn unpark_before_park_is_kept() {
+        let parker = Parker::new();
+        parker.unparker().unpark();
+        parker.unparker().unpark();
+        // Two unparks are still one token.
+        parker.park();
+        assert!(!parker.park_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn park_timeout_expires() {
+        let parker = Parker::new();
+        let start = Instant::now();
+        assert!(!parker.park_timeout(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn unpark_from_another_thread() {
+        let parker = Parker::new();
+        let waker = parker.unparker().waker();
+        let unparker = parker.unparker().clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            unparker.unpark();
+            thread::sleep(Duration::from_millis(10));
+            waker.wake();
+        });
+        parker.park();
+        parker.park();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use std::{
+    cell::UnsafeCell,
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt,
+    mem::MaybeUninit,
+    ptr, slice,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+/*
+Every thread gets a small integer id, and every ThreadLocal keeps one slot per id. Slots
+live in buckets that double in size: bucket 0 has one slot, bucket 1 two, bucket 2 four,
+and so on. A bucket is allocated the first time a thread needs a slot in it and is never
+moved afterwards, so a thread can hand out `&T` into its slot while other threads add
+theirs, without any locking.
+
+Ids are reused, smallest first, once their thread exits, which keeps the buckets small.
+*/
+
+const BUCKETS: usize = usize::BITS as usize;
+
+/// A value per thread that the owner of the container can also reach all at once.
+///
+/// `get_or` gives every thread its own value, created the first time that thread asks.
+/// Unlike a `thread_local!` static, a `ThreadLocal` is an ordinary value: it can be a
+/// field of a struct, and whoever has `&mut` to it can iterate over every thread's value.
+/// That is what per-thread counters need: threads bump their own counter without
+/// contending, and the totals are summed up at the end.
+///
+/// Values aren't dropped when their thread exits, only when the `ThreadLocal` is. A new
+/// thread may get the id of one that exited, and then it sees that thread's value.
+pub struct ThreadLocal<T: Send> {
+    buckets: [AtomicPtr<Entry<T>>; BUCKETS],
+}
+
+struct Entry<T> {
+    present: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Every thread only touches its own entry through `&self`; the ones it can reach from
+// other threads' values need `&mut self`. So `T: Send` is enough, like for a Mutex.
+unsafe impl<T: Send> Sync for ThreadLocal<T> {}
+
+impl<T: Send> ThreadLocal<T> {
+    pub const fn new() -> ThreadLocal<T> {
+        ThreadLocal {
+            buckets: [const { AtomicPtr::new(ptr::null_mut()) }; BUCKETS],
+        }
+    }
+
+    // The current thread's value, if it has created one.
+    pub fn get(&self) -> Option<&T> {
+        let (bucket, index) = location(thread_id());
+        let entries = self.buckets[bucket].load(Ordering::Acquire);
+        if entries.is_null() {
+            return None;
+        }
+        // SAFETY: a published bucket has `bucket_len(bucket)` entries and lives as long as
+        // `self`, and only this thread writes to its entry.
+        unsafe {
+            let entry = &*entries.add(index);
+            if entry.present.load(Ordering::Acquire) {
+                Some((*entry.value.get()).assume_init_ref())
+            } else {
+                None
+            }
+        }
+    }
+
+    // The current thread's value, created with `create` if this is the thread's first call.
+    pub fn get_or(&self, create: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let value = create();
+        // `create` may itself have called `get_or` on this same `ThreadLocal` and filled
+        // the entry. Writing over it would pull the value out from under the `&T` that
+        // call handed out, so keep the first value and drop ours.
+        if let Some(existing) = self.get() {
+            drop(value);
+            return existing;
+        }
+        let (bucket, index) = location(thread_id());
+        let entries = self.bucket(bucket);
+        // SAFETY: the bucket is live, and nobody else writes to this thread's entry. It
+        // is still empty: `get` just checked, after `create` returned.
+        unsafe {
+            let entry = &*entries.add(index);
+            (*entry.value.get()).write(value);
+            entry.present.store(true, Ordering::Release);
+            (*entry.value.get()).assume_init_ref()
+        }
+    }
+
+    pub fn get_or_default(&self) -> &T
+    where
+        T: Default,
+    {
+        self.get_or(T::default)
+    }
+
+    // Every thread's value. Needs `&mut self`, since the other threads could otherwise be
+    // using theirs right now.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.buckets
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(bucket, entries)| {
+                let entries = *entries.get_mut();
+                // SAFETY: a non-null bucket has `bucket_len(bucket)` entries, and `&mut self`
+                // gives us exclusive access to all of them.
+                (!entries.is_null())
+                    .then(|| unsafe { slice::from_raw_parts_mut(entries, bucket_len(bucket)) })
+            })
+            .flatten()
+            .filter_map(|entry| {
+                // SAFETY: a present entry's value is initialized.
+                let present = *entry.present.get_mut();
+                present.then(|| unsafe { entry.value.get_mut().assume_init_mut() })
+            })
+    }
+
+    // Drops every thread's value, leaving the container as if new.
+    pub fn clear(&mut self) {
+        *self = ThreadLocal::new();
+    }
+
+    // The bucket, allocated if no thread has needed it yet.
+    fn bucket(&self, bucket: usize) -> *mut Entry<T> {
+        let entries = self.buckets[bucket].load(Ordering::Acquire);
+        if !entries.is_null() {
+            return entries;
+        }
+        let new = allocate_bucket::<T>(bucket_len(bucket));
+        match self.buckets[bucket].compare_exchange(
+            ptr::null_mut(),
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => new,
+            Err(winner) => {
+                // Another thread allocated it first. Ours is empty, so just free it.
+                // SAFETY: `new` came from `allocate_bucket` with that length.
+                unsafe { free_bucket(new, bucket_len(bucket)) };
+                winner
+            }
+        }
+    }
+}
+
+impl<T: Send> Drop for ThreadLocal<T> {
+    fn drop(&mut self) {
+        for value in self.iter_mut() {
+            // SAFETY: every value is dropped once, and the entries are freed right after.
+            unsafe { ptr::drop_in_place(value) };
+        }
+        for (bucket, entries) in self.buckets.iter_mut().enumerate() {
+            let entries = *entries.get_mut();
+            if !entries.is_null() {
+                // SAFETY: the bucket came from `allocate_bucket` with that length.
+                unsafe { free_bucket(entries, bucket_len(bucket)) };
+            }
+        }
+    }
+}
+
+impl<T: Send> Default for ThreadLocal<T> {
+    fn default() -> Self {
+        ThreadLocal::new()
+    }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for ThreadLocal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the current thread's value can be read through `&self`.
+        f.debug_struct("ThreadLocal")
+            .field("local", &self.get())
+            .finish_non_exhaustive()
+    }
+}
+
+fn allocate_bucket<T>(len: usize) -> *mut Entry<T> {
+    let entries: Box<[Entry<T>]> = (0..len)
+        .map(|_| Entry {
+            present: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+        .collect();
+    Box::into_raw(entries).cast()
+}
+
+// Frees the entries without dropping any values; `MaybeUninit` never drops what it holds.
+unsafe fn free_bucket<T>(entries: *mut Entry<T>, len: usize) {
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(entries, len)) });
+}
+
+fn bucket_len(bucket: usize) -> usize {
+    1 << bucket
+}
+
+// Bucket `b` holds ids `2^b - 1 ..= 2^(b+1) - 2`.
+fn location(id: usize) -> (usize, usize) {
+    let bucket = (usize::BITS - 1 - (id + 1).leading_zeros()) as usize;
+    (bucket, id + 1 - bucket_len(bucket))
+}
+
+// Ids of exited threads, handed out again smallest first.
+struct ThreadIds {
+    next: usize,
+    free: BinaryHeap<Reverse<usize>>,
+}
+
+static THREAD_IDS: Mutex<ThreadIds> = Mutex::new(ThreadIds {
+    next: 0,
+    free: BinaryHeap::new(),
+});
+
+struct ThreadId(usize);
+
+impl ThreadId {
+    fn new() -> ThreadId {
+        let mut ids = THREAD_IDS.lock().unwrap();
+        match ids.free.pop() {
+            Some(Reverse(id)) => ThreadId(id),
+            None => {
+                ids.next += 1;
+                ThreadId(ids.next - 1)
+            }
+        }
+    }
+}
+
+impl Drop for ThreadId {
+    fn drop(&mut self) {
+        THREAD_IDS.lock().unwrap().free.push(Reverse(self.0));
+    }
+}
+
+thread_local! {
+    static THREAD_ID: ThreadId = ThreadId::new();
+}
+
+fn thread_id() -> usize {
+    THREAD_ID.with(|id| id.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::Cell,
+        sync::{atomic::AtomicUsize, Arc, Barrier},
+        thread,
+    };
+
+    #[test]
+    fn locations() {
+        assert_eq!(location(0), (0, 0));
+        assert_eq!(location(1), (1, 0));
+        assert_eq!(location(2), (1, 1));
+        assert_eq!(location(3), (2, 0));
+        assert_eq!(location(6), (2, 3));
+        assert_eq!(location(7), (3, 0));
+    }
+
+    #[test]
+    fn one_value_per_thread() {
+        let local = ThreadLocal::new();
+        assert!(local.get().is_none());
+        assert_eq!(*local.get_or(|| 1), 1);
+        assert_eq!(*local.get_or(|| 2), 1);
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(*local.get_or(|| 3), 3));
+        });
+        assert_eq!(local.get(), Some(&1));
+    }
+
+    #[test]
+    fn reentrant_create_keeps_first_value() {
+        let local = ThreadLocal::new();
+        let inner = Arc::new(1);
+        let outer = Arc::new(2);
+        let value = local.get_or(|| {
+            let first = local.get_or(|| Arc::clone(&inner));
+            assert_eq!(**first, 1);
+            Arc::clone(&outer)
+        });
+        assert_eq!(**value, 1);
+        // The value `create` returned was dropped rather than written over the first one.
+        assert_eq!(Arc::strong_count(&outer), 1);
+        drop(local);
+        assert_eq!(Arc::strong_count(&inner), 1);
+    }
+
+    #[test]
+    fn sum_per_thread_counters() {
+        let mut counters: ThreadLocal<Cell<usize>> = ThreadLocal::new();
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        let counter = counters.get_or_default();
+                        counter.set(counter.get() + 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(counters.iter_mut().map(|c| c.get()).sum::<usize>(), 8000);
+        counters.clear();
+        assert_eq!(counters.iter_mut().count(), 0);
+    }
+
+    #[test]
+    fn values_dropped_with_container() {
+        let value = Arc::new(AtomicUsize::new(0));
+        let local = ThreadLocal::new();
+        // Keep all four threads alive at once, so none of them reuses another's id.
+        let barrier = Barrier::new(4);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    local.get_or(|| Arc::clone(&value));
+                    barrier.wait();
+                });
+            }
+        });
+        // The threads are gone, but their values live as long as the container.
+        assert_eq!(Arc::strong_count(&value), 5);
+        drop(local);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
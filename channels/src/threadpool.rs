@@ -0,0 +1,193 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{channel, Receiver, Sender};
+
+// A job is any closure the workers can run once. It has to be boxed so jobs of different
+// closure types fit through the same channel.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of worker threads that run the jobs given to [`ThreadPool::execute`].
+///
+/// Jobs go through one of our channels: the pool holds the `Sender`, and every worker has
+/// its own clone of the `Receiver`. A job that panics doesn't take its worker down, the panic is
+/// caught and counted. Dropping the pool shuts it down gracefully: the channel closes, the
+/// workers finish whatever is still queued and exit, and the drop waits for them.
+pub struct ThreadPool {
+    // Our Sender needs `&mut self` to send, so it sits behind a mutex to let `execute` take
+    // `&self`. It is an Option so that Drop can close the channel before joining.
+    sender: Option<Mutex<Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+    state: Arc<State>,
+}
+
+struct State {
+    // Jobs that were sent but haven't finished yet, guarded so `join` can wait on it.
+    pending: Mutex<usize>,
+    idle: Condvar,
+    panicked: AtomicUsize,
+}
+
+impl ThreadPool {
+    // Panics if `size` is zero: a pool without workers would never run anything.
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0, "a ThreadPool needs at least one worker");
+        let (sender, receiver) = channel::<Job>();
+        let state = Arc::new(State {
+            pending: Mutex::new(0),
+            idle: Condvar::new(),
+            panicked: AtomicUsize::new(0),
+        });
+
+        let workers = (0..size)
+            .map(|i| {
+                let receiver = receiver.clone();
+                let state = Arc::clone(&state);
+                thread::Builder::new()
+                    .name(format!("threadpool-worker-{i}"))
+                    .spawn(move || work(receiver, &state))
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+
+        ThreadPool {
+            sender: Some(Mutex::new(sender)),
+            workers,
+            state,
+        }
+    }
+
+    // Queues `job` to run on one of the workers.
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+        let sender = self.sender.as_ref().expect("sender is only taken in drop");
+        sender
+            .lock()
+            .unwrap()
+            .send(Box::new(job))
+            .unwrap_or_else(|_| unreachable!("the workers hold the receiver until the pool drops"));
+    }
+
+    // Blocks until every job executed so far has finished, including ones that panicked.
+    // The pool keeps running and can take more jobs afterwards.
+    pub fn join(&self) {
+        let mut pending = self.state.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.state.idle.wait(pending).unwrap();
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    // How many jobs have panicked since the pool was created.
+    pub fn panic_count(&self) -> usize {
+        self.state.panicked.load(Ordering::Relaxed)
+    }
+}
+
+fn work(mut receiver: Receiver<Job>, state: &State) {
+    loop {
+        let Some(job) = receiver.recv() else {
+            // All senders are gone and the queue is drained: the pool is shutting down.
+            return;
+        };
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            state.panicked.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut pending = state.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            state.idle.notify_all();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Dropping the last Sender makes recv() return None once the queue is empty, which
+        // is what tells the workers to exit.
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            // Jobs can't panic a worker, so join only fails if the pool itself is broken.
+            worker.join().expect("threadpool worker panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn runs_every_job() {
+        let pool = ThreadPool::new(4);
+        let counter = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        pool.join();
+        assert_eq!(counter.load(Ordering::SeqCst), 100);
+
+        // The pool is still usable after join.
+        let counter2 = Arc::clone(&counter);
+        pool.execute(move || {
+            counter2.fetch_add(1, Ordering::SeqCst);
+        });
+        pool.join();
+        assert_eq!(counter.load(Ordering::SeqCst), 101);
+    }
+
+    #[test]
+    fn panics_are_isolated() {
+        let pool = ThreadPool::new(2);
+        let counter = Arc::new(AtomicUsize::new(0));
+        for i in 0..10 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                if i % 3 == 0 {
+                    panic!("job {i} failed");
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        pool.join();
+        assert_eq!(pool.panic_count(), 4);
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn drop_finishes_queued_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPool::new(2);
+        for _ in 0..20 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(1));
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(pool);
+        assert_eq!(counter.load(Ordering::SeqCst), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one worker")]
+    fn zero_workers() {
+        ThreadPool::new(0);
+    }
+}
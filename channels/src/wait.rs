@@ -0,0 +1,379 @@
+use std::{
+    collections::VecDeque,
+    hint,
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    sys::{atomic::Ordering, Condvar, MutexGuard},
+    Inner, Shared,
+};
+
+/*
+How a receiver waits for a message, chosen per channel with channel_with_strategy.
+
+A Condvar is the default and the cheapest in CPU: the receiver sleeps until a sender
+signals it. The cost is latency, since a notify goes through the condvar's own queue and
+the woken thread still has to take the mutex.
+
+Park has a send unpark the waiting receiver's Thread directly.
+
+Spin busy-waits for a little while first, watching the message count without taking the
+lock, and only parks when nothing shows up. A message sent right after the receiver ran
+dry is picked up without the receiver ever sleeping, at the price of burning a core for
+those iterations.
+
+Whatever the strategy, blocked receivers are served first come, first served. A receiver
+that has to wait takes a ticket and joins the line in Inner, and only the one at the front
+may take a message; the others wait even when they wake up and see one. A receiver that
+arrives while others are in line queues behind them instead of grabbing the message the
+front was woken for, so a busy consumer looping on recv can't starve an idle one. The
+non-blocking calls (try_recv, drain_into, a batch that already has messages from the
+buffer) don't wait, so they don't queue either: they take what's there.
+
+The line is what lets a wakeup go to the right receiver. Park and Spin unpark the thread
+at the front. A Condvar can't pick who wakes up, so with more than one in line it wakes
+them all and the ones whose turn it isn't go back to sleep; with a single one, notify_one
+is enough. No wakeup is lost, because the front is woken whenever it may have something
+to take:
+- a send wakes the front, as it is when the message was pushed;
+- a receiver that joins behind others while a message is queued wakes the front too, in
+  case the send picked a notify_one before the newcomer was waiting as well;
+- a receiver leaving the line with messages still queued passes the turn on (pass_on);
+- closing and dropping the last of a side wake the whole line;
+- so does the receiver that takes the last message of a closed channel: the others may
+  have woken up at the close, seen that message wasn't theirs, and gone back to sleep.
+park() may return spuriously, and an unpark may arrive after the receiver left the line,
+so a receiver always checks its turn again when it wakes.
+
+There is only the one "not empty" signal: the channel is unbounded, so a send never waits
+and there is no "not full" side to signal. The bounded mailbox in actor.rs has its own
+not_full Condvar for its senders.
+
+Parking uses std::thread directly, not the loom mocks: loom has no park_timeout. Under loom
+only the Condvar strategy is model-checked.
+*/
+
+/// How a waiting [`Receiver`](crate::Receiver) is put to sleep and woken up, see
+/// [`channel_with_strategy`](crate::channel_with_strategy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WaitStrategy {
+    // Sleep on a Condvar. The least CPU, the most latency.
+    #[default]
+    Condvar,
+    // Park the thread, and have a send unpark it directly.
+    Park,
+    // Spin for a bounded number of iterations, then park.
+    Spin,
+}
+
+// How often Spin checks for a message before parking.
+const SPINS: u32 = 1 << 10;
+
+// The blocked receivers, oldest first: their ticket and their thread, for Park and Spin.
+pub(crate) type Line = VecDeque<(u64, Thread)>;
+
+// Where the receivers of a channel wait, whatever the strategy.
+pub(crate) struct Signal {
+    strategy: WaitStrategy,
+    // Signalled when the queue gets a message, or the channel closes.
+    not_empty: Condvar,
+}
+
+// How a receiver left the line in wait_turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Turn {
+    // Its turn came with a message to take, or the channel is finished.
+    Ready,
+    // The deadline passed first. Whatever is queued belongs to a receiver further ahead.
+    TimedOut,
+}
+
+// Whom to wake, worked out from the line under the lock; the waking may happen after.
+pub(crate) enum Wakeup {
+    Nobody,
+    One,
+    All,
+    Thread(Thread),
+    Threads(Vec<Thread>),
+}
+
+impl Signal {
+    pub(crate) fn new(strategy: WaitStrategy) -> Signal {
+        Signal {
+            strategy,
+            not_empty: Condvar::new(),
+        }
+    }
+
+    // The first `n` receivers in line. usize::MAX wakes everyone.
+    pub(crate) fn first(&self, line: &Line, n: usize) -> Wakeup {
+        match (self.strategy, line.len().min(n)) {
+            (_, 0) => Wakeup::Nobody,
+            (WaitStrategy::Condvar, _) if line.len() == 1 => Wakeup::One,
+            (WaitStrategy::Condvar, _) => Wakeup::All,
+            (_, 1) => Wakeup::Thread(line[0].1.clone()),
+            (_, n) => Wakeup::Threads(line.iter().take(n).map(|(_, t)| t.clone()).collect()),
+        }
+    }
+
+    pub(crate) fn wake(&self, wakeup: Wakeup) {
+        match wakeup {
+            Wakeup::Nobody => {}
+            Wakeup::One => self.not_empty.notify_one(),
+            Wakeup::All => self.not_empty.notify_all(),
+            Wakeup::Thread(thread) => thread.unpark(),
+            Wakeup::Threads(threads) => threads.iter().for_each(Thread::unpark),
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    /*
+    Waits in line until it's this receiver's turn and there is a message, or the channel is
+    finished, and leaves the line. `deadline` cuts the wait short; None waits for as long
+    as it takes. On TimedOut the caller must not take anything: a message in the queue was
+    meant for a receiver ahead of it, which may have been woken and not run yet. On Ready the
+    caller looks at the queue to see whether there was a message, and calls pass_on once it
+    has taken what it wanted.
+    */
+    pub(crate) fn wait_turn<'a>(
+        &'a self,
+        mut inner: MutexGuard<'a, Inner<T>>,
+        deadline: Option<Instant>,
+    ) -> (MutexGuard<'a, Inner<T>>, Turn) {
+        if (inner.line.is_empty() && !inner.queue.is_empty()) || self.finished(&inner) {
+            return (inner, Turn::Ready);
+        }
+        let ticket = inner.next_ticket;
+        inner.next_ticket += 1;
+        inner.line.push_back((ticket, thread::current()));
+        if !inner.queue.is_empty() {
+            // Someone ahead of us has a message to take, see above.
+            self.available.wake(self.available.first(&inner.line, 1));
+        }
+        let mut turn = Turn::Ready;
+        while !self.ready(&inner, ticket) {
+            let timeout = match deadline {
+                Some(deadline) => match deadline
+                    .checked_duration_since(Instant::now())
+                    .filter(|left| !left.is_zero())
+                {
+                    Some(left) => Some(left),
+                    None => {
+                        turn = Turn::TimedOut;
+                        break;
+                    }
+                },
+                None => None,
+            };
+            inner = self.wait_for(inner, ticket, timeout);
+        }
+        inner.line.retain(|(t, _)| *t != ticket);
+        (inner, turn)
+    }
+
+    // Wakes the receiver now at the front of the line if there is something left for it,
+    // or everyone once the channel is finished. Called with the lock held, after a receiver
+    // that may have waited took its messages.
+    pub(crate) fn pass_on(&self, inner: &Inner<T>) {
+        let n = if self.finished(inner) {
+            usize::MAX
+        } else if !inner.queue.is_empty() {
+            1
+        } else {
+            0
+        };
+        self.available.wake(self.available.first(&inner.line, n));
+    }
+
+    fn ready(&self, inner: &Inner<T>, ticket: u64) -> bool {
+        let front = inner.line.front().is_some_and(|(t, _)| *t == ticket);
+        (front && !inner.queue.is_empty()) || self.finished(inner)
+    }
+
+    // Gives up the lock until woken, and takes it again. Like Condvar::wait, it may also
+    // return for no reason, so the caller checks its turn again.
+    fn wait_for<'a>(
+        &'a self,
+        inner: MutexGuard<'a, Inner<T>>,
+        ticket: u64,
+        timeout: Option<Duration>,
+    ) -> MutexGuard<'a, Inner<T>> {
+        self.counters.waited();
+        let signal = &self.available;
+        match (signal.strategy, timeout) {
+            (WaitStrategy::Condvar, None) => signal.not_empty.wait(inner).unwrap(),
+            (WaitStrategy::Condvar, Some(timeout)) => {
+                signal.not_empty.wait_timeout(inner, timeout).unwrap().0
+            }
+            (WaitStrategy::Park, _) => self.park(inner, timeout),
+            (WaitStrategy::Spin, _) => {
+                drop(inner);
+                for _ in 0..SPINS {
+                    if self.len.load(Ordering::Relaxed) > 0 {
+                        break;
+                    }
+                    hint::spin_loop();
+                }
+                let inner = self.inner.lock().unwrap();
+                // Only worth parking if it's still not our turn.
+                if self.ready(&inner, ticket) {
+                    inner
+                } else {
+                    self.park(inner, timeout)
+                }
+            }
+        }
+    }
+
+    // We are in line with our Thread since before the lock was first released, so an
+    // unpark can't be missed: it either comes before the park and returns it right away,
+    // or wakes it.
+    fn park<'a>(
+        &'a self,
+        inner: MutexGuard<'a, Inner<T>>,
+        timeout: Option<Duration>,
+    ) -> MutexGuard<'a, Inner<T>> {
+        drop(inner);
+        match timeout {
+            Some(timeout) => thread::park_timeout(timeout),
+            None => thread::park(),
+        }
+        self.inner.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Receiver;
+    use crate::{channel_with_strategy, RecvTimeoutError};
+
+    // Blocks until `n` receivers are waiting in line.
+    fn wait_for_line<T>(rx: &Receiver<T>, n: usize) {
+        while rx.shared.inner.lock().unwrap().line.len() < n {
+            thread::yield_now();
+        }
+    }
+
+    const STRATEGIES: [WaitStrategy; 3] = [
+        WaitStrategy::Condvar,
+        WaitStrategy::Park,
+        WaitStrategy::Spin,
+    ];
+
+    #[test]
+    fn every_strategy_delivers_everything() {
+        for strategy in STRATEGIES {
+            let (tx, rx) = channel_with_strategy(strategy);
+            let producers: Vec<_> = (0..4)
+                .map(|p| {
+                    let mut tx = tx.clone();
+                    thread::spawn(move || {
+                        for i in 0..1000 {
+                            tx.send(p * 1000 + i).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            drop(tx);
+            let consumers: Vec<_> = (0..3)
+                .map(|_| {
+                    let mut rx = rx.clone();
+                    thread::spawn(move || rx.iter().collect::<Vec<_>>())
+                })
+                .collect();
+            drop(rx);
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            let mut all: Vec<_> = consumers
+                .into_iter()
+                .flat_map(|c| c.join().unwrap())
+                .collect();
+            all.sort();
+            assert_eq!(all, (0..4000).collect::<Vec<_>>(), "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn every_strategy_times_out_and_closes() {
+        for strategy in STRATEGIES {
+            let (tx, mut rx) = channel_with_strategy::<()>(strategy);
+            let start = Instant::now();
+            let timeout = Duration::from_millis(20);
+            assert_eq!(rx.recv_timeout(timeout), Err(RecvTimeoutError::Timeout));
+            assert!(start.elapsed() >= timeout, "{strategy:?}");
+            assert!(rx.shared.inner.lock().unwrap().line.is_empty());
+            let closer = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                drop(tx);
+            });
+            assert_eq!(rx.recv(), None, "{strategy:?}");
+            closer.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn waiting_receivers_are_served_in_order() {
+        for strategy in STRATEGIES {
+            let (mut tx, rx) = channel_with_strategy(strategy);
+            let mut first = rx.clone();
+            let first = thread::spawn(move || first.recv());
+            wait_for_line(&rx, 1);
+            let mut second = rx.clone();
+            let second = thread::spawn(move || second.recv());
+            wait_for_line(&rx, 2);
+            // Both messages may be queued before either receiver runs; the order still holds.
+            tx.send_all([1, 2]).unwrap();
+            assert_eq!(first.join().unwrap(), Some(1), "{strategy:?}");
+            assert_eq!(second.join().unwrap(), Some(2), "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn a_newcomer_queues_behind_waiting_receivers() {
+        for strategy in STRATEGIES {
+            let (mut tx, mut rx) = channel_with_strategy(strategy);
+            let mut waiting = rx.clone();
+            let waiting = thread::spawn(move || waiting.recv());
+            wait_for_line(&rx, 1);
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            // Both messages are there, but the first belongs to the receiver that waited.
+            assert_eq!(rx.recv(), Some(2), "{strategy:?}");
+            assert_eq!(waiting.join().unwrap(), Some(1), "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn a_timed_out_receiver_leaves_the_message_to_the_front() {
+        for strategy in STRATEGIES {
+            let (mut tx, mut rx) = channel_with_strategy(strategy);
+            // Stands in for a receiver at the front that was woken for the message but hasn't
+            // run yet, which is when the one behind it may time out.
+            let front = {
+                let mut inner = rx.shared.inner.lock().unwrap();
+                let ticket = inner.next_ticket;
+                inner.next_ticket += 1;
+                inner.line.push_back((ticket, thread::current()));
+                ticket
+            };
+            tx.send(1).unwrap();
+            assert_eq!(
+                rx.recv_timeout(Duration::from_millis(20)),
+                Err(RecvTimeoutError::Timeout),
+                "{strategy:?}"
+            );
+            {
+                let mut inner = rx.shared.inner.lock().unwrap();
+                assert_eq!(inner.queue.len(), 1, "{strategy:?}");
+                assert_eq!(inner.line.len(), 1, "{strategy:?}");
+                inner.line.retain(|(t, _)| *t != front);
+            }
+            assert_eq!(rx.recv(), Some(1), "{strategy:?}");
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Model-checks the channel and the lock-free structures under every interleaving loom finds:
+//
+//     RUSTFLAGS="--cfg loom" cargo test --release --test loom
+#![cfg(loom)]
+
+use channels::{
+    channel,
+    lockfree::{reclaim, Queue, Stack},
+};
+use loom::{sync::Arc, thread};
+
+#[test]
+fn recv_sees_every_send() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        let mut tx2 = tx.clone();
+        let first = thread::spawn(move || tx.send(1).unwrap());
+        let second = thread::spawn(move || tx2.send(2).unwrap());
+        let mut received = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+        received.sort();
+        assert_eq!(received, [1, 2]);
+        // Both senders are gone once their threads finish, which must wake the receiver.
+        first.join().unwrap();
+        second.join().unwrap();
+        assert_eq!(rx.recv(), None);
+    });
+}
+
+#[test]
+fn recv_wakes_up_when_senders_drop() {
+    loom::model(|| {
+        let (tx, mut rx) = channel::<()>();
+        let dropper = thread::spawn(move || drop(tx));
+        assert_eq!(rx.recv(), None);
+        dropper.join().unwrap();
+    });
+}
+
+#[test]
+fn every_receiver_sees_the_close() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        let mut rx2 = rx.clone();
+        let other = thread::spawn(move || rx2.recv());
+        tx.send(1).unwrap();
+        drop(tx);
+        // One of the two gets the message, and neither is left waiting.
+        let mut got = [rx.recv(), other.join().unwrap()];
+        got.sort();
+        assert_eq!(got, [None, Some(1)]);
+    });
+}
+
+// Receivers take turns, see src/wait.rs. The one behind the front may see the close while
+// the last message is still queued for the front, and must still be woken once it's gone.
+#[test]
+fn the_line_sees_the_close() {
+    // With three threads the full model takes minutes; a lost wakeup here needs no more than
+    // a couple of preemptions to show.
+    let mut model = loom::model::Builder::new();
+    model.preemption_bound = Some(3);
+    model.check(|| {
+        let (mut tx, rx) = channel();
+        let waiting: Vec<_> = (0..2)
+            .map(|_| {
+                let mut rx = rx.clone();
+                thread::spawn(move || rx.recv())
+            })
+            .collect();
+        drop(rx);
+        tx.send(1).unwrap();
+        drop(tx);
+        let mut got: Vec<_> = waiting.into_iter().map(|w| w.join().unwrap()).collect();
+        got.sort();
+        assert_eq!(got, [None, Some(1)]);
+    });
+}
+
+#[test]
+fn last_sender_drop_races_with_send() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        let tx2 = tx.clone();
+        // One sender sends and goes away while the other is dropped: whichever drop is the
+        // last one, the message must come out before the close.
+        let sender = thread::spawn(move || tx.send(1).unwrap());
+        let dropper = thread::spawn(move || drop(tx2));
+        assert_eq!(rx.recv(), Some(1));
+        assert_eq!(rx.recv(), None);
+        sender.join().unwrap();
+        dropper.join().unwrap();
+        assert!(rx.is_empty());
+    });
+}
+
+#[test]
+fn buffer_swap_keeps_order() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        let sender = thread::spawn(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+            tx.send(3).unwrap();
+        });
+        // Depending on the interleaving, a recv finds one message or several, and moves the
+        // rest into the buffer; the order must not change either way.
+        let received: Vec<_> = rx.iter().collect();
+        assert_eq!(received, [1, 2, 3]);
+        sender.join().unwrap();
+    });
+}
+
+#[test]
+fn dropped_receiver_hands_its_buffer_on() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        // The lone receiver takes 1 and buffers 2.
+        assert_eq!(rx.recv(), Some(1));
+        let mut rx2 = rx.clone();
+        let other = thread::spawn(move || (rx2.recv(), rx2.recv()));
+        drop(rx);
+        assert_eq!(other.join().unwrap(), (Some(2), None));
+    });
+}
+
+#[test]
+fn draining_the_buffer_wakes_a_waiting_clone() {
+    loom::model(|| {
+        let (mut tx, mut rx) = channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Some(1));
+        let mut rx2 = rx.clone();
+        // rx2 finds the queue empty while 2 sits in rx's buffer, so it has to wait until rx
+        // has received it, and then see the end.
+        let other = thread::spawn(move || rx2.recv());
+        assert_eq!(rx.recv(), Some(2));
+        assert_eq!(other.join().unwrap(), None);
+    });
+}
+
+#[test]
+fn stack_push_pop() {
+    loom::model(|| {
+        let stack = Arc::new(Stack::new());
+        stack.push(0);
+        let pusher = {
+            let stack = Arc::clone(&stack);
+            thread::spawn(move || stack.push(1))
+        };
+        let popped = stack.pop();
+        pusher.join().unwrap();
+        let rest = stack.pop();
+        let mut all = vec![popped.unwrap(), rest.unwrap()];
+        all.sort();
+        assert_eq!(all, [0, 1]);
+        assert!(stack.is_empty());
+        reclaim::collect();
+    });
+}
+
+#[test]
+fn queue_keeps_fifo_order_per_producer() {
+    loom::model(|| {
+        let queue = Arc::new(Queue::new());
+        let producer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                queue.push(1);
+                queue.push(2);
+            })
+        };
+        let mut seen = Vec::new();
+        if let Some(value) = queue.pop() {
+            seen.push(value);
+        }
+        producer.join().unwrap();
+        while let Some(value) = queue.pop() {
+            seen.push(value);
+        }
+        assert_eq!(seen, [1, 2]);
+        reclaim::collect();
+    });
+}